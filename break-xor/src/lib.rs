@@ -0,0 +1,379 @@
+//! Breaks repeating-key XOR, as used by the older tools in this repository:
+//!
+//! - `xor0` repeats the fixed salt [`XOR0_SALT`].
+//! - `xor00` repeats `XOR0_SALT || password`.
+//! - `xor0000` repeats the password.
+//! - `TINYencryption` XORs every byte with [`TINY_KEY`].
+//!
+//! None of them needs the password to undo. [`analyze`] estimates the key
+//! length (see [`keylen`]), recovers each key byte from its column of the
+//! ciphertext and picks the key whose plaintext looks most like a real file:
+//!
+//! - Columns are scored as English text (see [`score`]), and as binary data
+//!   whose most common byte is zero.
+//! - A file signature (see [`magic`]) at the start of the plaintext fixes the
+//!   first key bytes directly.
+//! - The known keys of `xor0`, `xor00` and `TINYencryption` are always tried.
+//!
+//! Candidates are ranked by the byte entropy of their plaintext: with the
+//! right key every column has the same plaintext distribution, so the whole
+//! file has the fewest distinct-looking bytes. A key found at a multiple of
+//! its length is folded back to the shortest period that explains the data
+//! about as well. Keys that differ by one constant byte throughout only
+//! rename the plaintext bytes and tie; a matching file signature and then
+//! English text break the tie.
+
+use std::cmp::Ordering;
+use std::io;
+
+pub mod keylen;
+pub mod magic;
+pub mod score;
+
+pub use keylen::KeyLength;
+
+/// The salt `xor0` uses as its whole key, and `xor00` puts before the
+/// password.
+pub const XOR0_SALT: &[u8] = b"MY_SALT_FOR_XOR_KEY";
+/// The byte `TINYencryption` XORs every byte with.
+pub const TINY_KEY: u8 = 42;
+/// Longest key length tried unless the caller asks for more.
+pub const DEFAULT_MAX_KEY_LEN: usize = 64;
+/// Bytes from the start of the file that the analysis looks at. The key is
+/// the same for the rest of the file.
+pub const SAMPLE_LEN: usize = 1 << 20;
+/// Best-ranked key lengths that keys are recovered for.
+pub const TRIED_LENGTHS: usize = 6;
+
+/// How much more a key may cost, relative to its own cost, when it is folded
+/// to a shorter period. Structured binary data lets a multiple of the key
+/// length fit the sample slightly better than the real key.
+const FOLD_TOLERANCE: f64 = 0.01;
+/// Share of printable bytes from which a plaintext counts as text.
+const TEXT_PRINTABLE: f64 = 0.95;
+
+/// Where a recovered key came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// The tool's built-in key.
+    Known(&'static str),
+    /// The given file signature, with the other columns scored.
+    Signature(&'static str),
+    /// Each column scored as English text.
+    English,
+    /// Each column's most common byte taken as a zero byte.
+    Zero,
+}
+
+impl Method {
+    pub fn describe(self) -> String {
+        match self {
+            Method::Known(tool) => format!("built-in key of {}", tool),
+            Method::Signature(name) => format!("{} signature", name),
+            Method::English => "English letter frequencies".to_string(),
+            Method::Zero => "zero bytes in binary data".to_string(),
+        }
+    }
+}
+
+/// A candidate key and how its plaintext looks.
+#[derive(Clone, Debug)]
+pub struct Recovery {
+    /// The key, folded down to its shortest period.
+    pub key: Vec<u8>,
+    pub method: Method,
+    /// Byte entropy of the plaintext sample, in bits per byte.
+    pub entropy: f64,
+    /// [`entropy`](Self::entropy) plus the key's own bits spread over the
+    /// sample, so that a longer key has to explain the data better.
+    pub cost: f64,
+    /// [`score::english`] of the plaintext sample.
+    pub english: f64,
+    /// [`score::printable`] of the plaintext sample.
+    pub printable: f64,
+    /// The file signature the plaintext starts with.
+    pub signature: Option<&'static str>,
+}
+
+/// The result of [`analyze`].
+#[derive(Debug)]
+pub struct Analysis {
+    /// Key lengths, best first.
+    pub lengths: Vec<KeyLength>,
+    /// Candidate keys, best first. Never empty.
+    pub keys: Vec<Recovery>,
+}
+
+impl Analysis {
+    pub fn best(&self) -> &Recovery {
+        &self.keys[0]
+    }
+}
+
+/// XORs `data` with `key` repeated from its first byte, which both encrypts
+/// and decrypts for all of the tools.
+pub fn xor(data: &[u8], key: &[u8]) -> Vec<u8> {
+    data.iter()
+        .zip(key.iter().cycle())
+        .map(|(d, k)| d ^ k)
+        .collect()
+}
+
+/// The shortest prefix of `key` that repeats to the whole key.
+pub fn period(key: &[u8]) -> &[u8] {
+    let p = (1..key.len())
+        .filter(|&p| key.len().is_multiple_of(p))
+        .find(|&p| key.iter().enumerate().all(|(i, &b)| b == key[i % p]))
+        .unwrap_or(key.len());
+    &key[..p]
+}
+
+/// Byte counts of each column of `data` for key length `len`.
+fn columns(data: &[u8], len: usize) -> Vec<[u64; 256]> {
+    let mut counts = vec![[0u64; 256]; len];
+    for (i, &b) in data.iter().enumerate() {
+        counts[i % len][b as usize] += 1;
+    }
+    counts
+}
+
+/// Byte counts of the plaintext under `key`, whose length divides the
+/// number of `columns`.
+fn plain_counts(columns: &[[u64; 256]], key: &[u8]) -> [u64; 256] {
+    let mut counts = [0u64; 256];
+    for (c, column) in columns.iter().enumerate() {
+        let k = key[c % key.len()] as usize;
+        for (b, &n) in column.iter().enumerate() {
+            counts[b ^ k] += n;
+        }
+    }
+    counts
+}
+
+fn english_key(columns: &[[u64; 256]]) -> Vec<u8> {
+    columns
+        .iter()
+        .map(|counts| {
+            (0..=255u8)
+                .max_by(|&a, &b| {
+                    score::english_counts(counts, a).total_cmp(&score::english_counts(counts, b))
+                })
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+/// The most common byte of each column, or of each `len`-th key byte when
+/// folding a key, with ties going to the lowest byte.
+fn majority<'a>(groups: impl Iterator<Item = &'a [u64; 256]>) -> Vec<u8> {
+    groups
+        .map(|counts| {
+            (0..=255u8)
+                .rev()
+                .max_by_key(|&b| counts[b as usize])
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+/// `key` folded to `len`, which divides its length, by a vote per column.
+fn fold(key: &[u8], len: usize) -> Vec<u8> {
+    let mut votes = vec![[0u64; 256]; len];
+    for (i, &b) in key.iter().enumerate() {
+        votes[i % len][b as usize] += 1;
+    }
+    majority(votes.iter())
+}
+
+fn recovery(sample: &[u8], columns: &[[u64; 256]], key: &[u8], method: Method) -> Recovery {
+    let counts = plain_counts(columns, key);
+    let head = xor(&sample[..sample.len().min(32)], key);
+    let key = period(key).to_vec();
+    let entropy = score::entropy(&counts);
+    Recovery {
+        cost: entropy + (key.len() * 8) as f64 / sample.len() as f64,
+        key,
+        method,
+        entropy,
+        english: score::english_counts(&counts, 0),
+        printable: score::printable(&counts),
+        signature: magic::identify(&head),
+    }
+}
+
+/// `r` folded to the shortest period that costs at most [`FOLD_TOLERANCE`]
+/// more.
+fn shortest(sample: &[u8], columns: &[[u64; 256]], r: Recovery) -> Recovery {
+    for len in (1..r.key.len()).filter(|&len| r.key.len().is_multiple_of(len)) {
+        let folded = recovery(sample, columns, &fold(&r.key, len), r.method);
+        if folded.cost <= r.cost * (1.0 + FOLD_TOLERANCE) {
+            return folded;
+        }
+    }
+    r
+}
+
+fn is_text(r: &Recovery) -> bool {
+    r.printable >= TEXT_PRINTABLE
+}
+
+/// Best first: lowest cost, then, between keys that only rename the
+/// plaintext bytes, a file signature, then English text, then the shorter
+/// key.
+fn rank(a: &Recovery, b: &Recovery) -> Ordering {
+    a.cost
+        .total_cmp(&b.cost)
+        .then(b.signature.is_some().cmp(&a.signature.is_some()))
+        .then(is_text(b).cmp(&is_text(a)))
+        .then_with(|| {
+            if is_text(a) && is_text(b) {
+                b.english.total_cmp(&a.english)
+            } else {
+                Ordering::Equal
+            }
+        })
+        .then(a.key.len().cmp(&b.key.len()))
+}
+
+/// Recovers the key of a repeating-key XOR ciphertext.
+///
+/// `key_len` skips the length estimate and only tries that length (and the
+/// known keys). Otherwise the [`TRIED_LENGTHS`] best lengths up to `max_len`
+/// and their divisors are tried. Only the first [`SAMPLE_LEN`] bytes are
+/// analysed.
+pub fn analyze(data: &[u8], max_len: usize, key_len: Option<usize>) -> io::Result<Analysis> {
+    if data.len() < 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "need at least two bytes of ciphertext",
+        ));
+    }
+    let sample = &data[..data.len().min(SAMPLE_LEN)];
+    let lengths = keylen::rank(sample, max_len.max(1));
+    let tried: Vec<usize> = match key_len {
+        Some(0) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the key length must be at least 1",
+            ))
+        }
+        Some(len) => vec![len.min(sample.len())],
+        None => {
+            // Structured binary data can rank a multiple of the key length
+            // above the length itself, so its divisors are tried too.
+            let mut tried: Vec<usize> = lengths
+                .iter()
+                .take(TRIED_LENGTHS)
+                .flat_map(|l| (1..=l.len).filter(move |&d| l.len.is_multiple_of(d)))
+                .collect();
+            tried.sort_unstable();
+            tried.dedup();
+            tried
+        }
+    };
+
+    let mut keys = Vec::new();
+    for (tool, key) in [("xor0", XOR0_SALT), ("TINYencryption", &[TINY_KEY][..])] {
+        keys.push(recovery(
+            sample,
+            &columns(sample, key.len()),
+            key,
+            Method::Known(tool),
+        ));
+    }
+    for len in tried {
+        let columns = columns(sample, len);
+        let english = english_key(&columns);
+        let zero = majority(columns.iter());
+        let mut found = Vec::new();
+        if len > XOR0_SALT.len() {
+            let mut salted = english.clone();
+            salted[..XOR0_SALT.len()].copy_from_slice(XOR0_SALT);
+            found.push(recovery(sample, &columns, &salted, Method::Known("xor00")));
+        }
+        for (name, sig) in magic::SIGNATURES {
+            for base in [&zero, &english] {
+                if let Some(key) = magic::key_from(sample, sig, base) {
+                    found.push(recovery(sample, &columns, &key, Method::Signature(name)));
+                }
+            }
+        }
+        found.push(recovery(sample, &columns, &zero, Method::Zero));
+        found.push(recovery(sample, &columns, &english, Method::English));
+        keys.extend(found.into_iter().map(|r| shortest(sample, &columns, r)));
+    }
+
+    // The same key can come out of several methods; keep the first, which
+    // names the known tool or signature where there is one.
+    let mut unique: Vec<Recovery> = Vec::new();
+    for r in keys {
+        if !unique.iter().any(|u| u.key == r.key) {
+            unique.push(r);
+        }
+    }
+    unique.sort_by(rank);
+    Ok(Analysis {
+        lengths,
+        keys: unique,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "It was a bright cold day in April, and the clocks were striking \
+        thirteen. Winston Smith, his chin nuzzled into his breast in an effort to escape \
+        the vile wind, slipped quickly through the glass doors of Victory Mansions, though \
+        not quickly enough to prevent a swirl of gritty dust from entering along with him. \
+        The hallway smelt of boiled cabbage and old rag mats. At one end of it a coloured \
+        poster, too large for indoor display, had been tacked to the wall. It depicted \
+        simply an enormous face, more than a metre wide: the face of a man of about \
+        forty-five, with a heavy black moustache and ruggedly handsome features. Winston \
+        made for the stairs. It was no use trying the lift. Even at the best of times it \
+        was seldom working, and at present the electric current was cut off during \
+        daylight hours. It was part of the economy drive in preparation for Hate Week.\n";
+
+    fn best_key(ciphertext: &[u8]) -> Vec<u8> {
+        analyze(ciphertext, DEFAULT_MAX_KEY_LEN, None)
+            .unwrap()
+            .best()
+            .key
+            .clone()
+    }
+
+    #[test]
+    fn recovers_password_and_salted_keys_from_text() {
+        for key in [
+            &b"hunter2"[..],
+            b"correct horse battery",
+            XOR0_SALT,
+            b"MY_SALT_FOR_XOR_KEYswordfish",
+            &[TINY_KEY],
+        ] {
+            let ciphertext = xor(TEXT.as_bytes(), key);
+            assert_eq!(best_key(&ciphertext), key, "key {:?}", key);
+        }
+    }
+
+    #[test]
+    fn recovers_binary_keys_from_signature_and_zero_bytes() {
+        let mut plain = b"\x7fELF\x02\x01\x01\0".to_vec();
+        let mut x = 1u32;
+        for _ in 0..4000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            plain.extend_from_slice(&[(x >> 24) as u8 & 0x0f, 0, 0, 0, (x >> 16) as u8, 0, 0, 0]);
+        }
+        let key = b"s3cr3t!";
+        let analysis = analyze(&xor(&plain, key), DEFAULT_MAX_KEY_LEN, None).unwrap();
+        assert_eq!(analysis.best().key, key);
+        assert_eq!(analysis.best().signature, Some("ELF"));
+    }
+
+    #[test]
+    fn folds_keys_to_their_period() {
+        assert_eq!(period(b"abcabcabc"), b"abc");
+        assert_eq!(period(b"abcab"), b"abcab");
+        assert_eq!(period(b"*"), b"*");
+    }
+}
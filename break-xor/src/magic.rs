@@ -0,0 +1,48 @@
+//! File signatures used as known plaintext.
+//!
+//! The XOR tools encrypt from the first byte of the file, so a file that
+//! starts with a known signature gives away the first key bytes outright.
+
+/// Signatures that sit at offset 0, with the name reported for each.
+pub const SIGNATURES: &[(&str, &[u8])] = &[
+    ("PNG", b"\x89PNG\r\n\x1a\n"),
+    ("PDF", b"%PDF-1."),
+    ("ZIP", b"PK\x03\x04"),
+    ("GZIP", b"\x1f\x8b\x08"),
+    ("ELF", b"\x7fELF"),
+    ("JPEG", b"\xff\xd8\xff"),
+    ("GIF", b"GIF8"),
+    ("7Z", b"7z\xbc\xaf\x27\x1c"),
+    ("RAR", b"Rar!\x1a\x07"),
+    ("SQLITE", b"SQLite format 3\0"),
+    ("PE", b"MZ"),
+];
+
+/// The name of the signature `data` starts with.
+pub fn identify(data: &[u8]) -> Option<&'static str> {
+    SIGNATURES
+        .iter()
+        .find(|(_, sig)| data.starts_with(sig))
+        .map(|(name, _)| *name)
+}
+
+/// The key of length `len` that turns the start of `data` into `sig`, with
+/// the key bytes the signature does not reach taken from `base`. `None` if
+/// the signature is longer than the key and asks for two different bytes in
+/// the same column, which rules the signature out at this length.
+pub fn key_from(data: &[u8], sig: &[u8], base: &[u8]) -> Option<Vec<u8>> {
+    if data.len() < sig.len() {
+        return None;
+    }
+    let mut key = base.to_vec();
+    let mut set = vec![false; key.len()];
+    for (i, (&c, &p)) in data.iter().zip(sig).enumerate() {
+        let col = i % key.len();
+        if set[col] && key[col] != c ^ p {
+            return None;
+        }
+        key[col] = c ^ p;
+        set[col] = true;
+    }
+    Some(key)
+}
@@ -0,0 +1,148 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+use std::time::Instant;
+
+use break_xor::{analyze, xor, Recovery, DEFAULT_MAX_KEY_LEN};
+use clap::Parser;
+
+/// Recovers the key of a file encrypted with repeating-key XOR (xor0, xor00,
+/// xor0000, TINYencryption) and decrypts it.
+///
+/// Prints the likely key lengths, the recovered key and the start of the
+/// plaintext. With an output file, writes the whole plaintext there.
+#[derive(Parser)]
+#[command(name = "break-xor", version, about)]
+struct Cli {
+    /// Encrypted file
+    input: PathBuf,
+
+    /// Where to write the plaintext (must not exist)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Longest key length to try
+    #[arg(long, default_value_t = DEFAULT_MAX_KEY_LEN)]
+    max_key_len: usize,
+
+    /// Only try this key length
+    #[arg(short = 'l', long, conflicts_with_all = ["key", "key_hex"])]
+    key_len: Option<usize>,
+
+    /// Decrypt with this key instead of recovering one
+    #[arg(short, long, conflicts_with = "key_hex")]
+    key: Option<String>,
+
+    /// Decrypt with this key, given in hex
+    #[arg(long)]
+    key_hex: Option<String>,
+
+    /// Candidate key lengths and keys to list
+    #[arg(long, default_value_t = 5)]
+    top: usize,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> io::Result<()> {
+    let data = fs::read(&cli.input)?;
+    let key = match (cli.key, cli.key_hex) {
+        (Some(key), _) => key.into_bytes(),
+        (_, Some(hex)) => parse_hex(&hex)?,
+        (None, None) => recover(&data, cli.max_key_len, cli.key_len, cli.top)?,
+    };
+    if key.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the key is empty",
+        ));
+    }
+    let plain = xor(&data, &key);
+    println!("Plaintext: {}", preview(&plain[..plain.len().min(160)]));
+
+    if let Some(output) = cli.output {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&output)?;
+        file.write_all(&plain)?;
+        file.sync_all()?;
+        println!("Wrote {} bytes to {}", plain.len(), output.display());
+    }
+    Ok(())
+}
+
+fn recover(data: &[u8], max_len: usize, key_len: Option<usize>, top: usize) -> io::Result<Vec<u8>> {
+    let start = Instant::now();
+    let analysis = analyze(data, max_len, key_len)?;
+    let elapsed = start.elapsed();
+
+    println!("Key lengths (Hamming bits/byte, index of coincidence x256):");
+    for l in analysis.lengths.iter().take(top) {
+        println!("  {:>4}  {:>5.2}  {:>6.2}", l.len, l.hamming, l.ioc);
+    }
+    println!("Keys (entropy bits/byte, English score, printable):");
+    for r in analysis.keys.iter().take(top) {
+        println!("  {}", describe(r));
+    }
+    let best = analysis.best();
+    println!(
+        "Recovered a {}-byte key from {} bytes of ciphertext in {:.0?}, from {}",
+        best.key.len(),
+        data.len(),
+        elapsed,
+        best.method.describe()
+    );
+    println!("Key: {}", preview(&best.key));
+    println!("Key (hex): {}", hex(&best.key));
+    if let Some(name) = best.signature {
+        println!("The plaintext has the {} file signature", name);
+    }
+    Ok(best.key.clone())
+}
+
+fn describe(r: &Recovery) -> String {
+    format!(
+        "{:>5.2}  {:>6.2}  {:>4.0}%  {:<32}  {}",
+        r.entropy,
+        r.english,
+        r.printable * 100.0,
+        preview(&r.key[..r.key.len().min(32)]),
+        r.method.describe()
+    )
+}
+
+/// `data` as text, with anything other than printable ASCII escaped.
+fn preview(data: &[u8]) -> String {
+    data.iter()
+        .flat_map(|&b| std::ascii::escape_default(b))
+        .map(char::from)
+        .collect()
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex(text: &str) -> io::Result<Vec<u8>> {
+    let text = text.trim();
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "the key is not valid hex");
+    if !text.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            text.get(i..i + 2)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
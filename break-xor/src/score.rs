@@ -0,0 +1,102 @@
+//! How much a run of bytes looks like English text, or like any real data
+//! at all, from its byte counts.
+
+use std::sync::OnceLock;
+
+/// Relative frequency of `a`..`z` in English text, in percent.
+const LETTERS: [f64; 26] = [
+    8.2, 1.5, 2.8, 4.3, 12.7, 2.2, 2.0, 6.1, 7.0, 0.15, 0.77, 4.0, 2.4, 6.7, 7.5, 1.9, 0.095, 6.0,
+    6.3, 9.1, 2.8, 0.98, 2.4, 0.15, 2.0, 0.074,
+];
+
+/// Log-probability of each byte value in plain English text. Letters follow
+/// [`LETTERS`], mostly lower case; space, newline and punctuation get their
+/// usual share, and control or non-ASCII bytes are all but ruled out.
+fn table() -> &'static [f64; 256] {
+    static TABLE: OnceLock<[f64; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut p = [1e-6; 256];
+        for (i, f) in LETTERS.iter().enumerate() {
+            p[b'a' as usize + i] = 0.70 * f / 100.0;
+            p[b'A' as usize + i] = 0.04 * f / 100.0;
+        }
+        for b in 0x21..0x7fu8 {
+            if !b.is_ascii_alphabetic() {
+                p[b as usize] = 0.0005;
+            }
+        }
+        for b in b'0'..=b'9' {
+            p[b as usize] = 0.003;
+        }
+        for &b in b".,'\"-" {
+            p[b as usize] = 0.006;
+        }
+        p[b' ' as usize] = 0.16;
+        p[b'\n' as usize] = 0.012;
+        p[b'\r' as usize] = 0.002;
+        p[b'\t' as usize] = 0.001;
+        p.map(f64::ln)
+    })
+}
+
+/// Byte counts of `data`.
+pub fn counts(data: &[u8]) -> [u64; 256] {
+    let mut counts = [0u64; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+    counts
+}
+
+/// Mean log-probability per byte of `data` as English. Around -3 for prose,
+/// below -8 for random or binary data; 0 for empty input.
+pub fn english(data: &[u8]) -> f64 {
+    english_counts(&counts(data), 0)
+}
+
+/// [`english`] of bytes with the given `counts`, each XORed with `key`.
+pub fn english_counts(counts: &[u64; 256], key: u8) -> f64 {
+    let n: u64 = counts.iter().sum();
+    if n == 0 {
+        return 0.0;
+    }
+    let table = table();
+    let total: f64 = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, &c)| c > 0)
+        .map(|(b, &c)| c as f64 * table[b ^ key as usize])
+        .sum();
+    total / n as f64
+}
+
+/// Share of bytes with the given `counts` that are printable ASCII or
+/// common whitespace.
+pub fn printable(counts: &[u64; 256]) -> f64 {
+    let n: u64 = counts.iter().sum();
+    if n == 0 {
+        return 0.0;
+    }
+    let printable: u64 = (0..=255u8)
+        .filter(|b| b.is_ascii_graphic() || matches!(b, b' ' | b'\n' | b'\r' | b'\t'))
+        .map(|b| counts[b as usize])
+        .sum();
+    printable as f64 / n as f64
+}
+
+/// Byte entropy, in bits per byte, of bytes with the given `counts`. The
+/// counts are summed in sorted order, so renaming the bytes (XORing them all
+/// with one value) gives exactly the same result.
+pub fn entropy(counts: &[u64; 256]) -> f64 {
+    let n = counts.iter().sum::<u64>() as f64;
+    let mut sorted = *counts;
+    sorted.sort_unstable();
+    sorted
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / n;
+            -p * p.log2()
+        })
+        .sum()
+}
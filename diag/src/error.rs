@@ -0,0 +1,178 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Exit code for bad arguments.
+pub const EXIT_USAGE: i32 = 2;
+/// Exit code when the key file does not exist.
+pub const EXIT_KEY_MISSING: i32 = 3;
+/// Exit code when the key file is shorter than the operation needs.
+pub const EXIT_KEY_TOO_SHORT: i32 = 4;
+/// Exit code when a MAC or tag does not verify.
+pub const EXIT_INTEGRITY: i32 = 5;
+/// Exit code for other I/O errors.
+pub const EXIT_IO: i32 = 6;
+
+/// Why a tool failed.
+#[derive(Debug)]
+pub enum ToolError {
+    /// Bad or missing arguments.
+    Usage(String),
+    /// The key file does not exist.
+    KeyMissing { path: PathBuf },
+    /// The key file has fewer bytes than the operation needs.
+    KeyTooShort {
+        path: PathBuf,
+        needed: u64,
+        available: u64,
+    },
+    /// A MAC or tag did not verify: the data was modified, or the key is
+    /// wrong.
+    Integrity(String),
+    /// An I/O error, with the file it happened on when that is known.
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+}
+
+impl ToolError {
+    pub fn usage(message: impl Into<String>) -> ToolError {
+        ToolError::Usage(message.into())
+    }
+
+    pub fn integrity(message: impl Into<String>) -> ToolError {
+        ToolError::Integrity(message.into())
+    }
+
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> ToolError {
+        ToolError::Io {
+            path: Some(path.into()),
+            source,
+        }
+    }
+
+    /// The process exit code for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ToolError::Usage(_) => EXIT_USAGE,
+            ToolError::KeyMissing { .. } => EXIT_KEY_MISSING,
+            ToolError::KeyTooShort { .. } => EXIT_KEY_TOO_SHORT,
+            ToolError::Integrity(_) => EXIT_INTEGRITY,
+            ToolError::Io { .. } => EXIT_IO,
+        }
+    }
+
+    /// A short name for the variant, safe to log at any level.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ToolError::Usage(_) => "usage",
+            ToolError::KeyMissing { .. } => "key_missing",
+            ToolError::KeyTooShort { .. } => "key_too_short",
+            ToolError::Integrity(_) => "integrity",
+            ToolError::Io { .. } => "io",
+        }
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolError::Usage(message) | ToolError::Integrity(message) => f.write_str(message),
+            ToolError::KeyMissing { path } => {
+                write!(f, "Key file '{}' does not exist.", path.display())
+            }
+            ToolError::KeyTooShort {
+                path,
+                needed,
+                available,
+            } => write!(
+                f,
+                "Key file '{}' is too short: {} bytes needed, {} available.",
+                path.display(),
+                needed,
+                available
+            ),
+            ToolError::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "{}: {}", path.display(), source),
+            ToolError::Io { path: None, source } => write!(f, "{}", source),
+        }
+    }
+}
+
+impl Error for ToolError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ToolError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ToolError {
+    fn from(source: io::Error) -> ToolError {
+        ToolError::Io { path: None, source }
+    }
+}
+
+/// Adds the file an I/O error happened on.
+pub trait IoContext<T> {
+    fn path_context(self, path: impl AsRef<Path>) -> Result<T, ToolError>;
+}
+
+impl<T> IoContext<T> for io::Result<T> {
+    fn path_context(self, path: impl AsRef<Path>) -> Result<T, ToolError> {
+        self.map_err(|source| ToolError::io(path.as_ref(), source))
+    }
+}
+
+/// Prints `error` to standard error, logs it and exits with its code.
+///
+/// The log gets the kind and exit code; the message, which may name files,
+/// only at debug level.
+pub fn fail(error: ToolError) -> ! {
+    eprintln!("Error: {}", error);
+    tracing::error!(kind = error.kind(), exit_code = error.exit_code(), "failed");
+    tracing::debug!(error = %error, "error details");
+    process::exit(error.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variants_have_distinct_exit_codes() {
+        let errors = [
+            ToolError::usage("usage"),
+            ToolError::KeyMissing {
+                path: "key.key".into(),
+            },
+            ToolError::KeyTooShort {
+                path: "key.key".into(),
+                needed: 10,
+                available: 5,
+            },
+            ToolError::integrity("tag"),
+            ToolError::from(io::Error::other("disk")),
+        ];
+        let mut codes: Vec<i32> = errors.iter().map(ToolError::exit_code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        assert!(!codes.contains(&0) && !codes.contains(&1));
+    }
+
+    #[test]
+    fn io_errors_name_their_file() {
+        let result: io::Result<()> = Err(io::Error::new(io::ErrorKind::NotFound, "gone"));
+        let error = result.path_context("notes.txt").unwrap_err();
+        assert_eq!(error.to_string(), "notes.txt: gone");
+        assert_eq!(error.exit_code(), EXIT_IO);
+        assert!(error.source().is_some());
+    }
+}
@@ -0,0 +1,20 @@
+//! Errors, exit codes and logging shared by the small command-line tools
+//! (`xor1`, `xor2`, `key1`).
+//!
+//! - [`ToolError`] names the ways the tools fail, and each variant has its
+//!   own exit code, so scripts can tell a missing key from a forged file.
+//! - [`LogOptions`] takes the logging flags out of the arguments and sets up
+//!   `tracing`: to standard error, or appended to a file, as text or JSON.
+//! - [`fail`] prints an error for the user, logs it and exits.
+//!
+//! Paths and error details are only logged at debug level (`-vv`). Nothing
+//! secret is ever logged.
+
+mod error;
+mod log;
+
+pub use error::{
+    fail, IoContext, ToolError, EXIT_INTEGRITY, EXIT_IO, EXIT_KEY_MISSING, EXIT_KEY_TOO_SHORT,
+    EXIT_USAGE,
+};
+pub use log::{LogFormat, LogOptions, LOG_USAGE};
@@ -0,0 +1,192 @@
+use std::fs::OpenOptions;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use tracing_subscriber::filter::LevelFilter;
+
+use crate::error::{IoContext, ToolError};
+
+/// The logging flags, for usage messages.
+pub const LOG_USAGE: &str = "[-v|-vv|-vvv] [-q] [--log-file PATH] [--log-format text|json]";
+
+/// How log lines are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Where and how much to log.
+///
+/// Without a log file nothing is logged unless `-v` is given, since errors
+/// are printed anyway. With one, warnings and errors are appended to it by
+/// default. Each `-v` raises the level: info, debug (paths and error
+/// details), trace.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogOptions {
+    pub verbosity: u8,
+    pub quiet: bool,
+    pub file: Option<PathBuf>,
+    pub format: LogFormat,
+}
+
+impl LogOptions {
+    /// Takes the logging flags out of `args` and returns the rest, program
+    /// name first. Arguments after `--` are left alone.
+    pub fn from_args(args: Vec<String>) -> Result<(LogOptions, Vec<String>), ToolError> {
+        let mut options = LogOptions::default();
+        let mut rest = Vec::with_capacity(args.len());
+        let mut args = args.into_iter();
+        if let Some(program) = args.next() {
+            rest.push(program);
+        }
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            match flag {
+                "--" => {
+                    rest.extend(args.by_ref());
+                    break;
+                }
+                "--verbose" => options.verbosity = options.verbosity.saturating_add(1),
+                "-q" | "--quiet" => options.quiet = true,
+                "--log-file" => {
+                    let value = inline.or_else(|| args.next());
+                    options.file = Some(value.ok_or_else(|| missing_value(flag))?.into());
+                }
+                "--log-format" => {
+                    let value = inline.or_else(|| args.next());
+                    options.format = match value.as_deref() {
+                        Some("text") => LogFormat::Text,
+                        Some("json") => LogFormat::Json,
+                        Some(other) => {
+                            return Err(ToolError::usage(format!(
+                                "Unknown log format '{}'. Use text or json.",
+                                other
+                            )))
+                        }
+                        None => return Err(missing_value(flag)),
+                    };
+                }
+                _ if flag.len() > 1
+                    && flag.starts_with('-')
+                    && flag[1..].bytes().all(|b| b == b'v') =>
+                {
+                    let count = u8::try_from(flag.len() - 1).unwrap_or(u8::MAX);
+                    options.verbosity = options.verbosity.saturating_add(count);
+                }
+                _ => rest.push(arg),
+            }
+        }
+        Ok((options, rest))
+    }
+
+    /// The most detailed level that is logged.
+    pub fn level(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::OFF;
+        }
+        match self.verbosity {
+            0 if self.file.is_some() => LevelFilter::WARN,
+            0 => LevelFilter::OFF,
+            1 => LevelFilter::INFO,
+            2 => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
+        }
+    }
+
+    /// Installs the global `tracing` subscriber.
+    pub fn init(&self) -> Result<(), ToolError> {
+        let builder = tracing_subscriber::fmt()
+            .with_max_level(self.level())
+            .with_target(false);
+        // Setting the subscriber only fails if one is already set.
+        let _ = match (&self.file, self.format) {
+            (Some(path), format) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .path_context(path)?;
+                let builder = builder.with_ansi(false).with_writer(Mutex::new(file));
+                match format {
+                    LogFormat::Text => builder.try_init(),
+                    LogFormat::Json => builder.json().try_init(),
+                }
+            }
+            (None, LogFormat::Text) => builder.with_writer(io::stderr).try_init(),
+            (None, LogFormat::Json) => builder.with_writer(io::stderr).json().try_init(),
+        };
+        Ok(())
+    }
+}
+
+fn missing_value(flag: &str) -> ToolError {
+    ToolError::usage(format!("{} needs a value.", flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<(LogOptions, Vec<String>), ToolError> {
+        LogOptions::from_args(args.iter().map(|a| a.to_string()).collect())
+    }
+
+    #[test]
+    fn flags_are_taken_out_of_the_arguments() {
+        let (options, rest) = parse(&[
+            "xor2",
+            "-vv",
+            "e",
+            "--log-file",
+            "tool.log",
+            "notes.txt",
+            "--log-format=json",
+        ])
+        .unwrap();
+        assert_eq!(rest, ["xor2", "e", "notes.txt"]);
+        assert_eq!(options.verbosity, 2);
+        assert_eq!(options.file, Some(PathBuf::from("tool.log")));
+        assert_eq!(options.format, LogFormat::Json);
+        assert_eq!(options.level(), LevelFilter::DEBUG);
+    }
+
+    #[test]
+    fn arguments_after_double_dash_are_kept() {
+        let (options, rest) = parse(&["xor1", "--", "-v", "--log-file"]).unwrap();
+        assert_eq!(rest, ["xor1", "-v", "--log-file"]);
+        assert_eq!(options, LogOptions::default());
+    }
+
+    #[test]
+    fn verbosity_stops_at_the_top() {
+        let many = format!("-{}", "v".repeat(300));
+        let (options, _) = parse(&["xor2", &many, "-vv", "--verbose"]).unwrap();
+        assert_eq!(options.verbosity, u8::MAX);
+        assert_eq!(options.level(), LevelFilter::TRACE);
+    }
+
+    #[test]
+    fn default_level_depends_on_the_sink() {
+        assert_eq!(LogOptions::default().level(), LevelFilter::OFF);
+        let (options, _) = parse(&["key1", "--log-file", "key1.log"]).unwrap();
+        assert_eq!(options.level(), LevelFilter::WARN);
+        let (options, _) = parse(&["key1", "-v", "-q"]).unwrap();
+        assert_eq!(options.level(), LevelFilter::OFF);
+    }
+
+    #[test]
+    fn bad_values_are_usage_errors() {
+        assert_eq!(
+            parse(&["xor1", "--log-format", "xml"]).unwrap_err().kind(),
+            "usage"
+        );
+        assert_eq!(parse(&["xor1", "--log-file"]).unwrap_err().kind(), "usage");
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use password::Source;
+use secmem::SecretBuf;
+use zeroize::Zeroizing;
+
+use crate::formats::Format;
+
+/// Credentials gathered on demand while trying candidate formats.
+///
+/// The key file is read and the password prompted for at most once, so
+/// trying several formats does not ask the user twice.
+pub struct Secrets {
+    key_path: PathBuf,
+    key: Option<SecretBuf>,
+    password: Option<Zeroizing<String>>,
+    password_prompt: String,
+}
+
+impl Secrets {
+    pub fn new(key_path: PathBuf) -> Self {
+        Secrets {
+            key_path,
+            key: None,
+            password: None,
+            password_prompt: "Password: ".to_string(),
+        }
+    }
+
+    /// Names the formats a password might be for, so the prompt can say so.
+    pub fn set_password_formats(&mut self, formats: &[Format]) {
+        let names: Vec<&str> = formats.iter().map(|f| f.name()).collect();
+        if !names.is_empty() {
+            self.password_prompt = format!("Password ({}): ", names.join(" or "));
+        }
+    }
+
+    pub fn key_path(&self) -> &Path {
+        &self.key_path
+    }
+
+    /// Contents of the key file, read on first use.
+    pub fn key_file(&mut self, format: Format) -> Result<&[u8]> {
+        if self.key.is_none() {
+            let contents = Zeroizing::new(fs::read(&self.key_path).with_context(|| {
+                format!(
+                    "{} needs a key file, but '{}' could not be read",
+                    format.name(),
+                    self.key_path.display()
+                )
+            })?);
+            self.key = Some(SecretBuf::from_slice(&contents)?);
+        }
+        Ok(self.key.as_deref().unwrap())
+    }
+
+    /// Size of the key file, if there is one. Used to narrow down legacy formats.
+    pub fn key_file_len(&self) -> Option<usize> {
+        fs::metadata(&self.key_path)
+            .ok()
+            .filter(|m| m.is_file())
+            .map(|m| m.len() as usize)
+    }
+
+    /// The password, prompted for on first use.
+    pub fn password(&mut self) -> Result<&str> {
+        if self.password.is_none() {
+            let password = Source::Terminal
+                .read(&self.password_prompt)
+                .map_err(|e| anyhow!("Failed to read password: {}", e))?;
+            self.password = Some(password);
+        }
+        Ok(self.password.as_ref().unwrap().as_str())
+    }
+}
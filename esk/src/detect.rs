@@ -0,0 +1,158 @@
+use crate::formats::{Format, FEISTEL_KEY_LEN};
+
+/// What a file looks like before any credential is tried.
+pub struct Detection {
+    /// Set when the file starts with one of the `ESK?` headers.
+    pub header: Option<Format>,
+    /// Formats worth trying, most likely first.
+    pub candidates: Vec<Format>,
+}
+
+/// Sniffs the format of `data`.
+///
+/// Headered files name their format outright. Headerless files from older
+/// versions are narrowed down by their length against each format's block
+/// and nonce sizes, and by the size of the key file (`key_len`), since the
+/// key-file formats each generate keys of a fixed length.
+pub fn detect(data: &[u8], key_len: Option<usize>) -> Detection {
+    if let Some(format) = from_header(data) {
+        return Detection {
+            header: Some(format),
+            candidates: vec![format],
+        };
+    }
+    Detection {
+        header: None,
+        candidates: legacy_candidates(data.len(), key_len),
+    }
+}
+
+fn from_header(data: &[u8]) -> Option<Format> {
+    if data.len() < 5 {
+        return None;
+    }
+    let format = match &data[..4] {
+        m if m == enc::MAGIC => Format::Gcm,
+        m if m == aes_siv_encryption_app::MAGIC => Format::Siv,
+        m if m == xchacha20_encryption_app::MAGIC => Format::Xchacha,
+        m if m == feistel_cipher::MAGIC => Format::FeistelHmac,
+        m if m == post_quantum_file_crypto::MAGIC => Format::Pq,
+        // These two are unauthenticated, so trust only a fully valid header.
+        _ if fiestel::read_header(data).is_some() => Format::Feistel,
+        _ if custom_spn_encryption::read_header(data).is_some() => Format::Spn,
+        _ => return None,
+    };
+    Some(format)
+}
+
+fn legacy_candidates(len: usize, key_len: Option<usize>) -> Vec<Format> {
+    use aes_siv_encryption_app as siv;
+    use feistel_cipher as fh;
+    use post_quantum_file_crypto as pq;
+    use xchacha20_encryption_app as xchacha;
+
+    let mut candidates = Vec::new();
+
+    // Authenticated formats that need only the key file come first, as they
+    // can be ruled out without prompting.
+    if key_len == Some(siv::KEY_LENGTH) && len >= siv::TAG_LENGTH {
+        candidates.push(Format::Siv);
+    }
+    if key_len == Some(xchacha::KEY_SIZE) && len >= xchacha::NONCE_SIZE + xchacha::TAG_SIZE {
+        candidates.push(Format::Xchacha);
+    }
+
+    // Then the formats that prompt for a password: the post-quantum tool
+    // (whose key file is itself sealed), GCM (salt, nonce and tag around any
+    // length) and the HMAC Feistel (IV, whole padded blocks, then the HMAC).
+    if key_len == Some(pq::KEY_FILE_SIZE) && len >= pq::NONCE_SIZE + pq::TAG_SIZE {
+        candidates.push(Format::Pq);
+    }
+    if len >= enc::SALT_LEN + enc::NONCE_LEN + enc::TAG_LEN {
+        candidates.push(Format::Gcm);
+    }
+    if len >= fh::IV_SIZE + fh::BLOCK_SIZE + fh::HMAC_SIZE
+        && (len - fh::IV_SIZE - fh::HMAC_SIZE).is_multiple_of(fh::BLOCK_SIZE)
+    {
+        candidates.push(Format::FeistelHmac);
+    }
+
+    // Unauthenticated formats last. SPN pads to whole blocks with a key of
+    // 16 bytes or more; the S-box Feistel works on any length but always
+    // generates a 16-byte key.
+    if let Some(key_len) = key_len {
+        if key_len >= custom_spn_encryption::BLOCK_SIZE
+            && len > 0
+            && len.is_multiple_of(custom_spn_encryption::BLOCK_SIZE)
+        {
+            candidates.push(Format::Spn);
+        }
+        if key_len == FEISTEL_KEY_LEN && len > 0 {
+            candidates.push(Format::Feistel);
+        }
+    }
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_name_their_format() {
+        let siv = aes_siv_encryption_app::seal(&[1u8; 64], b"", b"x").unwrap();
+        let xchacha = xchacha20_encryption_app::seal(&[1u8; 32], b"x").unwrap();
+        let mut feistel = fiestel::write_header(16);
+        feistel.extend_from_slice(b"xy");
+        let mut spn = custom_spn_encryption::write_header(16, 3);
+        spn.extend_from_slice(&[0u8; 16]);
+
+        for (data, format) in [
+            (siv, Format::Siv),
+            (xchacha, Format::Xchacha),
+            (feistel, Format::Feistel),
+            (spn, Format::Spn),
+        ] {
+            let detection = detect(&data, None);
+            assert_eq!(detection.header, Some(format));
+            assert_eq!(detection.candidates, vec![format]);
+        }
+    }
+
+    #[test]
+    fn a_crafted_spn_header_off_the_block_grid_is_not_trusted() {
+        let mut data = custom_spn_encryption::write_header(u32::MAX as usize, 3);
+        data.extend_from_slice(&[0u8; 15]);
+        assert_eq!(detect(&data, Some(16)).header, None);
+
+        // Claiming more plaintext than there is ciphertext is not a header either.
+        let mut data = custom_spn_encryption::write_header(16, 17);
+        data.extend_from_slice(&[0u8; 16]);
+        assert_eq!(detect(&data, Some(16)).header, None);
+    }
+
+    #[test]
+    fn the_key_file_size_narrows_headerless_files() {
+        // A 64-byte key can only be AES-SIV's, and is tried before the password formats.
+        let candidates = detect(&[0u8; 48], Some(64)).candidates;
+        assert_eq!(candidates.first(), Some(&Format::Siv));
+        assert!(!candidates.contains(&Format::Xchacha));
+
+        let candidates = detect(&[0u8; 48], Some(32)).candidates;
+        assert_eq!(candidates.first(), Some(&Format::Xchacha));
+
+        // A 16-byte key fits both unauthenticated formats, which come last.
+        let candidates = detect(&[0u8; 48], Some(16)).candidates;
+        assert_eq!(&candidates[candidates.len() - 2..], &[Format::Spn, Format::Feistel]);
+
+        // Without a key file only password formats are left.
+        let candidates = detect(&[0u8; 48], None).candidates;
+        assert!(candidates.iter().all(|f| f.uses_password()));
+    }
+
+    #[test]
+    fn too_little_data_has_no_candidates() {
+        assert!(detect(&[0u8; 4], None).candidates.is_empty());
+    }
+}
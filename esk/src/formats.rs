@@ -0,0 +1,268 @@
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use zeroize::Zeroizing;
+
+use crate::credentials::Secrets;
+
+/// Size of the key xxxx-Feistel-Network generates; shorter keys are refused.
+pub const FEISTEL_KEY_LEN: usize = 16;
+
+/// Highest round count taken from a header or `--rounds`. The tools default to
+/// 16, and headers are not authenticated, so a forged count must not decide
+/// how much memory or time decryption takes.
+pub const MAX_ROUNDS: usize = 1024;
+
+/// Every file format `esk` knows how to open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// yyy-AES-256-GCM (`ESKG`)
+    Gcm,
+    /// yyy-AES-SIV (`ESKS`)
+    Siv,
+    /// zzzchacha20-main and wwwww-misc/cha (`ESKX`)
+    Xchacha,
+    /// xxxx-Feistel-Network, AES S-box rounds (`ESKF`)
+    Feistel,
+    /// wwwww-misc/Feistel-Network, CBC with HMAC (`ESKH`)
+    FeistelHmac,
+    /// wwwww-misc/SPN-custom (`ESKP`)
+    Spn,
+    /// wwwww-misc/Post-Quantum-Crypto 333 (`ESKQ`)
+    Pq,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Gcm => "AES-256-GCM",
+            Format::Siv => "AES-SIV",
+            Format::Xchacha => "XChaCha20-Poly1305",
+            Format::Feistel => "Feistel (AES S-box)",
+            Format::FeistelHmac => "Feistel (HMAC)",
+            Format::Spn => "SPN",
+            Format::Pq => "Post-quantum XChaCha20-Poly1305",
+        }
+    }
+
+    /// Whether a wrong guess is caught by a tag or MAC. Unauthenticated
+    /// formats decrypt any input to something, so they are only tried when
+    /// nothing else fits.
+    pub fn authenticated(self) -> bool {
+        !matches!(self, Format::Feistel | Format::Spn)
+    }
+
+    /// Whether the format asks for a password (GCM may also use a key file).
+    pub fn uses_password(self) -> bool {
+        matches!(self, Format::Gcm | Format::FeistelHmac | Format::Pq)
+    }
+
+    /// Decrypts `data`, asking `secrets` for whatever credential the format uses.
+    pub fn decrypt(
+        self,
+        data: &[u8],
+        secrets: &mut Secrets,
+        options: &Options,
+    ) -> Result<Zeroizing<Vec<u8>>> {
+        match self {
+            Format::Gcm => {
+                let flags = enc::container_flags(data);
+                let password = if flags & enc::FLAG_PASSWORD != 0 {
+                    Some(Zeroizing::new(secrets.password()?.as_bytes().to_vec()))
+                } else {
+                    None
+                };
+                let key_file_hash = if flags & enc::FLAG_KEY_FILE != 0 {
+                    Some(enc::hash_key_file(secrets.key_path())?)
+                } else {
+                    None
+                };
+                let credentials = enc::Credentials {
+                    password: password.as_deref().map(|p| p.as_slice()),
+                    key_file_hash: key_file_hash.as_deref(),
+                };
+                enc::open_container(data, &credentials)
+            }
+            Format::Siv => {
+                let key = secrets.key_file(self)?;
+                aes_siv_encryption_app::open(key, options.aad.as_bytes(), data)
+            }
+            Format::Xchacha => {
+                let key = secrets.key_file(self)?;
+                xchacha20_encryption_app::open(key, data)
+            }
+            Format::Feistel => {
+                let key = secrets.key_file(self)?;
+                check_key_len(key, FEISTEL_KEY_LEN)?;
+                let (body, rounds) = match fiestel::read_header(data) {
+                    Some(rounds) => (&data[fiestel::HEADER_SIZE..], rounds),
+                    None => (data, options.rounds),
+                };
+                check_rounds(rounds)?;
+                if rounds == 0 || body.is_empty() {
+                    return Err(anyhow!("Nothing to decrypt with this key and round count."));
+                }
+                Ok(Zeroizing::new(fiestel::feistel_decrypt(body, key, rounds)))
+            }
+            Format::FeistelHmac => {
+                let password = secrets.password()?;
+                Ok(Zeroizing::new(feistel_cipher::decrypt_data(password.as_bytes(), data)?))
+            }
+            Format::Spn => {
+                let key = secrets.key_file(self)?;
+                check_key_len(key, custom_spn_encryption::BLOCK_SIZE)?;
+                match custom_spn_encryption::read_header(data) {
+                    Some((rounds, length)) => {
+                        check_rounds(rounds)?;
+                        let body = &data[custom_spn_encryption::HEADER_SIZE..];
+                        let mut plaintext = custom_spn_encryption::spn_decrypt(body, key, rounds);
+                        plaintext.truncate(length);
+                        Ok(Zeroizing::new(plaintext))
+                    }
+                    // Headerless files keep their zero padding.
+                    None => {
+                        check_rounds(options.rounds)?;
+                        let block = custom_spn_encryption::BLOCK_SIZE;
+                        if data.is_empty() || !data.len().is_multiple_of(block) {
+                            return Err(anyhow!(
+                                "SPN files are whole {}-byte blocks.",
+                                block
+                            ));
+                        }
+                        Ok(Zeroizing::new(custom_spn_encryption::spn_decrypt(
+                            data,
+                            key,
+                            options.rounds,
+                        )))
+                    }
+                }
+            }
+            Format::Pq => {
+                let key_file = Zeroizing::new(secrets.key_file(self)?.to_vec());
+                let passphrase = secrets.password()?;
+                let key = Zeroizing::new(post_quantum_file_crypto::unlock_key(&key_file, passphrase)?);
+                Ok(Zeroizing::new(post_quantum_file_crypto::open(&key, data)?))
+            }
+        }
+    }
+}
+
+/// Refuses a key file too short for the format's key schedule.
+fn check_key_len(key: &[u8], min: usize) -> Result<()> {
+    if key.len() < min {
+        return Err(anyhow!(
+            "The key file has {} bytes; this format needs at least {}.",
+            key.len(),
+            min
+        ));
+    }
+    Ok(())
+}
+
+/// Refuses a round count above [`MAX_ROUNDS`].
+fn check_rounds(rounds: usize) -> Result<()> {
+    if rounds > MAX_ROUNDS {
+        return Err(anyhow!(
+            "{} rounds is more than the {} this tool accepts.",
+            rounds,
+            MAX_ROUNDS
+        ));
+    }
+    Ok(())
+}
+
+/// Format parameters that are not stored in legacy files.
+pub struct Options {
+    /// Associated data for AES-SIV.
+    pub aad: String,
+    /// Round count for headerless Feistel and SPN files.
+    pub rounds: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const PLAINTEXT: &[u8] = b"shelf 12, box 3: the 1998 tax returns";
+
+    fn options() -> Options {
+        Options { aad: String::new(), rounds: 16 }
+    }
+
+    /// Writes `key` to a key file of its own and returns credentials reading it.
+    fn secrets(name: &str, key: &[u8]) -> (Secrets, PathBuf) {
+        let path = std::env::temp_dir().join(format!("esk-{}-{}.key", name, std::process::id()));
+        fs::write(&path, key).unwrap();
+        (Secrets::new(path.clone()), path)
+    }
+
+    fn decrypt(format: Format, name: &str, key: &[u8], data: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        let (mut secrets, path) = secrets(name, key);
+        let result = format.decrypt(data, &mut secrets, &options());
+        fs::remove_file(path).unwrap();
+        result
+    }
+
+    fn spn_file(key: &[u8], rounds: usize) -> Vec<u8> {
+        let mut data = custom_spn_encryption::write_header(rounds, PLAINTEXT.len());
+        data.extend(custom_spn_encryption::spn_encrypt(PLAINTEXT, key, rounds));
+        data
+    }
+
+    #[test]
+    fn key_file_formats_decrypt_what_their_tools_wrote() {
+        let siv_key = [3u8; aes_siv_encryption_app::KEY_LENGTH];
+        let sealed = aes_siv_encryption_app::seal(&siv_key, b"", PLAINTEXT).unwrap();
+        assert_eq!(&*decrypt(Format::Siv, "siv", &siv_key, &sealed).unwrap(), PLAINTEXT);
+
+        let xchacha_key = [4u8; xchacha20_encryption_app::KEY_SIZE];
+        let sealed = xchacha20_encryption_app::seal(&xchacha_key, PLAINTEXT).unwrap();
+        let opened = decrypt(Format::Xchacha, "xchacha", &xchacha_key, &sealed).unwrap();
+        assert_eq!(&*opened, PLAINTEXT);
+
+        // The S-box Feistel splits the data in half, so give it an even length.
+        let feistel_key = [5u8; FEISTEL_KEY_LEN];
+        let even = &PLAINTEXT[..PLAINTEXT.len() & !1];
+        let mut data = fiestel::write_header(16);
+        data.extend(fiestel::feistel_encrypt(even, &feistel_key, 16));
+        assert_eq!(&*decrypt(Format::Feistel, "feistel", &feistel_key, &data).unwrap(), even);
+
+        let spn_key = [6u8; 24];
+        let data = spn_file(&spn_key, 12);
+        assert_eq!(&*decrypt(Format::Spn, "spn", &spn_key, &data).unwrap(), PLAINTEXT);
+    }
+
+    #[test]
+    fn a_forged_round_count_is_refused_before_allocating() {
+        let key = [6u8; 16];
+        let mut data = spn_file(&key, 16);
+        data[5..9].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = decrypt(Format::Spn, "spn-rounds", &key, &data).unwrap_err();
+        assert!(err.to_string().contains("rounds"), "{}", err);
+
+        let mut data = fiestel::write_header(16);
+        data.extend(fiestel::feistel_encrypt(PLAINTEXT, &key, 16));
+        data[5..9].copy_from_slice(&(MAX_ROUNDS as u32 + 1).to_le_bytes());
+        assert!(decrypt(Format::Feistel, "feistel-rounds", &key, &data).is_err());
+    }
+
+    #[test]
+    fn short_key_files_are_refused_instead_of_panicking() {
+        let data = spn_file(&[6u8; 16], 16);
+        let err = decrypt(Format::Spn, "spn-short", &[6u8; 8], &data).unwrap_err();
+        assert!(err.to_string().contains("at least 16"), "{}", err);
+
+        let mut data = fiestel::write_header(16);
+        data.extend(fiestel::feistel_encrypt(PLAINTEXT, &[5u8; 16], 16));
+        assert!(decrypt(Format::Feistel, "feistel-short", &[5u8; 4], &data).is_err());
+    }
+
+    #[test]
+    fn headerless_spn_data_must_be_whole_blocks() {
+        let key = [6u8; 16];
+        assert!(decrypt(Format::Spn, "spn-ragged", &key, &[0u8; 20]).is_err());
+        let whole = custom_spn_encryption::spn_encrypt(&[7u8; 32], &key, 16);
+        assert_eq!(&*decrypt(Format::Spn, "spn-legacy", &key, &whole).unwrap(), &[7u8; 32]);
+    }
+}
@@ -0,0 +1,199 @@
+mod credentials;
+mod detect;
+mod formats;
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use credentials::Secrets;
+use detect::detect;
+use formats::{Format, Options};
+
+const DEFAULT_KEY_FILE: &str = "key.key";
+
+/// Decrypts files from any of the esk tools, working out which one made them.
+#[derive(Parser)]
+#[command(name = "esk", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Reports which tool most likely produced each file
+    Detect {
+        /// Encrypted files to inspect
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    /// Detects a file's format, asks for its credential and decrypts it
+    Decrypt {
+        /// Encrypted file
+        input: String,
+        /// Where to write the plaintext (default: INPUT without `.enc`, or INPUT.dec)
+        #[arg(short, long)]
+        output: Option<String>,
+        #[command(flatten)]
+        key: KeyArgs,
+        /// Skip detection and use this format
+        #[arg(short, long, value_enum)]
+        format: Option<Format>,
+        /// Associated data the file was encrypted with (AES-SIV)
+        #[arg(short, long, default_value = "")]
+        aad: String,
+        /// Round count for headerless Feistel and SPN files
+        #[arg(short, long, default_value_t = 16)]
+        rounds: usize,
+    },
+}
+
+#[derive(Args)]
+struct KeyArgs {
+    /// Key file, for the tools that use one
+    #[arg(short, long, default_value = DEFAULT_KEY_FILE)]
+    key: PathBuf,
+}
+
+fn main() -> Result<()> {
+    // Keep key material out of core dumps.
+    if let Err(e) = secmem::harden_process() {
+        eprintln!("Warning: could not disable core dumps: {}", e);
+    }
+
+    let cli = Cli::parse();
+    match cli.command {
+        Commands::Detect { inputs, key } => {
+            let key_len = Secrets::new(key.key).key_file_len();
+            for input in &inputs {
+                let data = fs::read(input)
+                    .with_context(|| format!("Failed to read input file '{}'.", input))?;
+                println!("{}: {}", input, describe(&data, key_len));
+            }
+            Ok(())
+        }
+        Commands::Decrypt {
+            input,
+            output,
+            key,
+            format,
+            aad,
+            rounds,
+        } => {
+            let output = output.unwrap_or_else(|| default_output(&input));
+            let options = Options { aad, rounds };
+            decrypt_file(&input, &output, key.key, format, &options)
+        }
+    }
+}
+
+fn describe(data: &[u8], key_len: Option<usize>) -> String {
+    let detection = detect(data, key_len);
+    if let Some(format) = detection.header {
+        return format!("{} (header)", format.name());
+    }
+    if detection.candidates.is_empty() {
+        return "unknown".to_string();
+    }
+    let names: Vec<&str> = detection.candidates.iter().map(|f| f.name()).collect();
+    format!("no header; could be {}", names.join(", "))
+}
+
+/// `file.enc` decrypts to `file`; anything else gets `.dec` appended.
+fn default_output(input: &str) -> String {
+    match input.strip_suffix(".enc") {
+        Some(stem) if !stem.is_empty() => stem.to_string(),
+        _ => format!("{}.dec", input),
+    }
+}
+
+fn decrypt_file(
+    input: &str,
+    output: &str,
+    key_path: PathBuf,
+    format: Option<Format>,
+    options: &Options,
+) -> Result<()> {
+    let data =
+        fs::read(input).with_context(|| format!("Failed to read input file '{}'.", input))?;
+    let mut secrets = Secrets::new(key_path);
+
+    let (candidates, from_header) = match format {
+        Some(format) => (vec![format], true),
+        None => {
+            let detection = detect(&data, secrets.key_file_len());
+            (detection.candidates, detection.header.is_some())
+        }
+    };
+    if candidates.is_empty() {
+        return Err(anyhow!(
+            "Cannot tell which tool produced '{}'. Pass --format, or --key if it used a key file.",
+            input
+        ));
+    }
+
+    let password_formats: Vec<Format> =
+        candidates.iter().copied().filter(|f| f.uses_password()).collect();
+    secrets.set_password_formats(&password_formats);
+
+    let (authenticated, unauthenticated): (Vec<Format>, Vec<Format>) =
+        candidates.into_iter().partition(|f| f.authenticated());
+
+    // A wrong guess fails authentication, so these can simply be tried in turn.
+    let mut failures = Vec::new();
+    for format in authenticated {
+        match format.decrypt(&data, &mut secrets, options) {
+            Ok(plaintext) => return write_plaintext(input, output, format, &plaintext),
+            Err(e) => failures.push(format!("  {}: {}", format.name(), e)),
+        }
+    }
+
+    // Unauthenticated formats always "succeed", so they are only guessed when
+    // nothing authenticated fits and there is a single candidate left.
+    match unauthenticated.as_slice() {
+        [] => {}
+        [format] if !failures.is_empty() && !from_header => failures.push(format!(
+            "  could also be {}, which cannot be checked; pass --format {} to try it",
+            format.name(),
+            format.to_possible_value().unwrap().get_name()
+        )),
+        [format] => {
+            eprintln!(
+                "Warning: {} files are not authenticated; a wrong key or format gives garbage, not an error.",
+                format.name()
+            );
+            if !from_header {
+                eprintln!("Warning: '{}' has no header; guessing {}.", input, format.name());
+            }
+            match format.decrypt(&data, &mut secrets, options) {
+                Ok(plaintext) => return write_plaintext(input, output, *format, &plaintext),
+                Err(e) => failures.push(format!("  {}: {}", format.name(), e)),
+            }
+        }
+        several => {
+            let names: Vec<&str> = several.iter().map(|f| f.name()).collect();
+            failures.push(format!(
+                "  could also be {}, which cannot be told apart; pass --format",
+                names.join(" or ")
+            ));
+        }
+    }
+
+    Err(anyhow!(
+        "Could not decrypt '{}':\n{}",
+        input,
+        failures.join("\n")
+    ))
+}
+
+fn write_plaintext(input: &str, output: &str, format: Format, plaintext: &[u8]) -> Result<()> {
+    fs::write(output, plaintext)
+        .with_context(|| format!("Failed to write output file '{}'.", output))?;
+    println!("Decrypted '{}' ({}) to '{}'.", input, format.name(), output);
+    Ok(())
+}
@@ -1,51 +1,61 @@
 use sha2::{Digest, Sha256};
 use std::{
-    fs::{File, OpenOptions},
+    env,
+    fs::File,
     io::{BufWriter, Write},
-    process,
 };
+use diag::{IoContext, LogOptions, ToolError, LOG_USAGE};
 use rpassword::read_password;
+use tracing::{debug, info};
 
 const SALT: &str = "MY_HARD_CODED_SALT";
 const MAX_KEY_SIZE: u64 = 5_000_000_000; // 5 GB
 const BLOCK_SIZE: usize = 32; // 32 bytes for each SHA-256 output
+const KEY_FILE: &str = "key.key";
 
 fn main() {
-    if let Err(err) = run() {
-        // If there's an error, we log it and print a short message to stderr.
-        log_error(&err.to_string());
-        eprintln!("An error occurred. Check error.txt for details.");
-        process::exit(1);
+    let (logging, args) =
+        LogOptions::from_args(env::args().collect()).unwrap_or_else(|e| diag::fail(e));
+    if let Err(e) = logging.init() {
+        diag::fail(e);
+    }
+    if let Err(e) = run(&args) {
+        // If there's an error, print it, log its kind and exit with its code.
+        diag::fail(e);
     }
 }
 
-fn run() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = std::env::args().collect();
+fn run(args: &[String]) -> Result<(), ToolError> {
     if args.len() < 2 {
-        return Err("Usage: key_maker <length_in_bytes>".into());
+        return Err(ToolError::usage(format!(
+            "Usage: key_maker <length_in_bytes> {}",
+            LOG_USAGE
+        )));
     }
 
     // 1) Parse desired length in bytes
-    let length: u64 = args[1].parse().map_err(|_| "length_in_bytes must be a valid integer.")?;
+    let length: u64 = args[1]
+        .parse()
+        .map_err(|_| ToolError::usage("length_in_bytes must be a valid integer."))?;
 
     // 2) Check bounds
-    if length < 1 || length > MAX_KEY_SIZE {
-        return Err(format!(
+    if !(1..=MAX_KEY_SIZE).contains(&length) {
+        return Err(ToolError::usage(format!(
             "length_in_bytes must be between 1 and {} (5 GB).",
             MAX_KEY_SIZE
-        )
-        .into());
+        )));
     }
 
-    // 3) Prompt user for password (not echoed)
+    // 3) Prompt user for password (not echoed). It is never logged.
     println!("Enter password (will not be echoed):");
     let password = read_password()?;
 
     println!("Generating key of {} bytes...", length);
+    info!(length, "generating key");
+    debug!(path = KEY_FILE, "output");
 
     // 4) Open "key.key" for writing
-    let file = File::create("key.key")
-        .map_err(|_| "Failed to create 'key.key' file in current directory.")?;
+    let file = File::create(KEY_FILE).path_context(KEY_FILE)?;
     let mut writer = BufWriter::new(file);
 
     // 5) Write bytes in a streaming fashion to avoid large memory usage
@@ -64,27 +74,16 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
         let remaining = length - total_written;
         let to_write = std::cmp::min(remaining, BLOCK_SIZE as u64) as usize;
 
-        writer.write_all(&block_hash[..to_write])?;
+        writer.write_all(&block_hash[..to_write]).path_context(KEY_FILE)?;
 
         total_written += to_write as u64;
         block_index += 1;
     }
 
-    writer.flush()?;
+    writer.flush().path_context(KEY_FILE)?;
 
-    println!("Successfully wrote {} bytes to 'key.key'.", total_written);
+    println!("Successfully wrote {} bytes to '{}'.", total_written, KEY_FILE);
+    info!(bytes = total_written, "done");
 
     Ok(())
 }
-
-/// Log an error message to "error.txt" in the current directory.
-/// If opening/writing to error.txt fails, we do nothing more than swallow that error.
-fn log_error(msg: &str) {
-    if let Ok(mut file) = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("error.txt")
-    {
-        let _ = writeln!(file, "{}", msg);
-    }
-}
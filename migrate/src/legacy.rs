@@ -0,0 +1,110 @@
+//! The unauthenticated XOR schemes, exactly as the old tools run them.
+//!
+//! Each scheme XORs the whole file from its first byte with a keystream, so
+//! the same call encrypts and decrypts.
+
+use std::fmt;
+
+use break_xor::{TINY_KEY, XOR0_SALT};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+/// A legacy tool's way of turning its credential into a keystream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Scheme {
+    /// `xor0`: `MY_SALT_FOR_XOR_KEY` repeated. No password.
+    Xor0,
+    /// `xor00`: `MY_SALT_FOR_XOR_KEY` and the password, repeated.
+    Xor00,
+    /// `xor000`: SHA-256 of the password and a block counter.
+    Xor000,
+    /// `xor0000`: the password repeated.
+    Xor0000,
+    /// `TINYencryption`: every byte XORed with 42. No password.
+    Tiny,
+}
+
+impl Scheme {
+    pub fn name(self) -> &'static str {
+        match self {
+            Scheme::Xor0 => "xor0",
+            Scheme::Xor00 => "xor00",
+            Scheme::Xor000 => "xor000",
+            Scheme::Xor0000 => "xor0000",
+            Scheme::Tiny => "tiny",
+        }
+    }
+
+    /// Whether the tool asked for a password.
+    pub fn needs_password(self) -> bool {
+        matches!(self, Scheme::Xor00 | Scheme::Xor000 | Scheme::Xor0000)
+    }
+
+    /// Encrypts or decrypts `data` with the tool's keystream.
+    ///
+    /// The tools read the password with `read_line` and trimmed it, so it is
+    /// trimmed here too. `xor0000` divided by the password length and could
+    /// not run with an empty password, so that is refused.
+    pub fn apply(self, data: &[u8], password: &str) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+        let password = password.trim();
+        let key: Zeroizing<Vec<u8>> = match self {
+            Scheme::Xor0 => Zeroizing::new(XOR0_SALT.to_vec()),
+            Scheme::Xor00 => Zeroizing::new([XOR0_SALT, password.as_bytes()].concat()),
+            Scheme::Xor000 => expand_key(password, data.len()),
+            Scheme::Xor0000 => {
+                if password.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "xor0000 cannot have used an empty password."
+                    ));
+                }
+                Zeroizing::new(password.as_bytes().to_vec())
+            }
+            Scheme::Tiny => Zeroizing::new(vec![TINY_KEY]),
+        };
+        Ok(Zeroizing::new(break_xor::xor(data, &key)))
+    }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// `xor000`'s keystream: SHA-256 of the password and a little-endian 64-bit
+/// block counter, block after block, cut to `length` bytes.
+pub fn expand_key(password: &str, length: usize) -> Zeroizing<Vec<u8>> {
+    let mut key = Zeroizing::new(Vec::with_capacity(length + 32));
+    let mut block_count = 0u64;
+    while key.len() < length {
+        let mut hasher = Sha256::new();
+        hasher.update(password.as_bytes());
+        hasher.update(block_count.to_le_bytes());
+        key.extend_from_slice(&hasher.finalize());
+        block_count += 1;
+    }
+    key.truncate(length);
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schemes_match_the_tools() {
+        // xor000's first key block is SHA-256(password || 0u64 LE).
+        let first = Sha256::digest(b"pw\0\0\0\0\0\0\0\0");
+        assert_eq!(&expand_key("pw", 40)[..32], &first[..]);
+        assert_eq!(expand_key("pw", 40).len(), 40);
+
+        assert_eq!(&Scheme::Tiny.apply(b"AB", "").unwrap()[..], b"kh");
+        assert_eq!(&Scheme::Xor0.apply(b"\0\0", "").unwrap()[..], b"MY");
+        assert_eq!(
+            &Scheme::Xor00.apply(&[0; 21], " pw\n").unwrap()[19..],
+            b"pw"
+        );
+        assert_eq!(&Scheme::Xor0000.apply(&[0; 5], "ab").unwrap()[..], b"ababa");
+        assert!(Scheme::Xor0000.apply(b"x", " ").is_err());
+    }
+}
@@ -0,0 +1,214 @@
+//! Moves files from the unauthenticated XOR tools (`xor0`, `xor00`, `xor000`,
+//! `xor0000`, `TINYencryption`) into the AES-256-GCM container of
+//! `yyy-AES-256-GCM`, which `enc` and `esk` decrypt.
+//!
+//! A file is only replaced once the new container has been written, read
+//! back from disk and decrypted to the same plaintext. The legacy schemes
+//! have no magic bytes or checksum, so whether the legacy decryption itself
+//! was right can only be guessed, from the plaintext's entropy.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use enc::{open_container, seal_container, Credentials, MAGIC};
+
+mod legacy;
+
+pub use legacy::{expand_key, Scheme};
+
+/// Extension of the migrated container, as `enc` writes it.
+const ENC_EXTENSION: &str = "enc";
+/// Suffix of the container while it is being written and checked.
+const TMP_SUFFIX: &str = ".migrate-tmp";
+
+/// Largest share of the legacy file's byte entropy that a decrypted file may
+/// keep, for the schemes where a wrong password goes unnoticed. Small random
+/// files vary too much in entropy for a fixed margin.
+const MAX_ENTROPY_RATIO: f64 = 0.9;
+
+/// How [`migrate_file`] treats the legacy file.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Options {
+    /// Migrate even if the plaintext does not look decrypted, as happens
+    /// for files that were compressed before encryption.
+    pub force: bool,
+    /// Leave the legacy file in place.
+    pub keep: bool,
+}
+
+/// Where a migrated file goes: the same name for `xor0` and `xor00` files,
+/// which already end in `.enc`, and `<input>.enc` for the tools that
+/// overwrote their input.
+pub fn migrated_path(input: &Path) -> PathBuf {
+    if input.extension().is_some_and(|ext| ext == ENC_EXTENSION) {
+        input.to_path_buf()
+    } else {
+        let mut name = input.as_os_str().to_owned();
+        name.push(".");
+        name.push(ENC_EXTENSION);
+        PathBuf::from(name)
+    }
+}
+
+fn entropy(data: &[u8]) -> f64 {
+    break_xor::score::entropy(&break_xor::score::counts(data))
+}
+
+/// Migrates one legacy file and returns the path of the container.
+///
+/// `password` is the legacy tool's password (ignored by the schemes without
+/// one); `credentials` protect the new container.
+pub fn migrate_file(
+    input: &Path,
+    scheme: Scheme,
+    password: &str,
+    credentials: &Credentials,
+    options: Options,
+) -> anyhow::Result<PathBuf> {
+    let legacy = fs::read(input)?;
+    if legacy.starts_with(MAGIC) {
+        return Err(anyhow::anyhow!("Already an AES-256-GCM container."));
+    }
+    let plaintext = scheme.apply(&legacy, password)?;
+
+    // XOR never fails, so a wrong password or scheme only shows in the
+    // result. The right keystream takes away the ciphertext's randomness;
+    // any other leaves it (or adds more).
+    if scheme.needs_password()
+        && !options.force
+        && !legacy.is_empty()
+        && entropy(&plaintext) > entropy(&legacy) * MAX_ENTROPY_RATIO
+    {
+        return Err(anyhow::anyhow!(
+            "The result does not look decrypted (wrong password or scheme?). \
+             Use --force if the file was compressed before encryption."
+        ));
+    }
+    let output = migrated_path(input);
+    if output != input && output.exists() {
+        return Err(anyhow::anyhow!("{} already exists.", output.display()));
+    }
+    let contents = seal_container(&plaintext, credentials)?;
+
+    let mut tmp = output.as_os_str().to_owned();
+    tmp.push(TMP_SUFFIX);
+    let tmp = PathBuf::from(tmp);
+    let checked = write_and_check(&tmp, &contents, &plaintext, credentials);
+    if let Err(e) = checked {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    fs::rename(&tmp, &output)?;
+    if output != input && !options.keep {
+        fs::remove_file(input)?;
+    }
+    sync_parent(&output)?;
+    Ok(output)
+}
+
+/// Writes the container to a new file, syncs it, and decrypts what is on
+/// disk back to `plaintext`.
+fn write_and_check(
+    path: &Path,
+    contents: &[u8],
+    plaintext: &[u8],
+    credentials: &Credentials,
+) -> anyhow::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    let written = fs::read(path)?;
+    let decrypted = open_container(&written, credentials)?;
+    if *decrypted != plaintext {
+        return Err(anyhow::anyhow!(
+            "The new container did not decrypt to the same data."
+        ));
+    }
+    Ok(())
+}
+
+/// Makes the rename and the removal of the legacy file durable.
+fn sync_parent(path: &Path) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &[u8] = b"Quarterly figures, second draft. Do not circulate outside the team.\n\
+        Revenue is up on the year, mostly from the renewals signed in March, while\n\
+        hardware costs stayed flat. The hiring plan below assumes the same rate.\n";
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("migrate-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn migrates_and_removes_the_legacy_file() {
+        let dir = scratch("roundtrip");
+        let credentials = Credentials {
+            password: Some(b"new password"),
+            key_file_hash: None,
+        };
+
+        let in_place = dir.join("report.txt");
+        fs::write(&in_place, &*Scheme::Xor000.apply(TEXT, "old").unwrap()).unwrap();
+        let wrong = migrate_file(
+            &in_place,
+            Scheme::Xor000,
+            "wrong",
+            &credentials,
+            Options::default(),
+        );
+        assert!(wrong.is_err());
+        assert!(in_place.exists() && !dir.join("report.txt.enc").exists());
+
+        let output = migrate_file(
+            &in_place,
+            Scheme::Xor000,
+            "old",
+            &credentials,
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(output, dir.join("report.txt.enc"));
+        assert!(!in_place.exists());
+        assert_eq!(
+            &*open_container(&fs::read(&output).unwrap(), &credentials).unwrap(),
+            TEXT
+        );
+
+        let renamed = dir.join("notes.txt.enc");
+        fs::write(&renamed, &*Scheme::Xor0.apply(TEXT, "").unwrap()).unwrap();
+        let output =
+            migrate_file(&renamed, Scheme::Xor0, "", &credentials, Options::default()).unwrap();
+        assert_eq!(output, renamed);
+        assert_eq!(
+            &*open_container(&fs::read(&output).unwrap(), &credentials).unwrap(),
+            TEXT
+        );
+        assert!(
+            migrate_file(&renamed, Scheme::Xor0, "", &credentials, Options::default()).is_err()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,193 @@
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::Parser;
+use password::Source;
+use zeroize::Zeroizing;
+
+use enc::{hash_key_file, Credentials};
+use migrate::{migrate_file, Options, Scheme};
+use secmem::SecretBuf;
+
+/// Exit code when every file was migrated.
+const EXIT_OK: u8 = 0;
+/// Exit code when at least one file failed.
+const EXIT_FAILED: u8 = 1;
+/// Exit code for bad arguments or unreadable credentials.
+const EXIT_USAGE: u8 = 2;
+
+/// Moves files from the legacy XOR tools into the AES-256-GCM container of
+/// `enc`.
+///
+/// Each file is decrypted with the legacy scheme, sealed into a new
+/// container, and the container is read back and checked before the legacy
+/// file is removed. `.enc` files (from xor0 and xor00) are replaced under the
+/// same name; other files become `<file>.enc`.
+#[derive(Parser)]
+#[command(name = "migrate", version, about)]
+struct Cli {
+    /// The tool that encrypted the files
+    #[arg(value_enum)]
+    scheme: Scheme,
+
+    /// Files, or directories whose files are all migrated
+    inputs: Vec<PathBuf>,
+
+    /// Also migrate the files named in this listing, one per line ("-" for
+    /// standard input)
+    #[arg(short, long)]
+    list: Option<PathBuf>,
+
+    /// Read the legacy password from the first line of this file
+    #[arg(long, group = "legacy_source")]
+    legacy_password_file: Option<PathBuf>,
+
+    /// Read the legacy password from this environment variable
+    #[arg(long, group = "legacy_source")]
+    legacy_password_env: Option<String>,
+
+    /// Read the new password from the first line of this file
+    #[arg(long, group = "password_source")]
+    password_file: Option<PathBuf>,
+
+    /// Read the new password from this environment variable
+    #[arg(long, group = "password_source")]
+    password_env: Option<String>,
+
+    /// Key file to use as a second factor of the new container
+    #[arg(long)]
+    key_file: Option<PathBuf>,
+
+    /// Migrate files even when the result does not look decrypted (for
+    /// files that were compressed before encryption)
+    #[arg(long)]
+    force: bool,
+
+    /// Keep the legacy files next to the new containers
+    #[arg(long)]
+    keep: bool,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    // Keep keys and passwords out of core dumps.
+    if let Err(e) = secmem::harden_process() {
+        eprintln!("Warning: could not disable core dumps: {}", e);
+    }
+
+    match run(cli) {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(EXIT_USAGE)
+        }
+    }
+}
+
+fn run(cli: Cli) -> anyhow::Result<u8> {
+    let mut inputs = expand_inputs(&cli.inputs)?;
+    if let Some(list) = &cli.list {
+        inputs.extend(read_list(list)?);
+    }
+    if inputs.is_empty() {
+        return Err(anyhow::anyhow!("No files to migrate."));
+    }
+
+    let legacy_password = if cli.scheme.needs_password() {
+        read_legacy_password(&cli)?
+    } else {
+        Zeroizing::new(String::new())
+    };
+    let password = read_new_password(&cli)?;
+    let key_file_hash = match &cli.key_file {
+        Some(path) => Some(hash_key_file(path)?),
+        None => None,
+    };
+    let credentials = Credentials {
+        password: Some(&password),
+        key_file_hash: key_file_hash.as_deref(),
+    };
+    let options = Options {
+        force: cli.force,
+        keep: cli.keep,
+    };
+
+    let mut failures = 0;
+    for input in &inputs {
+        match migrate_file(input, cli.scheme, &legacy_password, &credentials, options) {
+            Ok(output) => println!("OK      {} -> {}", input.display(), output.display()),
+            Err(e) => {
+                failures += 1;
+                println!("FAILED  {}: {}", input.display(), e);
+            }
+        }
+    }
+
+    if inputs.len() > 1 {
+        println!("{} migrated, {} failed.", inputs.len() - failures, failures);
+    }
+
+    Ok(if failures == 0 { EXIT_OK } else { EXIT_FAILED })
+}
+
+/// Replaces each directory with the regular files directly inside it, in
+/// name order.
+fn expand_inputs(inputs: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(input)
+                .map_err(|e| anyhow::anyhow!("Cannot list {}: {}", input.display(), e))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file())
+                .collect();
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(input.clone());
+        }
+    }
+    Ok(files)
+}
+
+/// Reads one path per line, skipping blank lines.
+fn read_list(list: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let lines: Vec<String> = if list == Path::new("-") {
+        io::stdin().lock().lines().collect::<io::Result<_>>()?
+    } else {
+        fs::read_to_string(list)
+            .map_err(|e| anyhow::anyhow!("Cannot read listing {}: {}", list.display(), e))?
+            .lines()
+            .map(str::to_string)
+            .collect()
+    };
+    Ok(lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Reads the legacy tools' password, asking twice on the terminal. XOR takes
+/// any password, so a typo would only show as garbage.
+fn read_legacy_password(cli: &Cli) -> anyhow::Result<Zeroizing<String>> {
+    let source =
+        Source::new(cli.legacy_password_file.as_deref(), cli.legacy_password_env.as_deref(), None);
+    Ok(source.read_confirmed(
+        &format!("{} password: ", cli.scheme),
+        &format!("Repeat {} password: ", cli.scheme),
+    )?)
+}
+
+/// Reads the password for the new containers, asking twice on the terminal.
+fn read_new_password(cli: &Cli) -> anyhow::Result<SecretBuf> {
+    let source = Source::new(cli.password_file.as_deref(), cli.password_env.as_deref(), None);
+    let password = source.read_confirmed("New password: ", "Repeat new password: ")?;
+    if password.is_empty() {
+        return Err(anyhow::anyhow!("The new password is empty."));
+    }
+    Ok(SecretBuf::from_slice(password.as_bytes())?)
+}
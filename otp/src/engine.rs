@@ -0,0 +1,440 @@
+//! The streaming XOR and the policies around it.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use padledger::{BurnSide, Ledger, PadHeader, Tagger, Untagged, HEADER_LEN};
+use zeroize::Zeroizing;
+
+use crate::files::{replace_atomically, write_new};
+use crate::key::KeyStream;
+use crate::{KeyLength, Output, Overwrite, PadMode, Policy};
+
+/// Bytes read from the input and key at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// What a run did to its input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Encrypted with a fresh ledger range.
+    Encrypted,
+    /// Checked and decrypted with the range its header names.
+    Decrypted,
+    /// XORed with the raw key; the same run undoes it.
+    Xored,
+}
+
+/// The result of [`XorEngine::run`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub action: Action,
+    /// The key range used, in ledger mode.
+    pub header: Option<PadHeader>,
+    /// Bytes XORed.
+    pub bytes: u64,
+    pub output: PathBuf,
+    /// Whether a short raw key was repeated.
+    pub key_wrapped: bool,
+    /// Whether the key range was burned afterwards.
+    pub burned: bool,
+}
+
+/// XORs files with a one-time-pad key file under a [`Policy`].
+#[derive(Clone, Debug)]
+pub struct XorEngine {
+    key: PathBuf,
+    policy: Policy,
+}
+
+impl XorEngine {
+    /// Checks that `policy` makes sense and returns an engine using `key`.
+    pub fn new(key: impl Into<PathBuf>, policy: Policy) -> io::Result<XorEngine> {
+        if policy.pad == PadMode::Raw && policy.burn {
+            return Err(invalid_input(
+                "Burning needs the ledger to know which key bytes were used.",
+            ));
+        }
+        if policy.pad != PadMode::Raw && policy.key_length == KeyLength::Wrap {
+            return Err(invalid_input(
+                "The ledger never reuses key bytes; key reuse only applies to raw keys.",
+            ));
+        }
+        Ok(XorEngine {
+            key: key.into(),
+            policy,
+        })
+    }
+
+    pub fn key(&self) -> &Path {
+        &self.key
+    }
+
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// XORs the file at `input` and writes the result where the policy says.
+    ///
+    /// Nothing appears at the output path until the result is complete and
+    /// `fsync`ed. In ledger mode a ciphertext's tag is checked in a first
+    /// pass, before anything is written.
+    pub fn run(&self, input: &Path) -> io::Result<Outcome> {
+        let output = match &self.policy.output {
+            Output::File(path) => path.clone(),
+            Output::InPlace => input.to_path_buf(),
+        };
+        if self.policy.output != Output::InPlace
+            && self.policy.overwrite == Overwrite::Refuse
+            && output.exists()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Output file '{}' already exists.", output.display()),
+            ));
+        }
+
+        let mut input_file = File::open(input).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Unable to open input file '{}': {}", input.display(), e),
+            )
+        })?;
+        let input_len = input_file.metadata()?.len();
+        let (header, action, mut key) = match self.policy.pad {
+            PadMode::Raw => (
+                None,
+                Action::Xored,
+                KeyStream::raw(&self.key, input_len, self.policy.key_length)?,
+            ),
+            PadMode::Ledger | PadMode::LedgerUntagged => {
+                let untagged = if self.policy.pad == PadMode::LedgerUntagged {
+                    Untagged::Accept
+                } else {
+                    Untagged::Refuse
+                };
+                let (header, encrypting) =
+                    pad_range(&self.key, &mut input_file, input_len, untagged)?;
+                let action = if encrypting {
+                    Action::Encrypted
+                } else {
+                    Action::Decrypted
+                };
+                (Some(header), action, KeyStream::range(&self.key, &header)?)
+            }
+        };
+        let bytes = header.map_or(input_len, |header| header.data_len());
+        let key_wrapped = key.wraps();
+
+        let key_path = &self.key;
+        // The input is moved in, so it is closed before an in-place rename.
+        let write = move |file: &mut File| {
+            let mut writer = BufWriter::with_capacity(CHUNK_SIZE, file);
+            let mut tagger = match header {
+                Some(header) if action == Action::Encrypted => {
+                    writer.write_all(&header.to_bytes())?;
+                    Some(Tagger::new(key_path, &header)?)
+                }
+                _ => None,
+            };
+            xor_stream(
+                input_file.take(bytes),
+                &mut key,
+                &mut writer,
+                tagger.as_mut(),
+            )?;
+            if let Some(tagger) = tagger {
+                writer.write_all(&tagger.finalize())?;
+            }
+            writer.flush()
+        };
+        match (&self.policy.output, self.policy.overwrite) {
+            (Output::File(_), Overwrite::Refuse) => write_new(&output, write)?,
+            _ => replace_atomically(&output, write)?,
+        }
+
+        let mut burned = false;
+        if let (true, Some(header)) = (self.policy.burn, header) {
+            let side = if action == Action::Encrypted {
+                BurnSide::Encrypt
+            } else {
+                BurnSide::Decrypt
+            };
+            padledger::burn(&self.key, &header, side)?;
+            burned = true;
+        }
+        Ok(Outcome {
+            action,
+            header,
+            bytes,
+            output,
+            key_wrapped,
+            burned,
+        })
+    }
+}
+
+/// XORs everything `input` yields with key bytes and writes it to `output`,
+/// feeding the result to `tagger` if there is one. Returns the bytes
+/// written.
+pub fn xor_stream<R: Read, W: Write>(
+    mut input: R,
+    key: &mut KeyStream,
+    mut output: W,
+    mut tagger: Option<&mut Tagger>,
+) -> io::Result<u64> {
+    let mut buffer = Zeroizing::new(vec![0u8; CHUNK_SIZE]);
+    let mut key_buffer = Zeroizing::new(vec![0u8; CHUNK_SIZE]);
+    let mut total = 0;
+    loop {
+        let bytes_read = match input.read(&mut buffer) {
+            Ok(0) => return Ok(total),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        key.fill(&mut key_buffer[..bytes_read])?;
+        for (data, key) in buffer[..bytes_read].iter_mut().zip(key_buffer.iter()) {
+            *data ^= key;
+        }
+        if let Some(tagger) = tagger.as_deref_mut() {
+            tagger.update(&buffer[..bytes_read]);
+        }
+        output.write_all(&buffer[..bytes_read])?;
+        total += bytes_read as u64;
+    }
+}
+
+/// Reads the pad header from `input` and checks the tag of the file, or
+/// reserves a fresh tagged range of `key` if there is none. Returns the
+/// range and whether this is an encryption. Untagged headers are refused
+/// unless `untagged` accepts them.
+///
+/// A ciphertext's range is recorded in the key's ledger, so this side never
+/// encrypts with it. On return `input` is positioned at the start of the
+/// data to XOR.
+pub fn pad_range<R: Read + Seek>(
+    key: &Path,
+    input: &mut R,
+    input_len: u64,
+    untagged: Untagged,
+) -> io::Result<(PadHeader, bool)> {
+    let mut head = [0u8; HEADER_LEN];
+    let header = if input_len >= HEADER_LEN as u64 {
+        input.read_exact(&mut head)?;
+        PadHeader::parse(&head)
+    } else {
+        None
+    };
+
+    match header {
+        Some(header) => {
+            header.require_tag(untagged)?;
+            header.check(key)?;
+            if header.message_len() != input_len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Ciphertext length does not match its pad header.",
+                ));
+            }
+            // A first pass over the file, so nothing is written for a forged
+            // or damaged one
+            if header.tagged {
+                padledger::verify_stream(key, &header, &mut *input)?;
+                input.seek(SeekFrom::Start(HEADER_LEN as u64))?;
+            }
+            Ledger::open(key)?.record(&header)?;
+            Ok((header, false))
+        }
+        None => {
+            input.seek(SeekFrom::Start(0))?;
+            let header = Ledger::open(key)?.reserve_tagged(input_len)?;
+            Ok((header, true))
+        }
+    }
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::fs;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// A fresh directory per case, as proptest runs many.
+    fn temp_dir() -> PathBuf {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "otp-engine-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn raw(output: &Path, key_length: KeyLength) -> Policy {
+        Policy {
+            pad: PadMode::Raw,
+            key_length,
+            ..Policy::to_file(output)
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn raw_xor_twice_gives_back_the_input(
+            data in proptest::collection::vec(any::<u8>(), 0..200_000),
+            key in proptest::collection::vec(any::<u8>(), 1..300_000),
+        ) {
+            let dir = temp_dir();
+            let (input, key_path) = (dir.join("in"), dir.join("key"));
+            fs::write(&input, &data).unwrap();
+            fs::write(&key_path, &key).unwrap();
+
+            let key_length = if key.len() < data.len() { KeyLength::Wrap } else { KeyLength::Require };
+            let once = XorEngine::new(&key_path, raw(&dir.join("once"), key_length)).unwrap();
+            let outcome = once.run(&input).unwrap();
+            prop_assert_eq!(outcome.key_wrapped, key.len() < data.len());
+            let twice = XorEngine::new(&key_path, raw(&dir.join("twice"), key_length)).unwrap();
+            twice.run(&dir.join("once")).unwrap();
+            prop_assert_eq!(&fs::read(dir.join("twice")).unwrap(), &data);
+
+            // In place, twice over, is the same.
+            let in_place = Policy { output: Output::InPlace, ..raw(Path::new(""), key_length) };
+            let engine = XorEngine::new(&key_path, in_place).unwrap();
+            engine.run(&input).unwrap();
+            prop_assert_eq!(fs::read(&input).unwrap(), fs::read(dir.join("once")).unwrap());
+            engine.run(&input).unwrap();
+            prop_assert_eq!(fs::read(&input).unwrap(), data);
+            fs::remove_dir_all(dir).unwrap();
+        }
+
+        #[test]
+        fn ledger_encrypt_then_decrypt_gives_back_the_input(
+            data in proptest::collection::vec(any::<u8>(), 0..200_000),
+        ) {
+            let dir = temp_dir();
+            let (input, key_path) = (dir.join("in"), dir.join("key"));
+            fs::write(&input, &data).unwrap();
+            let key: Vec<u8> = (0..data.len() + 1000).map(|i| (i * 7 + i / 251) as u8).collect();
+            fs::write(&key_path, key).unwrap();
+
+            let sealed = XorEngine::new(&key_path, Policy::to_file(dir.join("sealed"))).unwrap();
+            let outcome = sealed.run(&input).unwrap();
+            prop_assert_eq!(outcome.action, Action::Encrypted);
+            let header = outcome.header.unwrap();
+            prop_assert_eq!(fs::metadata(dir.join("sealed")).unwrap().len(), header.message_len());
+
+            let opened = XorEngine::new(&key_path, Policy::to_file(dir.join("opened"))).unwrap();
+            let outcome = opened.run(&dir.join("sealed")).unwrap();
+            prop_assert_eq!(outcome.action, Action::Decrypted);
+            prop_assert_eq!(fs::read(dir.join("opened")).unwrap(), data);
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn policies_are_enforced() {
+        let dir = temp_dir();
+        let (input, key_path, output) = (dir.join("in"), dir.join("key"), dir.join("out"));
+        fs::write(&input, [1u8; 100]).unwrap();
+        fs::write(&key_path, [2u8; 10]).unwrap();
+
+        // Short raw key without wrapping, and an existing output.
+        let engine = XorEngine::new(&key_path, raw(&output, KeyLength::Require)).unwrap();
+        assert_eq!(
+            engine.run(&input).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert!(!output.exists());
+        fs::write(&output, b"keep").unwrap();
+        let engine = XorEngine::new(&key_path, raw(&output, KeyLength::Wrap)).unwrap();
+        assert_eq!(
+            engine.run(&input).unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        assert_eq!(fs::read(&output).unwrap(), b"keep");
+
+        // Contradictory policies.
+        let burn_raw = Policy {
+            burn: true,
+            ..raw(&output, KeyLength::Require)
+        };
+        assert!(XorEngine::new(&key_path, burn_raw).is_err());
+        let wrap_ledger = Policy {
+            key_length: KeyLength::Wrap,
+            ..Policy::to_file(&output)
+        };
+        assert!(XorEngine::new(&key_path, wrap_ledger).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tampered_ciphertext_writes_nothing() {
+        let dir = temp_dir();
+        let (input, key_path) = (dir.join("in"), dir.join("key"));
+        fs::write(&input, b"attack at dawn").unwrap();
+        fs::write(&key_path, [9u8; 500]).unwrap();
+        XorEngine::new(&key_path, Policy::to_file(dir.join("sealed")))
+            .unwrap()
+            .run(&input)
+            .unwrap();
+
+        let mut sealed = fs::read(dir.join("sealed")).unwrap();
+        sealed[HEADER_LEN] ^= 1;
+        fs::write(dir.join("sealed"), &sealed).unwrap();
+        let opened = XorEngine::new(&key_path, Policy::in_place()).unwrap();
+        assert!(opened.run(&dir.join("sealed")).is_err());
+        assert_eq!(fs::read(dir.join("sealed")).unwrap(), sealed);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stripped_tag_is_refused_unless_untagged_is_accepted() {
+        let dir = temp_dir();
+        let (input, key_path) = (dir.join("in"), dir.join("key"));
+        fs::write(&input, b"attack at dawn").unwrap();
+        fs::write(&key_path, [9u8; 500]).unwrap();
+        let header = XorEngine::new(&key_path, Policy::to_file(dir.join("sealed")))
+            .unwrap()
+            .run(&input)
+            .unwrap()
+            .header
+            .unwrap();
+
+        // Rewrite the header as version 1 over the data range and cut the tag.
+        let sealed = fs::read(dir.join("sealed")).unwrap();
+        let downgraded = PadHeader {
+            offset: header.data_offset(),
+            length: header.data_len(),
+            tagged: false,
+            ..header
+        };
+        let mut stripped = downgraded.to_bytes().to_vec();
+        stripped.extend_from_slice(&sealed[HEADER_LEN..sealed.len() - padledger::TAG_LEN]);
+        fs::write(dir.join("stripped"), &stripped).unwrap();
+
+        let refused = XorEngine::new(&key_path, Policy::to_file(dir.join("refused"))).unwrap();
+        let err = refused.run(&dir.join("stripped")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(!dir.join("refused").exists());
+
+        let legacy = Policy {
+            pad: PadMode::LedgerUntagged,
+            ..Policy::to_file(dir.join("opened"))
+        };
+        XorEngine::new(&key_path, legacy)
+            .unwrap()
+            .run(&dir.join("stripped"))
+            .unwrap();
+        assert_eq!(fs::read(dir.join("opened")).unwrap(), b"attack at dawn");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,78 @@
+//! Output files that only appear once they are complete and on disk.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+/// Creates `path`, which must not exist, with owner-only permissions, fills
+/// it and fsyncs it. A failed write removes the partial file.
+pub fn write_new(path: &Path, write: impl FnOnce(&mut File) -> io::Result<()>) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).map_err(|e| {
+        if e.kind() == io::ErrorKind::AlreadyExists {
+            io::Error::new(
+                e.kind(),
+                format!("Output file '{}' already exists.", path.display()),
+            )
+        } else {
+            e
+        }
+    })?;
+    let result = write(&mut file).and_then(|()| file.sync_all());
+    if result.is_err() {
+        let _ = fs::remove_file(path);
+    }
+    result
+}
+
+/// Replaces `path`, or creates it, with new contents: they go to a temporary
+/// file next to it, which is fsync'd and then renamed over the original. The
+/// original is never deleted first, so a crash leaves either the old file or
+/// the new one. An existing file keeps its permissions.
+pub fn replace_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut File) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut temp_name = path.as_os_str().to_os_string();
+    temp_name.push(".otp-tmp");
+    let temp_path = PathBuf::from(temp_name);
+
+    let permissions = match fs::metadata(path) {
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    write_new(&temp_path, |file| {
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        write(file)
+    })?;
+    if let Err(e) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    sync_parent(path)
+}
+
+/// Makes a rename durable. Directories cannot be opened for syncing on
+/// Windows, where the rename is already durable once it returns.
+pub fn sync_parent(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
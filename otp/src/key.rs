@@ -0,0 +1,101 @@
+//! Reading key bytes alongside the input.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use padledger::PadHeader;
+
+use crate::KeyLength;
+
+/// Key bytes read in step with the input, from the start of the key or from
+/// a ledger range.
+pub struct KeyStream {
+    file: File,
+    start: u64,
+    wrap: bool,
+}
+
+impl KeyStream {
+    /// Opens `key` for XORing `input_len` bytes from its start.
+    ///
+    /// A key shorter than the input is refused unless `key_length` allows
+    /// wrapping, in which case a warning goes to stderr.
+    pub fn raw(key: &Path, input_len: u64, key_length: KeyLength) -> io::Result<KeyStream> {
+        let file = open_key(key)?;
+        let key_len = file.metadata()?.len();
+        if key_len < input_len {
+            if key_len == 0 {
+                return Err(invalid(format!("Key file '{}' is empty.", key.display())));
+            }
+            if key_length == KeyLength::Require {
+                return Err(invalid(format!(
+                    "Key file '{}' is shorter than the input ({} < {} bytes). Reusing key bytes breaks the one-time pad; key reuse must be allowed explicitly (--allow-key-reuse).",
+                    key.display(),
+                    key_len,
+                    input_len
+                )));
+            }
+            eprintln!(
+                "Warning: repeating a {}-byte key over {} bytes. This is not a one-time pad and offers little security.",
+                key_len, input_len
+            );
+        }
+        Ok(KeyStream {
+            file,
+            start: 0,
+            wrap: key_len < input_len,
+        })
+    }
+
+    /// Opens `key` at the range `header` XORs with its data.
+    pub fn range(key: &Path, header: &PadHeader) -> io::Result<KeyStream> {
+        let mut file = open_key(key)?;
+        file.seek(SeekFrom::Start(header.data_offset()))?;
+        Ok(KeyStream {
+            file,
+            start: header.data_offset(),
+            wrap: false,
+        })
+    }
+
+    /// Whether the key is shorter than the input and will be repeated.
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+    /// Fills `buf` with the next key bytes.
+    pub fn fill(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.file.read(&mut buf[filled..]) {
+                Ok(0) if self.wrap => {
+                    self.file.seek(SeekFrom::Start(self.start))?;
+                }
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Key file ended before the input.",
+                    ))
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn open_key(key: &Path) -> io::Result<File> {
+    File::open(key).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Key file '{}' cannot be opened: {}", key.display(), e),
+        )
+    })
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
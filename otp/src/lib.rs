@@ -0,0 +1,102 @@
+//! The "read key, read input, XOR, write" core of the one-time-pad tools.
+//!
+//! An [`XorEngine`] streams a file through the XOR under an explicit
+//! [`Policy`]:
+//!
+//! - [`PadMode::Ledger`] takes a fresh, tagged range of the key from its
+//!   [`padledger`] ledger to encrypt, or checks and decrypts the range a pad
+//!   header names. [`PadMode::LedgerUntagged`] also decrypts untagged
+//!   headers from before tags, which must be asked for. [`PadMode::Raw`] XORs
+//!   from the start of the key, as files from before the ledger need.
+//! - [`KeyLength`] says whether a raw key shorter than the input is refused
+//!   or repeated. Repeating it is not a one-time pad, so it must be asked for.
+//! - [`Output`] writes a new file or replaces the input, and [`Overwrite`]
+//!   says whether an existing output file may be replaced.
+//! - `burn` overwrites the used key range with random data once the output
+//!   is on disk.
+//!
+//! Output always goes through a file that is `fsync`ed before it appears
+//! under its final name, so a failed or interrupted run leaves no partial
+//! output and never destroys the input.
+
+use std::path::PathBuf;
+
+mod engine;
+mod files;
+mod key;
+
+pub use engine::{pad_range, xor_stream, Action, Outcome, XorEngine};
+pub use files::{replace_atomically, sync_parent, write_new};
+pub use key::KeyStream;
+
+/// Name of the key file the tools look for when none is given.
+pub const DEFAULT_KEY: &str = "key.key";
+
+/// Which key bytes are XORed with the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadMode {
+    /// A range from the key's ledger, with a pad header and a one-time tag.
+    Ledger,
+    /// As [`PadMode::Ledger`], but files with an untagged (version 1) pad
+    /// header are decrypted too. Nothing authenticates their data.
+    LedgerUntagged,
+    /// The key from its first byte, with no header or tag.
+    Raw,
+}
+
+/// What to do when a raw key is shorter than the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyLength {
+    /// Refuse to run.
+    Require,
+    /// Start over at the beginning of the key, with a warning. This is
+    /// repeating-key XOR and is easily broken.
+    Wrap,
+}
+
+/// Where the result goes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Output {
+    File(PathBuf),
+    /// Replace the input file.
+    InPlace,
+}
+
+/// Whether an existing output file may be replaced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overwrite {
+    Refuse,
+    Replace,
+}
+
+/// How an [`XorEngine`] treats the key, the input and the output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Policy {
+    pub pad: PadMode,
+    pub key_length: KeyLength,
+    pub output: Output,
+    pub overwrite: Overwrite,
+    /// Overwrite the used key range once done. Not for [`PadMode::Raw`].
+    pub burn: bool,
+}
+
+impl Policy {
+    /// Ledger mode into `output`, which must not exist yet.
+    pub fn to_file(output: impl Into<PathBuf>) -> Policy {
+        Policy {
+            pad: PadMode::Ledger,
+            key_length: KeyLength::Require,
+            output: Output::File(output.into()),
+            overwrite: Overwrite::Refuse,
+            burn: false,
+        }
+    }
+
+    /// Ledger mode, replacing the input.
+    pub fn in_place() -> Policy {
+        Policy {
+            output: Output::InPlace,
+            ..Policy::to_file(PathBuf::new())
+        }
+    }
+}
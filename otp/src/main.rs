@@ -0,0 +1,124 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+
+use clap::Parser;
+use otp::{Action, KeyLength, Output, Overwrite, PadMode, Policy, XorEngine, DEFAULT_KEY};
+
+/// XORs a file with a one-time-pad key file.
+///
+/// By default each encryption takes a fresh, tagged range of the key from its
+/// ledger, and files with a pad header are checked and decrypted. Input and
+/// output names that are left out are asked for.
+#[derive(Parser)]
+#[command(name = "otp", version, about)]
+struct Cli {
+    /// File to encrypt or decrypt
+    input: Option<PathBuf>,
+
+    /// Where to write the result
+    #[arg(conflicts_with = "in_place")]
+    output: Option<PathBuf>,
+
+    /// Key file
+    #[arg(short, long, default_value = DEFAULT_KEY)]
+    key: PathBuf,
+
+    /// Replace the input with the result, through an fsync'd temporary file
+    #[arg(short, long)]
+    in_place: bool,
+
+    /// Replace the output file if it exists
+    #[arg(short, long)]
+    force: bool,
+
+    /// XOR from the start of the key, without a pad header, tag or ledger
+    /// (for files from before the ledger)
+    #[arg(long)]
+    legacy: bool,
+
+    /// With --legacy, repeat a key that is shorter than the input. This is
+    /// repeating-key XOR, not a one-time pad, and is easily broken
+    #[arg(long, requires = "legacy")]
+    allow_key_reuse: bool,
+
+    /// Also decrypt files whose pad header has no tag (from before tags were
+    /// added). Nothing checks their data, and a stripped tag looks the same
+    #[arg(long, conflicts_with = "legacy")]
+    legacy_untagged: bool,
+
+    /// Overwrite the used key bytes with random data once the output is on
+    /// disk
+    #[arg(long, conflicts_with = "legacy")]
+    burn: bool,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> io::Result<()> {
+    let input = match cli.input {
+        Some(input) => input,
+        None => prompt("Enter input file name:")?,
+    };
+    let output = match (cli.in_place, cli.output) {
+        (true, _) => Output::InPlace,
+        (false, Some(output)) => Output::File(output),
+        (false, None) => Output::File(prompt("Enter output file name:")?),
+    };
+    let policy = Policy {
+        pad: if cli.legacy {
+            PadMode::Raw
+        } else if cli.legacy_untagged {
+            PadMode::LedgerUntagged
+        } else {
+            PadMode::Ledger
+        },
+        key_length: if cli.allow_key_reuse {
+            KeyLength::Wrap
+        } else {
+            KeyLength::Require
+        },
+        output,
+        overwrite: if cli.force {
+            Overwrite::Replace
+        } else {
+            Overwrite::Refuse
+        },
+        burn: cli.burn,
+    };
+
+    let outcome = XorEngine::new(cli.key, policy)?.run(&input)?;
+    let done = match outcome.action {
+        Action::Encrypted => "Encrypted",
+        Action::Decrypted => "Decrypted",
+        Action::Xored => "XORed",
+    };
+    println!(
+        "{} {} bytes into '{}'.",
+        done,
+        outcome.bytes,
+        outcome.output.display()
+    );
+    if let (true, Some(header)) = (outcome.burned, outcome.header) {
+        println!(
+            "Burned key bytes {}..{}.",
+            header.offset,
+            header.offset + header.length
+        );
+    }
+    Ok(())
+}
+
+fn prompt(question: &str) -> io::Result<PathBuf> {
+    println!("{}", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(PathBuf::from(answer.trim()))
+}
@@ -0,0 +1,79 @@
+//! `pad init <pad> front|back` sets up a pad for two-party use;
+//! `pad status <pad>` shows how much of it each end has used.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::ExitCode;
+
+use padledger::{Direction, Ledger, PAD_ID_SOURCE_LEN};
+
+const USAGE: &str = "Usage: pad init <pad> front|back\n       pad status <pad>";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["init", pad, direction] => match Direction::from_name(direction) {
+            Some(direction) => init(Path::new(pad), direction),
+            None => {
+                eprintln!("{}", USAGE);
+                return ExitCode::FAILURE;
+            }
+        },
+        ["status", pad] => status(Path::new(pad)),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn init(pad: &Path, direction: Direction) -> io::Result<()> {
+    Ledger::open(pad)?.init(direction)?;
+    let other = match direction {
+        Direction::Front => "back",
+        Direction::Back => "front",
+    };
+    println!(
+        "'{}' will take ranges from the {}. The other party must run `pad init <pad> {}` on their copy.",
+        pad.display(),
+        direction.name(),
+        other
+    );
+    status(pad)
+}
+
+fn status(pad: &Path) -> io::Result<()> {
+    let ledger = Ledger::open(pad)?;
+    let pad_len = fs::metadata(pad)?.len();
+    let id: String = ledger
+        .pad_id()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    println!(
+        "Pad:       {} ({} bytes, id {})",
+        pad.display(),
+        pad_len,
+        id
+    );
+    match ledger.direction() {
+        Some(direction) => println!("This side: {}", direction.name()),
+        None => println!("This side: not set up for two parties; ranges come from the front"),
+    }
+    println!(
+        "Front:     {}..{} used",
+        PAD_ID_SOURCE_LEN,
+        ledger.next_offset()
+    );
+    println!("Back:      {}..{} used", ledger.back_offset(), pad_len);
+    println!("Free:      {} bytes", ledger.remaining());
+    Ok(())
+}
@@ -0,0 +1,1011 @@
+//! Pad-consumption ledger for the one-time-pad tools.
+//!
+//! A one-time pad is only secure if no byte of it is ever used twice. The
+//! [`Ledger`] keeps a high-water mark for each pad in a sidecar file
+//! (`<pad>.ledger`), guarded by a lock file and `fsync`ed before any pad byte
+//! is handed out. Every ciphertext starts with a [`PadHeader`] naming the pad
+//! and the range it consumed, so the receiving side knows where to start.
+//!
+//! The first [`PAD_ID_SOURCE_LEN`] bytes of every pad are never used for
+//! encryption; their hash identifies the pad, so both parties derive the same
+//! id without exchanging anything.
+//!
+//! New messages are tagged: the first [`MAC_KEY_LEN`] bytes of their range key
+//! a one-time Poly1305 over the header and ciphertext, and the tag follows the
+//! ciphertext. A one-time key makes this a Wegman-Carter MAC, so a forgery
+//! succeeds with negligible probability however much computing power an
+//! attacker has. [`open`] checks the tag before returning any plaintext.
+//!
+//! Untagged (version 1) headers still parse, but binary messages carrying one
+//! are refused unless the caller passes [`Untagged::Accept`]: stripping the
+//! tag and rewriting the header is all it takes to turn a tagged message into
+//! an untagged one that nothing checks.
+//!
+//! Two parties sharing a pad each run `pad init` on their copy, one taking
+//! [`Direction::Front`] and the other [`Direction::Back`]. Each then takes
+//! ranges from its own end, and headers say which end a range came from. The
+//! ledger tracks how far both ends have been consumed, moving the peer's end
+//! as its messages are opened, and [`Ledger::reserve`] warns when the two
+//! are about to meet.
+//!
+//! Tools that [`burn`] used ranges overwrite them with random data once a
+//! message has been encrypted or read, and append the range to an audit log
+//! (`<pad>.burnlog`). Headers naming a burned range are rejected.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use poly1305::universal_hash::{KeyInit, UniversalHash};
+use poly1305::{Block, Poly1305};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Magic bytes identifying a ciphertext that carries a pad header.
+pub const MAGIC: &[u8; 4] = b"ESKO";
+/// Header version of untagged messages.
+pub const VERSION: u8 = 1;
+/// Header version of messages followed by a one-time Poly1305 tag.
+pub const VERSION_TAGGED: u8 = 2;
+/// Set in the version byte when the range was taken from the back of the pad.
+pub const FROM_BACK: u8 = 0x80;
+/// Length of a pad id.
+pub const PAD_ID_LEN: usize = 8;
+/// Magic + version + pad id + offset + length.
+pub const HEADER_LEN: usize = 4 + 1 + PAD_ID_LEN + 8 + 8;
+/// Pad bytes reserved for deriving the pad id.
+pub const PAD_ID_SOURCE_LEN: u64 = 32;
+/// Pad bytes at the start of a tagged range that key its Poly1305 tag.
+pub const MAC_KEY_LEN: u64 = 32;
+/// Length of the tag that ends a tagged message.
+pub const TAG_LEN: usize = 16;
+
+/// How long to wait for another process to release the ledger.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Chunk size used when overwriting burned ranges.
+const BURN_CHUNK: usize = 64 * 1024;
+/// Two-party ledgers warn once less than this share of the pad is left
+/// between the two ends.
+const MEET_WARNING_FRACTION: u64 = 10;
+
+/// Identifies a pad without revealing any byte that is used for encryption.
+pub type PadId = [u8; PAD_ID_LEN];
+
+/// Which end of a shared pad a party takes its ranges from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Front,
+    Back,
+}
+
+impl Direction {
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::Front => "front",
+            Direction::Back => "back",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Direction> {
+        match name {
+            "front" => Some(Direction::Front),
+            "back" => Some(Direction::Back),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a message with an untagged (version 1) header may be decrypted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Untagged {
+    Refuse,
+    /// For messages from before tags were added. Their data is not
+    /// authenticated.
+    Accept,
+}
+
+/// The range of a pad that one ciphertext consumed.
+///
+/// For tagged messages the range starts with the [`MAC_KEY_LEN`] bytes of the
+/// tag key, followed by the bytes XORed with the data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PadHeader {
+    pub pad_id: PadId,
+    pub offset: u64,
+    pub length: u64,
+    pub tagged: bool,
+    pub direction: Direction,
+}
+
+impl PadHeader {
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        bytes[..4].copy_from_slice(MAGIC);
+        bytes[4] = if self.tagged { VERSION_TAGGED } else { VERSION };
+        if self.direction == Direction::Back {
+            bytes[4] |= FROM_BACK;
+        }
+        bytes[5..13].copy_from_slice(&self.pad_id);
+        bytes[13..21].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[21..29].copy_from_slice(&self.length.to_le_bytes());
+        bytes
+    }
+
+    /// Parses the header at the start of `data`, if there is one.
+    pub fn parse(data: &[u8]) -> Option<PadHeader> {
+        if data.len() < HEADER_LEN || &data[..4] != MAGIC {
+            return None;
+        }
+        let tagged = match data[4] & !FROM_BACK {
+            VERSION => false,
+            VERSION_TAGGED => true,
+            _ => return None,
+        };
+        let direction = if data[4] & FROM_BACK != 0 {
+            Direction::Back
+        } else {
+            Direction::Front
+        };
+        let header = PadHeader {
+            pad_id: data[5..13].try_into().unwrap(),
+            offset: u64::from_le_bytes(data[13..21].try_into().unwrap()),
+            length: u64::from_le_bytes(data[21..29].try_into().unwrap()),
+            tagged,
+            direction,
+        };
+        (!tagged || header.length >= MAC_KEY_LEN).then_some(header)
+    }
+
+    /// Offset of the first pad byte XORed with the data.
+    pub fn data_offset(&self) -> u64 {
+        if self.tagged {
+            self.offset + MAC_KEY_LEN
+        } else {
+            self.offset
+        }
+    }
+
+    /// Length of the data this header covers.
+    pub fn data_len(&self) -> u64 {
+        self.length - (self.data_offset() - self.offset)
+    }
+
+    /// Length of the whole message: header, ciphertext and any tag.
+    pub fn message_len(&self) -> u64 {
+        let tag_len = if self.tagged { TAG_LEN as u64 } else { 0 };
+        HEADER_LEN as u64 + self.data_len() + tag_len
+    }
+
+    /// Refuses an untagged header unless `untagged` accepts it.
+    pub fn require_tag(&self, untagged: Untagged) -> io::Result<()> {
+        if self.tagged || untagged == Untagged::Accept {
+            return Ok(());
+        }
+        Err(invalid(
+            "Ciphertext has no authentication tag, which may have been stripped. \
+             Only messages from before tags were added lack one; accept untagged \
+             messages explicitly to decrypt them."
+                .to_string(),
+        ))
+    }
+
+    /// Checks that this header was written against `pad` and stays inside it.
+    pub fn check(&self, pad: &Path) -> io::Result<()> {
+        if pad_id(pad)? != self.pad_id {
+            return Err(invalid(format!(
+                "Ciphertext was not encrypted with pad '{}'.",
+                pad.display()
+            )));
+        }
+        let pad_len = fs::metadata(pad)?.len();
+        let end = self.offset.checked_add(self.length);
+        if self.offset < PAD_ID_SOURCE_LEN || end.is_none_or(|end| end > pad_len) {
+            return Err(invalid(format!(
+                "Ciphertext refers to pad bytes {}..{} but '{}' is only {} bytes.",
+                self.offset,
+                self.offset.saturating_add(self.length),
+                pad.display(),
+                pad_len
+            )));
+        }
+        if let Some((start, end)) = burned_ranges(pad)?
+            .into_iter()
+            .find(|&(start, end)| start < self.offset + self.length && self.offset < end)
+        {
+            return Err(invalid(format!(
+                "Pad bytes {}..{} of '{}' were already burned; this message can no longer be decrypted.",
+                start,
+                end,
+                pad.display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Derives the id of `pad` from its reserved leading bytes.
+pub fn pad_id(pad: &Path) -> io::Result<PadId> {
+    let mut source = [0u8; PAD_ID_SOURCE_LEN as usize];
+    File::open(pad)?.read_exact(&mut source).map_err(|_| {
+        invalid(format!(
+            "Pad '{}' is too short; the first {} bytes identify it.",
+            pad.display(),
+            PAD_ID_SOURCE_LEN
+        ))
+    })?;
+    let digest = Sha256::digest(source);
+    Ok(digest[..PAD_ID_LEN].try_into().unwrap())
+}
+
+/// Path of the ledger kept for `pad`.
+pub fn ledger_path(pad: &Path) -> PathBuf {
+    with_suffix(pad, ".ledger")
+}
+
+/// The consumption state of one pad, held under an exclusive lock.
+///
+/// Bytes below [`next_offset`](Ledger::next_offset) and from
+/// [`back_offset`](Ledger::back_offset) on have been used; everything in
+/// between is free.
+pub struct Ledger {
+    pad: PathBuf,
+    path: PathBuf,
+    pad_id: PadId,
+    pad_len: u64,
+    direction: Option<Direction>,
+    next: u64,
+    back: u64,
+    _lock: LockFile,
+}
+
+impl Ledger {
+    /// Locks the ledger for `pad` and reads it, starting a new one if needed.
+    pub fn open(pad: &Path) -> io::Result<Ledger> {
+        let path = ledger_path(pad);
+        let lock = LockFile::acquire(&with_suffix(&path, ".lock"))?;
+        let pad_id = pad_id(pad)?;
+        let pad_len = fs::metadata(pad)?.len();
+
+        let state = match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let state = parse_state(&contents)
+                    .ok_or_else(|| invalid(format!("Ledger '{}' is corrupt.", path.display())))?;
+                if state.pad_id != pad_id {
+                    return Err(invalid(format!(
+                        "Ledger '{}' belongs to a different pad than '{}'.",
+                        path.display(),
+                        pad.display()
+                    )));
+                }
+                state
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => State {
+                pad_id,
+                direction: None,
+                next: PAD_ID_SOURCE_LEN,
+                back: None,
+            },
+            Err(e) => return Err(e),
+        };
+
+        Ok(Ledger {
+            pad: pad.to_path_buf(),
+            path,
+            pad_id,
+            pad_len,
+            direction: state.direction,
+            next: state.next,
+            back: state.back.unwrap_or(pad_len).min(pad_len),
+            _lock: lock,
+        })
+    }
+
+    pub fn pad_id(&self) -> PadId {
+        self.pad_id
+    }
+
+    /// The end this side takes ranges from, once `pad init` has set it.
+    /// Ledgers without one take ranges from the front.
+    pub fn direction(&self) -> Option<Direction> {
+        self.direction
+    }
+
+    /// Offset of the first unused pad byte at the front.
+    pub fn next_offset(&self) -> u64 {
+        self.next
+    }
+
+    /// Offset just past the last unused pad byte at the back.
+    pub fn back_offset(&self) -> u64 {
+        self.back
+    }
+
+    /// Pad bytes not yet consumed from either end.
+    pub fn remaining(&self) -> u64 {
+        self.back.saturating_sub(self.next)
+    }
+
+    /// Assigns this side an end of the pad for two-party use.
+    ///
+    /// The other party must take the opposite end. Setting the direction a
+    /// ledger already has is allowed; changing it is not, because the peer
+    /// may already be using the other end.
+    pub fn init(&mut self, direction: Direction) -> io::Result<()> {
+        match self.direction {
+            Some(current) if current != direction => Err(invalid(format!(
+                "Pad '{}' is already set up to be used from the {}.",
+                self.pad.display(),
+                current.name()
+            ))),
+            _ => {
+                self.direction = Some(direction);
+                self.store(self.next, self.back)
+            }
+        }
+    }
+
+    /// Marks a range the other party used as consumed, so this side never
+    /// encrypts with it.
+    pub fn record(&mut self, header: &PadHeader) -> io::Result<()> {
+        let end = header.offset + header.length;
+        match header.direction {
+            Direction::Front if end > self.next => self.store(end, self.back),
+            Direction::Back if header.offset < self.back => self.store(self.next, header.offset),
+            _ => Ok(()),
+        }
+    }
+
+    /// Consumes fresh pad bytes for `data_len` bytes of data plus the key of
+    /// their tag.
+    pub fn reserve_tagged(&mut self, data_len: u64) -> io::Result<PadHeader> {
+        let mut header = self.reserve(data_len + MAC_KEY_LEN)?;
+        header.tagged = true;
+        Ok(header)
+    }
+
+    /// Consumes `length` fresh pad bytes from this side's end.
+    ///
+    /// The new mark is on disk before this returns, so a crash during
+    /// encryption wastes the range rather than reusing it. On a two-party
+    /// pad, a warning goes to stderr once the ends are close: the other side
+    /// may already have used bytes this side has not heard about yet.
+    pub fn reserve(&mut self, length: u64) -> io::Result<PadHeader> {
+        if length > self.remaining() {
+            return Err(invalid(format!(
+                "Pad '{}' has {} unused bytes left, {} needed.",
+                self.pad.display(),
+                self.remaining(),
+                length
+            )));
+        }
+        let direction = self.direction.unwrap_or(Direction::Front);
+        let offset = match direction {
+            Direction::Front => self.next,
+            Direction::Back => self.back - length,
+        };
+        match direction {
+            Direction::Front => self.store(offset + length, self.back)?,
+            Direction::Back => self.store(self.next, offset)?,
+        }
+        if self.direction.is_some() {
+            let usable = self.pad_len.saturating_sub(PAD_ID_SOURCE_LEN);
+            if self.remaining() < usable / MEET_WARNING_FRACTION {
+                eprintln!(
+                    "Warning: only {} bytes of pad '{}' are left between the two ends. The other party may be close to them too; arrange a new pad.",
+                    self.remaining(),
+                    self.pad.display()
+                );
+            }
+        }
+        Ok(PadHeader {
+            pad_id: self.pad_id,
+            offset,
+            length,
+            tagged: false,
+            direction,
+        })
+    }
+
+    fn store(&mut self, next: u64, back: u64) -> io::Result<()> {
+        let state = State {
+            pad_id: self.pad_id,
+            direction: self.direction,
+            next,
+            back: (back < self.pad_len).then_some(back),
+        };
+        let tmp = with_suffix(&self.path, ".tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(format_state(&state).as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&tmp, &self.path)?;
+        sync_parent(&self.path)?;
+        self.next = next;
+        self.back = back;
+        Ok(())
+    }
+}
+
+/// Which side of an exchange burned a range, for the audit log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BurnSide {
+    Encrypt,
+    Decrypt,
+}
+
+/// Path of the audit log of burned ranges for `pad`.
+pub fn burn_log_path(pad: &Path) -> PathBuf {
+    with_suffix(pad, ".burnlog")
+}
+
+/// Overwrites the range `header` names with random data, `fsync`s the pad and
+/// appends the range to the audit log.
+///
+/// Call this only once the ciphertext or plaintext is safely on disk.
+pub fn burn(pad: &Path, header: &PadHeader, side: BurnSide) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(pad)?;
+    file.seek(SeekFrom::Start(header.offset))?;
+    let mut noise = vec![0u8; BURN_CHUNK];
+    let mut left = header.length;
+    while left > 0 {
+        let n = left.min(BURN_CHUNK as u64) as usize;
+        OsRng.fill_bytes(&mut noise[..n]);
+        file.write_all(&noise[..n])?;
+        left -= n as u64;
+    }
+    file.sync_all()?;
+
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let side = match side {
+        BurnSide::Encrypt => "encrypt",
+        BurnSide::Decrypt => "decrypt",
+    };
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(burn_log_path(pad))?;
+    writeln!(
+        log,
+        "{} burned {}..{} after {} pad {}",
+        seconds,
+        header.offset,
+        header.offset + header.length,
+        side,
+        to_hex(&header.pad_id)
+    )?;
+    log.sync_all()
+}
+
+/// Ranges recorded in the audit log, as `(start, end)`.
+pub fn burned_ranges(pad: &Path) -> io::Result<Vec<(u64, u64)>> {
+    let contents = match fs::read_to_string(burn_log_path(pad)) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|line| {
+            let (start, end) = line.split_whitespace().nth(2)?.split_once("..")?;
+            Some((start.parse().ok()?, end.parse().ok()?))
+        })
+        .collect())
+}
+
+/// Reads the pad bytes `header` XORs with its data.
+pub fn read_range(pad: &Path, header: &PadHeader) -> io::Result<Vec<u8>> {
+    read_at(pad, header.data_offset(), header.data_len() as usize)
+}
+
+/// One-time Poly1305 over a tagged header and its ciphertext.
+///
+/// The header is absorbed first and the lengths last, as in the
+/// ChaCha20-Poly1305 construction, so neither can be changed unnoticed.
+pub struct Tagger {
+    mac: Poly1305,
+    partial: [u8; 16],
+    filled: usize,
+    len: u64,
+}
+
+impl Tagger {
+    /// Reads the tag key of `header` from `pad`.
+    pub fn new(pad: &Path, header: &PadHeader) -> io::Result<Tagger> {
+        if !header.tagged {
+            return Err(invalid("Untagged messages have no tag key.".to_string()));
+        }
+        let key = read_at(pad, header.offset, MAC_KEY_LEN as usize)?;
+        let mut mac = Poly1305::new(key.as_slice().into());
+        mac.update_padded(&header.to_bytes());
+        Ok(Tagger {
+            mac,
+            partial: [0u8; 16],
+            filled: 0,
+            len: 0,
+        })
+    }
+
+    /// Absorbs the next piece of ciphertext.
+    pub fn update(&mut self, mut ciphertext: &[u8]) {
+        self.len += ciphertext.len() as u64;
+        if self.filled > 0 {
+            let n = ciphertext.len().min(16 - self.filled);
+            self.partial[self.filled..self.filled + n].copy_from_slice(&ciphertext[..n]);
+            self.filled += n;
+            ciphertext = &ciphertext[n..];
+            if self.filled < 16 {
+                return;
+            }
+            self.mac.update(&[Block::from(self.partial)]);
+            self.filled = 0;
+        }
+        let whole = ciphertext.len() / 16 * 16;
+        self.mac.update_padded(&ciphertext[..whole]);
+        let rest = &ciphertext[whole..];
+        self.partial[..rest.len()].copy_from_slice(rest);
+        self.filled = rest.len();
+    }
+
+    pub fn finalize(self) -> [u8; TAG_LEN] {
+        self.finish().finalize().into()
+    }
+
+    /// Compares against `tag` in constant time.
+    pub fn verify(self, tag: &[u8]) -> io::Result<()> {
+        if tag.len() == TAG_LEN && self.finish().verify(Block::from_slice(tag)).is_ok() {
+            Ok(())
+        } else {
+            Err(invalid(
+                "Authentication tag does not match; the message was modified or the pad is wrong."
+                    .to_string(),
+            ))
+        }
+    }
+
+    fn finish(mut self) -> Poly1305 {
+        self.mac.update_padded(&self.partial[..self.filled]);
+        let mut lengths = [0u8; 16];
+        lengths[..8].copy_from_slice(&(HEADER_LEN as u64).to_le_bytes());
+        lengths[8..].copy_from_slice(&self.len.to_le_bytes());
+        self.mac.update(&[Block::from(lengths)]);
+        self.mac
+    }
+}
+
+/// Checks the tag of a tagged message, reading its ciphertext and tag from
+/// `reader`, which must be positioned just past the header.
+pub fn verify_stream<R: Read>(pad: &Path, header: &PadHeader, reader: R) -> io::Result<()> {
+    let mut tagger = Tagger::new(pad, header)?;
+    let mut reader = reader.take(header.data_len() + TAG_LEN as u64);
+    let mut chunk = vec![0u8; BURN_CHUNK];
+    let mut left = header.data_len();
+    while left > 0 {
+        let n = left.min(BURN_CHUNK as u64) as usize;
+        reader.read_exact(&mut chunk[..n])?;
+        tagger.update(&chunk[..n]);
+        left -= n as u64;
+    }
+    let mut tag = [0u8; TAG_LEN];
+    reader.read_exact(&mut tag)?;
+    tagger.verify(&tag)
+}
+
+/// Encrypts `plaintext` with a fresh tagged range of `pad`, returning the
+/// header and the whole message (header, ciphertext, tag).
+pub fn seal(pad: &Path, plaintext: &[u8]) -> io::Result<(PadHeader, Vec<u8>)> {
+    let header = Ledger::open(pad)?.reserve_tagged(plaintext.len() as u64)?;
+    let key = read_range(pad, &header)?;
+    let mut message = Vec::with_capacity(header.message_len() as usize);
+    message.extend_from_slice(&header.to_bytes());
+    message.extend(plaintext.iter().zip(&key).map(|(p, k)| p ^ k));
+    let mut tagger = Tagger::new(pad, &header)?;
+    tagger.update(&message[HEADER_LEN..]);
+    message.extend_from_slice(&tagger.finalize());
+    Ok((header, message))
+}
+
+/// Decrypts a message that starts with a pad header, after checking that it
+/// belongs to `pad` and that its tag verifies. Untagged messages are refused
+/// unless `untagged` accepts them. The range is then recorded in the ledger,
+/// so this side never reuses it.
+///
+/// Returns `None` if `message` has no pad header.
+pub fn open(
+    pad: &Path,
+    message: &[u8],
+    untagged: Untagged,
+) -> io::Result<Option<(PadHeader, Vec<u8>)>> {
+    let Some(header) = PadHeader::parse(message) else {
+        return Ok(None);
+    };
+    header.require_tag(untagged)?;
+    header.check(pad)?;
+    if header.message_len() != message.len() as u64 {
+        return Err(invalid(
+            "Ciphertext length does not match its pad header.".to_string(),
+        ));
+    }
+    let ciphertext = &message[HEADER_LEN..HEADER_LEN + header.data_len() as usize];
+    if header.tagged {
+        let mut tagger = Tagger::new(pad, &header)?;
+        tagger.update(ciphertext);
+        tagger.verify(&message[HEADER_LEN + ciphertext.len()..])?;
+    }
+    let key = read_range(pad, &header)?;
+    let plaintext = ciphertext.iter().zip(&key).map(|(c, k)| c ^ k).collect();
+    Ledger::open(pad)?.record(&header)?;
+    Ok(Some((header, plaintext)))
+}
+
+fn read_at(pad: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    let mut file = File::open(pad)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = vec![0u8; len];
+    file.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// What a ledger file holds. `back` is only written once something has been
+/// taken from the back, so single-party ledgers keep their old form.
+struct State {
+    pad_id: PadId,
+    direction: Option<Direction>,
+    next: u64,
+    back: Option<u64>,
+}
+
+fn format_state(state: &State) -> String {
+    let mut text = format!("pad_id {}\n", to_hex(&state.pad_id));
+    if let Some(direction) = state.direction {
+        text += &format!("direction {}\n", direction.name());
+    }
+    text += &format!("next {}\n", state.next);
+    if let Some(back) = state.back {
+        text += &format!("back {}\n", back);
+    }
+    text
+}
+
+fn parse_state(contents: &str) -> Option<State> {
+    let mut pad_id = None;
+    let mut direction = None;
+    let mut next = None;
+    let mut back = None;
+    for line in contents.lines() {
+        match line.split_once(' ') {
+            Some(("pad_id", hex)) => pad_id = from_hex(hex),
+            Some(("direction", name)) => direction = Some(Direction::from_name(name)?),
+            Some(("next", value)) => next = value.parse().ok(),
+            Some(("back", value)) => back = Some(value.parse().ok()?),
+            _ => {}
+        }
+    }
+    Some(State {
+        pad_id: pad_id?,
+        direction,
+        next: next?,
+        back,
+    })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<PadId> {
+    if hex.len() != PAD_ID_LEN * 2 {
+        return None;
+    }
+    let mut id = [0u8; PAD_ID_LEN];
+    for (i, byte) in id.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(id)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Makes a rename durable. Directories cannot be opened for syncing on
+/// Windows, where the rename is already durable once it returns.
+fn sync_parent(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// An exclusive lock held by creating a file, removed on drop.
+struct LockFile {
+    path: PathBuf,
+}
+
+impl LockFile {
+    fn acquire(path: &Path) -> io::Result<LockFile> {
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(mut file) => {
+                    let _ = writeln!(file, "{}", std::process::id());
+                    return Ok(LockFile {
+                        path: path.to_path_buf(),
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if start.elapsed() > LOCK_TIMEOUT {
+                        return Err(io::Error::new(
+                            io::ErrorKind::WouldBlock,
+                            format!(
+                                "Pad ledger is locked by '{}'. If no other encryption is running, delete it.",
+                                path.display()
+                            ),
+                        ));
+                    }
+                    thread::sleep(Duration::from_millis(50));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pad file in the temp directory, removed with its ledger on drop.
+    struct TempPad(PathBuf);
+
+    impl TempPad {
+        fn new(name: &str, len: usize) -> TempPad {
+            let path =
+                std::env::temp_dir().join(format!("padledger-{}-{}", std::process::id(), name));
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 31 + 7) as u8).collect();
+            fs::write(&path, bytes).unwrap();
+            TempPad(path)
+        }
+    }
+
+    impl Drop for TempPad {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+            let _ = fs::remove_file(ledger_path(&self.0));
+            let _ = fs::remove_file(burn_log_path(&self.0));
+        }
+    }
+
+    #[test]
+    fn header_round_trips() {
+        let header = PadHeader {
+            pad_id: [1, 2, 3, 4, 5, 6, 7, 8],
+            offset: 4096,
+            length: 123,
+            tagged: false,
+            direction: Direction::Front,
+        };
+        assert_eq!(PadHeader::parse(&header.to_bytes()), Some(header));
+        let tagged = PadHeader {
+            tagged: true,
+            ..header
+        };
+        assert_eq!(PadHeader::parse(&tagged.to_bytes()), Some(tagged));
+        let from_back = PadHeader {
+            direction: Direction::Back,
+            ..tagged
+        };
+        assert_eq!(PadHeader::parse(&from_back.to_bytes()), Some(from_back));
+        assert_eq!(tagged.data_len(), 123 - MAC_KEY_LEN);
+        assert_eq!(PadHeader::parse(b"not a header at all, honestly"), None);
+    }
+
+    #[test]
+    fn sealed_message_opens() {
+        let pad = TempPad::new("seal", 1024);
+        let (header, message) = seal(&pad.0, b"attack at dawn").unwrap();
+        assert_eq!(header.length, 14 + MAC_KEY_LEN);
+        assert_eq!(message.len() as u64, header.message_len());
+        let (opened, plaintext) = open(&pad.0, &message, Untagged::Refuse).unwrap().unwrap();
+        assert_eq!(opened, header);
+        assert_eq!(plaintext, b"attack at dawn");
+        assert!(open(&pad.0, b"no header here", Untagged::Refuse).unwrap().is_none());
+    }
+
+    #[test]
+    fn any_modification_fails_the_tag() {
+        let pad = TempPad::new("forge", 1024);
+        let (_, message) = seal(&pad.0, b"pay alice 10").unwrap();
+        // Flip a bit in the pad id, the ciphertext and the tag in turn.
+        for i in [6, HEADER_LEN + 10, message.len() - 1] {
+            let mut forged = message.clone();
+            forged[i] ^= 0x01;
+            assert!(open(&pad.0, &forged, Untagged::Refuse).is_err(), "byte {} not covered", i);
+        }
+    }
+
+    #[test]
+    fn tag_does_not_depend_on_chunking() {
+        let pad = TempPad::new("chunks", 1024);
+        let header = Ledger::open(&pad.0).unwrap().reserve_tagged(100).unwrap();
+        let data: Vec<u8> = (0..100).collect();
+        let mut whole = Tagger::new(&pad.0, &header).unwrap();
+        whole.update(&data);
+        let mut pieces = Tagger::new(&pad.0, &header).unwrap();
+        for chunk in data.chunks(7) {
+            pieces.update(chunk);
+        }
+        assert_eq!(whole.finalize(), pieces.finalize());
+    }
+
+    #[test]
+    fn untagged_messages_open_only_when_accepted() {
+        let pad = TempPad::new("untagged", 256);
+        let header = Ledger::open(&pad.0).unwrap().reserve(5).unwrap();
+        let key = read_range(&pad.0, &header).unwrap();
+        let mut message = header.to_bytes().to_vec();
+        message.extend(b"hello".iter().zip(&key).map(|(p, k)| p ^ k));
+        assert!(open(&pad.0, &message, Untagged::Refuse).is_err());
+        let (_, plaintext) = open(&pad.0, &message, Untagged::Accept).unwrap().unwrap();
+        assert_eq!(plaintext, b"hello");
+    }
+
+    #[test]
+    fn stripping_the_tag_is_refused() {
+        let pad = TempPad::new("strip", 1024);
+        let (header, message) = seal(&pad.0, b"pay alice 10").unwrap();
+        // The same ciphertext under a version 1 header naming only its data
+        // range, with the tag cut off.
+        let downgraded = PadHeader {
+            offset: header.data_offset(),
+            length: header.data_len(),
+            tagged: false,
+            ..header
+        };
+        let mut stripped = downgraded.to_bytes().to_vec();
+        stripped.extend_from_slice(&message[HEADER_LEN..message.len() - TAG_LEN]);
+        let err = open(&pad.0, &stripped, Untagged::Refuse).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reservations_never_overlap() {
+        let pad = TempPad::new("overlap", 1024);
+        let first = Ledger::open(&pad.0).unwrap().reserve(100).unwrap();
+        let second = Ledger::open(&pad.0).unwrap().reserve(200).unwrap();
+        assert_eq!(first.offset, PAD_ID_SOURCE_LEN);
+        assert_eq!(second.offset, first.offset + first.length);
+        second.check(&pad.0).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_run_past_the_end() {
+        let pad = TempPad::new("end", 100);
+        let mut ledger = Ledger::open(&pad.0).unwrap();
+        assert_eq!(ledger.remaining(), 100 - PAD_ID_SOURCE_LEN);
+        assert!(ledger.reserve(100).is_err());
+        ledger.reserve(100 - PAD_ID_SOURCE_LEN).unwrap();
+        assert!(ledger.reserve(1).is_err());
+    }
+
+    #[test]
+    fn header_from_another_pad_is_rejected() {
+        let pad = TempPad::new("mine", 256);
+        let other = TempPad::new("other", 256);
+        fs::write(&other.0, [0x55u8; 256]).unwrap();
+        let header = Ledger::open(&pad.0).unwrap().reserve(10).unwrap();
+        assert!(header.check(&other.0).is_err());
+    }
+
+    #[test]
+    fn burned_range_is_overwritten_and_rejected() {
+        let pad = TempPad::new("burn", 512);
+        let header = Ledger::open(&pad.0).unwrap().reserve(64).unwrap();
+        let before = read_range(&pad.0, &header).unwrap();
+        burn(&pad.0, &header, BurnSide::Encrypt).unwrap();
+        assert_ne!(read_range(&pad.0, &header).unwrap(), before);
+        assert_eq!(
+            burned_ranges(&pad.0).unwrap(),
+            vec![(header.offset, header.offset + 64)]
+        );
+        assert!(header.check(&pad.0).is_err());
+        // The id bytes are never burned, so the pad keeps its id.
+        assert_eq!(pad_id(&pad.0).unwrap(), header.pad_id);
+    }
+
+    #[test]
+    fn receiver_records_the_senders_range() {
+        let pad = TempPad::new("record", 512);
+        let header = PadHeader {
+            pad_id: pad_id(&pad.0).unwrap(),
+            offset: PAD_ID_SOURCE_LEN,
+            length: 100,
+            tagged: false,
+            direction: Direction::Front,
+        };
+        let mut ledger = Ledger::open(&pad.0).unwrap();
+        ledger.record(&header).unwrap();
+        assert_eq!(ledger.reserve(1).unwrap().offset, PAD_ID_SOURCE_LEN + 100);
+    }
+
+    #[test]
+    fn two_parties_take_opposite_ends() {
+        let alice = TempPad::new("alice", 1024);
+        let bob = TempPad::new("bob", 1024);
+        Ledger::open(&alice.0)
+            .unwrap()
+            .init(Direction::Front)
+            .unwrap();
+        Ledger::open(&bob.0).unwrap().init(Direction::Back).unwrap();
+
+        let (to_bob, message) = seal(&alice.0, b"hello bob").unwrap();
+        let (to_alice, reply) = seal(&bob.0, b"hello alice").unwrap();
+        assert_eq!(to_bob.offset, PAD_ID_SOURCE_LEN);
+        assert_eq!(to_alice.direction, Direction::Back);
+        assert_eq!(to_alice.offset + to_alice.length, 1024);
+
+        assert_eq!(open(&bob.0, &message, Untagged::Refuse).unwrap().unwrap().1, b"hello bob");
+        assert_eq!(open(&alice.0, &reply, Untagged::Refuse).unwrap().unwrap().1, b"hello alice");
+        for pad in [&alice, &bob] {
+            let ledger = Ledger::open(&pad.0).unwrap();
+            assert_eq!(ledger.next_offset(), to_bob.offset + to_bob.length);
+            assert_eq!(ledger.back_offset(), to_alice.offset);
+        }
+        assert!(Ledger::open(&alice.0)
+            .unwrap()
+            .init(Direction::Back)
+            .is_err());
+    }
+
+    #[test]
+    fn ends_never_cross() {
+        let pad = TempPad::new("meet", 256);
+        let mut ledger = Ledger::open(&pad.0).unwrap();
+        ledger.init(Direction::Front).unwrap();
+        ledger
+            .record(&PadHeader {
+                pad_id: ledger.pad_id(),
+                offset: 200,
+                length: 56,
+                tagged: false,
+                direction: Direction::Back,
+            })
+            .unwrap();
+        assert_eq!(ledger.remaining(), 200 - PAD_ID_SOURCE_LEN);
+        assert!(ledger.reserve(200 - PAD_ID_SOURCE_LEN + 1).is_err());
+        assert_eq!(ledger.reserve(10).unwrap().offset, PAD_ID_SOURCE_LEN);
+    }
+
+    #[test]
+    fn lock_is_released_on_drop() {
+        let pad = TempPad::new("lock", 256);
+        let ledger = Ledger::open(&pad.0).unwrap();
+        let lock = with_suffix(&ledger_path(&pad.0), ".lock");
+        assert!(lock.exists());
+        drop(ledger);
+        assert!(!lock.exists());
+    }
+}
@@ -0,0 +1,139 @@
+//! The straddling checkerboard that turns text into digits for the digits
+//! mode, after the classic one-time-pad conversion tables.
+//!
+//! The six most common letters take one digit and everything else two, so
+//! messages stay short. Numbers are sent between two `FIG` codes with every
+//! digit written three times, which makes a misread digit stand out.
+//!
+//! | code | meaning |
+//! |---|---|
+//! | 0 | space |
+//! | 1-6 | A E I N O T |
+//! | 70-79 | B C D F G H J K L M |
+//! | 80-89 | P Q R S U V W X Y Z |
+//! | 90 | FIG (start or end of a number) |
+//! | 91-99 | `. , ? - ' / : ( )` |
+
+use std::io;
+
+use crate::invalid;
+
+const SINGLE: &[u8; 6] = b"AEINOT";
+const SEVENTIES: &[u8; 10] = b"BCDFGHJKLM";
+const EIGHTIES: &[u8; 10] = b"PQRSUVWXYZ";
+const PUNCTUATION: &[u8; 9] = b".,?-'/:()";
+const FIG: [u8; 2] = [9, 0];
+
+/// Converts text to digit values. Letters are case-insensitive and any
+/// whitespace becomes a space.
+pub fn encode(text: &str) -> io::Result<Vec<u8>> {
+    let mut digits = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            digits.extend_from_slice(&FIG);
+            let mut number = Some(c);
+            while let Some(d) = number {
+                let value = d as u8 - b'0';
+                digits.extend_from_slice(&[value; 3]);
+                number = chars.next_if(char::is_ascii_digit);
+            }
+            digits.extend_from_slice(&FIG);
+            continue;
+        }
+        let upper = c.to_ascii_uppercase() as u8;
+        if c.is_whitespace() {
+            digits.push(0);
+        } else if let Some(i) = position(SINGLE, upper, c) {
+            digits.push(i + 1);
+        } else if let Some(i) = position(SEVENTIES, upper, c) {
+            digits.extend_from_slice(&[7, i]);
+        } else if let Some(i) = position(EIGHTIES, upper, c) {
+            digits.extend_from_slice(&[8, i]);
+        } else if let Some(i) = position(PUNCTUATION, upper, c) {
+            digits.extend_from_slice(&[9, i + 1]);
+        } else {
+            return Err(invalid(format!(
+                "'{}' cannot be sent in digits mode; use letters, numbers, spaces and . , ? - ' / : ( )",
+                c
+            )));
+        }
+    }
+    Ok(digits)
+}
+
+fn position(table: &[u8], upper: u8, c: char) -> Option<u8> {
+    if !c.is_ascii() {
+        return None;
+    }
+    table.iter().position(|&t| t == upper).map(|i| i as u8)
+}
+
+/// Converts digit values back to text. Trailing spaces, which pad the last
+/// group, are dropped.
+pub fn decode(digits: &[u8]) -> io::Result<String> {
+    let mut text = String::new();
+    let mut i = 0;
+    let garbled = |i: usize| {
+        invalid(format!(
+            "Digit {} does not decode; the message is garbled.",
+            i + 1
+        ))
+    };
+    while i < digits.len() {
+        let two = digits.get(i + 1).copied();
+        match digits[i] {
+            0 => text.push(' '),
+            d @ 1..=6 => text.push(SINGLE[d as usize - 1] as char),
+            7 => text.push(SEVENTIES[two.ok_or_else(|| garbled(i))? as usize] as char),
+            8 => text.push(EIGHTIES[two.ok_or_else(|| garbled(i))? as usize] as char),
+            9 if two == Some(0) => {
+                // A number: triples until the closing FIG.
+                i += 2;
+                loop {
+                    match digits.get(i..i + 2) {
+                        Some([9, 0]) => break,
+                        Some(_) => {}
+                        None => return Err(garbled(i)),
+                    }
+                    match digits.get(i..i + 3) {
+                        Some(&[a, b, c]) if a == b && b == c => text.push((b'0' + a) as char),
+                        _ => return Err(garbled(i)),
+                    }
+                    i += 3;
+                }
+            }
+            9 => text.push(PUNCTUATION[two.ok_or_else(|| garbled(i))? as usize - 1] as char),
+            _ => return Err(garbled(i)),
+        }
+        i += if (1..=6).contains(&digits[i]) || digits[i] == 0 {
+            1
+        } else {
+            2
+        };
+    }
+    text.truncate(text.trim_end_matches(' ').len());
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trips() {
+        let text = "Meet at Pier 39, 0400 (sharp)?";
+        let digits = encode(text).unwrap();
+        assert_eq!(decode(&digits).unwrap(), text.to_uppercase());
+        assert_eq!(encode("at").unwrap(), [1, 6]);
+        assert_eq!(encode("b 7").unwrap(), [7, 0, 0, 9, 0, 7, 7, 7, 9, 0]);
+    }
+
+    #[test]
+    fn garbled_numbers_and_symbols_are_rejected() {
+        assert!(encode("naïve").is_err());
+        assert!(decode(&[9, 0, 1, 1, 2, 9, 0]).is_err());
+        assert!(decode(&[9, 0, 1, 1, 1]).is_err());
+        assert!(decode(&[7]).is_err());
+    }
+}
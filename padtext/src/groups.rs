@@ -0,0 +1,142 @@
+//! Lines of symbol groups, each ending in a checksum, for copying by hand or
+//! reading aloud.
+//!
+//! A line looks like `07  38271 99102 44810 02957 61234 | 31415`: a line
+//! number, the groups, and a CRC-16 of the page or message context, the line
+//! number and the symbols, written in the same alphabet. A mistyped symbol,
+//! two swapped symbols or a line under the wrong number fails the check.
+
+use std::io;
+
+use crate::{invalid, Format};
+
+/// Renders one line.
+pub fn format_line(format: Format, context: u64, number: usize, symbols: &[u8]) -> String {
+    let groups: Vec<String> = symbols
+        .chunks(format.group_len())
+        .map(|group| group.iter().map(|&s| format.symbol(s)).collect())
+        .collect();
+    let checksum = render_checksum(format, checksum(context, number, symbols));
+    format!("{:02}  {} | {}", number, groups.join(" "), checksum)
+}
+
+/// A line read back: its number and symbol values.
+pub struct Line {
+    pub number: usize,
+    pub symbols: Vec<u8>,
+}
+
+/// Parses every numbered line of `text` in `format`, checking each checksum.
+///
+/// Blank lines and lines that do not start with a number are skipped, so page
+/// headings and notes can stay in. All bad lines are reported together.
+pub fn parse_lines(format: Format, text: &str, context: u64) -> io::Result<Vec<Line>> {
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+    for (index, raw) in text.lines().enumerate() {
+        let Some((body, check)) = raw.split_once('|') else {
+            continue;
+        };
+        let mut tokens = body.split_whitespace();
+        let Some(number) = tokens.next().and_then(|t| t.parse::<usize>().ok()) else {
+            continue;
+        };
+        let parsed: Option<Vec<u8>> = tokens
+            .flat_map(str::chars)
+            .map(|c| format.value(c))
+            .collect();
+        let Some(symbols) = parsed else {
+            errors.push(format!(
+                "text line {}: not a {} group",
+                index + 1,
+                format.name()
+            ));
+            continue;
+        };
+        let expected = render_checksum(format, checksum(context, number, &symbols));
+        let typed: String = check.split_whitespace().collect();
+        if !typed.eq_ignore_ascii_case(&expected) {
+            errors.push(format!(
+                "text line {} (line {:02}): checksum does not match; check the groups for typos",
+                index + 1,
+                number
+            ));
+            continue;
+        }
+        lines.push(Line { number, symbols });
+    }
+    if !errors.is_empty() {
+        return Err(invalid(errors.join("\n")));
+    }
+    Ok(lines)
+}
+
+/// CRC-16/CCITT-FALSE over the context, the line number and the symbols.
+fn checksum(context: u64, number: usize, symbols: &[u8]) -> u16 {
+    let mut crc: u16 = 0xffff;
+    let prefix = context
+        .to_be_bytes()
+        .into_iter()
+        .chain((number as u16).to_be_bytes());
+    for byte in prefix.chain(symbols.iter().copied()) {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Writes a checksum in the alphabet of `format`, with enough symbols to
+/// hold all 16 bits.
+fn render_checksum(format: Format, crc: u16) -> String {
+    let radix = format.radix() as u32;
+    let mut len = 0;
+    while radix.pow(len) <= u16::MAX as u32 {
+        len += 1;
+    }
+    let mut value = crc as u32;
+    let mut symbols = vec![0u8; len as usize];
+    for symbol in symbols.iter_mut().rev() {
+        *symbol = (value % radix) as u8;
+        value /= radix;
+    }
+    symbols.into_iter().map(|s| format.symbol(s)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typos_and_swaps_fail_the_checksum() {
+        let symbols = [3, 8, 2, 7, 1, 9, 9, 1, 0, 2];
+        let line = format_line(Format::Digits, 7, 4, &symbols);
+        let parsed = parse_lines(Format::Digits, &line, 7).unwrap();
+        assert_eq!(parsed[0].number, 4);
+        assert_eq!(parsed[0].symbols, symbols);
+
+        let typo = line.replacen("38271", "38371", 1);
+        let swap = line.replacen("38271", "83271", 1);
+        let renumbered = line.replacen("04", "05", 1);
+        for bad in [typo, swap, renumbered] {
+            assert!(parse_lines(Format::Digits, &bad, 7).is_err(), "{}", bad);
+        }
+        assert!(parse_lines(Format::Digits, &line, 8).is_err());
+    }
+
+    #[test]
+    fn letters_are_case_insensitive() {
+        let line = format_line(Format::Letters, 0, 1, &[0, 25, 13]);
+        assert!(line.starts_with("01  AZN | "));
+        let lower = line.to_lowercase();
+        assert_eq!(
+            parse_lines(Format::Letters, &lower, 0).unwrap()[0].symbols,
+            [0, 25, 13]
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! Printable forms of one-time-pad material, for pads and messages that
+//! travel on paper, by voice or by hand.
+//!
+//! Pads are read in pages of [`PAGE_BYTES`] bytes, counted from the end of
+//! the pad id (see [`padledger::PAD_ID_SOURCE_LEN`]), so page 7 names the
+//! same key material in every format. A page becomes symbols in one of four
+//! [`Format`]s:
+//!
+//! - `digits`: 250 decimal digits in classic five-digit groups, for
+//!   messages turned into digits with the checkerboard and added mod 10.
+//! - `letters`: 250 letters in five-letter groups, added mod 26.
+//! - `hex` and `base32`: the raw page bytes, for copying a pad by hand.
+//!
+//! Digits and letters come from the pad bytes by rejection sampling, so
+//! every symbol is uniform. Every printed line carries a checksum (see
+//! [`groups`]), and [`decrypt_text`] and [`read_booklet`] refuse lines that
+//! were typed back in wrongly, listing all of them at once.
+
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+use padledger::{Direction, PadHeader, PAD_ID_SOURCE_LEN};
+
+mod checkerboard;
+pub mod groups;
+mod message;
+
+pub use message::{decrypt_text, encrypt_text, is_message};
+
+/// Pad bytes per booklet page.
+pub const PAGE_BYTES: u64 = 400;
+/// Digits or letters per page: ten lines of five groups.
+pub const PAGE_SYMBOLS: usize = 250;
+
+/// How key material and ciphertext are written out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Digits,
+    Letters,
+    Hex,
+    Base32,
+}
+
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Digits => "digits",
+            Format::Letters => "letters",
+            Format::Hex => "hex",
+            Format::Base32 => "base32",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "digits" => Some(Format::Digits),
+            "letters" => Some(Format::Letters),
+            "hex" => Some(Format::Hex),
+            "base32" => Some(Format::Base32),
+            _ => None,
+        }
+    }
+
+    /// Number of distinct symbols.
+    pub fn radix(self) -> u8 {
+        match self {
+            Format::Digits => 10,
+            Format::Letters => 26,
+            Format::Hex => 16,
+            Format::Base32 => 32,
+        }
+    }
+
+    /// Symbols per group.
+    pub fn group_len(self) -> usize {
+        match self {
+            Format::Digits | Format::Letters => 5,
+            Format::Hex | Format::Base32 => 4,
+        }
+    }
+
+    /// Symbols per printed line.
+    pub fn line_len(self) -> usize {
+        match self {
+            Format::Digits | Format::Letters => 25,
+            Format::Hex | Format::Base32 => 32,
+        }
+    }
+
+    /// The character for symbol value `value`.
+    pub fn symbol(self, value: u8) -> char {
+        let c = match self {
+            Format::Digits => b'0' + value,
+            Format::Letters => b'A' + value,
+            Format::Hex => b"0123456789ABCDEF"[value as usize],
+            Format::Base32 => BASE32[value as usize],
+        };
+        c as char
+    }
+
+    /// The value of character `c`, ignoring case.
+    pub fn value(self, c: char) -> Option<u8> {
+        let c = c.to_ascii_uppercase();
+        if !c.is_ascii() {
+            return None;
+        }
+        let c = c as u8;
+        match self {
+            Format::Digits => c.is_ascii_digit().then(|| c - b'0'),
+            Format::Letters => c.is_ascii_uppercase().then(|| c - b'A'),
+            Format::Hex => (c as char).to_digit(16).map(|v| v as u8),
+            Format::Base32 => BASE32.iter().position(|&b| b == c).map(|v| v as u8),
+        }
+    }
+
+    fn is_modular(self) -> bool {
+        matches!(self, Format::Digits | Format::Letters)
+    }
+}
+
+pub(crate) fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Number of whole pages in `pad`.
+pub fn page_count(pad: &Path) -> io::Result<u64> {
+    Ok(fs::metadata(pad)?.len().saturating_sub(PAD_ID_SOURCE_LEN) / PAGE_BYTES)
+}
+
+/// Offset of the first byte of `page`.
+pub fn page_offset(page: u64) -> u64 {
+    PAD_ID_SOURCE_LEN + page * PAGE_BYTES
+}
+
+/// The page that starts at `offset`, if one does.
+pub fn page_at(offset: u64) -> Option<u64> {
+    let rel = offset.checked_sub(PAD_ID_SOURCE_LEN)?;
+    (rel % PAGE_BYTES == 0).then_some(rel / PAGE_BYTES)
+}
+
+/// The pad range covering `pages`, as a header.
+pub(crate) fn page_header(
+    pad: &Path,
+    pages: Range<u64>,
+    direction: Direction,
+) -> io::Result<PadHeader> {
+    Ok(PadHeader {
+        pad_id: padledger::pad_id(pad)?,
+        offset: page_offset(pages.start),
+        length: (pages.end - pages.start) * PAGE_BYTES,
+        tagged: false,
+        direction,
+    })
+}
+
+/// The symbols of one page in `format`.
+///
+/// For digits and letters, pad bytes that would favour some symbols
+/// (250 and up for digits, 234 and up for letters) are skipped.
+pub fn page_symbols(bytes: &[u8], format: Format) -> io::Result<Vec<u8>> {
+    match format {
+        Format::Digits | Format::Letters => {
+            let radix = format.radix();
+            let limit = 256 / radix as u16 * radix as u16;
+            let symbols: Vec<u8> = bytes
+                .iter()
+                .filter(|&&b| (b as u16) < limit)
+                .map(|&b| b % radix)
+                .take(PAGE_SYMBOLS)
+                .collect();
+            if symbols.len() < PAGE_SYMBOLS {
+                return Err(invalid(format!(
+                    "Page has too few usable bytes for {} {}; skip it.",
+                    PAGE_SYMBOLS,
+                    format.name()
+                )));
+            }
+            Ok(symbols)
+        }
+        Format::Hex => Ok(bytes.iter().flat_map(|&b| [b >> 4, b & 0xf]).collect()),
+        Format::Base32 => Ok(bits_to_symbols(bytes, 5)),
+    }
+}
+
+fn bits_to_symbols(bytes: &[u8], width: u32) -> Vec<u8> {
+    let mut symbols = Vec::new();
+    let (mut acc, mut bits) = (0u32, 0u32);
+    for &b in bytes {
+        acc = (acc << 8) | b as u32;
+        bits += 8;
+        while bits >= width {
+            bits -= width;
+            symbols.push((acc >> bits) as u8 & ((1 << width) - 1));
+        }
+    }
+    if bits > 0 {
+        symbols.push((acc << (width - bits)) as u8 & ((1 << width) - 1));
+    }
+    symbols
+}
+
+fn symbols_to_bytes(symbols: &[u8], format: Format) -> Vec<u8> {
+    let width = format.radix().trailing_zeros();
+    let mut bytes = Vec::new();
+    let (mut acc, mut bits) = (0u32, 0u32);
+    for &s in symbols {
+        acc = (acc << width) | s as u32;
+        bits += width;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+        }
+    }
+    bytes
+}
+
+/// Reads the symbols of consecutive `pages` of `pad`.
+pub(crate) fn read_pages(pad: &Path, pages: Range<u64>, format: Format) -> io::Result<Vec<u8>> {
+    let header = page_header(pad, pages.clone(), Direction::Front)?;
+    let bytes = padledger::read_range(pad, &header)?;
+    let mut symbols = Vec::new();
+    for (page, bytes) in pages.zip(bytes.chunks(PAGE_BYTES as usize)) {
+        let page_symbols =
+            page_symbols(bytes, format).map_err(|e| invalid(format!("Page {}: {}", page, e)))?;
+        symbols.extend(page_symbols);
+    }
+    Ok(symbols)
+}
+
+/// Prints `pages` of `pad` as a booklet in `format`.
+///
+/// Each page starts with a heading such as `PAGE 00007  PAD 1f2e…  DIGITS`
+/// and its lines are checksummed against the page number. Hex and base32
+/// booklets that start at page 0 also carry the pad id bytes, as line 00 of
+/// page 0, so [`read_booklet`] can rebuild the whole pad from them.
+pub fn booklet(pad: &Path, format: Format, pages: Range<u64>) -> io::Result<String> {
+    let available = page_count(pad)?;
+    if pages.end > available || pages.is_empty() {
+        return Err(invalid(format!(
+            "Pad '{}' has pages 0..{}; pages {}..{} were asked for.",
+            pad.display(),
+            available,
+            pages.start,
+            pages.end
+        )));
+    }
+    let id: String = padledger::pad_id(pad)?
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let mut out = String::new();
+    for page in pages {
+        out += &format!(
+            "PAGE {:05}  PAD {}  {}\n\n",
+            page,
+            id,
+            format.name().to_uppercase()
+        );
+        if page == 0 && !format.is_modular() {
+            let header = PadHeader {
+                pad_id: [0; padledger::PAD_ID_LEN],
+                offset: 0,
+                length: PAD_ID_SOURCE_LEN,
+                tagged: false,
+                direction: Direction::Front,
+            };
+            let source = padledger::read_range(pad, &header)?;
+            out += &groups::format_line(format, 0, 0, &page_symbols(&source, format)?);
+            out.push('\n');
+        }
+        let symbols = read_pages(pad, page..page + 1, format)?;
+        for (i, line) in symbols.chunks(format.line_len()).enumerate() {
+            out += &groups::format_line(format, page, i + 1, line);
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Rebuilds pad bytes from a typed-in hex or base32 booklet.
+///
+/// Returns each page found with its offset in the pad and its bytes; the id
+/// bytes of page 0 come back at offset 0. Every line's checksum is checked
+/// and every bad line reported.
+pub fn read_booklet(text: &str) -> io::Result<Vec<(u64, Vec<u8>)>> {
+    let mut chunks = Vec::new();
+    let mut errors = Vec::new();
+    for section in text.split("PAGE ").skip(1) {
+        let (heading, body) = section.split_once('\n').unwrap_or((section, ""));
+        let fields: Vec<&str> = heading.split_whitespace().collect();
+        let page = fields.first().and_then(|p| p.parse::<u64>().ok());
+        let format = fields
+            .get(3)
+            .and_then(|f| Format::from_name(&f.to_lowercase()));
+        let (Some(page), Some(format)) = (page, format) else {
+            errors.push(format!(
+                "Unreadable page heading 'PAGE {}'.",
+                heading.trim()
+            ));
+            continue;
+        };
+        if format.is_modular() {
+            errors.push(format!(
+                "Page {} is in {}, which cannot be turned back into pad bytes.",
+                page,
+                format.name()
+            ));
+            continue;
+        }
+        let lines = match groups::parse_lines(format, body, page) {
+            Ok(lines) => lines,
+            Err(e) => {
+                errors.push(format!("Page {}:\n{}", page, e));
+                continue;
+            }
+        };
+        let mut data = Vec::new();
+        for line in lines {
+            if line.number == 0 && page == 0 {
+                chunks.push((0, symbols_to_bytes(&line.symbols, format)));
+            } else if line.number != data.len() / format.line_len() + 1 {
+                errors.push(format!(
+                    "Page {}: line {:02} is missing.",
+                    page,
+                    data.len() / format.line_len() + 1
+                ));
+                break;
+            } else {
+                data.extend(line.symbols);
+            }
+        }
+        let bytes = symbols_to_bytes(&data, format);
+        if bytes.len() as u64 != PAGE_BYTES {
+            errors.push(format!(
+                "Page {} has {} bytes, not {}.",
+                page,
+                bytes.len(),
+                PAGE_BYTES
+            ));
+            continue;
+        }
+        chunks.push((page_offset(page), bytes));
+    }
+    if !errors.is_empty() {
+        return Err(invalid(errors.join("\n")));
+    }
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_pad(name: &str, len: usize) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("padtext-{}-{}", std::process::id(), name));
+        let bytes: Vec<u8> = (0..len).map(|i| (i * 131 + i / 7) as u8).collect();
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn digit_pages_are_uniform_and_full() {
+        let bytes: Vec<u8> = (0..=255).cycle().take(PAGE_BYTES as usize).collect();
+        let digits = page_symbols(&bytes, Format::Digits).unwrap();
+        assert_eq!(digits.len(), PAGE_SYMBOLS);
+        assert!(digits.iter().all(|&d| d < 10));
+        assert!(page_symbols(&[255; 400], Format::Letters).is_err());
+    }
+
+    #[test]
+    fn hex_and_base32_booklets_rebuild_the_pad() {
+        let pad = temp_pad("booklet", 32 + 3 * 400 + 17);
+        let original = fs::read(&pad).unwrap();
+        for format in [Format::Hex, Format::Base32] {
+            let text = booklet(&pad, format, 0..3).unwrap();
+            let mut rebuilt = vec![0u8; original.len()];
+            for (offset, bytes) in read_booklet(&text).unwrap() {
+                rebuilt[offset as usize..offset as usize + bytes.len()].copy_from_slice(&bytes);
+            }
+            assert_eq!(rebuilt[..32 + 1200], original[..32 + 1200]);
+
+            // One mistyped symbol is caught and located.
+            let line = text.lines().find(|l| l.starts_with("02  ")).unwrap();
+            let first = line.chars().nth(4).unwrap();
+            let typo = if first == 'A' { 'B' } else { 'A' };
+            let bad = text.replacen(line, &line.replacen(first, &typo.to_string(), 1), 1);
+            let err = read_booklet(&bad).unwrap_err().to_string();
+            assert!(err.contains("line 02"), "{}", err);
+        }
+        fs::remove_file(pad).unwrap();
+    }
+}
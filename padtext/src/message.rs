@@ -0,0 +1,303 @@
+//! Messages written as symbol groups and added to the pad symbol by symbol.
+//!
+//! A message looks like this:
+//!
+//! ```text
+//! MSG DIGITS  PAD 1f2e3d4c5b6a7980
+//!
+//! 00  00000 00007 | 48213
+//! 01  38271 99102 44810 02957 61234 | 31415
+//! 02  70115 | 90210
+//! ```
+//!
+//! Line 00 is the indicator: the booklet page the key starts on. The key is
+//! that page and as many following ones as the message needs, so a message
+//! can also be enciphered by hand from a printed booklet. Each message uses
+//! whole pages, which the ledger hands out like any other range.
+
+use std::io;
+use std::path::Path;
+
+use padledger::{Direction, Ledger, PadHeader, PAD_ID_SOURCE_LEN};
+
+use crate::groups::{format_line, parse_lines};
+use crate::{
+    checkerboard, invalid, page_at, page_count, page_header, page_offset, read_pages, Format,
+    PAGE_BYTES, PAGE_SYMBOLS,
+};
+
+/// Symbols in the indicator on line 00.
+const INDICATOR_LEN: usize = 10;
+/// Letter used to fill the last group in letters mode.
+const FILLER: u8 = b'X' - b'A';
+
+/// Encrypts `plaintext` with fresh pages of `pad`, in digits or letters.
+///
+/// Digits mode keeps letters, numbers, spaces and common punctuation (see
+/// the checkerboard); letters mode keeps only the letters A to Z, as in
+/// hand-sent traffic.
+pub fn encrypt_text(
+    pad: &Path,
+    format: Format,
+    plaintext: &str,
+) -> io::Result<(PadHeader, String)> {
+    let mut symbols = match format {
+        Format::Digits => checkerboard::encode(plaintext)?,
+        Format::Letters => plaintext
+            .chars()
+            .filter(char::is_ascii_alphabetic)
+            .map(|c| c.to_ascii_uppercase() as u8 - b'A')
+            .collect(),
+        _ => return Err(text_only(format)),
+    };
+    if symbols.is_empty() {
+        return Err(invalid("Nothing to encrypt.".to_string()));
+    }
+    let filler = if format == Format::Letters { FILLER } else { 0 };
+    while symbols.len() % format.group_len() != 0 {
+        symbols.push(filler);
+    }
+
+    let pages = symbols.len().div_ceil(PAGE_SYMBOLS) as u64;
+    let header = reserve_pages(pad, pages)?;
+    let first = page_at(header.offset).unwrap();
+    let key = read_pages(pad, first..first + pages, format)?;
+    let radix = format.radix();
+    let ciphertext: Vec<u8> = symbols
+        .iter()
+        .zip(&key)
+        .map(|(p, k)| (p + k) % radix)
+        .collect();
+
+    let mut out = format!(
+        "MSG {}  PAD {}\n\n",
+        format.name().to_uppercase(),
+        hex_id(&header.pad_id)
+    );
+    out += &format_line(format, 0, 0, &to_radix(first, format));
+    out.push('\n');
+    for (i, line) in ciphertext.chunks(format.line_len()).enumerate() {
+        out += &format_line(format, 0, i + 1, line);
+        out.push('\n');
+    }
+    Ok((header, out))
+}
+
+/// Decrypts a message typed back in, checking every line, and records its
+/// pages in the ledger of `pad`.
+///
+/// The pages are taken to come from the other party's end: the back if
+/// this side uses the front, and the front otherwise.
+pub fn decrypt_text(pad: &Path, text: &str) -> io::Result<(PadHeader, String)> {
+    let (format, id) = text
+        .lines()
+        .find_map(parse_heading)
+        .ok_or_else(|| invalid("No 'MSG <format>  PAD <id>' heading found.".to_string()))?;
+    let pad_id = padledger::pad_id(pad)?;
+    if id != hex_id(&pad_id) {
+        return Err(invalid(format!(
+            "Message was not encrypted with pad '{}'.",
+            pad.display()
+        )));
+    }
+
+    let mut lines = parse_lines(format, text, 0)?.into_iter();
+    let indicator = lines
+        .next()
+        .filter(|line| line.number == 0 && line.symbols.len() == INDICATOR_LEN)
+        .ok_or_else(|| invalid("Line 00 must hold the page indicator.".to_string()))?;
+    let first = from_radix(&indicator.symbols, format);
+    let mut ciphertext = Vec::new();
+    for (expected, line) in (1..).zip(lines) {
+        if line.number != expected {
+            return Err(invalid(format!("Line {:02} is missing.", expected)));
+        }
+        ciphertext.extend(line.symbols);
+    }
+    if ciphertext.is_empty() {
+        return Err(invalid("Message has no groups.".to_string()));
+    }
+
+    let pages = ciphertext.len().div_ceil(PAGE_SYMBOLS) as u64;
+    if first.saturating_add(pages) > page_count(pad)? {
+        return Err(invalid(format!(
+            "Message refers to pages {}..{}, past the end of pad '{}'.",
+            first,
+            first.saturating_add(pages),
+            pad.display()
+        )));
+    }
+    let mut ledger = Ledger::open(pad)?;
+    let direction = match ledger.direction() {
+        Some(Direction::Front) => Direction::Back,
+        _ => Direction::Front,
+    };
+    let header = page_header(pad, first..first + pages, direction)?;
+    header.check(pad)?;
+
+    let key = read_pages(pad, first..first + pages, format)?;
+    let radix = format.radix();
+    let symbols: Vec<u8> = ciphertext
+        .iter()
+        .zip(&key)
+        .map(|(c, k)| (c + radix - k) % radix)
+        .collect();
+    let plaintext = match format {
+        Format::Digits => checkerboard::decode(&symbols)?,
+        _ => symbols.iter().map(|&s| format.symbol(s)).collect(),
+    };
+    ledger.record(&header)?;
+    Ok((header, plaintext))
+}
+
+/// Whether `text` is a message in digits or letters.
+pub fn is_message(text: &str) -> bool {
+    text.lines().any(|line| parse_heading(line).is_some())
+}
+
+/// Reserves `pages` whole pages from this side's end of the pad.
+///
+/// The bytes between the ledger's mark and the nearest page boundary are
+/// consumed too, so no page is ever shared with another message.
+fn reserve_pages(pad: &Path, pages: u64) -> io::Result<PadHeader> {
+    let mut ledger = Ledger::open(pad)?;
+    let length = pages * PAGE_BYTES;
+    let (gap, first) = match ledger.direction().unwrap_or(Direction::Front) {
+        Direction::Front => {
+            let first = (ledger.next_offset() - PAD_ID_SOURCE_LEN).div_ceil(PAGE_BYTES);
+            (page_offset(first) - ledger.next_offset(), first)
+        }
+        Direction::Back => {
+            let end = ledger.back_offset().saturating_sub(PAD_ID_SOURCE_LEN) / PAGE_BYTES;
+            (
+                ledger.back_offset() - page_offset(end),
+                end.saturating_sub(pages),
+            )
+        }
+    };
+    if gap + length > ledger.remaining() {
+        return Err(invalid(format!(
+            "Pad '{}' has no {} whole unused pages left.",
+            pad.display(),
+            pages
+        )));
+    }
+    if gap > 0 {
+        ledger.reserve(gap)?;
+    }
+    let header = ledger.reserve(length)?;
+    debug_assert_eq!(header.offset, page_offset(first));
+    Ok(header)
+}
+
+fn parse_heading(line: &str) -> Option<(Format, String)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    match fields[..] {
+        ["MSG", format, "PAD", id] => {
+            let format = Format::from_name(&format.to_lowercase())?;
+            matches!(format, Format::Digits | Format::Letters).then(|| (format, id.to_lowercase()))
+        }
+        _ => None,
+    }
+}
+
+fn hex_id(id: &[u8]) -> String {
+    id.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn text_only(format: Format) -> io::Error {
+    invalid(format!(
+        "Messages are written in digits or letters, not {}.",
+        format.name()
+    ))
+}
+
+fn to_radix(mut value: u64, format: Format) -> Vec<u8> {
+    let radix = format.radix() as u64;
+    let mut symbols = vec![0u8; INDICATOR_LEN];
+    for symbol in symbols.iter_mut().rev() {
+        *symbol = (value % radix) as u8;
+        value /= radix;
+    }
+    symbols
+}
+
+fn from_radix(symbols: &[u8], format: Format) -> u64 {
+    symbols
+        .iter()
+        .fold(0, |value, &s| value * format.radix() as u64 + s as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn pad_pair(name: &str) -> (PathBuf, PathBuf) {
+        let bytes: Vec<u8> = (0..32 + 20 * PAGE_BYTES as usize)
+            .map(|i| (i * 167 + i / 3) as u8)
+            .collect();
+        let dir = std::env::temp_dir();
+        let paths = ["alice", "bob"].map(|who| {
+            let path = dir.join(format!("padtext-{}-{}-{}", std::process::id(), name, who));
+            fs::write(&path, &bytes).unwrap();
+            let _ = fs::remove_file(padledger::ledger_path(&path));
+            path
+        });
+        let [a, b] = paths;
+        (a, b)
+    }
+
+    fn cleanup(paths: &[PathBuf]) {
+        for path in paths {
+            let _ = fs::remove_file(path);
+            let _ = fs::remove_file(padledger::ledger_path(path));
+        }
+    }
+
+    #[test]
+    fn digit_and_letter_messages_round_trip_between_two_pads() {
+        let (alice, bob) = pad_pair("round-trip");
+        Ledger::open(&alice)
+            .unwrap()
+            .init(Direction::Front)
+            .unwrap();
+        Ledger::open(&bob).unwrap().init(Direction::Back).unwrap();
+
+        let long = "Meet at the north gate, 0600. ".repeat(20);
+        let (sent, message) = encrypt_text(&alice, Format::Digits, &long).unwrap();
+        assert!(is_message(&message));
+        assert_eq!(sent.offset, page_offset(0));
+        let (received, plaintext) = decrypt_text(&bob, &message).unwrap();
+        assert_eq!(received, sent);
+        assert_eq!(plaintext, long.trim_end().to_uppercase());
+
+        let (reply, message) = encrypt_text(&bob, Format::Letters, "Agreed, see you").unwrap();
+        assert_eq!(reply.offset + reply.length, page_offset(20));
+        assert_eq!(decrypt_text(&alice, &message).unwrap().1, "AGREEDSEEYOUXXX");
+
+        // Bob has seen Alice's pages, so his ledger moved past them.
+        assert_eq!(
+            Ledger::open(&bob).unwrap().next_offset(),
+            sent.offset + sent.length
+        );
+        cleanup(&[alice, bob]);
+    }
+
+    #[test]
+    fn typed_errors_and_wrong_pads_are_refused() {
+        let (alice, bob) = pad_pair("errors");
+        let (_, message) = encrypt_text(&alice, Format::Digits, "attack at dawn").unwrap();
+        let line = message.lines().find(|l| l.starts_with("01")).unwrap();
+        let digit = line.as_bytes()[4] as char;
+        let other = if digit == '9' { "0" } else { "9" };
+        let typo = message.replacen(line, &line.replacen(digit, other, 1), 1);
+        let err = decrypt_text(&bob, &typo).unwrap_err().to_string();
+        assert!(err.contains("line 01"), "{}", err);
+
+        fs::write(&bob, [7u8; 32 + 400]).unwrap();
+        assert!(decrypt_text(&bob, &message).is_err());
+        cleanup(&[alice, bob]);
+    }
+}
@@ -0,0 +1,188 @@
+//! Password sources for the command-line tools.
+//!
+//! A password is read from the first line of a file, from an environment
+//! variable, from an inherited file descriptor, or from the terminal without
+//! echo. Tools map their `--password-file`, `--password-env` and
+//! `--password-fd` flags onto a `Source` and keep their own prompts.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+use zeroize::Zeroizing;
+
+/// Where a password comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source<'a> {
+    /// The first line of this file.
+    File(&'a Path),
+    /// This environment variable.
+    Env(&'a str),
+    /// The first line read from this already-open file descriptor.
+    Fd(i32),
+    /// The terminal, without echo.
+    Terminal,
+}
+
+impl<'a> Source<'a> {
+    /// The source named by a tool's flags; the terminal if none is given.
+    /// Clap groups keep more than one from being set.
+    pub fn new(file: Option<&'a Path>, env: Option<&'a str>, fd: Option<i32>) -> Self {
+        match (file, env, fd) {
+            (Some(path), _, _) => Source::File(path),
+            (None, Some(var), _) => Source::Env(var),
+            (None, None, Some(fd)) => Source::Fd(fd),
+            (None, None, None) => Source::Terminal,
+        }
+    }
+
+    /// Whether the password will be typed, so a tool can ask for it twice.
+    pub fn is_terminal(&self) -> bool {
+        *self == Source::Terminal
+    }
+
+    /// Reads the password, showing `prompt` if it comes from the terminal.
+    pub fn read(&self, prompt: &str) -> io::Result<Zeroizing<String>> {
+        match *self {
+            Source::File(path) => {
+                let contents = Zeroizing::new(fs::read_to_string(path).map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!("Cannot read password file {}: {}", path.display(), e),
+                    )
+                })?);
+                Ok(first_line(&contents))
+            }
+            Source::Env(var) => env::var(var).map(Zeroizing::new).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Environment variable {} is not set.", var),
+                )
+            }),
+            Source::Fd(fd) => {
+                let mut contents = Zeroizing::new(String::new());
+                open_fd(fd)?.read_to_string(&mut contents)?;
+                Ok(first_line(&contents))
+            }
+            Source::Terminal => rpassword::prompt_password(prompt).map(Zeroizing::new),
+        }
+    }
+
+    /// Like `read`, but a typed password is asked for again with `repeat`
+    /// and refused if the two differ.
+    pub fn read_confirmed(&self, prompt: &str, repeat: &str) -> io::Result<Zeroizing<String>> {
+        let password = self.read(prompt)?;
+        if self.is_terminal() && *Source::Terminal.read(repeat)? != *password {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The passwords do not match.",
+            ));
+        }
+        Ok(password)
+    }
+}
+
+/// Returns the first line of `contents` without its line ending.
+fn first_line(contents: &str) -> Zeroizing<String> {
+    Zeroizing::new(contents.lines().next().unwrap_or("").to_string())
+}
+
+/// Duplicates an inherited file descriptor, leaving the caller's one open.
+#[cfg(unix)]
+fn open_fd(fd: i32) -> io::Result<File> {
+    use std::os::unix::io::BorrowedFd;
+
+    // SAFETY: F_GETFD only reads the descriptor flags, and fails if it is not open.
+    if fd < 0 || unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid file descriptor {}.", fd),
+        ));
+    }
+    // SAFETY: the descriptor was just checked to be open, and is only
+    // borrowed long enough to duplicate it; it is never closed here.
+    let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+    Ok(File::from(borrowed.try_clone_to_owned()?))
+}
+
+#[cfg(not(unix))]
+fn open_fd(_fd: i32) -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--password-fd is only supported on Unix.",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::process;
+
+    fn scratch_file(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("password-{}-{}", process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn flags_pick_the_source() {
+        let path = Path::new("pw.txt");
+        assert_eq!(Source::new(Some(path), None, None), Source::File(path));
+        assert_eq!(Source::new(None, Some("PW"), None), Source::Env("PW"));
+        assert_eq!(Source::new(None, None, Some(3)), Source::Fd(3));
+        assert!(Source::new(None, None, None).is_terminal());
+    }
+
+    #[test]
+    fn files_give_their_first_line() {
+        let path = scratch_file("first-line", "  correct horse \r\nsecond line\n");
+        let password = Source::File(&path).read("unused").unwrap();
+        assert_eq!(*password, "  correct horse ");
+        fs::remove_file(&path).unwrap();
+
+        let err = Source::File(&path).read("unused").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().starts_with("Cannot read password file"), "{}", err);
+    }
+
+    #[test]
+    fn environment_variables_are_read_whole() {
+        let var = format!("PASSWORD_TEST_{}", process::id());
+        env::set_var(&var, "battery staple");
+        assert_eq!(*Source::Env(&var).read("unused").unwrap(), "battery staple");
+        env::remove_var(&var);
+
+        let err = Source::Env(&var).read("unused").unwrap_err();
+        assert_eq!(err.to_string(), format!("Environment variable {} is not set.", var));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn descriptors_give_their_first_line() {
+        use std::os::unix::io::AsRawFd;
+
+        let path = scratch_file("fd", "tr0ub4dor\n&3\n");
+        let file = File::open(&path).unwrap();
+        let fd = file.as_raw_fd();
+        assert_eq!(*Source::Fd(fd).read("unused").unwrap(), "tr0ub4dor");
+        // SAFETY: as in open_fd. The descriptor still belongs to the caller.
+        assert_ne!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, -1);
+        drop(file);
+        fs::remove_file(&path).unwrap();
+
+        for fd in [-1, i32::MAX] {
+            let err = Source::Fd(fd).read("unused").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn only_typed_passwords_are_confirmed() {
+        let path = scratch_file("confirmed", "hunter2\n");
+        let password = Source::File(&path).read_confirmed("unused", "unused").unwrap();
+        assert_eq!(*password, "hunter2");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+//! Locked, guard-paged buffers for key material, shared by the AEAD tools.
+//!
+//! A [`SecretBuf`] lives in its own mapping with an inaccessible page on either
+//! side, is `mlock`ed so it never reaches swap, and is wiped before the mapping
+//! is released. Call [`harden_process`] once at startup to keep secrets out of
+//! core dumps as well.
+
+use std::fmt;
+use std::io;
+use std::ops::{Deref, DerefMut};
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Disables core dumps (and, on Linux, ptrace attachment by non-root users).
+pub fn harden_process() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+        // SAFETY: plain syscall with a valid pointer to a local.
+        if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: PR_SET_DUMPABLE takes a single integer argument.
+        if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// A fixed-size secret buffer that is locked in memory and wiped on drop.
+pub struct SecretBuf {
+    inner: imp::Region,
+}
+
+impl SecretBuf {
+    /// Allocates `len` zeroed bytes.
+    pub fn new(len: usize) -> io::Result<Self> {
+        Ok(SecretBuf { inner: imp::Region::new(len)? })
+    }
+
+    /// Copies `bytes` into a new buffer. The caller remains responsible for
+    /// wiping the source.
+    pub fn from_slice(bytes: &[u8]) -> io::Result<Self> {
+        let mut buf = Self::new(bytes.len())?;
+        buf.copy_from_slice(bytes);
+        Ok(buf)
+    }
+
+    /// Moves `bytes` into a new buffer and wipes the original.
+    pub fn from_vec(mut bytes: Vec<u8>) -> io::Result<Self> {
+        let buf = Self::from_slice(&bytes);
+        bytes.zeroize();
+        buf
+    }
+
+    /// Whether the pages are locked in RAM. Locking can fail when the
+    /// `RLIMIT_MEMLOCK` budget is exhausted; the buffer still works.
+    pub fn is_locked(&self) -> bool {
+        self.inner.locked
+    }
+}
+
+impl Deref for SecretBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.inner.as_slice()
+    }
+}
+
+impl DerefMut for SecretBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.inner.as_mut_slice()
+    }
+}
+
+impl Zeroize for SecretBuf {
+    fn zeroize(&mut self) {
+        self.inner.as_mut_slice().zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretBuf {}
+
+impl Drop for SecretBuf {
+    fn drop(&mut self) {
+        self.zeroize();
+        #[cfg(test)]
+        tests::record_drop(self);
+        // The region unmaps itself after this.
+    }
+}
+
+impl fmt::Debug for SecretBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBuf({} bytes)", self.len())
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::io;
+    use std::ptr;
+
+    /// guard page | data pages | guard page, with the data ending flush
+    /// against the trailing guard so overruns fault immediately.
+    pub struct Region {
+        base: *mut u8,
+        total: usize,
+        data: *mut u8,
+        len: usize,
+        pub locked: bool,
+    }
+
+    // SAFETY: the region is exclusively owned, like a Box<[u8]>.
+    unsafe impl Send for Region {}
+    unsafe impl Sync for Region {}
+
+    fn page_size() -> usize {
+        // SAFETY: sysconf has no preconditions.
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+
+    impl Region {
+        pub fn new(len: usize) -> io::Result<Self> {
+            let page = page_size();
+            let data_pages = len.div_ceil(page).max(1) * page;
+            let total = data_pages + 2 * page;
+
+            // SAFETY: anonymous private mapping; result checked below.
+            let base = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    total,
+                    libc::PROT_NONE,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                    -1,
+                    0,
+                )
+            };
+            if base == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            let base = base as *mut u8;
+
+            // SAFETY: the data pages lie inside the mapping we just created.
+            let data_start = unsafe { base.add(page) };
+            if unsafe {
+                libc::mprotect(
+                    data_start as *mut libc::c_void,
+                    data_pages,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                )
+            } != 0
+            {
+                let err = io::Error::last_os_error();
+                unsafe { libc::munmap(base as *mut libc::c_void, total) };
+                return Err(err);
+            }
+
+            // SAFETY: same range as above; failure only means we run unlocked.
+            let locked = unsafe { libc::mlock(data_start as *const libc::c_void, data_pages) } == 0;
+
+            #[cfg(target_os = "linux")]
+            unsafe {
+                libc::madvise(data_start as *mut libc::c_void, data_pages, libc::MADV_DONTDUMP);
+            }
+
+            Ok(Region {
+                base,
+                total,
+                data: unsafe { data_start.add(data_pages - len) },
+                len,
+                locked,
+            })
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            // SAFETY: `data..data+len` is readable for the life of the region.
+            unsafe { std::slice::from_raw_parts(self.data, self.len) }
+        }
+
+        pub fn as_mut_slice(&mut self) -> &mut [u8] {
+            // SAFETY: as above, and we hold the only reference.
+            unsafe { std::slice::from_raw_parts_mut(self.data, self.len) }
+        }
+    }
+
+    impl Drop for Region {
+        fn drop(&mut self) {
+            let page = page_size();
+            let data_pages = self.total - 2 * page;
+            // SAFETY: unlocking and unmapping exactly what `new` mapped.
+            unsafe {
+                let data_start = self.base.add(page);
+                if self.locked {
+                    libc::munlock(data_start as *const libc::c_void, data_pages);
+                }
+                libc::munmap(self.base as *mut libc::c_void, self.total);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::io;
+
+    /// Fallback without guard pages or locking; still wiped on drop.
+    pub struct Region {
+        bytes: Box<[u8]>,
+        pub locked: bool,
+    }
+
+    impl Region {
+        pub fn new(len: usize) -> io::Result<Self> {
+            Ok(Region { bytes: vec![0u8; len].into_boxed_slice(), locked: false })
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            &self.bytes
+        }
+
+        pub fn as_mut_slice(&mut self) -> &mut [u8] {
+            &mut self.bytes
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    thread_local! {
+        /// Whether the most recently dropped buffer was all zeroes when wiped.
+        static LAST_DROP_WIPED: Cell<Option<bool>> = const { Cell::new(None) };
+    }
+
+    pub(super) fn record_drop(buf: &SecretBuf) {
+        let wiped = buf.iter().all(|&b| b == 0);
+        LAST_DROP_WIPED.with(|cell| cell.set(Some(wiped)));
+    }
+
+    fn last_drop_wiped() -> Option<bool> {
+        LAST_DROP_WIPED.with(|cell| cell.take())
+    }
+
+    #[test]
+    fn key_buffer_is_wiped_on_drop() {
+        let key = SecretBuf::from_slice(&[0xA5; 32]).unwrap();
+        assert!(key.iter().all(|&b| b == 0xA5));
+        drop(key);
+        assert_eq!(last_drop_wiped(), Some(true));
+    }
+
+    #[test]
+    fn large_buffer_is_wiped_on_drop() {
+        let mut buf = SecretBuf::new(3 * 4096 + 17).unwrap();
+        buf.fill(0xFF);
+        drop(buf);
+        assert_eq!(last_drop_wiped(), Some(true));
+    }
+
+    #[test]
+    fn zeroize_clears_contents() {
+        let mut buf = SecretBuf::from_slice(b"secret").unwrap();
+        buf.zeroize();
+        assert_eq!(&buf[..], &[0u8; 6]);
+    }
+
+    #[test]
+    fn empty_buffer_is_allowed() {
+        let buf = SecretBuf::new(0).unwrap();
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn harden_process_succeeds() {
+        harden_process().unwrap();
+    }
+}
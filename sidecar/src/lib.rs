@@ -0,0 +1,148 @@
+//! Sidecar files for the `--detached` mode of the AEAD tools.
+//!
+//! A sidecar holds everything in a container except the ciphertext body: the
+//! header and per-file parameters (salt, nonce) exactly as they appear in the
+//! container, then the tag. The ciphertext file holds the body alone. Since
+//! the header keeps its magic bytes, a sidecar names the tool that wrote it.
+
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Extension of the default sidecar path.
+pub const EXTENSION: &str = "meta";
+
+/// Where a container keeps its tag.
+#[derive(Clone, Copy, Debug)]
+pub enum TagPosition {
+    /// Between the prefix and the body, as the SIV does.
+    BeforeBody,
+    /// After the body, as GCM and Poly1305 do.
+    AfterBody,
+}
+
+/// The shape of a container: a prefix (header and parameters), a body and a tag.
+#[derive(Clone, Copy, Debug)]
+pub struct Layout {
+    pub prefix_len: usize,
+    pub tag_len: usize,
+    pub tag: TagPosition,
+}
+
+/// A container or sidecar too short for its layout.
+#[derive(Debug)]
+pub struct LengthError {
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl fmt::Display for LengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Sidecar data has incorrect length (expected {} bytes, found {}).",
+            self.expected, self.found
+        )
+    }
+}
+
+impl Error for LengthError {}
+
+impl Layout {
+    /// Length of every sidecar written with this layout.
+    pub fn sidecar_len(&self) -> usize {
+        self.prefix_len + self.tag_len
+    }
+
+    /// Where the body starts in an attached container.
+    pub fn body_offset(&self) -> usize {
+        match self.tag {
+            TagPosition::BeforeBody => self.prefix_len + self.tag_len,
+            TagPosition::AfterBody => self.prefix_len,
+        }
+    }
+
+    /// Splits a container into its sidecar and body.
+    pub fn detach<'a>(&self, container: &'a [u8]) -> Result<(Vec<u8>, &'a [u8]), LengthError> {
+        if container.len() < self.sidecar_len() {
+            return Err(LengthError { expected: self.sidecar_len(), found: container.len() });
+        }
+        let (prefix, rest) = container.split_at(self.prefix_len);
+        let (tag, body) = match self.tag {
+            TagPosition::BeforeBody => rest.split_at(self.tag_len),
+            TagPosition::AfterBody => {
+                let (body, tag) = rest.split_at(rest.len() - self.tag_len);
+                (tag, body)
+            }
+        };
+        Ok(([prefix, tag].concat(), body))
+    }
+
+    /// Puts a container back together from its sidecar and body.
+    pub fn attach(&self, sidecar: &[u8], body: &[u8]) -> Result<Vec<u8>, LengthError> {
+        if sidecar.len() != self.sidecar_len() {
+            return Err(LengthError { expected: self.sidecar_len(), found: sidecar.len() });
+        }
+        let (prefix, tag) = sidecar.split_at(self.prefix_len);
+        Ok(match self.tag {
+            TagPosition::BeforeBody => [prefix, tag, body].concat(),
+            TagPosition::AfterBody => [prefix, body, tag].concat(),
+        })
+    }
+}
+
+/// The default sidecar for a ciphertext: `<ciphertext>.meta`.
+pub fn default_path(ciphertext: &Path) -> PathBuf {
+    let mut name = ciphertext.as_os_str().to_owned();
+    name.push(".");
+    name.push(EXTENSION);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIV: Layout = Layout { prefix_len: 5, tag_len: 16, tag: TagPosition::BeforeBody };
+    const GCM: Layout = Layout { prefix_len: 34, tag_len: 16, tag: TagPosition::AfterBody };
+
+    #[test]
+    fn detach_then_attach_restores_the_container() {
+        for layout in [SIV, GCM] {
+            let container: Vec<u8> = (0..100).collect();
+            let (sidecar, body) = layout.detach(&container).unwrap();
+            assert_eq!(sidecar.len(), layout.sidecar_len());
+            assert_eq!(body.len(), container.len() - layout.sidecar_len());
+            assert_eq!(layout.attach(&sidecar, body).unwrap(), container);
+        }
+    }
+
+    #[test]
+    fn the_sidecar_is_the_prefix_then_the_tag() {
+        let container: Vec<u8> = (0..60).collect();
+
+        // SIV: header, tag, body.
+        let (sidecar, body) = SIV.detach(&container).unwrap();
+        assert_eq!(sidecar, &container[..21]);
+        assert_eq!(body, &container[21..]);
+        assert_eq!(SIV.body_offset(), 21);
+
+        // GCM: header, salt, nonce, body, tag.
+        let (sidecar, body) = GCM.detach(&container).unwrap();
+        assert_eq!(sidecar, [&container[..34], &container[44..]].concat());
+        assert_eq!(body, &container[34..44]);
+        assert_eq!(GCM.body_offset(), 34);
+    }
+
+    #[test]
+    fn short_input_is_refused() {
+        assert!(GCM.detach(&[0; 49]).is_err());
+        assert!(SIV.attach(&[0; 20], b"body").is_err());
+        assert!(SIV.attach(&[0; 22], b"body").is_err());
+    }
+
+    #[test]
+    fn the_default_path_appends_meta() {
+        assert_eq!(default_path(Path::new("dir/a.enc")), PathBuf::from("dir/a.enc.meta"));
+    }
+}
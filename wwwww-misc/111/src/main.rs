@@ -1,15 +1,20 @@
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
 use clap::Parser;
-use rand::seq::SliceRandom;
-use rand::thread_rng;
-use std::fs::{File, OpenOptions};
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha256;
+use std::fs::File;
 use std::io::{self, Read, Write};
 use std::path::Path;
+use zeroize::Zeroizing;
 
 /// Command line arguments parser
 #[derive(Parser, Debug)]
 #[command(name = "XOR File Encryptor")]
 #[command(version = "1.0")]
-#[command(about = "Encrypt and decrypt files using XOR and a 256-byte nonce with S-box randomization")]
+#[command(about = "Encrypt and decrypt files with XChaCha20-Poly1305 under per-file keys derived from key.key")]
 struct Args {
     /// Mode: either "E" for encrypt or "D" for decrypt
     mode: String,
@@ -21,68 +26,48 @@ struct Args {
     output_file: String,
 }
 
-const NONCE_SIZE: usize = 256; // 256-byte nonce for XOR-based encryption
-
-// Rijndael S-box (AES S-box)
-const RIJNDAEL_SBOX: [u8; 256] = [
-    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5,
-    0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
-    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0,
-    0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
-    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc,
-    0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
-    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a,
-    0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
-    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0,
-    0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
-    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b,
-    0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
-    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85,
-    0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
-    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5,
-    0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
-    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17,
-    0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
-    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88,
-    0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
-    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c,
-    0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
-    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9,
-    0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
-    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6,
-    0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
-    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e,
-    0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
-    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94,
-    0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
-    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68,
-    0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
-];
-
-// Function to generate a 256-byte nonce with no repeated values and randomize with S-box
-fn generate_sbox_nonce() -> [u8; NONCE_SIZE] {
-    let mut nonce: [u8; NONCE_SIZE] = [0; NONCE_SIZE];
-    for (i, val) in nonce.iter_mut().enumerate() {
-        *val = i as u8;
-    }
-
-    // Shuffle the nonce to randomize it
-    let mut rng = thread_rng();
-    nonce.shuffle(&mut rng);
+// Files start with MAGIC, VERSION and a random 24-byte nonce; the
+// XChaCha20-Poly1305 ciphertext and its 16-byte tag follow. The whole header
+// is authenticated as associated data.
+const MAGIC: &[u8; 4] = b"XCH1";
+const VERSION: u8 = 1;
+const NONCE_SIZE: usize = 24;
+const HEADER_SIZE: usize = MAGIC.len() + 1 + NONCE_SIZE;
+const TAG_SIZE: usize = 16;
+// HKDF info for the per-file key
+const KEY_INFO: &[u8] = b"xcha file key v1";
+
+// Older files start with a 256-byte nonce: a shuffled permutation of 0..=255
+// mapped through the AES S-box, so every byte value appears exactly once.
+const LEGACY_NONCE_SIZE: usize = 256;
+
+// Derive the key for one file from key.key and the file's nonce with
+// HKDF-SHA256, so no two files are encrypted under the same key
+fn file_key(key: &[u8], nonce: &[u8]) -> Zeroizing<[u8; 32]> {
+    let mut file_key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(nonce), key)
+        .expand(KEY_INFO, &mut file_key[..])
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    file_key
+}
 
-    // Apply the Rijndael S-box transformation to each byte in the nonce
-    for val in nonce.iter_mut() {
-        *val = RIJNDAEL_SBOX[*val as usize];
+// A legacy file starts with a nonce in which no byte repeats. For a new file
+// (random nonce and ciphertext) the odds of that are below 1 in 10^100.
+fn is_legacy(contents: &[u8]) -> bool {
+    if contents.len() < LEGACY_NONCE_SIZE {
+        return false;
     }
-
-    nonce
+    let mut seen = [false; 256];
+    contents[..LEGACY_NONCE_SIZE]
+        .iter()
+        .all(|&byte| !std::mem::replace(&mut seen[byte as usize], true))
 }
 
-// XOR encrypt/decrypt using the key and the 256-byte nonce
-fn xor_encrypt_decrypt(data: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
+// XOR decrypt a legacy file using the key and the 256-byte nonce
+fn xor_decrypt_legacy(data: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
     let mut output = Vec::with_capacity(data.len());
 
-    // XOR each byte of the plaintext with the corresponding key and nonce byte
+    // XOR each byte with the corresponding key and nonce byte
     for (i, byte) in data.iter().enumerate() {
         let xor_byte = byte ^ key[i % key.len()] ^ nonce[i % nonce.len()];
         output.push(xor_byte);
@@ -99,7 +84,7 @@ fn read_key_file() -> io::Result<Vec<u8>> {
         std::process::exit(1);
     }
 
-    let mut file = File::open(&path)?;
+    let mut file = File::open(path)?;
     let mut key = Vec::new();
     file.read_to_end(&mut key)?;
 
@@ -131,56 +116,95 @@ fn write_output_file(file_out: &str, data: &[u8]) -> io::Result<()> {
     Ok(())
 }
 
-// Encrypt the input file
-fn encrypt_file(file_in: &str, file_out: &str) -> io::Result<()> {
-    let key = read_key_file()?;  // Read the key from key.key
-    let contents = read_input_file(file_in)?;  // Read the input file (plaintext)
-
-    // Generate a new nonce for encryption
-    let nonce = generate_sbox_nonce();
-
-    // Encrypt the plaintext using the generated nonce
-    let encrypted_message = xor_encrypt_decrypt(&contents, &key, &nonce);
+// Encrypt `contents` into a new file: header, ciphertext, tag
+fn seal(key: &[u8], contents: &[u8]) -> io::Result<Vec<u8>> {
+    // Generate a new random nonce and the header it goes in
+    let mut nonce = [0u8; NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+    let mut result = Vec::with_capacity(HEADER_SIZE + contents.len() + TAG_SIZE);
+    result.extend_from_slice(MAGIC);
+    result.push(VERSION);
+    result.extend_from_slice(&nonce);
+
+    // Encrypt under the per-file key, authenticating the header as well
+    let cipher = XChaCha20Poly1305::new(file_key(key, &nonce)[..].into());
+    let payload = Payload { msg: contents, aad: &result };
+    let encrypted_message = cipher
+        .encrypt(XNonce::from_slice(&nonce), payload)
+        .map_err(|_| io::Error::other("XChaCha20-Poly1305 encryption failed"))?;
+    result.extend_from_slice(&encrypted_message);
+    Ok(result)
+}
 
-    // Prepend the nonce to the encrypted message
-    let mut result = Vec::with_capacity(NONCE_SIZE + encrypted_message.len());
-    result.extend_from_slice(&nonce);  // Add the nonce at the beginning
-    result.extend_from_slice(&encrypted_message);  // Add the encrypted message
+// Check the tag over the header and ciphertext of a new file, then decrypt
+fn open(key: &[u8], contents: &[u8]) -> io::Result<Vec<u8>> {
+    let (header, encrypted_message) = contents.split_at(HEADER_SIZE);
+    let nonce = &header[MAGIC.len() + 1..];
+    let cipher = XChaCha20Poly1305::new(file_key(key, nonce)[..].into());
+    let payload = Payload { msg: encrypted_message, aad: header };
+    cipher.decrypt(XNonce::from_slice(nonce), payload).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "authentication failed: wrong key.key, or the file was modified",
+        )
+    })
+}
 
-    // Double-check the nonce size to ensure it is correct
-    assert_eq!(nonce.len(), NONCE_SIZE, "Generated nonce is not 256 bytes");
+// Encrypt the input file
+fn encrypt_file(file_in: &str, file_out: &str) -> io::Result<()> {
+    let key = Zeroizing::new(read_key_file()?);  // Read the key from key.key
+    let contents = Zeroizing::new(read_input_file(file_in)?);  // Read the input file (plaintext)
+    let result = seal(&key, &contents)?;
 
     // Write the result to the output file
     write_output_file(file_out, &result)?;
 
-    println!("Encryption complete. Nonce used is 256 bytes.");
+    println!("Encryption complete (XChaCha20-Poly1305).");
 
     Ok(())
 }
 
-// Decrypt the input file
+// Decrypt the input file, which may also be in the legacy format
 fn decrypt_file(file_in: &str, file_out: &str) -> io::Result<()> {
-    let key = read_key_file()?;  // Read the key from key.key
+    let key = Zeroizing::new(read_key_file()?);  // Read the key from key.key
     let contents = read_input_file(file_in)?;  // Read the input file (ciphertext)
 
-    if contents.len() <= NONCE_SIZE {
-        eprintln!("Error: Input file is too small to contain a valid nonce.");
+    if is_legacy(&contents) {
+        return decrypt_legacy(&contents, &key, file_out);
+    }
+    if contents.len() < HEADER_SIZE + TAG_SIZE || &contents[..MAGIC.len()] != MAGIC {
+        eprintln!("Error: Input file was not encrypted by this program.");
+        std::process::exit(1);
+    }
+    if contents[MAGIC.len()] != VERSION {
+        eprintln!("Error: Unsupported file version {}.", contents[MAGIC.len()]);
         std::process::exit(1);
     }
 
-    // Extract the nonce from the beginning of the file
-    let (nonce, encrypted_message) = contents.split_at(NONCE_SIZE);
+    let decrypted_message = Zeroizing::new(open(&key, &contents)?);
+
+    // Write the decrypted message to the output file
+    write_output_file(file_out, &decrypted_message)?;
 
-    // Double-check the nonce size to ensure it is correct
-    assert_eq!(nonce.len(), NONCE_SIZE, "Extracted nonce is not 256 bytes");
+    println!("Decryption complete (XChaCha20-Poly1305).");
+
+    Ok(())
+}
+
+// Decrypt a file written with the 256-byte S-box nonce. These files carry no
+// tag, so nothing can tell whether they were modified.
+fn decrypt_legacy(contents: &[u8], key: &[u8], file_out: &str) -> io::Result<()> {
+    // Extract the nonce from the beginning of the file
+    let (nonce, encrypted_message) = contents.split_at(LEGACY_NONCE_SIZE);
 
     // Decrypt the file using the extracted nonce
-    let decrypted_message = xor_encrypt_decrypt(encrypted_message, &key, nonce);
+    let decrypted_message = Zeroizing::new(xor_decrypt_legacy(encrypted_message, key, nonce));
 
     // Write the decrypted message to the output file
     write_output_file(file_out, &decrypted_message)?;
 
-    println!("Decryption complete. Nonce used is 256 bytes.");
+    println!("Decryption complete (legacy 256-byte nonce format).");
+    println!("Warning: legacy files are not authenticated. Encrypt the file again to upgrade it.");
 
     Ok(())
 }
@@ -217,3 +241,52 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"0123456789abcdef0123456789abcdef";
+    const PLAINTEXT: &[u8] = b"Invoice 2291: 14 crates, paid in full.\n";
+
+    // A legacy file: a 256-byte nonce with every byte value once, as the
+    // S-box mapped permutation was, then the data XORed with key and nonce
+    fn legacy_file() -> Vec<u8> {
+        let nonce: Vec<u8> = (0..=255u8).map(|i| i.wrapping_mul(167).wrapping_add(13)).collect();
+        let mut file = nonce.clone();
+        for (i, byte) in PLAINTEXT.iter().enumerate() {
+            file.push(byte ^ KEY[i % KEY.len()] ^ nonce[i % nonce.len()]);
+        }
+        file
+    }
+
+    #[test]
+    fn legacy_files_are_detected_and_decrypt() {
+        let file = legacy_file();
+        assert!(is_legacy(&file));
+        let (nonce, encrypted_message) = file.split_at(LEGACY_NONCE_SIZE);
+        assert_eq!(xor_decrypt_legacy(encrypted_message, KEY, nonce), PLAINTEXT);
+    }
+
+    #[test]
+    fn new_files_round_trip_and_are_not_legacy() {
+        let sealed = seal(KEY, PLAINTEXT).unwrap();
+        assert_eq!(&sealed[..MAGIC.len()], MAGIC);
+        assert_eq!(sealed.len(), HEADER_SIZE + PLAINTEXT.len() + TAG_SIZE);
+        assert!(!is_legacy(&sealed));
+        assert_eq!(open(KEY, &sealed).unwrap(), PLAINTEXT);
+        assert!(open(b"another key entirely", &sealed).is_err());
+    }
+
+    #[test]
+    fn a_modified_header_or_ciphertext_fails_authentication() {
+        let sealed = seal(KEY, PLAINTEXT).unwrap();
+        // Version byte, a nonce byte, a ciphertext byte, a tag byte
+        for index in [MAGIC.len(), MAGIC.len() + 1, HEADER_SIZE, sealed.len() - 1] {
+            let mut tampered = sealed.clone();
+            tampered[index] ^= 1;
+            let err = open(KEY, &tampered).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "byte {}", index);
+        }
+    }
+}
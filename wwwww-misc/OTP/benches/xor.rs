@@ -0,0 +1,160 @@
+//! Compares the memory-mapped engine and the buffered pipeline against the
+//! serialized buffered loop they replaced.
+//!
+//! The file benchmarks use `OTP_BENCH_MB` MiB of data (default 512); set it
+//! to a few thousand for the multi-GB numbers in the README:
+//!
+//!     OTP_BENCH_MB=4096 cargo bench
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+
+use aotp::mapped::{xor_mapped, xor_words, MappedJob};
+use aotp::pipeline;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rayon::prelude::*;
+use zeroize::Zeroize;
+
+const BUFFER_SIZE: usize = 8 * 1024 * 1024;
+
+/// The XOR from the buffered loop: byte by byte inside 1 MB rayon chunks.
+fn byte_chunks(input: &[u8], key: &[u8], output: &mut [u8]) {
+    output
+        .par_chunks_mut(1024 * 1024)
+        .zip(input.par_chunks(1024 * 1024))
+        .zip(key.par_chunks(1024 * 1024))
+        .for_each(|((output_chunk, input_chunk), key_chunk)| {
+            for i in 0..input_chunk.len() {
+                output_chunk[i] = input_chunk[i] ^ key_chunk[i];
+            }
+        });
+}
+
+/// The buffered loop: three fresh buffers per iteration, copied through
+/// `BufReader` and `BufWriter`, and zeroed afterwards.
+fn buffered_loop(input: &File, key: &File, output: &File) {
+    let mut input_reader = BufReader::with_capacity(BUFFER_SIZE, input);
+    let mut key_reader = BufReader::with_capacity(BUFFER_SIZE, key);
+    let mut writer = BufWriter::with_capacity(BUFFER_SIZE, output);
+    loop {
+        let mut input_buffer = vec![0u8; BUFFER_SIZE];
+        let mut key_buffer = vec![0u8; BUFFER_SIZE];
+        let mut output_buffer = vec![0u8; BUFFER_SIZE];
+        let n = input_reader.read(&mut input_buffer).unwrap();
+        if n == 0 {
+            break;
+        }
+        input_buffer.truncate(n);
+        key_buffer.truncate(n);
+        output_buffer.truncate(n);
+        key_reader.read_exact(&mut key_buffer).unwrap();
+        byte_chunks(&input_buffer, &key_buffer, &mut output_buffer);
+        writer.write_all(&output_buffer).unwrap();
+        input_buffer.zeroize();
+        key_buffer.zeroize();
+        output_buffer.zeroize();
+    }
+    writer.flush().unwrap();
+}
+
+fn kernels(c: &mut Criterion) {
+    let len = 64 * 1024 * 1024;
+    let input: Vec<u8> = (0..len).map(|i| i as u8).collect();
+    let key: Vec<u8> = (0..len).map(|i| (i * 7) as u8).collect();
+    let mut output = vec![0u8; len];
+
+    let mut group = c.benchmark_group("kernel");
+    group.throughput(Throughput::Bytes(len as u64));
+    group.bench_function("byte chunks", |b| {
+        b.iter(|| byte_chunks(&input, &key, &mut output))
+    });
+    group.bench_function("words", |b| b.iter(|| xor_words(&input, &key, &mut output)));
+    group.finish();
+}
+
+fn files(c: &mut Criterion) {
+    let mb: u64 = env::var("OTP_BENCH_MB")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(512);
+    let len = mb * 1024 * 1024;
+    let dir = env::temp_dir().join(format!("aotp-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input_path = write_test_file(&dir, "input", len, 3);
+    let key_path = write_test_file(&dir, "key", len, 5);
+    let output_path = dir.join("output");
+
+    let mut group = c.benchmark_group("file");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(len));
+    group.bench_function(
+        BenchmarkId::new("buffered loop", format!("{} MiB", mb)),
+        |b| {
+            b.iter(|| {
+                let output = File::create(&output_path).unwrap();
+                buffered_loop(
+                    &File::open(&input_path).unwrap(),
+                    &File::open(&key_path).unwrap(),
+                    &output,
+                );
+            })
+        },
+    );
+    group.bench_function(BenchmarkId::new("mapped", format!("{} MiB", mb)), |b| {
+        b.iter(|| {
+            let input = File::open(&input_path).unwrap();
+            let key = File::open(&key_path).unwrap();
+            let output = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&output_path)
+                .unwrap();
+            let job = MappedJob {
+                input: &input,
+                input_offset: 0,
+                key: &key,
+                key_offset: 0,
+                len,
+                prefix: &[],
+            };
+            xor_mapped(&job, &output, None, |_| {}).unwrap();
+        })
+    });
+    group.bench_function(BenchmarkId::new("pipeline", format!("{} MiB", mb)), |b| {
+        b.iter(|| {
+            let output = File::create(&output_path).unwrap();
+            pipeline::run(
+                File::open(&input_path).unwrap(),
+                File::open(&key_path).unwrap(),
+                &output,
+                None,
+                None,
+                |_| {},
+            )
+            .unwrap();
+        })
+    });
+    group.finish();
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn write_test_file(dir: &std::path::Path, name: &str, len: u64, seed: u8) -> PathBuf {
+    let path = dir.join(name);
+    let mut writer = BufWriter::new(File::create(&path).unwrap());
+    let block: Vec<u8> = (0..1024 * 1024)
+        .map(|i| (i as u8).wrapping_mul(seed))
+        .collect();
+    for _ in 0..len / block.len() as u64 {
+        writer.write_all(&block).unwrap();
+    }
+    writer.flush().unwrap();
+    path
+}
+
+criterion_group!(benches, kernels, files);
+criterion_main!(benches);
@@ -0,0 +1,4 @@
+//! XOR engines shared by the `aotp` binary and its benchmarks.
+
+pub mod mapped;
+pub mod pipeline;
@@ -1,13 +1,13 @@
 use clap::Parser;
-use sysinfo::System; // Removed SystemExt from here
-use zeroize::Zeroize;
 use indicatif::{ProgressBar, ProgressStyle};
 use log::{debug, error, info};
-use env_logger;
+use aotp::mapped::{xor_mapped, MappedJob};
+use aotp::pipeline::{self, MAX_CHUNK, MIN_CHUNK};
+use padledger::{BurnSide, Tagger, Untagged};
+use padtext::Format;
 use std::path::Path;
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufWriter, Read, Write};
-use rayon::prelude::*;
+use std::io::{Read, Seek, SeekFrom, Write};
 
 #[cfg(unix)]
 use std::os::unix::fs::OpenOptionsExt;
@@ -16,10 +16,6 @@ use std::os::unix::fs::OpenOptionsExt;
 // #[cfg(windows)]
 // use std::os::windows::fs::OpenOptionsExt;
 
-/// Constants for buffer sizes (in bytes)
-const MIN_BUFFER_SIZE: usize = 4 * 1024 * 1024;    // 4 MB
-const MAX_BUFFER_SIZE: usize = 64 * 1024 * 1024;   // 64 MB
-
 /// A simple OTP XOR Encryption/Decryption CLI tool.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -33,58 +29,44 @@ struct Args {
     /// Output file path
     output: String,
 
-    /// Buffer size in megabytes (optional)
+    /// Fixed chunk size in megabytes for buffered I/O (default: adapts to throughput)
     #[arg(short, long)]
     buffer_size: Option<usize>,
 
     /// Overwrite output file if it exists
     #[arg(short, long)]
     force: bool,
-}
-
-/// Determines the optimal buffer size based on system memory and file size.
-/// Ensures the buffer size is within defined minimum and maximum limits.
-fn determine_buffer_size(system: &System, file_size: u64) -> usize {
-    let available_memory_bytes = system.available_memory(); // Now directly on System
-
-    // Use 1/20th of available memory or MAX_BUFFER_SIZE, whichever is smaller
-    let buffer_based_on_mem = (available_memory_bytes / 20)
-        .min(MAX_BUFFER_SIZE as u64) as usize;
-
-    // Determine buffer size based on file size
-    let buffer_based_on_file = if file_size > 10 * 1024 * 1024 * 1024 {
-        32 * 1024 * 1024 // 32 MB for files > 10 GB
-    } else if file_size > 1 * 1024 * 1024 * 1024 {
-        16 * 1024 * 1024 // 16 MB for files > 1 GB
-    } else {
-        8 * 1024 * 1024 // 8 MB for smaller files
-    };
-
-    // Choose the smaller buffer size to prevent excessive memory usage
-    let buffer_size = buffer_based_on_mem.min(buffer_based_on_file);
 
-    // Clamp the buffer size within defined limits
-    buffer_size.clamp(MIN_BUFFER_SIZE, MAX_BUFFER_SIZE)
+    /// XOR from the start of the key without a pad header or ledger
+    /// (only for files from versions before the ledger)
+    #[arg(long)]
+    legacy: bool,
+
+    /// Also decrypt files whose pad header has no tag (from versions before
+    /// the tag). Nothing checks their data, and a stripped tag looks the same
+    #[arg(long, conflicts_with = "legacy")]
+    legacy_untagged: bool,
+
+    /// Overwrite the used pad bytes with random data once done, on both the
+    /// encrypting and the decrypting side
+    #[arg(long, conflicts_with = "legacy")]
+    burn: bool,
+
+    /// Use buffered reads and writes instead of memory-mapping the files
+    #[arg(long)]
+    no_mmap: bool,
+
+    /// Encrypt text into printable groups (digits: mod-10 groups of five,
+    /// letters: mod-26 groups of five), or decrypt such a message
+    #[arg(long, value_name = "digits|letters", value_parser = parse_text_format, conflicts_with = "legacy")]
+    text: Option<Format>,
 }
 
-/// Processes a chunk of data by performing XOR between input and key buffers.
-/// Utilizes chunk-based parallelism for optimized performance.
-fn process_chunk(input: &[u8], key: &[u8], output: &mut [u8]) {
-    let chunk_size = 1 * 1024 * 1024; // 1 MB
-
-    // Ensure that input, key, and output have the same length
-    assert_eq!(input.len(), key.len());
-    assert_eq!(input.len(), output.len());
-
-    output
-        .par_chunks_mut(chunk_size)
-        .zip(input.par_chunks(chunk_size))
-        .zip(key.par_chunks(chunk_size))
-        .for_each(|((output_chunk, input_chunk), key_chunk)| {
-            for i in 0..input_chunk.len() {
-                output_chunk[i] = input_chunk[i] ^ key_chunk[i];
-            }
-        });
+fn parse_text_format(name: &str) -> Result<Format, String> {
+    match Format::from_name(name) {
+        Some(format @ (Format::Digits | Format::Letters)) => Ok(format),
+        _ => Err("expected 'digits' or 'letters'".to_string()),
+    }
 }
 
 fn main() -> std::io::Result<()> {
@@ -96,32 +78,16 @@ fn main() -> std::io::Result<()> {
     // Parse command-line arguments
     let args = Args::parse();
 
-    // Initialize system information
-    let mut sys = System::new_all();
-    sys.refresh_memory();
-
     // Get input file size
     let input_metadata = std::fs::metadata(&args.input)?;
     let input_size = input_metadata.len();
 
-    // Determine buffer size: user-specified or dynamic based on system and file size
-    let buffer_size = if let Some(mb) = args.buffer_size {
-        let buffer = mb * 1024 * 1024;
-        let clamped = buffer.clamp(MIN_BUFFER_SIZE, MAX_BUFFER_SIZE);
-        info!("Buffer size specified by user: {} MB", clamped / (1024 * 1024));
-        println!("Buffer size used: {} MB", clamped / (1024 * 1024));
+    // A user-specified chunk size turns off adaptation in the buffered pipeline
+    let fixed_chunk = args.buffer_size.map(|mb| {
+        let clamped = mb.saturating_mul(1024 * 1024).clamp(MIN_CHUNK, MAX_CHUNK);
+        info!("Chunk size specified by user: {} KB", clamped / 1024);
         clamped
-    } else {
-        let buffer_size = determine_buffer_size(&sys, input_size);
-        info!(
-            "Dynamically determined buffer size: {} MB based on system resources and file size.",
-            buffer_size / (1024 * 1024)
-        );
-        println!("Buffer size used: {} MB", buffer_size / (1024 * 1024));
-        buffer_size
-    };
-
-    debug!("Using buffer size: {} bytes", buffer_size);
+    });
 
     // Check if output file already exists to prevent overwriting
     if Path::new(&args.output).exists() && !args.force {
@@ -149,6 +115,10 @@ fn main() -> std::io::Result<()> {
         std::process::exit(1);
     }
 
+    if let Some(format) = args.text {
+        return run_text(&args, format);
+    }
+
     // Get key file size
     let key_metadata = std::fs::metadata(&args.key)?;
     let key_size = key_metadata.len();
@@ -164,21 +134,52 @@ fn main() -> std::io::Result<()> {
         key_size as f64 / 1_073_741_824.0
     );
 
-    if key_size < input_size {
-        error!(
-            "Key file '{}' is smaller ({}) than input file '{}' ({}).",
-            args.key, key_size, args.input, input_size
-        );
-        std::process::exit(1);
-    }
-
     // Open input and key files
-    let input_file = File::open(&args.input)?;
-    let key_file = File::open(&args.key)?;
+    let mut input_file = File::open(&args.input)?;
+    let mut key_file = File::open(&args.key)?;
+
+    // Work out which pad bytes to use. Files with a pad header are decrypted
+    // from the range it names, once their tag has been checked; anything else
+    // is encrypted with a fresh range from the ledger, so no pad byte is ever
+    // used twice.
+    let (pad_range, data_size) = if args.legacy {
+        if key_size < input_size {
+            error!(
+                "Key file '{}' is smaller ({}) than input file '{}' ({}).",
+                args.key, key_size, args.input, input_size
+            );
+            std::process::exit(1);
+        }
+        (None, input_size)
+    } else {
+        let untagged = if args.legacy_untagged {
+            Untagged::Accept
+        } else {
+            Untagged::Refuse
+        };
+        match otp::pad_range(Path::new(&args.key), &mut input_file, input_size, untagged) {
+            Ok((header, encrypting)) => {
+                key_file.seek(SeekFrom::Start(header.data_offset()))?;
+                info!(
+                    "{} with pad bytes {}..{}.",
+                    if encrypting { "Encrypting" } else { "Decrypting" },
+                    header.offset,
+                    header.offset + header.length
+                );
+                (Some((header, encrypting)), header.data_len())
+            }
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    };
 
     // Open output file with secure permissions (rw-------)
+    // (readable too, so it can be memory-mapped)
     #[cfg(unix)]
     let output_file = OpenOptions::new()
+        .read(true)
         .write(true)
         .create(true)
         .truncate(true)
@@ -187,18 +188,24 @@ fn main() -> std::io::Result<()> {
 
     #[cfg(not(unix))]
     let output_file = OpenOptions::new()
+        .read(true)
         .write(true)
         .create(true)
         .truncate(true)
         .open(&args.output)?;
 
-    // Create buffered readers and writer with configurable buffer size
-    let mut input_reader = BufReader::with_capacity(buffer_size, input_file);
-    let mut key_reader = BufReader::with_capacity(buffer_size, key_file);
-    let mut writer = BufWriter::with_capacity(buffer_size, output_file);
+    // New ciphertexts start with the pad header and end with a one-time
+    // Poly1305 tag keyed from the pad
+    let (prefix, mut tagger) = match pad_range {
+        Some((header, true)) => (
+            header.to_bytes().to_vec(),
+            Some(Tagger::new(Path::new(&args.key), &header)?),
+        ),
+        _ => (Vec::new(), None),
+    };
 
     // Initialize progress bar
-    let pb = ProgressBar::new(input_size);
+    let pb = ProgressBar::new(data_size);
     pb.set_style(
         ProgressStyle::default_bar()
             .template(
@@ -208,49 +215,124 @@ fn main() -> std::io::Result<()> {
             .progress_chars("#>-"),
     );
 
-    // Start processing loop
-    loop {
-        // Allocate buffers
-        let mut input_buffer = vec![0u8; buffer_size];
-        let mut key_buffer = vec![0u8; buffer_size];
-        let mut output_buffer = vec![0u8; buffer_size];
-
-        // Read a chunk from input file
-        let input_bytes_read = input_reader.read(&mut input_buffer)?;
-        if input_bytes_read == 0 {
-            break; // EOF
+    // Map the files when the output is a regular file; pipes and other
+    // special files go through the buffered loop
+    if !args.no_mmap && output_file.metadata()?.is_file() {
+        debug!("Using memory-mapped I/O.");
+        let input_offset = input_file.stream_position()?;
+        let key_offset = key_file.stream_position()?;
+        let job = MappedJob {
+            input: &input_file,
+            input_offset,
+            key: &key_file,
+            key_offset,
+            len: data_size,
+            prefix: &prefix,
+        };
+        xor_mapped(&job, &output_file, tagger, |n| pb.inc(n))?;
+    } else {
+        debug!("Using buffered I/O.");
+
+        // Read, XOR and write on separate stages so disk and CPU overlap
+        (&output_file).write_all(&prefix)?;
+        let report = pipeline::run(
+            (&input_file).take(data_size),
+            &key_file,
+            &output_file,
+            fixed_chunk,
+            tagger.as_mut(),
+            |n| pb.inc(n),
+        )?;
+        if let Some(tagger) = tagger {
+            (&output_file).write_all(&tagger.finalize())?;
         }
 
-        // Resize buffers to actual bytes read
-        input_buffer.truncate(input_bytes_read);
-        key_buffer.truncate(input_bytes_read);
-        output_buffer.truncate(input_bytes_read);
-
-        // Read the corresponding chunk from key file
-        key_reader.read_exact(&mut key_buffer)?;
-
-        // Perform XOR operation
-        process_chunk(&input_buffer, &key_buffer, &mut output_buffer);
-
-        // Write the processed chunk to output file
-        writer.write_all(&output_buffer)?;
-
-        // Update progress bar
-        pb.inc(input_bytes_read as u64);
-
-        // Zero out the buffers securely
-        input_buffer.zeroize();
-        key_buffer.zeroize();
-        output_buffer.zeroize();
+        // Stats go to stderr: the output may well be stdout here
+        pb.suspend(|| {
+            eprintln!(
+                "Read {:.1} MB/s, XOR {:.1} MB/s, write {:.1} MB/s; overall {:.1} MB/s ({}-bound, final chunk {} KB)",
+                report.read.mb_per_sec(),
+                report.xor.mb_per_sec(),
+                report.write.mb_per_sec(),
+                report.mb_per_sec(),
+                if report.bottleneck() == "xor" { "CPU" } else { "disk" },
+                report.final_chunk / 1024
+            )
+        });
     }
 
-    // Flush the writer to ensure all data is written
-    writer.flush()?;
-
     // Finish progress bar
     pb.finish_with_message("Processing complete.");
 
+    if args.burn {
+        if let Some((header, encrypting)) = pad_range {
+            // The output must be on disk before the only other copy of the
+            // pad bytes is destroyed.
+            output_file.sync_all()?;
+            let key = Path::new(&args.key);
+            let side = if encrypting {
+                BurnSide::Encrypt
+            } else {
+                BurnSide::Decrypt
+            };
+            padledger::burn(key, &header, side)?;
+            info!(
+                "Burned pad bytes {}..{}.",
+                header.offset,
+                header.offset + header.length
+            );
+        }
+    }
+
     info!("Encryption/Decryption completed successfully.");
 
     Ok(())
 }
+
+/// Encrypts a text file into digit or letter groups, or decrypts a message
+/// in that form, using whole booklet pages of the key.
+fn run_text(args: &Args, format: Format) -> std::io::Result<()> {
+    let key = Path::new(&args.key);
+    let input = std::fs::read_to_string(&args.input)?;
+    let result = if padtext::is_message(&input) {
+        padtext::decrypt_text(key, &input).map(|(header, text)| (header, text + "\n", false))
+    } else {
+        padtext::encrypt_text(key, format, &input).map(|(header, text)| (header, text, true))
+    };
+    let (header, output, encrypting) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    info!(
+        "{} text with pad bytes {}..{}.",
+        if encrypting { "Encrypted" } else { "Decrypted" },
+        header.offset,
+        header.offset + header.length
+    );
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut output_file = options.open(&args.output)?;
+    output_file.write_all(output.as_bytes())?;
+
+    if args.burn {
+        output_file.sync_all()?;
+        let side = if encrypting {
+            BurnSide::Encrypt
+        } else {
+            BurnSide::Decrypt
+        };
+        padledger::burn(key, &header, side)?;
+        info!(
+            "Burned pad bytes {}..{}.",
+            header.offset,
+            header.offset + header.length
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,215 @@
+//! Zero-copy XOR over memory-mapped files.
+//!
+//! Input, key and output are mapped, and the output is sized up front with
+//! `set_len`, so the data is never copied through user-space buffers. The
+//! work is split on output pages and spread over the rayon pool.
+
+use std::fs::File;
+use std::io::{self, Write};
+
+use memmap2::{Mmap, MmapMut, MmapOptions};
+use padledger::{Tagger, TAG_LEN};
+use rayon::prelude::*;
+
+/// Size of the ranges handed to each rayon task. A multiple of every common
+/// page size, so the ranges split the output on page boundaries.
+pub const SPLIT_SIZE: usize = 1024 * 1024;
+
+/// XORs `input` with `key` into `output` a machine word at a time.
+///
+/// The loop has no cross-iteration dependencies, so the compiler turns it
+/// into SIMD loads and stores on any target that has them.
+pub fn xor_words(input: &[u8], key: &[u8], output: &mut [u8]) {
+    assert_eq!(input.len(), key.len());
+    assert_eq!(input.len(), output.len());
+
+    let mut out_words = output.chunks_exact_mut(8);
+    let mut in_words = input.chunks_exact(8);
+    let mut key_words = key.chunks_exact(8);
+    for ((out, inp), key) in (&mut out_words).zip(&mut in_words).zip(&mut key_words) {
+        let word = u64::from_ne_bytes(inp.try_into().unwrap())
+            ^ u64::from_ne_bytes(key.try_into().unwrap());
+        out.copy_from_slice(&word.to_ne_bytes());
+    }
+    for ((out, inp), key) in out_words
+        .into_remainder()
+        .iter_mut()
+        .zip(in_words.remainder())
+        .zip(key_words.remainder())
+    {
+        *out = inp ^ key;
+    }
+}
+
+/// XORs `key` into `data` a machine word at a time.
+pub fn xor_words_in_place(data: &mut [u8], key: &[u8]) {
+    assert_eq!(data.len(), key.len());
+
+    let mut data_words = data.chunks_exact_mut(8);
+    let mut key_words = key.chunks_exact(8);
+    for (word, key) in (&mut data_words).zip(&mut key_words) {
+        let xored = u64::from_ne_bytes((&*word).try_into().unwrap())
+            ^ u64::from_ne_bytes(key.try_into().unwrap());
+        word.copy_from_slice(&xored.to_ne_bytes());
+    }
+    for (byte, key) in data_words
+        .into_remainder()
+        .iter_mut()
+        .zip(key_words.remainder())
+    {
+        *byte ^= key;
+    }
+}
+
+/// One XOR job: `len` bytes of `input` from `input_offset`, XORed with the
+/// key from `key_offset`.
+pub struct MappedJob<'a> {
+    pub input: &'a File,
+    pub input_offset: u64,
+    pub key: &'a File,
+    pub key_offset: u64,
+    pub len: u64,
+    /// Written to the output before the data (the pad header when encrypting).
+    pub prefix: &'a [u8],
+}
+
+/// Runs `job` through memory maps, writing `prefix`, the XORed data and, if
+/// `tagger` is given, the tag over the data into `output`.
+///
+/// `output` must be a regular file opened for reading and writing. Its
+/// mapping is flushed before this returns. `on_progress` is called with the
+/// size of each finished range, from the rayon workers.
+pub fn xor_mapped(
+    job: &MappedJob,
+    output: &File,
+    tagger: Option<Tagger>,
+    on_progress: impl Fn(u64) + Sync,
+) -> io::Result<()> {
+    let len = usize::try_from(job.len)
+        .map_err(|_| io::Error::other("Input is too large to map on this platform."))?;
+    let prefix_len = job.prefix.len();
+    let tag_len = if tagger.is_some() { TAG_LEN } else { 0 };
+    output.set_len((prefix_len + len + tag_len) as u64)?;
+    if len == 0 {
+        // Zero-length mappings are an error on most systems.
+        let mut output = output;
+        output.write_all(job.prefix)?;
+        if let Some(tagger) = tagger {
+            output.write_all(&tagger.finalize())?;
+        }
+        return Ok(());
+    }
+
+    // SAFETY: the maps are only valid while no other process truncates the
+    // files. The tool owns its output, and the input and key are treated
+    // like any file read: concurrent changes give wrong output, not UB in
+    // safe code, as the bytes are only ever copied as plain `u8`.
+    let input = unsafe { map_range(job.input, job.input_offset, len)? };
+    let key = unsafe { map_range(job.key, job.key_offset, len)? };
+    let mut out = unsafe { MmapMut::map_mut(output)? };
+
+    out[..prefix_len].copy_from_slice(job.prefix);
+    let data = &mut out[prefix_len..prefix_len + len];
+
+    // The first range runs up to the first split boundary of the output
+    // file; the rest are whole SPLIT_SIZE ranges, each starting on a page.
+    let head = ((SPLIT_SIZE - prefix_len % SPLIT_SIZE) % SPLIT_SIZE).min(len);
+    let (data_head, data_rest) = data.split_at_mut(head);
+    xor_words(&input[..head], &key[..head], data_head);
+    on_progress(head as u64);
+    data_rest
+        .par_chunks_mut(SPLIT_SIZE)
+        .zip(input[head..].par_chunks(SPLIT_SIZE))
+        .zip(key[head..].par_chunks(SPLIT_SIZE))
+        .for_each(|((out, inp), key)| {
+            xor_words(inp, key, out);
+            on_progress(out.len() as u64);
+        });
+
+    if let Some(mut tagger) = tagger {
+        tagger.update(&out[prefix_len..prefix_len + len]);
+        out[prefix_len + len..].copy_from_slice(&tagger.finalize());
+    }
+    out.flush()
+}
+
+unsafe fn map_range(file: &File, offset: u64, len: usize) -> io::Result<Mmap> {
+    let available = file.metadata()?.len().saturating_sub(offset);
+    if (available as usize) < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "File is shorter than the range to map.",
+        ));
+    }
+    MmapOptions::new().offset(offset).len(len).map(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, OpenOptions};
+    use std::path::PathBuf;
+
+    fn temp_file(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("aotp-{}-{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn words_match_bytewise_xor() {
+        for len in [0, 1, 7, 8, 9, 63, 1000] {
+            let input: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let key: Vec<u8> = (0..len).map(|i| (i * 13 + 5) as u8).collect();
+            let mut output = vec![0u8; len];
+            xor_words(&input, &key, &mut output);
+            let expected: Vec<u8> = input.iter().zip(&key).map(|(a, b)| a ^ b).collect();
+            assert_eq!(output, expected, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn ranges_cover_the_data_around_a_prefix() {
+        // Longer than two split ranges, with offsets and a prefix that do not
+        // line up with them.
+        let len = 2 * SPLIT_SIZE + 12345;
+        let input: Vec<u8> = (0..len + 29).map(|i| (i * 7) as u8).collect();
+        let key: Vec<u8> = (0..len + 100).map(|i| (i * 31 + 1) as u8).collect();
+        let input_path = temp_file("map-in", &input);
+        let key_path = temp_file("map-key", &key);
+        let output_path = temp_file("map-out", &[]);
+
+        let output = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&output_path)
+            .unwrap();
+        let job = MappedJob {
+            input: &File::open(&input_path).unwrap(),
+            input_offset: 29,
+            key: &File::open(&key_path).unwrap(),
+            key_offset: 100,
+            len: len as u64,
+            prefix: b"prefix",
+        };
+        let done = std::sync::atomic::AtomicU64::new(0);
+        xor_mapped(&job, &output, None, |n| {
+            done.fetch_add(n, std::sync::atomic::Ordering::Relaxed);
+        })
+        .unwrap();
+
+        let written = fs::read(&output_path).unwrap();
+        let expected: Vec<u8> = input[29..]
+            .iter()
+            .zip(&key[100..])
+            .map(|(a, b)| a ^ b)
+            .collect();
+        assert_eq!(&written[..6], b"prefix");
+        assert!(written[6..] == expected[..]);
+        assert_eq!(done.into_inner(), len as u64);
+
+        for path in [input_path, key_path, output_path] {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
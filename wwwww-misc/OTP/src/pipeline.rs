@@ -0,0 +1,363 @@
+//! Three-stage XOR pipeline for input that cannot be memory-mapped.
+//!
+//! A reader thread fills chunks of input and key, the calling thread XORs
+//! them on the rayon pool (and feeds the tagger, which needs the chunks in
+//! order), and a writer thread writes them out. The stages hand chunks over
+//! bounded channels and recycle a fixed set of buffers, so all three run at
+//! once and nothing is allocated per chunk.
+//!
+//! Unless a fixed size is asked for, the reader adjusts the chunk size by
+//! hill climbing on the throughput it measures: it keeps doubling or halving
+//! while the rate improves and turns round when it gets worse.
+
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use padledger::Tagger;
+use rayon::prelude::*;
+use zeroize::Zeroize;
+
+use crate::mapped::xor_words_in_place;
+
+/// Smallest chunk the pipeline adapts down to.
+pub const MIN_CHUNK: usize = 256 * 1024;
+/// Largest chunk the pipeline adapts up to.
+pub const MAX_CHUNK: usize = 64 * 1024 * 1024;
+/// Chunk size the pipeline starts from.
+const START_CHUNK: usize = 4 * 1024 * 1024;
+/// Buffers in flight: one per stage, so every stage can work at once.
+const BUFFERS: usize = 3;
+/// Chunks read between chunk-size adjustments.
+const CHUNKS_PER_EPOCH: u32 = 4;
+/// Rate changes smaller than this are treated as noise.
+const RATE_TOLERANCE: f64 = 0.05;
+/// Size of the pieces each XOR task works on.
+const XOR_SPLIT: usize = 1024 * 1024;
+
+/// Bytes a stage handled and the time it spent working on them, not
+/// counting time spent waiting for the other stages.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StageStats {
+    pub bytes: u64,
+    pub busy: Duration,
+}
+
+impl StageStats {
+    /// Throughput while busy, in MB/s.
+    pub fn mb_per_sec(&self) -> f64 {
+        mb_per_sec(self.bytes, self.busy)
+    }
+}
+
+/// What a pipeline run measured.
+#[derive(Clone, Copy, Debug)]
+pub struct Report {
+    pub read: StageStats,
+    pub xor: StageStats,
+    pub write: StageStats,
+    pub wall: Duration,
+    /// Chunk size in use when the input ran out.
+    pub final_chunk: usize,
+}
+
+impl Report {
+    /// End-to-end throughput, in MB/s.
+    pub fn mb_per_sec(&self) -> f64 {
+        mb_per_sec(self.xor.bytes, self.wall)
+    }
+
+    /// Name of the slowest stage, which bounds the whole pipeline.
+    pub fn bottleneck(&self) -> &'static str {
+        let stages = [
+            ("read", self.read),
+            ("xor", self.xor),
+            ("write", self.write),
+        ];
+        stages
+            .iter()
+            .min_by(|a, b| a.1.mb_per_sec().total_cmp(&b.1.mb_per_sec()))
+            .map(|(name, _)| *name)
+            .unwrap()
+    }
+}
+
+fn mb_per_sec(bytes: u64, time: Duration) -> f64 {
+    bytes as f64 / 1_000_000.0 / time.as_secs_f64().max(1e-9)
+}
+
+struct Chunk {
+    data: Vec<u8>,
+    key: Vec<u8>,
+    len: usize,
+}
+
+/// XORs everything `input` yields with the same number of bytes from `key`
+/// and writes the result to `output`.
+///
+/// `fixed_chunk` turns adaptation off. `tagger`, if given, absorbs the
+/// output. `on_progress` is called from the writer thread with the size of
+/// each chunk written.
+pub fn run<R, K, W>(
+    input: R,
+    key: K,
+    output: W,
+    fixed_chunk: Option<usize>,
+    mut tagger: Option<&mut Tagger>,
+    on_progress: impl Fn(u64) + Sync,
+) -> io::Result<Report>
+where
+    R: Read + Send,
+    K: Read + Send,
+    W: Write + Send,
+{
+    let start = Instant::now();
+    let (free_tx, free_rx) = sync_channel::<Chunk>(BUFFERS);
+    let (read_tx, read_rx) = sync_channel::<io::Result<Chunk>>(1);
+    let (xored_tx, xored_rx) = sync_channel::<Chunk>(1);
+    for _ in 0..BUFFERS {
+        free_tx
+            .send(Chunk {
+                data: Vec::new(),
+                key: Vec::new(),
+                len: 0,
+            })
+            .unwrap();
+    }
+
+    thread::scope(|scope| {
+        let reader = scope.spawn(move || read_stage(input, key, fixed_chunk, free_rx, read_tx));
+        let on_progress = &on_progress;
+        let writer = scope.spawn(move || write_stage(output, xored_rx, free_tx, on_progress));
+
+        // XOR stage, on this thread.
+        let mut xor = StageStats::default();
+        let mut failed = None;
+        for chunk in read_rx {
+            let mut chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    failed = Some(e);
+                    break;
+                }
+            };
+            let began = Instant::now();
+            let len = chunk.len;
+            chunk.data[..len]
+                .par_chunks_mut(XOR_SPLIT)
+                .zip(chunk.key[..len].par_chunks(XOR_SPLIT))
+                .for_each(|(data, key)| xor_words_in_place(data, key));
+            if let Some(tagger) = tagger.as_deref_mut() {
+                tagger.update(&chunk.data[..len]);
+            }
+            xor.busy += began.elapsed();
+            xor.bytes += len as u64;
+            if xored_tx.send(chunk).is_err() {
+                // The writer stopped; its error is reported below.
+                break;
+            }
+        }
+        drop(xored_tx);
+
+        let write = writer.join().unwrap();
+        let (read, final_chunk) = reader.join().unwrap();
+        if let Some(e) = failed {
+            return Err(e);
+        }
+        Ok(Report {
+            read,
+            xor,
+            write: write?,
+            wall: start.elapsed(),
+            final_chunk,
+        })
+    })
+}
+
+fn read_stage<R: Read, K: Read>(
+    mut input: R,
+    mut key: K,
+    fixed_chunk: Option<usize>,
+    free: Receiver<Chunk>,
+    out: SyncSender<io::Result<Chunk>>,
+) -> (StageStats, usize) {
+    let mut stats = StageStats::default();
+    let mut sizer = ChunkSizer::new(fixed_chunk);
+    for mut chunk in free {
+        let size = sizer.size;
+        let began = Instant::now();
+        chunk.data.resize(size, 0);
+        chunk.key.resize(size, 0);
+        let result = read_full(&mut input, &mut chunk.data).and_then(|n| {
+            key.read_exact(&mut chunk.key[..n])?;
+            Ok(n)
+        });
+        stats.busy += began.elapsed();
+        match result {
+            Ok(0) => break,
+            Ok(n) => {
+                chunk.len = n;
+                stats.bytes += n as u64;
+                if out.send(Ok(chunk)).is_err() {
+                    break;
+                }
+                sizer.record(n);
+            }
+            Err(e) => {
+                let _ = out.send(Err(e));
+                break;
+            }
+        }
+    }
+    (stats, sizer.size)
+}
+
+fn write_stage<W: Write>(
+    mut output: W,
+    chunks: Receiver<Chunk>,
+    free: SyncSender<Chunk>,
+    on_progress: &(impl Fn(u64) + Sync),
+) -> io::Result<StageStats> {
+    let mut stats = StageStats::default();
+    for mut chunk in chunks {
+        let began = Instant::now();
+        output.write_all(&chunk.data[..chunk.len])?;
+        stats.busy += began.elapsed();
+        stats.bytes += chunk.len as u64;
+        on_progress(chunk.len as u64);
+
+        chunk.data.zeroize();
+        chunk.key.zeroize();
+        // The reader may already have finished.
+        let _ = free.send(chunk);
+    }
+    let began = Instant::now();
+    output.flush()?;
+    stats.busy += began.elapsed();
+    Ok(stats)
+}
+
+/// Reads until `buf` is full or the input ends.
+fn read_full<R: Read>(input: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match input.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Picks the next chunk size from the rate the pipeline sustains.
+///
+/// The reader only gets a free buffer once a chunk has left the pipeline, so
+/// the rate it reads at over an epoch is the rate of the slowest stage.
+struct ChunkSizer {
+    size: usize,
+    fixed: bool,
+    growing: bool,
+    epoch_start: Instant,
+    epoch_bytes: u64,
+    epoch_chunks: u32,
+    last_rate: Option<f64>,
+}
+
+impl ChunkSizer {
+    fn new(fixed_chunk: Option<usize>) -> ChunkSizer {
+        ChunkSizer {
+            size: fixed_chunk.unwrap_or(START_CHUNK),
+            fixed: fixed_chunk.is_some(),
+            growing: true,
+            epoch_start: Instant::now(),
+            epoch_bytes: 0,
+            epoch_chunks: 0,
+            last_rate: None,
+        }
+    }
+
+    fn record(&mut self, bytes: usize) {
+        self.epoch_bytes += bytes as u64;
+        self.epoch_chunks += 1;
+        if self.fixed || self.epoch_chunks < CHUNKS_PER_EPOCH {
+            return;
+        }
+        let rate = self.epoch_bytes as f64 / self.epoch_start.elapsed().as_secs_f64().max(1e-9);
+        self.adjust(rate);
+        self.epoch_start = Instant::now();
+        self.epoch_bytes = 0;
+        self.epoch_chunks = 0;
+    }
+
+    fn adjust(&mut self, rate: f64) {
+        if let Some(last) = self.last_rate {
+            if rate < last * (1.0 - RATE_TOLERANCE) {
+                // The last step made things worse: go back the other way.
+                self.growing = !self.growing;
+            } else if rate < last * (1.0 + RATE_TOLERANCE) {
+                // No real difference: stay at this size.
+                self.last_rate = Some(rate);
+                return;
+            }
+        }
+        self.last_rate = Some(rate);
+        let next = if self.growing {
+            self.size.saturating_mul(2)
+        } else {
+            self.size / 2
+        };
+        self.size = next.clamp(MIN_CHUNK, MAX_CHUNK);
+        if self.size == MIN_CHUNK || self.size == MAX_CHUNK {
+            self.growing = self.size == MIN_CHUNK;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_matches_bytewise_xor() {
+        let len = 3 * MIN_CHUNK + 999;
+        let input: Vec<u8> = (0..len).map(|i| (i * 3) as u8).collect();
+        let key: Vec<u8> = (0..len).map(|i| (i * 11 + 7) as u8).collect();
+        let mut output = Vec::new();
+        let report = run(
+            &input[..],
+            &key[..],
+            &mut output,
+            Some(MIN_CHUNK),
+            None,
+            |_| {},
+        )
+        .unwrap();
+        let expected: Vec<u8> = input.iter().zip(&key).map(|(a, b)| a ^ b).collect();
+        assert!(output == expected);
+        assert_eq!(report.read.bytes, len as u64);
+        assert_eq!(report.write.bytes, len as u64);
+    }
+
+    #[test]
+    fn short_key_is_an_error() {
+        let input = [1u8; 1000];
+        let key = [2u8; 10];
+        assert!(run(&input[..], &key[..], Vec::new(), None, None, |_| {}).is_err());
+    }
+
+    #[test]
+    fn sizer_turns_round_when_the_rate_drops() {
+        let mut sizer = ChunkSizer::new(None);
+        sizer.adjust(100.0);
+        assert_eq!(sizer.size, START_CHUNK * 2);
+        sizer.adjust(200.0);
+        assert_eq!(sizer.size, START_CHUNK * 4);
+        sizer.adjust(50.0);
+        assert_eq!(sizer.size, START_CHUNK * 2);
+        sizer.adjust(51.0);
+        assert_eq!(sizer.size, START_CHUNK * 2);
+    }
+}
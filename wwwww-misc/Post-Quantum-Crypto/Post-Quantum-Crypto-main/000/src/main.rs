@@ -5,6 +5,26 @@ use std::io::{self, Read, Write};
 use std::path::Path;
 
 use std::io::stdin;
+use std::process;
+
+use clap::Parser;
+
+/// Encrypts or decrypts a file with Kyber1024, creating a keypair in the
+/// current directory if there is none.
+///
+/// A mode or file name that is left out is asked for.
+#[derive(Parser)]
+#[command(name = "post_quantum_file_crypto", version, about)]
+struct Cli {
+    /// E to encrypt, D to decrypt
+    mode: Option<String>,
+
+    /// File to encrypt or decrypt
+    input: Option<String>,
+
+    /// Where to write the result
+    output: Option<String>,
+}
 
 // Encrypt a random symmetric key using Kyber1024
 fn encrypt_key(public_key: &PublicKey) -> (Vec<u8>, Vec<u8>) {
@@ -85,6 +105,8 @@ fn decrypt_file(input_path: &str, output_path: &str, secret_key: &SecretKey) ->
 }
 
 fn main() {
+    let cli = Cli::parse();
+
     // Load or generate keypair
     let keypair = if Path::new("public_key.bin").exists() && Path::new("secret_key.bin").exists() {
         println!("Loading keys from files...");
@@ -98,43 +120,48 @@ fn main() {
 
     let (public_key, secret_key) = keypair;
 
-    // Get user input for mode and file paths
-    println!("Enter mode (E for encrypt, D for decrypt): ");
-    let mut mode = String::new();
-    stdin().read_line(&mut mode).expect("Failed to read mode");
+    // Take the mode and file paths from the arguments, or ask for them
+    let mode = cli.mode.unwrap_or_else(|| prompt("Enter mode (E for encrypt, D for decrypt): "));
     let mode = mode.trim().to_uppercase();
-
-    println!("Enter input filename: ");
-    let mut input_filename = String::new();
-    stdin().read_line(&mut input_filename).expect("Failed to read input filename");
-    let input_filename = input_filename.trim();
-
-    println!("Enter output filename: ");
-    let mut output_filename = String::new();
-    stdin().read_line(&mut output_filename).expect("Failed to read output filename");
-    let output_filename = output_filename.trim();
+    let input_filename = cli.input.unwrap_or_else(|| prompt("Enter input filename: "));
+    let output_filename = cli.output.unwrap_or_else(|| prompt("Enter output filename: "));
 
     match mode.as_str() {
         "E" => {
             // Encrypt the file
-            match encrypt_file(input_filename, output_filename, &public_key) {
+            match encrypt_file(&input_filename, &output_filename, &public_key) {
                 Ok(_) => println!("File encrypted successfully!"),
-                Err(e) => eprintln!("Error encrypting file: {}", e),
+                Err(e) => {
+                    eprintln!("Error encrypting file: {}", e);
+                    process::exit(1);
+                }
             }
         }
         "D" => {
             // Decrypt the file
-            match decrypt_file(input_filename, output_filename, &secret_key) {
+            match decrypt_file(&input_filename, &output_filename, &secret_key) {
                 Ok(_) => println!("File decrypted successfully!"),
-                Err(e) => eprintln!("Error decrypting file: {}", e),
+                Err(e) => {
+                    eprintln!("Error decrypting file: {}", e);
+                    process::exit(1);
+                }
             }
         }
         _ => {
             eprintln!("Invalid mode entered. Please enter 'E' for encryption or 'D' for decryption.");
+            process::exit(1);
         }
     }
 }
 
+// Ask for a value on standard input, for arguments that were left out
+fn prompt(question: &str) -> String {
+    println!("{}", question);
+    let mut answer = String::new();
+    stdin().read_line(&mut answer).expect("Failed to read input");
+    answer.trim().to_string()
+}
+
 // Save keypair to files
 fn save_keypair(public_key: &PublicKey, secret_key: &SecretKey) -> io::Result<()> {
     let mut public_key_file = fs::File::create("public_key.bin")?;
@@ -161,4 +188,4 @@ fn load_keypair() -> io::Result<(PublicKey, SecretKey)> {
     let secret_key = SecretKey::from_bytes(&secret_key_bytes).expect("Failed to create secret key from bytes");
 
     Ok((public_key, secret_key))
-}
\ No newline at end of file
+}
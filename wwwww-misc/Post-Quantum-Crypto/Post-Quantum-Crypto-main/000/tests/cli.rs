@@ -0,0 +1,103 @@
+use pqcrypto::kem::kyber1024::{ciphertext_bytes, decapsulate, Ciphertext, SecretKey};
+use pqcrypto::traits::kem::{Ciphertext as _, SecretKey as _, SharedSecret as _};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const MINUTES: &[u8] = b"Minutes, 12 May: budget approved; review moves to June.\n";
+
+fn run(dir: &Path, args: &[&str]) -> bool {
+    Command::new(env!("CARGO_BIN_EXE_post_quantum_file_crypto"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap()
+        .success()
+}
+
+#[test]
+fn output_is_a_kyber_ciphertext_then_the_xored_data() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("minutes.txt"), MINUTES).unwrap();
+
+    assert!(run(dir.path(), &["E", "minutes.txt", "minutes.pq"]));
+    assert_eq!(
+        fs::read(dir.path().join("public_key.bin")).unwrap().len(),
+        1568
+    );
+    let secret_key = fs::read(dir.path().join("secret_key.bin")).unwrap();
+    assert_eq!(secret_key.len(), 3168);
+
+    let file = fs::read(dir.path().join("minutes.pq")).unwrap();
+    let (kem, rest) = file.split_at(ciphertext_bytes());
+    let (length, data) = rest.split_at(8);
+    assert_eq!(
+        u64::from_le_bytes(length.try_into().unwrap()),
+        MINUTES.len() as u64
+    );
+
+    // Decapsulating with the saved secret key gives the XOR key
+    let secret_key = SecretKey::from_bytes(&secret_key).unwrap();
+    let shared = decapsulate(&Ciphertext::from_bytes(kem).unwrap(), &secret_key);
+    let plain: Vec<u8> = data
+        .iter()
+        .zip(shared.as_bytes().iter().cycle())
+        .map(|(byte, key)| byte ^ key)
+        .collect();
+    assert_eq!(plain, MINUTES);
+}
+
+#[test]
+fn the_keypair_is_kept_and_each_file_gets_a_new_shared_key() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("minutes.txt"), MINUTES).unwrap();
+
+    assert!(run(dir.path(), &["E", "minutes.txt", "a.pq"]));
+    let public_key = fs::read(dir.path().join("public_key.bin")).unwrap();
+    assert!(run(dir.path(), &["E", "minutes.txt", "b.pq"]));
+    assert_eq!(
+        fs::read(dir.path().join("public_key.bin")).unwrap(),
+        public_key
+    );
+    assert_ne!(
+        fs::read(dir.path().join("a.pq")).unwrap(),
+        fs::read(dir.path().join("b.pq")).unwrap()
+    );
+
+    // The mode is not case sensitive
+    for (input, output) in [("a.pq", "a.txt"), ("b.pq", "b.txt")] {
+        assert!(run(dir.path(), &["d", input, output]));
+        assert_eq!(fs::read(dir.path().join(output)).unwrap(), MINUTES);
+    }
+}
+
+#[test]
+fn asks_for_the_files_after_the_mode() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("minutes.txt"), MINUTES).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_post_quantum_file_crypto"))
+        .arg("E")
+        .current_dir(dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    write!(child.stdin.take().unwrap(), "minutes.txt\nminutes.pq\n").unwrap();
+    assert!(child.wait().unwrap().success());
+    let length = fs::metadata(dir.path().join("minutes.pq")).unwrap().len();
+    assert_eq!(length, (ciphertext_bytes() + 8 + MINUTES.len()) as u64);
+}
+
+#[test]
+fn fails_for_an_unknown_mode_or_a_missing_file() {
+    let dir = TempDir::new().unwrap();
+    assert!(!run(dir.path(), &["X", "minutes.txt", "out.pq"]));
+    assert!(!run(dir.path(), &["E", "missing.txt", "out.pq"]));
+    assert!(!dir.path().join("out.pq").exists());
+}
@@ -1,10 +1,29 @@
 use pqcrypto_frodo::frodokem640shake::*;
 use pqcrypto_traits::kem::Ciphertext as CiphertextTrait;
-use blake3;
 use std::fs;
 use std::io::{self, BufReader, BufWriter, Read, Write};
 use std::path::Path;
 use std::io::stdin;
+use std::process;
+
+use clap::Parser;
+
+/// Encrypts or decrypts a file with a key in `key.key`, which is created if it does not
+/// exist.
+///
+/// A mode or file name that is left out is asked for.
+#[derive(Parser)]
+#[command(name = "post_quantum_file_crypto", version, about)]
+struct Cli {
+    /// E to encrypt, D to decrypt
+    mode: Option<String>,
+
+    /// File to encrypt or decrypt
+    input: Option<String>,
+
+    /// Where to write the result
+    output: Option<String>,
+}
 
 // Generate a strong post-quantum key using FrodoKEM and BLAKE3
 fn generate_key() -> Vec<u8> {
@@ -106,39 +125,46 @@ fn xor_encrypt(data: &[u8], key: &[u8]) -> Vec<u8> {
 }
 
 fn main() {
-    // Get user input for mode and file paths
-    println!("Enter mode (E for encrypt, D for decrypt): ");
-    let mut mode = String::new();
-    stdin().read_line(&mut mode).expect("Failed to read mode");
-    let mode = mode.trim().to_uppercase();
+    let cli = Cli::parse();
 
-    println!("Enter input filename: ");
-    let mut input_filename = String::new();
-    stdin().read_line(&mut input_filename).expect("Failed to read input filename");
-    let input_filename = input_filename.trim();
-
-    println!("Enter output filename: ");
-    let mut output_filename = String::new();
-    stdin().read_line(&mut output_filename).expect("Failed to read output filename");
-    let output_filename = output_filename.trim();
+    // Take the mode and file paths from the arguments, or ask for them
+    let mode = cli.mode.unwrap_or_else(|| prompt("Enter mode (E for encrypt, D for decrypt): "));
+    let mode = mode.trim().to_uppercase();
+    let input_filename = cli.input.unwrap_or_else(|| prompt("Enter input filename: "));
+    let output_filename = cli.output.unwrap_or_else(|| prompt("Enter output filename: "));
 
     match mode.as_str() {
         "E" => {
             // Encrypt the file
-            match encrypt_file(input_filename, output_filename) {
+            match encrypt_file(&input_filename, &output_filename) {
                 Ok(_) => println!("File encrypted successfully!"),
-                Err(e) => eprintln!("Error encrypting file: {}", e),
+                Err(e) => {
+                    eprintln!("Error encrypting file: {}", e);
+                    process::exit(1);
+                }
             }
         }
         "D" => {
             // Decrypt the file
-            match decrypt_file(input_filename, output_filename) {
+            match decrypt_file(&input_filename, &output_filename) {
                 Ok(_) => println!("File decrypted successfully!"),
-                Err(e) => eprintln!("Error decrypting file: {}", e),
+                Err(e) => {
+                    eprintln!("Error decrypting file: {}", e);
+                    process::exit(1);
+                }
             }
         }
         _ => {
             eprintln!("Invalid mode entered. Please enter 'E' for encryption or 'D' for decryption.");
+            process::exit(1);
         }
     }
-}
\ No newline at end of file
+}
+
+// Ask for a value on standard input, for arguments that were left out
+fn prompt(question: &str) -> String {
+    println!("{}", question);
+    let mut answer = String::new();
+    stdin().read_line(&mut answer).expect("Failed to read input");
+    answer.trim().to_string()
+}
@@ -0,0 +1,90 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+fn run(dir: &Path, args: &[&str]) -> bool {
+    Command::new(env!("CARGO_BIN_EXE_post_quantum_file_crypto"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap()
+        .success()
+}
+
+/// More than two 4 KB chunks, so the key has to line up across them.
+fn photo() -> Vec<u8> {
+    (0..10_000u32).map(|i| (i % 251) as u8).collect()
+}
+
+fn xor_with(data: &[u8], key: &[u8]) -> Vec<u8> {
+    data.iter()
+        .zip(key.iter().cycle())
+        .map(|(byte, key)| byte ^ key)
+        .collect()
+}
+
+#[test]
+fn a_new_32_byte_key_is_repeated_over_the_file() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("photo.raw"), photo()).unwrap();
+
+    assert!(run(dir.path(), &["E", "photo.raw", "photo.enc"]));
+    let key = fs::read(dir.path().join("key.key")).unwrap();
+    assert_eq!(key.len(), 32);
+    assert_eq!(
+        fs::read(dir.path().join("photo.enc")).unwrap(),
+        xor_with(&photo(), &key)
+    );
+
+    assert!(run(dir.path(), &["d", "photo.enc", "photo.out"]));
+    assert_eq!(fs::read(dir.path().join("photo.out")).unwrap(), photo());
+}
+
+#[test]
+fn an_existing_key_file_is_used_as_it_is() {
+    let dir = TempDir::new().unwrap();
+    let key: Vec<u8> = (100..132).collect();
+    fs::write(dir.path().join("key.key"), &key).unwrap();
+    fs::write(dir.path().join("photo.raw"), photo()).unwrap();
+
+    assert!(run(dir.path(), &["E", "photo.raw", "photo.enc"]));
+    assert_eq!(fs::read(dir.path().join("key.key")).unwrap(), key);
+    assert_eq!(
+        fs::read(dir.path().join("photo.enc")).unwrap(),
+        xor_with(&photo(), &key)
+    );
+}
+
+#[test]
+fn decrypting_needs_the_key_file() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("photo.enc"), photo()).unwrap();
+
+    assert!(!run(dir.path(), &["D", "photo.enc", "photo.out"]));
+    assert!(!dir.path().join("key.key").exists());
+}
+
+#[test]
+fn asks_for_the_mode_and_files() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("photo.raw"), photo()).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_post_quantum_file_crypto"))
+        .current_dir(dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    write!(child.stdin.take().unwrap(), "e\nphoto.raw\nphoto.enc\n").unwrap();
+    assert!(child.wait().unwrap().success());
+    let key = fs::read(dir.path().join("key.key")).unwrap();
+    assert_eq!(
+        fs::read(dir.path().join("photo.enc")).unwrap(),
+        xor_with(&photo(), &key)
+    );
+}
@@ -0,0 +1,90 @@
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+fn run(dir: &Path, args: &[&str]) -> bool {
+    Command::new(env!("CARGO_BIN_EXE_post_quantum_file_crypto"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap()
+        .success()
+}
+
+/// Spans several 4 KB chunks, and the last one is partial.
+fn archive() -> Vec<u8> {
+    (0..9_000u32).map(|i| (i * 31 % 256) as u8).collect()
+}
+
+/// ChaCha20 under `key` with the all-zero nonce the tool always uses.
+fn chacha20_zero_nonce(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut out = data.to_vec();
+    ChaCha20::new(key.into(), &[0u8; 12].into()).apply_keystream(&mut out);
+    out
+}
+
+#[test]
+fn output_is_one_chacha20_stream_under_key_key() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("backup.tar"), archive()).unwrap();
+
+    assert!(run(dir.path(), &["E", "backup.tar", "backup.enc"]));
+    let key = fs::read(dir.path().join("key.key")).unwrap();
+    assert_eq!(key.len(), 32);
+    // The keystream carries on across chunks instead of restarting
+    let encrypted = fs::read(dir.path().join("backup.enc")).unwrap();
+    assert_eq!(encrypted, chacha20_zero_nonce(&key, &archive()));
+
+    assert!(run(dir.path(), &["D", "backup.enc", "backup.out"]));
+    assert_eq!(fs::read(dir.path().join("backup.out")).unwrap(), archive());
+}
+
+#[test]
+fn the_fixed_nonce_makes_encryption_deterministic() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("key.key"), [9u8; 32]).unwrap();
+    fs::write(dir.path().join("backup.tar"), archive()).unwrap();
+
+    assert!(run(dir.path(), &["e", "backup.tar", "a.enc"]));
+    assert!(run(dir.path(), &["e", "backup.tar", "b.enc"]));
+    let a = fs::read(dir.path().join("a.enc")).unwrap();
+    assert_eq!(a, fs::read(dir.path().join("b.enc")).unwrap());
+    assert_eq!(a, chacha20_zero_nonce(&[9u8; 32], &archive()));
+}
+
+#[test]
+fn decrypting_needs_the_key_file() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("backup.enc"), archive()).unwrap();
+
+    assert!(!run(dir.path(), &["D", "backup.enc", "backup.out"]));
+    assert!(!dir.path().join("key.key").exists());
+}
+
+#[test]
+fn asks_for_the_output_when_it_is_left_out() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("key.key"), [9u8; 32]).unwrap();
+    fs::write(dir.path().join("notes.txt"), b"short").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_post_quantum_file_crypto"))
+        .args(["E", "notes.txt"])
+        .current_dir(dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    writeln!(child.stdin.take().unwrap(), "notes.enc").unwrap();
+    assert!(child.wait().unwrap().success());
+    assert_eq!(
+        fs::read(dir.path().join("notes.enc")).unwrap(),
+        chacha20_zero_nonce(&[9u8; 32], b"short")
+    );
+}
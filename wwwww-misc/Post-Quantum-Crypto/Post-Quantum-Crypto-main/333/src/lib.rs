@@ -0,0 +1,94 @@
+//! XChaCha20-Poly1305 file format and passphrase-protected key file used by
+//! the post-quantum tool, shared with the `esk` front-end.
+//!
+//! New files start with a 5-byte header (`ESKQ` + version) that is bound to the
+//! ciphertext as associated data. Headerless files (nonce || ciphertext) from
+//! earlier versions are still accepted.
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::Rng;
+use std::io;
+
+pub const NONCE_SIZE: usize = 24;
+pub const TAG_SIZE: usize = 16;
+pub const KEY_SIZE: usize = 32;
+/// nonce || encrypted key || tag
+pub const KEY_FILE_SIZE: usize = NONCE_SIZE + KEY_SIZE + TAG_SIZE;
+pub const MAGIC: &[u8; 4] = b"ESKQ";
+pub const VERSION: u8 = 1;
+pub const HEADER_SIZE: usize = 5;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// Decrypt the contents of `key.key` with a BLAKE3-hashed passphrase
+pub fn unlock_key(key_file: &[u8], passphrase: &str) -> io::Result<Vec<u8>> {
+    if key_file.len() < NONCE_SIZE + TAG_SIZE {
+        return Err(invalid("Failed to read nonce from key file"));
+    }
+    let (nonce, encrypted_key) = key_file.split_at(NONCE_SIZE);
+
+    // Derive the decryption key from the passphrase
+    let derived_key = blake3::hash(passphrase.as_bytes());
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(derived_key.as_bytes()));
+
+    cipher
+        .decrypt(XNonce::from_slice(nonce), encrypted_key)
+        .map_err(|_| invalid("Failed to decrypt key"))
+}
+
+// Whether `data` starts with the current header
+pub fn has_header(data: &[u8]) -> bool {
+    data.len() >= HEADER_SIZE && &data[..4] == MAGIC && data[4] == VERSION
+}
+
+// Encrypt a whole file into header || nonce || ciphertext
+pub fn seal(key: &[u8], data: &[u8]) -> io::Result<Vec<u8>> {
+    if key.len() != KEY_SIZE {
+        return Err(invalid("Invalid key length"));
+    }
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+
+    let mut header = [0u8; HEADER_SIZE];
+    header[..4].copy_from_slice(MAGIC);
+    header[4] = VERSION;
+
+    let nonce = XNonce::from(rand::thread_rng().gen::<[u8; NONCE_SIZE]>());
+    let encrypted = cipher
+        .encrypt(&nonce, Payload { msg: data, aad: &header })
+        .map_err(|_| invalid("Failed to encrypt the file data"))?;
+
+    let mut output = Vec::with_capacity(HEADER_SIZE + NONCE_SIZE + encrypted.len());
+    output.extend_from_slice(&header);
+    output.extend_from_slice(&nonce);
+    output.extend_from_slice(&encrypted);
+    Ok(output)
+}
+
+// Decrypt a headered or legacy headerless file
+pub fn open(key: &[u8], data: &[u8]) -> io::Result<Vec<u8>> {
+    if key.len() != KEY_SIZE {
+        return Err(invalid("Invalid key length"));
+    }
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+
+    if has_header(data) && data.len() >= HEADER_SIZE + NONCE_SIZE + TAG_SIZE {
+        let (header, rest) = data.split_at(HEADER_SIZE);
+        let (nonce, encrypted) = rest.split_at(NONCE_SIZE);
+        let payload = Payload { msg: encrypted, aad: header };
+        if let Ok(decrypted) = cipher.decrypt(XNonce::from_slice(nonce), payload) {
+            return Ok(decrypted);
+        }
+        // Otherwise a legacy nonce happened to start with the magic bytes
+    }
+
+    if data.len() < NONCE_SIZE + TAG_SIZE {
+        return Err(invalid("File too short to contain a nonce and tag"));
+    }
+    let (nonce, encrypted) = data.split_at(NONCE_SIZE);
+    cipher
+        .decrypt(XNonce::from_slice(nonce), encrypted)
+        .map_err(|_| invalid("Failed to decrypt file data"))
+}
@@ -1,17 +1,48 @@
 use pqcrypto_frodo::frodokem640shake::*;
 use pqcrypto_traits::kem::Ciphertext as CiphertextTrait;
-use blake3;
 use chacha20poly1305::aead::{Aead, KeyInit};
 use chacha20poly1305::{XChaCha20Poly1305, XNonce};
 use chacha20poly1305::Key;
+use post_quantum_file_crypto::{open, seal, unlock_key};
 use rand::Rng;
-use rpassword::read_password;
 use std::fs;
 use std::io::{self, BufReader, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::Parser;
+use password::Source;
+use zeroize::Zeroizing;
+
+/// Encrypts or decrypts a file with XChaCha20-Poly1305 and a FrodoKEM-derived
+/// key, kept in `key.key` under a passphrase.
+///
+/// A mode or file name that is left out is asked for. Without
+/// --password-file or --password-env the passphrase is read from the
+/// terminal, without echo.
+#[derive(Parser)]
+#[command(name = "post_quantum_file_crypto", version, about)]
+struct Cli {
+    /// E to encrypt, D to decrypt
+    mode: Option<String>,
+
+    /// File to encrypt or decrypt
+    input: Option<String>,
+
+    /// Where to write the result
+    output: Option<String>,
+
+    /// Read the passphrase from the first line of this file
+    #[arg(long, group = "password_source")]
+    password_file: Option<PathBuf>,
+
+    /// Read the passphrase from this environment variable
+    #[arg(long, group = "password_source")]
+    password_env: Option<String>,
+}
 
 // Generate a strong post-quantum key using FrodoKEM and BLAKE3
-fn generate_key() -> Vec<u8> {
+fn generate_key(passphrase: &str) -> Vec<u8> {
     println!("Generating a new keypair using FrodoKEM...");
     let (public_key, _secret_key) = keypair();
     let (_, shared_secret) = encapsulate(&public_key);
@@ -24,29 +55,21 @@ fn generate_key() -> Vec<u8> {
     let hash = blake3::hash(shared_secret_bytes);
     let key = hash.as_bytes().to_vec();
     println!("Symmetric key derived using BLAKE3.");
-    save_key_secure(&key).expect("Failed to save key to file");
+    save_key_secure(&key, passphrase).expect("Failed to save key to file");
     println!("Key saved securely to key.key.");
     key
 }
 
 // Save the generated key securely using a passphrase
-fn save_key_secure(key: &[u8]) -> io::Result<()> {
-    println!("Enter a passphrase to secure the key: ");
-    let passphrase = read_password().expect("Failed to read passphrase");
-
-    println!("Passphrase entered for encryption: {}", passphrase);
-
+fn save_key_secure(key: &[u8], passphrase: &str) -> io::Result<()> {
     // Derive an encryption key from the passphrase using BLAKE3
     let derived_key = blake3::hash(passphrase.as_bytes());
     let encryption_key = Key::from_slice(&derived_key.as_bytes()[..32]);
 
-    println!("Derived key (hashed passphrase) for encryption: {:?}", derived_key);
-
     let cipher = XChaCha20Poly1305::new(encryption_key);
 
     // Generate a random nonce
     let nonce = XNonce::from(rand::thread_rng().gen::<[u8; 24]>());
-    println!("Random nonce generated for securing key: {:?}", nonce);
 
     // Encrypt the key with XChaCha20-Poly1305
     let encrypted_key = match cipher.encrypt(&nonce, key.as_ref()) {
@@ -62,7 +85,7 @@ fn save_key_secure(key: &[u8]) -> io::Result<()> {
         Ok(file) => file,
         Err(e) => {
             eprintln!("Failed to create key file: {:?}", e);
-            return Err(io::Error::new(io::ErrorKind::Other, "Failed to create key file"));
+            return Err(io::Error::other("Failed to create key file"));
         }
     };
     
@@ -74,53 +97,31 @@ fn save_key_secure(key: &[u8]) -> io::Result<()> {
 }
 
 // Load the key securely using a passphrase
-fn load_key_secure() -> io::Result<Vec<u8>> {
-    println!("Enter passphrase to unlock the key: ");
-    let passphrase = read_password().expect("Failed to read passphrase");
-
-    println!("Passphrase entered for decryption: {}", passphrase);
-
+fn load_key_secure(passphrase: &str) -> io::Result<Vec<u8>> {
     let mut key_file = fs::File::open("key.key")?;
-    let mut nonce = [0u8; 24]; // XChaCha20 requires a 24-byte nonce
-
-    println!("Reading nonce from key file...");
-    if let Err(e) = key_file.read_exact(&mut nonce) {
-        eprintln!("Failed to read nonce from key file: {:?}", e);
-        return Err(io::Error::new(io::ErrorKind::InvalidData, "Failed to read nonce from key file"));
+    let mut key_file_data = Vec::new();
+    println!("Reading key file...");
+    if let Err(e) = key_file.read_to_end(&mut key_file_data) {
+        eprintln!("Failed to read key file: {:?}", e);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Failed to read key file"));
     }
-    println!("Nonce read successfully: {:?}", nonce);
-
-    let mut encrypted_key = Vec::new();
-    println!("Reading encrypted key from key file...");
-    if let Err(e) = key_file.read_to_end(&mut encrypted_key) {
-        eprintln!("Failed to read encrypted key from key file: {:?}", e);
-        return Err(io::Error::new(io::ErrorKind::InvalidData, "Failed to read encrypted key from key file"));
-    }
-    println!("Encrypted key length: {}", encrypted_key.len());
-
-    // Derive the decryption key from the passphrase
-    let derived_key = blake3::hash(passphrase.as_bytes());
-    let decryption_key = Key::from_slice(&derived_key.as_bytes()[..32]);
-
-    println!("Derived key (hashed passphrase) for decryption: {:?}", derived_key);
-
-    let cipher = XChaCha20Poly1305::new(decryption_key);
+    println!("Key file length: {}", key_file_data.len());
 
     println!("Attempting to decrypt key...");
-    match cipher.decrypt(&nonce.into(), encrypted_key.as_ref()) {
+    match unlock_key(&key_file_data, passphrase) {
         Ok(decrypted_key) => {
             println!("Key decrypted successfully.");
             Ok(decrypted_key)
         }
-        Err(_) => {
+        Err(e) => {
             eprintln!("Failed to decrypt the key. Possible causes include an incorrect passphrase or a corrupted key file.");
-            Err(io::Error::new(io::ErrorKind::InvalidData, "Failed to decrypt key"))
+            Err(e)
         }
     }
 }
 
 // Encrypt an entire file at once
-fn encrypt_file(input_path: &str, output_path: &str) -> io::Result<()> {
+fn encrypt_file(input_path: &str, output_path: &str, passphrase: &str) -> io::Result<()> {
     println!("Starting encryption process...");
 
     let mut input_file = fs::File::open(input_path)?;
@@ -128,35 +129,26 @@ fn encrypt_file(input_path: &str, output_path: &str) -> io::Result<()> {
 
     let key = if Path::new("key.key").exists() {
         println!("Loading key from file...");
-        match load_key_secure() {
+        match load_key_secure(passphrase) {
             Ok(key) => key,
             Err(_) => {
                 eprintln!("Failed to load key from key file.");
-                return Err(io::Error::new(io::ErrorKind::Other, "Failed to load key from file"));
+                return Err(io::Error::other("Failed to load key from file"));
             }
         }
     } else {
         println!("Generating new key...");
-        generate_key()
+        generate_key(passphrase)
     };
 
     println!("Encryption key loaded successfully.");
-    let encryption_key = Key::from_slice(&key);
-    let cipher = XChaCha20Poly1305::new(encryption_key);
-
-    let nonce = XNonce::from(rand::thread_rng().gen::<[u8; 24]>());
-    println!("Generated random nonce for file encryption: {:?}", nonce);
-
-    // Write the nonce to the output file (it will be needed for decryption)
-    output_file.write_all(&nonce)?;
 
     // Read the entire file into memory
     let mut data = Vec::new();
     input_file.read_to_end(&mut data)?;
 
-    // Encrypt the entire file
-    let encrypted_data = cipher.encrypt(&nonce, data.as_ref())
-        .expect("Failed to encrypt the file data");
+    // Encrypt the entire file; the output carries the header and nonce
+    let encrypted_data = seal(&key, &data)?;
 
     output_file.write_all(&encrypted_data)?;
 
@@ -165,28 +157,21 @@ fn encrypt_file(input_path: &str, output_path: &str) -> io::Result<()> {
 }
 
 // Decrypt an entire file at once
-fn decrypt_file(input_path: &str, output_path: &str) -> io::Result<()> {
+fn decrypt_file(input_path: &str, output_path: &str, passphrase: &str) -> io::Result<()> {
     println!("Starting decryption process...");
 
     let mut input_file = BufReader::new(fs::File::open(input_path)?);
     let mut output_file = fs::File::create(output_path)?;
 
     println!("Loading the key...");
-    let key = load_key_secure()?;
-
-    let decryption_key = Key::from_slice(&key);
-    let cipher = XChaCha20Poly1305::new(decryption_key);
-
-    let mut nonce = [0u8; 24];
-    input_file.read_exact(&mut nonce)?;
-    println!("Nonce read successfully: {:?}", nonce);
+    let key = load_key_secure(passphrase)?;
 
     // Read the entire encrypted file into memory
     let mut encrypted_data = Vec::new();
     input_file.read_to_end(&mut encrypted_data)?;
 
     // Decrypt the entire file
-    let decrypted_data = match cipher.decrypt(&nonce.into(), encrypted_data.as_ref()) {
+    let decrypted_data = match open(&key, &encrypted_data) {
         Ok(decrypted_data) => decrypted_data,
         Err(_) => {
             eprintln!("Failed to decrypt the file. This might indicate an incorrect key or a corrupted file.");
@@ -202,36 +187,56 @@ fn decrypt_file(input_path: &str, output_path: &str) -> io::Result<()> {
 
 // Main function to provide user interface for encrypting or decrypting files
 fn main() {
-    println!("Enter mode (E for encrypt, D for decrypt): ");
-    let mut mode = String::new();
-    std::io::stdin().read_line(&mut mode).expect("Failed to read mode");
-    let mode = mode.trim().to_uppercase();
-
-    println!("Enter input filename: ");
-    let mut input_filename = String::new();
-    std::io::stdin().read_line(&mut input_filename).expect("Failed to read input filename");
-    let input_filename = input_filename.trim();
+    let cli = Cli::parse();
 
-    println!("Enter output filename: ");
-    let mut output_filename = String::new();
-    std::io::stdin().read_line(&mut output_filename).expect("Failed to read output filename");
-    let output_filename = output_filename.trim();
+    // Take the mode and file paths from the arguments, or ask for them
+    let mode = cli.mode.clone().unwrap_or_else(|| prompt("Enter mode (E for encrypt, D for decrypt): "));
+    let mode = mode.trim().to_uppercase();
+    let input_filename = cli.input.clone().unwrap_or_else(|| prompt("Enter input filename: "));
+    let output_filename = cli.output.clone().unwrap_or_else(|| prompt("Enter output filename: "));
 
     match mode.as_str() {
         "E" => {
-            match encrypt_file(input_filename, output_filename) {
+            let passphrase = read_passphrase(&cli, "Enter a passphrase to secure the key: ");
+            match encrypt_file(&input_filename, &output_filename, &passphrase) {
                 Ok(_) => println!("File encrypted successfully!"),
-                Err(e) => eprintln!("Error encrypting file: {}", e),
+                Err(e) => {
+                    eprintln!("Error encrypting file: {}", e);
+                    process::exit(1);
+                }
             }
         }
         "D" => {
-            match decrypt_file(input_filename, output_filename) {
+            let passphrase = read_passphrase(&cli, "Enter passphrase to unlock the key: ");
+            match decrypt_file(&input_filename, &output_filename, &passphrase) {
                 Ok(_) => println!("File decrypted successfully!"),
-                Err(e) => eprintln!("Error decrypting file: {}", e),
+                Err(e) => {
+                    eprintln!("Error decrypting file: {}", e);
+                    process::exit(1);
+                }
             }
         }
         _ => {
             eprintln!("Invalid mode entered. Please enter 'E' for encryption or 'D' for decryption.");
+            process::exit(1);
         }
     }
 }
+
+// Read the passphrase from the file or environment variable given, or from
+// the terminal without echo. It is never printed.
+fn read_passphrase(cli: &Cli, question: &str) -> Zeroizing<String> {
+    let source = Source::new(cli.password_file.as_deref(), cli.password_env.as_deref(), None);
+    source.read(question).unwrap_or_else(|e| {
+        eprintln!("Failed to read passphrase: {}", e);
+        process::exit(1);
+    })
+}
+
+// Ask for a value on standard input, for arguments that were left out
+fn prompt(question: &str) -> String {
+    println!("{}", question);
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).expect("Failed to read input");
+    answer.trim().to_string()
+}
@@ -0,0 +1,161 @@
+use post_quantum_file_crypto::{
+    open, unlock_key, HEADER_SIZE, KEY_FILE_SIZE, KEY_SIZE, MAGIC, NONCE_SIZE, TAG_SIZE,
+};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const CONTRACT: &[u8] = b"Clause 7.2 amended: payment due within 45 days.\n";
+const PASSPHRASE: &str = "violet kettle ninety";
+
+fn contract_dir() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("contract.txt"), CONTRACT).unwrap();
+    dir
+}
+
+fn tool(dir: &Path, passphrase: &str) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_post_quantum_file_crypto"));
+    command
+        .args(["--password-env", "PQ333_PASSPHRASE"])
+        .env("PQ333_PASSPHRASE", passphrase)
+        .current_dir(dir)
+        .stdin(Stdio::null());
+    command
+}
+
+fn run(dir: &Path, passphrase: &str, args: &[&str]) -> bool {
+    tool(dir, passphrase)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap()
+        .success()
+}
+
+#[test]
+fn the_key_file_is_sealed_under_the_passphrase() {
+    let dir = contract_dir();
+
+    assert!(run(
+        dir.path(),
+        PASSPHRASE,
+        &["E", "contract.txt", "contract.pq"]
+    ));
+    let key_file = fs::read(dir.path().join("key.key")).unwrap();
+    assert_eq!(key_file.len(), KEY_FILE_SIZE);
+    let key = unlock_key(&key_file, PASSPHRASE).unwrap();
+    assert_eq!(key.len(), KEY_SIZE);
+    assert!(unlock_key(&key_file, "violet kettle").is_err());
+
+    // Header, nonce, then the XChaCha20-Poly1305 ciphertext and tag
+    let sealed = fs::read(dir.path().join("contract.pq")).unwrap();
+    assert_eq!(&sealed[..4], MAGIC);
+    assert_eq!(
+        sealed.len(),
+        HEADER_SIZE + NONCE_SIZE + CONTRACT.len() + TAG_SIZE
+    );
+    assert_eq!(open(&key, &sealed).unwrap(), CONTRACT);
+
+    // The passphrase is never echoed
+    let output = tool(dir.path(), PASSPHRASE)
+        .args(["d", "contract.pq", "back.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stdout).contains(PASSPHRASE));
+    assert_eq!(fs::read(dir.path().join("back.txt")).unwrap(), CONTRACT);
+}
+
+#[test]
+fn a_wrong_passphrase_opens_neither_the_key_nor_the_file() {
+    let dir = contract_dir();
+    assert!(run(
+        dir.path(),
+        PASSPHRASE,
+        &["E", "contract.txt", "contract.pq"]
+    ));
+    let key_file = fs::read(dir.path().join("key.key")).unwrap();
+
+    // Encrypting with the existing key needs the right passphrase too
+    assert!(!run(
+        dir.path(),
+        "violet kettle",
+        &["E", "contract.txt", "other.pq"]
+    ));
+    assert!(!run(
+        dir.path(),
+        "violet kettle",
+        &["D", "contract.pq", "back.txt"]
+    ));
+    assert_ne!(
+        fs::read(dir.path().join("back.txt")).unwrap_or_default(),
+        CONTRACT
+    );
+    assert_eq!(fs::read(dir.path().join("key.key")).unwrap(), key_file);
+}
+
+#[test]
+fn reads_the_first_line_of_a_password_file_and_asks_for_the_files() {
+    let dir = contract_dir();
+    fs::write(
+        dir.path().join("passphrase.txt"),
+        format!("{}\nsecond line\n", PASSPHRASE),
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_post_quantum_file_crypto"))
+        .args(["--password-file", "passphrase.txt", "E"])
+        .current_dir(dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    write!(child.stdin.take().unwrap(), "contract.txt\ncontract.pq\n").unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let key = unlock_key(&fs::read(dir.path().join("key.key")).unwrap(), PASSPHRASE).unwrap();
+    assert_eq!(
+        open(&key, &fs::read(dir.path().join("contract.pq")).unwrap()).unwrap(),
+        CONTRACT
+    );
+}
+
+#[test]
+fn an_unset_variable_or_two_sources_stop_it() {
+    let dir = contract_dir();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_post_quantum_file_crypto"))
+        .args([
+            "--password-env",
+            "PQ333_UNSET_PASSPHRASE",
+            "E",
+            "contract.txt",
+            "contract.pq",
+        ])
+        .env_remove("PQ333_UNSET_PASSPHRASE")
+        .current_dir(dir.path())
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Environment variable PQ333_UNSET_PASSPHRASE is not set."));
+    assert!(!dir.path().join("key.key").exists());
+
+    let status = tool(dir.path(), PASSPHRASE)
+        .args([
+            "--password-file",
+            "passphrase.txt",
+            "E",
+            "contract.txt",
+            "contract.pq",
+        ])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(2));
+}
@@ -1,9 +1,10 @@
 use clap::Parser;
-use argon2::Argon2;
+use password::Source;
+use argon2::{Argon2, Params};
 use rand::{rngs::OsRng, RngCore};
 use std::fs::File;
 use std::io::{self, Read, Write, stdin};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 // Configurable variables
@@ -12,18 +13,53 @@ const OUTPUT_FILE_PATH: &str = "1.key";
 const SALT_FILE_PATH: &str = "salt.dat"; // Salt file path
 const MAX_KEY_SIZE_BYTES: usize = 5 * 1024 * 1024 * 1024; // Maximum key size: 5GB
 
+/// Derives a key of any size from a password with Argon2.
+///
+/// A size that is left out is asked for. Without --password-file or
+/// --password-env the password is read from the terminal, without echo.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
-struct Args {}
+struct Args {
+    /// Size of the key, e.g. 1GB, 500MB, 1024B
+    size: Option<String>,
+
+    /// Where to write the key
+    #[arg(short, long, default_value = OUTPUT_FILE_PATH)]
+    output: PathBuf,
+
+    /// Salt file, created with a random salt if it does not exist
+    #[arg(long, default_value = SALT_FILE_PATH)]
+    salt: PathBuf,
+
+    /// Overwrite the key file without asking
+    #[arg(short, long)]
+    force: bool,
+
+    /// Read the password from the first line of this file
+    #[arg(long, group = "password_source")]
+    password_file: Option<PathBuf>,
+
+    /// Read the password from this environment variable
+    #[arg(long, group = "password_source")]
+    password_env: Option<String>,
+}
 
 fn main() -> io::Result<()> {
+    let args = Args::parse();
+
     // Start measuring time
     let start_time = Instant::now();
 
-    // Ask for key size
-    println!("Enter the size of the key (e.g., 1GB, 500MB, 1024B) [default: {} bytes]:", DEFAULT_KEY_SIZE);
-    let mut size_input = String::new();
-    stdin().read_line(&mut size_input)?;
+    // Take the key size from the arguments, or ask for it
+    let size_input = match &args.size {
+        Some(size) => size.clone(),
+        None => {
+            println!("Enter the size of the key (e.g., 1GB, 500MB, 1024B) [default: {} bytes]:", DEFAULT_KEY_SIZE);
+            let mut size_input = String::new();
+            stdin().read_line(&mut size_input)?;
+            size_input
+        }
+    };
 
     // Validate key size input before parsing
     let key_size: usize = match parse_key_size(size_input.trim()) {
@@ -38,23 +74,22 @@ fn main() -> io::Result<()> {
     // Inform user that large key generation may take time
     println!("Note: Generating a cryptographic key of size {} bytes may take some time. Please be patient.", key_size);
 
-    // Enforce a minimum password length
-    println!("Enter a password to generate a deterministic key:");
-    let mut password = String::new();
-    stdin().read_line(&mut password)?;
-
+    let source = Source::new(args.password_file.as_deref(), args.password_env.as_deref(), None);
+    let password = source.read("Enter a password to generate a deterministic key: ")?;
+    // Surrounding whitespace is dropped, as the old prompt did, so the same
+    // password still gives the same key.
     let password = password.trim().as_bytes();
 
     // Read or generate the salt
-    let salt = get_salt()?;
+    let salt = get_salt(&args.salt)?;
 
     // Define the output file path
-    let output_path = PathBuf::from(OUTPUT_FILE_PATH);
+    let output_path = &args.output;
 
     // Check if the file already exists
-    if output_path.exists() {
+    if output_path.exists() && !args.force {
         // Ask for confirmation before overwriting
-        println!("The file '{}' already exists. Do you want to overwrite it? [y/N]", OUTPUT_FILE_PATH);
+        println!("The file '{}' already exists. Do you want to overwrite it? [y/N]", output_path.display());
         let mut input = String::new();
         stdin().read_line(&mut input)?;
         let input = input.trim().to_lowercase();
@@ -69,26 +104,28 @@ fn main() -> io::Result<()> {
     // Initialize the Argon2 hasher with default parameters
     let argon2 = Argon2::default();
 
-    // Generate the hash with progress feedback
+    // Generate the hash with progress feedback. Argon2 cannot produce fewer
+    // than 4 bytes at a time, so small keys are made in one piece.
     let mut derived_key = vec![0u8; key_size];
-    let chunk_size = key_size / 10;
-    for i in 0..10 {
+    let chunks = if key_size / 10 < Params::MIN_OUTPUT_LEN { 1 } else { 10 };
+    let chunk_size = key_size / chunks;
+    for i in 0..chunks {
         let start = i * chunk_size;
-        let end = if i == 9 { key_size } else { start + chunk_size };
-        argon2.hash_password_into(&password, &salt, &mut derived_key[start..end])
+        let end = if i == chunks - 1 { key_size } else { start + chunk_size };
+        argon2.hash_password_into(password, &salt, &mut derived_key[start..end])
             .expect("Error hashing password");
-        println!("Progress: {}% complete", (i + 1) * 10);
+        println!("Progress: {}% complete", (i + 1) * 100 / chunks);
     }
 
     // Write the derived key to the output file
-    let mut file = File::create(&output_path)?;
+    let mut file = File::create(output_path)?;
     file.write_all(&derived_key)?;
 
     // Print the duration of the operation
     let duration = start_time.elapsed();
     println!(
         "Successfully generated a {}-byte key and saved it to '{}'.",
-        key_size, OUTPUT_FILE_PATH
+        key_size, output_path.display()
     );
     println!("Operation completed in {:.2?} seconds.", duration);
 
@@ -114,15 +151,13 @@ fn parse_key_size(input: &str) -> Result<usize, &'static str> {
     }
 }
 
-fn get_salt() -> io::Result<Vec<u8>> {
-    let salt_path = PathBuf::from(SALT_FILE_PATH);
-
+fn get_salt(salt_path: &Path) -> io::Result<Vec<u8>> {
     if salt_path.exists() {
         // Read the salt from the file
-        let mut file = File::open(&salt_path)?;
+        let mut file = File::open(salt_path)?;
         let mut salt = Vec::new();
         file.read_to_end(&mut salt)?;
-        println!("Using existing salt from '{}'.", SALT_FILE_PATH);
+        println!("Using existing salt from '{}'.", salt_path.display());
         Ok(salt)
     } else {
         // Generate a random salt
@@ -130,9 +165,9 @@ fn get_salt() -> io::Result<Vec<u8>> {
         OsRng.fill_bytes(&mut salt);
 
         // Save the salt to the file
-        let mut file = File::create(&salt_path)?;
+        let mut file = File::create(salt_path)?;
         file.write_all(&salt)?;
-        println!("Generated new salt and saved to '{}'.", SALT_FILE_PATH);
+        println!("Generated new salt and saved to '{}'.", salt_path.display());
         Ok(salt)
     }
 }
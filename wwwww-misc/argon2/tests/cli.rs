@@ -0,0 +1,116 @@
+use argon2::Argon2;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const PASSWORD: &str = "correct horse battery";
+const SALT: [u8; 32] = [0x5a; 32];
+
+/// A directory with a fixed salt file, so the key can be worked out here.
+fn salted_dir() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("salt.dat"), SALT).unwrap();
+    dir
+}
+
+fn keygen(dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_argon2-keygen"));
+    command
+        .current_dir(dir)
+        .args(["--password-env", "ARGON2_TEST_PASSWORD"])
+        .env("ARGON2_TEST_PASSWORD", PASSWORD)
+        .stdout(Stdio::null());
+    command
+}
+
+fn argon2_default(password: &str, salt: &[u8], len: usize) -> Vec<u8> {
+    let mut key = vec![0u8; len];
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .unwrap();
+    key
+}
+
+#[test]
+fn the_key_is_argon2id_of_the_password_and_salt_file() {
+    let dir = salted_dir();
+
+    let status = keygen(dir.path())
+        .args(["32B", "-o", "a.key"])
+        .stdin(Stdio::null())
+        .status();
+    assert!(status.unwrap().success());
+    assert_eq!(
+        fs::read(dir.path().join("a.key")).unwrap(),
+        argon2_default(PASSWORD, &SALT, 32)
+    );
+    // The existing salt is used as it is
+    assert_eq!(fs::read(dir.path().join("salt.dat")).unwrap(), SALT);
+}
+
+#[test]
+fn a_missing_salt_file_is_created_and_then_reused() {
+    let dir = TempDir::new().unwrap();
+
+    for key in ["a.key", "b.key"] {
+        let status = keygen(dir.path())
+            .args(["1KB", "-o", key])
+            .stdin(Stdio::null())
+            .status();
+        assert!(status.unwrap().success());
+    }
+    let salt = fs::read(dir.path().join("salt.dat")).unwrap();
+    assert_eq!(salt.len(), 32);
+    assert_ne!(salt, SALT);
+    let key = fs::read(dir.path().join("a.key")).unwrap();
+    assert_eq!(key.len(), 1024);
+    assert_eq!(fs::read(dir.path().join("b.key")).unwrap(), key);
+}
+
+#[test]
+fn asks_for_the_size_and_trims_the_password_file_line() {
+    let dir = salted_dir();
+    fs::write(
+        dir.path().join("password.txt"),
+        format!("  {}  \nnext line\n", PASSWORD),
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_argon2-keygen"))
+        .current_dir(dir.path())
+        .args(["--password-file", "password.txt"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    // An empty answer takes the default of 32 bytes
+    writeln!(child.stdin.take().unwrap()).unwrap();
+    assert!(child.wait().unwrap().success());
+    assert_eq!(
+        fs::read(dir.path().join("1.key")).unwrap(),
+        argon2_default(PASSWORD, &SALT, 32)
+    );
+}
+
+#[test]
+fn overwrites_only_when_forced() {
+    let dir = salted_dir();
+    fs::write(dir.path().join("1.key"), b"old").unwrap();
+
+    // Without --force the question is answered by the closed standard input
+    let status = keygen(dir.path()).arg("32").stdin(Stdio::null()).status();
+    assert!(status.unwrap().success());
+    assert_eq!(fs::read(dir.path().join("1.key")).unwrap(), b"old");
+
+    let status = keygen(dir.path())
+        .args(["32", "--force"])
+        .stdin(Stdio::null())
+        .status();
+    assert!(status.unwrap().success());
+    assert_eq!(
+        fs::read(dir.path().join("1.key")).unwrap(),
+        argon2_default(PASSWORD, &SALT, 32)
+    );
+}
@@ -1,25 +1,50 @@
 use std::fs::File;
 use std::io::{self, BufReader, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process;
 
-fn main() {
-    // Prompt the user for the filename
-    println!("Enter the filename to analyze:");
-
-    // Read the filename from stdin
-    let mut filename = String::new();
-    if let Err(e) = io::stdin().read_line(&mut filename) {
-        eprintln!("Failed to read input: {}", e);
-        process::exit(1);
-    }
+use clap::Parser;
+
+/// Counts how often each byte value occurs in a file and estimates its
+/// entropy, writing the report to a text file.
+///
+/// A file name that is left out is asked for.
+#[derive(Parser)]
+#[command(name = "binary_analyzer", version, about)]
+struct Cli {
+    /// File to analyze
+    file: Option<PathBuf>,
+
+    /// Where to write the report
+    #[arg(short, long, default_value = "report.txt")]
+    report: PathBuf,
+}
 
-    // Trim the input to remove any trailing newline characters
-    let filename = filename.trim();
+fn main() {
+    let cli = Cli::parse();
+
+    // Take the filename from the arguments, or prompt the user for it
+    let filename = match cli.file {
+        Some(file) => file,
+        None => {
+            println!("Enter the filename to analyze:");
+
+            // Read the filename from stdin
+            let mut filename = String::new();
+            if let Err(e) = io::stdin().read_line(&mut filename) {
+                eprintln!("Failed to read input: {}", e);
+                process::exit(1);
+            }
+
+            // Trim the input to remove any trailing newline characters
+            PathBuf::from(filename.trim())
+        }
+    };
+    let report_name = cli.report.display();
 
     // Check if the file exists
     if !Path::new(&filename).exists() {
-        eprintln!("File '{}' does not exist.", filename);
+        eprintln!("File '{}' does not exist.", filename.display());
         process::exit(1);
     }
 
@@ -27,7 +52,7 @@ fn main() {
     let file = match File::open(&filename) {
         Ok(f) => f,
         Err(e) => {
-            eprintln!("Failed to open file '{}': {}", filename, e);
+            eprintln!("Failed to open file '{}': {}", filename.display(), e);
             process::exit(1);
         }
     };
@@ -37,7 +62,7 @@ fn main() {
 
     // Read the entire file into the buffer
     if let Err(e) = reader.read_to_end(&mut buffer) {
-        eprintln!("Failed to read file '{}': {}", filename, e);
+        eprintln!("Failed to read file '{}': {}", filename.display(), e);
         process::exit(1);
     }
 
@@ -49,10 +74,10 @@ fn main() {
     }
 
     // Prepare to write the report
-    let report = match File::create("report.txt") { // Removed `mut` here
+    let report = match File::create(&cli.report) {
         Ok(f) => f,
         Err(e) => {
-            eprintln!("Failed to create report.txt: {}", e);
+            eprintln!("Failed to create {}: {}", report_name, e);
             process::exit(1);
         }
     };
@@ -61,7 +86,7 @@ fn main() {
 
     // Write the header
     if let Err(e) = writeln!(writer, "Binary Character Frequencies:\n") {
-        eprintln!("Failed to write to report.txt: {}", e);
+        eprintln!("Failed to write to {}: {}", report_name, e);
         process::exit(1);
     }
 
@@ -69,21 +94,21 @@ fn main() {
     for (byte, &count) in frequencies.iter().enumerate() {
         // Display byte in hexadecimal for better readability
         if let Err(e) = writeln!(writer, "Byte {:02X} ({}): {}", byte, byte, count) {
-            eprintln!("Failed to write to report.txt: {}", e);
+            eprintln!("Failed to write to {}: {}", report_name, e);
             process::exit(1);
         }
     }
 
     // Add a separator
     if let Err(e) = writeln!(writer, "\nEntropy and Randomness Analysis:\n") {
-        eprintln!("Failed to write to report.txt: {}", e);
+        eprintln!("Failed to write to {}: {}", report_name, e);
         process::exit(1);
     }
 
     // Calculate entropy
     let entropy = calculate_entropy(&frequencies, buffer.len() as f64);
     if let Err(e) = writeln!(writer, "Shannon Entropy: {:.4} bits per byte", entropy) {
-        eprintln!("Failed to write to report.txt: {}", e);
+        eprintln!("Failed to write to {}: {}", report_name, e);
         process::exit(1);
     }
 
@@ -96,11 +121,11 @@ fn main() {
         - Entropy close to 8 bits per byte indicates high randomness.\n\
         - Lower entropy suggests patterns or redundancy in the data."
     ) {
-        eprintln!("Failed to write to report.txt: {}", e);
+        eprintln!("Failed to write to {}: {}", report_name, e);
         process::exit(1);
     }
 
-    println!("Analysis complete. Report saved to 'report.txt'.");
+    println!("Analysis complete. Report saved to '{}'.", report_name);
 }
 
 /// Calculates the Shannon entropy of the data.
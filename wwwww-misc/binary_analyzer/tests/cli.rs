@@ -0,0 +1,69 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+#[test]
+fn counts_every_byte_value_in_the_report_given() {
+    let dir = TempDir::new().unwrap();
+    let input = dir.path().join("data.bin");
+    let report = dir.path().join("entropy.txt");
+    // Every byte value four times: exactly 8 bits per byte
+    let data: Vec<u8> = (0..4).flat_map(|_| 0..=255u8).collect();
+    fs::write(&input, data).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_binary_analyzer"))
+        .arg(&input)
+        .arg("--report")
+        .arg(&report)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let report = fs::read_to_string(report).unwrap();
+    assert_eq!(
+        report
+            .lines()
+            .filter(|line| line.starts_with("Byte "))
+            .count(),
+        256
+    );
+    assert!(report.contains("Byte 00 (0): 4"));
+    assert!(report.contains("Byte FF (255): 4"));
+    assert!(report.contains("Shannon Entropy: 8.0000 bits per byte"));
+}
+
+#[test]
+fn asks_for_the_file_and_defaults_to_report_txt() {
+    let dir = TempDir::new().unwrap();
+    // Two values, equally often: one bit per byte
+    fs::write(dir.path().join("coin.bin"), b"HTTHHTTHHT").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_binary_analyzer"))
+        .current_dir(dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    writeln!(child.stdin.take().unwrap(), "coin.bin").unwrap();
+    assert!(child.wait().unwrap().success());
+    let report = fs::read_to_string(dir.path().join("report.txt")).unwrap();
+    assert!(report.contains("Byte 48 (72): 5"));
+    assert!(report.contains("Byte 54 (84): 5"));
+    assert!(report.contains("Byte 41 (65): 0"));
+    assert!(report.contains("Shannon Entropy: 1.0000 bits per byte"));
+}
+
+#[test]
+fn writes_no_report_for_a_missing_file() {
+    let dir = TempDir::new().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_binary_analyzer"))
+        .arg("nope.bin")
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("File 'nope.bin' does not exist."));
+    assert!(!dir.path().join("report.txt").exists());
+}
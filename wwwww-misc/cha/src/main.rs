@@ -1,7 +1,7 @@
 
 use anyhow::{anyhow, Context, Result};
 use chacha20poly1305::{
-    aead::{Aead, KeyInit},
+    aead::{Aead, KeyInit, Payload},
     XChaCha20Poly1305, XNonce,
 };
 use clap::{Parser, Subcommand};
@@ -15,6 +15,11 @@ use std::{
 const DEFAULT_KEY_FILE: &str = "key.key";
 const KEY_SIZE: usize = 32; // 32 bytes for XChaCha20 key
 const NONCE_SIZE: usize = 24; // 24 bytes for XChaCha20 nonce
+const TAG_SIZE: usize = 16; // 16 bytes for the Poly1305 tag
+// Same header as zzzchacha20-main, so `esk` can recognise these files.
+const MAGIC: &[u8; 4] = b"ESKX";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 5; // magic + version
 
 #[derive(Parser)]
 #[command(
@@ -136,8 +141,12 @@ fn encrypt_file(input_path: &str, output_path: &str, key_path: &str) -> Result<(
     rand::thread_rng().fill_bytes(&mut nonce_bytes);
     let nonce = XNonce::from_slice(&nonce_bytes);
 
+    let mut header = [0u8; HEADER_SIZE];
+    header[..4].copy_from_slice(MAGIC);
+    header[4] = VERSION;
+
     let ciphertext = cipher
-        .encrypt(nonce, data.as_ref())
+        .encrypt(nonce, Payload { msg: &data, aad: &header })
         .map_err(|_| anyhow!("Encryption failed."))?;
 
     let output_file = OpenOptions::new()
@@ -148,6 +157,9 @@ fn encrypt_file(input_path: &str, output_path: &str, key_path: &str) -> Result<(
         .with_context(|| format!("Failed to create output file '{}'.", output_path))?;
     let mut writer = BufWriter::new(output_file);
 
+    writer
+        .write_all(&header)
+        .with_context(|| "Failed to write header to output file.")?;
     writer
         .write_all(&nonce_bytes)
         .with_context(|| "Failed to write nonce to output file.")?;
@@ -168,20 +180,13 @@ fn decrypt_file(input_path: &str, output_path: &str, key_path: &str) -> Result<(
         .with_context(|| format!("Failed to open input file '{}'.", input_path))?;
     let mut reader = BufReader::new(input_file);
 
-    let mut nonce_bytes = [0u8; NONCE_SIZE];
-    reader
-        .read_exact(&mut nonce_bytes)
-        .with_context(|| "Failed to read nonce from input file.")?;
-    let nonce = XNonce::from_slice(&nonce_bytes);
-
-    let mut ciphertext = Vec::new();
+    let mut contents = Vec::new();
     reader
-        .read_to_end(&mut ciphertext)
-        .with_context(|| "Failed to read ciphertext from input file.")?;
+        .read_to_end(&mut contents)
+        .with_context(|| "Failed to read input file.")?;
 
-    let plaintext = cipher
-        .decrypt(nonce, ciphertext.as_ref())
-        .map_err(|_| anyhow!("Decryption failed. Incorrect key or corrupted data."))?;
+    let plaintext = decrypt_contents(&cipher, &contents)
+        .ok_or_else(|| anyhow!("Decryption failed. Incorrect key or corrupted data."))?;
 
     let output_file = OpenOptions::new()
         .write(true)
@@ -198,3 +203,27 @@ fn decrypt_file(input_path: &str, output_path: &str, key_path: &str) -> Result<(
     println!("File successfully decrypted to '{}'.", output_path);
     Ok(())
 }
+
+/// Decrypts header || nonce || ciphertext, or the headerless nonce || ciphertext
+/// written by earlier versions.
+fn decrypt_contents(cipher: &XChaCha20Poly1305, contents: &[u8]) -> Option<Vec<u8>> {
+    let has_header = contents.len() >= HEADER_SIZE
+        && &contents[..4] == MAGIC
+        && contents[4] == VERSION;
+
+    if has_header && contents.len() >= HEADER_SIZE + NONCE_SIZE + TAG_SIZE {
+        let (header, rest) = contents.split_at(HEADER_SIZE);
+        let (nonce_bytes, ciphertext) = rest.split_at(NONCE_SIZE);
+        let payload = Payload { msg: ciphertext, aad: header };
+        if let Ok(plaintext) = cipher.decrypt(XNonce::from_slice(nonce_bytes), payload) {
+            return Some(plaintext);
+        }
+        // Otherwise a legacy nonce happened to start with the magic bytes.
+    }
+
+    if contents.len() < NONCE_SIZE + TAG_SIZE {
+        return None;
+    }
+    let (nonce_bytes, ciphertext) = contents.split_at(NONCE_SIZE);
+    cipher.decrypt(XNonce::from_slice(nonce_bytes), ciphertext).ok()
+}
@@ -1,17 +1,19 @@
 
 use std::env;
-use std::fs::{File, metadata};
-use std::io::{Read, Write};
 use std::error::Error;
+use std::fs::metadata;
 use std::path::Path;
 
+use otp::{KeyLength, Output, Overwrite, PadMode, Policy, XorEngine};
+
 fn main() -> Result<(), Box<dyn Error>> {
     // Collect command-line arguments
     let args: Vec<String> = env::args().collect();
 
-    // Ensure proper usage
-    if args.len() != 4 {
-        eprintln!("Usage: {} <input file> <output file> <key file>", args[0]);
+    // Ensure proper usage; `--legacy` reads files from before the pad ledger
+    let legacy = args.len() == 5 && args[4] == "--legacy";
+    if args.len() != 4 && !legacy {
+        eprintln!("Usage: {} <input file> <output file> <key file> [--legacy]", args[0]);
         return Err("Invalid number of arguments.".into());
     }
 
@@ -20,108 +22,40 @@ fn main() -> Result<(), Box<dyn Error>> {
     let output_path = Path::new(&args[2]);
     let key_path = Path::new(&args[3]);
 
-    // Open the key file
-    let mut key_file = File::open(&key_path).map_err(|e| {
-        format!(
-            "Key file '{}' not found or cannot be opened: {}",
-            key_path.display(),
-            e
-        )
-    })?;
-    let mut key = Vec::new();
-    key_file
-        .read_to_end(&mut key)
-        .map_err(|e| format!("Failed to read key file '{}': {}", key_path.display(), e))?;
-
-    // Check if the key file is empty
-    if key.is_empty() {
-        return Err(
-            format!(
-                "Key file '{}' is empty. Please provide a valid key.",
-                key_path.display()
-            )
-            .into(),
-        );
-    }
-
-    // Open the input file
-    let mut input_file = File::open(&input_path).map_err(|e| {
-        format!(
-            "Unable to open input file '{}': {}",
-            input_path.display(),
-            e
-        )
-    })?;
-    let mut input_data = Vec::new();
-    input_file
-        .read_to_end(&mut input_data)
-        .map_err(|e| format!("Failed to read input file '{}': {}", input_path.display(), e))?;
-
     // Check if the input file is empty
-    if input_data.is_empty() {
-        return Err(
-            format!(
-                "Input file '{}' is empty. Nothing to process.",
-                input_path.display()
-            )
-            .into(),
-        );
-    }
-
-    // Ensure key length is at least as long as the input data
-    if key.len() < input_data.len() {
-        return Err(
-            "Key is shorter than input data. Please provide a key of sufficient length.".into(),
-        );
-    }
-
-    // Encrypt or decrypt using XOR
-    let processed_data = xor_process(&input_data, &key);
-
-    // Write the processed data to the output file
-    let mut output_file = File::create(&output_path).map_err(|e| {
-        format!(
-            "Unable to create output file '{}': {}",
-            output_path.display(),
-            e
-        )
-    })?;
-    output_file
-        .write_all(&processed_data)
-        .map_err(|e| format!("Failed to write to output file '{}': {}", output_path.display(), e))?;
-
-    // Verify output file size matches input file size
-    let input_size = metadata(&input_path)
+    let input_size = metadata(input_path)
         .map_err(|e| {
             format!(
-                "Unable to read input file metadata '{}': {}",
+                "Unable to open input file '{}': {}",
                 input_path.display(),
                 e
             )
         })?
         .len();
-    let output_size = metadata(&output_path)
-        .map_err(|e| {
+    if input_size == 0 {
+        return Err(
             format!(
-                "Unable to read output file metadata '{}': {}",
-                output_path.display(),
-                e
+                "Input file '{}' is empty. Nothing to process.",
+                input_path.display()
             )
-        })?
-        .len();
-    if input_size != output_size {
-        return Err("Error: Output file size does not match input file size.".into());
+            .into(),
+        );
     }
 
+    // Pick the pad bytes. A pad header names the range to decrypt with, and
+    // its tag is checked first; otherwise the ledger hands out a fresh range
+    // so no byte is reused. Legacy files use the key from its start, which
+    // must be at least as long as the input.
+    let policy = Policy {
+        pad: if legacy { PadMode::Raw } else { PadMode::Ledger },
+        key_length: KeyLength::Require,
+        output: Output::File(output_path.to_path_buf()),
+        overwrite: Overwrite::Replace,
+        burn: false,
+    };
+    XorEngine::new(key_path, policy)?.run(input_path)?;
+
     println!("Operation completed successfully.");
 
     Ok(())
 }
-
-// Function to XOR the input data with the key
-fn xor_process(data: &[u8], key: &[u8]) -> Vec<u8> {
-    data.iter()
-        .zip(key.iter())
-        .map(|(&data_byte, &key_byte)| data_byte ^ key_byte)
-        .collect()
-}
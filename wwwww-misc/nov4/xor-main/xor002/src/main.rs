@@ -3,30 +3,52 @@ use std::fs::{self, File};
 use std::io::Write;
 use std::process;
 use argon2::{self, Algorithm, Argon2, Params, Version};
-use sha3::{Sha3_512, Digest};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use ring::hmac;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::{Sha3_512, Shake256, Digest};
 
-/// Generate a deterministic key based on the password using Argon2.
-/// The key will be as long as the file's content and will not repeat.
-fn generate_key(password: &str, length: usize) -> Vec<u8> {
-    const ARGON2_MEMORY_COST: u32 = 65536;
-    const ARGON2_TIME_COST: u32 = 3;
-    const ARGON2_PARALLELISM: u32 = 1;
+// v2 files: MAGIC, VERSION, a random 16-byte salt, the ciphertext, then an
+// HMAC-SHA256 tag over everything before it.
+const MAGIC: &[u8; 4] = b"X002";
+const VERSION: u8 = 2;
+const SALT_LENGTH: usize = 16;
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + SALT_LENGTH;
+const TAG_LENGTH: usize = 32;
+// Domain separation for the SHAKE256 keystream
+const KEYSTREAM_CONTEXT: &[u8] = b"xor002 v2 keystream";
 
-    let params = Params::new(ARGON2_MEMORY_COST, ARGON2_TIME_COST, ARGON2_PARALLELISM, Some(32))
+const ARGON2_MEMORY_COST: u32 = 65536;
+const ARGON2_TIME_COST: u32 = 3;
+const ARGON2_PARALLELISM: u32 = 1;
+
+/// Runs Argon2id over the password with the given salt.
+fn argon2_key(password: &str, salt: &[u8], length: usize) -> Vec<u8> {
+    let params = Params::new(ARGON2_MEMORY_COST, ARGON2_TIME_COST, ARGON2_PARALLELISM, Some(length))
         .expect("Failed to create Argon2 parameters");
     let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
 
+    let mut derived_key = vec![0u8; length];
+
+    argon2
+        .hash_password_into(password.as_bytes(), salt, &mut derived_key)
+        .expect("Key derivation failed");
+
+    derived_key
+}
+
+/// The v1 key: Argon2 with a salt taken from SHA3-512 of the password, and
+/// its 32 bytes repeated to the length of the file. Kept only to decrypt old
+/// files; the salt depends on nothing but the password, and the key repeats.
+fn generate_legacy_key(password: &str, length: usize) -> Vec<u8> {
     // Derive a deterministic salt from the password using SHA3-512
     let mut hasher = Sha3_512::new();
-    hasher.update(password.as_bytes());
+    Digest::update(&mut hasher, password.as_bytes());
     let full_salt = hasher.finalize();
     let salt = &full_salt[..16]; // Truncate to 16 bytes to use as salt
 
-    let mut derived_key = vec![0u8; 32];
-
-    argon2
-        .hash_password_into(password.as_bytes(), &salt, &mut derived_key)
-        .expect("Key derivation failed");
+    let derived_key = argon2_key(password, salt, 32);
 
     // Extend or truncate the key to match the length of the file data
     let mut final_key = vec![0u8; length];
@@ -37,23 +59,84 @@ fn generate_key(password: &str, length: usize) -> Vec<u8> {
     final_key
 }
 
+/// The v2 keys: 64 bytes of Argon2id output with a random salt, split into a
+/// keystream key and a MAC key.
+fn generate_keys(password: &str, salt: &[u8]) -> (Vec<u8>, hmac::Key) {
+    let derived_key = argon2_key(password, salt, 64);
+    let (keystream_key, mac_key) = derived_key.split_at(32);
+    (keystream_key.to_vec(), hmac::Key::new(hmac::HMAC_SHA256, mac_key))
+}
+
+/// SHAKE256 of the keystream key, read out to the length of the file. Unlike
+/// the v1 key it never repeats.
+fn generate_keystream(keystream_key: &[u8], length: usize) -> Vec<u8> {
+    let mut shake = Shake256::default();
+    shake.update(KEYSTREAM_CONTEXT);
+    shake.update(keystream_key);
+    let mut keystream = vec![0u8; length];
+    shake.finalize_xof().read(&mut keystream);
+    keystream
+}
+
 /// XOR encryption/decryption function.
 /// This is symmetric, so the same function can encrypt and decrypt.
 fn xor_encrypt_decrypt(data: &[u8], key: &[u8]) -> Vec<u8> {
     data.iter().zip(key.iter()).map(|(d, k)| d ^ k).collect()
 }
 
+/// Whether `data` is a v2 file.
+fn is_v2(data: &[u8]) -> bool {
+    data.len() >= HEADER_LENGTH + TAG_LENGTH && &data[..MAGIC.len()] == MAGIC && data[MAGIC.len()] == VERSION
+}
+
+/// Encrypts to the v2 format: header, ciphertext, tag.
+fn encrypt_v2(plaintext: &[u8], password: &str) -> Vec<u8> {
+    let mut salt = [0u8; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    let (keystream_key, mac_key) = generate_keys(password, &salt);
+    let keystream = generate_keystream(&keystream_key, plaintext.len());
+
+    let mut output = Vec::with_capacity(HEADER_LENGTH + plaintext.len() + TAG_LENGTH);
+    output.extend_from_slice(MAGIC);
+    output.push(VERSION);
+    output.extend_from_slice(&salt);
+    output.extend_from_slice(&xor_encrypt_decrypt(plaintext, &keystream));
+    let tag = hmac::sign(&mac_key, &output);
+    output.extend_from_slice(tag.as_ref());
+    output
+}
+
+/// Checks the tag of a v2 file, then decrypts it.
+fn decrypt_v2(data: &[u8], password: &str) -> Result<Vec<u8>, String> {
+    let (authenticated, tag) = data.split_at(data.len() - TAG_LENGTH);
+    let salt = &authenticated[MAGIC.len() + 1..HEADER_LENGTH];
+    let (keystream_key, mac_key) = generate_keys(password, salt);
+    hmac::verify(&mac_key, authenticated, tag)
+        .map_err(|_| "Wrong password, or the file has been modified.".to_string())?;
+
+    let ciphertext = &authenticated[HEADER_LENGTH..];
+    let keystream = generate_keystream(&keystream_key, ciphertext.len());
+    Ok(xor_encrypt_decrypt(ciphertext, &keystream))
+}
+
 fn main() {
     // Parse command-line arguments
     let args: Vec<String> = env::args().collect();
-    if args.len() != 4 {
-        eprintln!("Usage: {} <input_file> <output_file> <password>", args[0]);
+    let legacy = args.len() == 6 && args[1] == "d" && args[5] == "--legacy";
+    if args.len() != 5 && !legacy {
+        eprintln!("Usage: {} <e|d> <input_file> <output_file> <password> [--legacy]", args[0]);
+        eprintln!("e encrypts to the v2 format and d decrypts a v2 file. d --legacy decrypts a file from the old format.");
         process::exit(1);
     }
 
-    let input_file = &args[1];
-    let output_file = &args[2];
-    let password = &args[3];
+    let mode = &args[1];
+    let input_file = &args[2];
+    let output_file = &args[3];
+    let password = &args[4];
+    if mode != "e" && mode != "d" {
+        eprintln!("Invalid mode. Use 'e' for encryption or 'd' for decryption.");
+        process::exit(1);
+    }
 
     // Read the input file into memory
     let input_data = match fs::read(input_file) {
@@ -64,18 +147,85 @@ fn main() {
         }
     };
 
-    // Generate the deterministic key based on the password and file length
-    let key = generate_key(password, input_data.len());
-
-    // XOR encryption/decryption
-    let encrypted_data = xor_encrypt_decrypt(&input_data, &key);
+    let (output_data, action) = if mode == "e" {
+        (encrypt_v2(&input_data, password), "encrypted")
+    } else if legacy {
+        if is_v2(&input_data) {
+            eprintln!("{} is a v2 file. Decrypt it without --legacy.", input_file);
+            process::exit(1);
+        }
+        // Old files are only ever decrypted; nothing is written in the old format
+        let key = generate_legacy_key(password, input_data.len());
+        (xor_encrypt_decrypt(&input_data, &key), "decrypted (old format)")
+    } else if is_v2(&input_data) {
+        match decrypt_v2(&input_data, password) {
+            Ok(data) => (data, "decrypted"),
+            Err(err) => {
+                eprintln!("Failed to decrypt: {}", err);
+                process::exit(1);
+            }
+        }
+    } else {
+        eprintln!(
+            "{} has no v2 header. If it is a file from the old format, decrypt it with --legacy.",
+            input_file
+        );
+        process::exit(1);
+    };
 
     // Write the output data to the specified output file
-    match File::create(output_file).and_then(|mut file| file.write_all(&encrypted_data)) {
-        Ok(_) => println!("File successfully processed and saved to {}", output_file),
+    match File::create(output_file).and_then(|mut file| file.write_all(&output_data)) {
+        Ok(_) => println!("File successfully {} and saved to {}", action, output_file),
         Err(err) => {
             eprintln!("Failed to write output file: {}", err);
             process::exit(1);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWORD: &str = "correct horse";
+
+    #[test]
+    fn v2_files_decrypt_to_the_plaintext() {
+        let plaintext = b"quarterly figures, final draft\n";
+        let sealed = encrypt_v2(plaintext, PASSWORD);
+        assert!(is_v2(&sealed));
+        assert_eq!(sealed.len(), HEADER_LENGTH + plaintext.len() + TAG_LENGTH);
+        assert_eq!(decrypt_v2(&sealed, PASSWORD).unwrap(), plaintext);
+        assert!(decrypt_v2(&sealed, "wrong horse").is_err());
+    }
+
+    #[test]
+    fn a_modified_header_salt_or_tag_is_refused() {
+        let sealed = encrypt_v2(b"quarterly figures", PASSWORD);
+        // Version byte, first salt byte, last tag byte
+        for index in [MAGIC.len(), MAGIC.len() + 1, sealed.len() - 1] {
+            let mut tampered = sealed.clone();
+            tampered[index] ^= 1;
+            assert!(decrypt_v2(&tampered, PASSWORD).is_err(), "byte {}", index);
+        }
+    }
+
+    #[test]
+    fn each_encryption_gets_its_own_salt() {
+        let first = encrypt_v2(b"same text", PASSWORD);
+        let second = encrypt_v2(b"same text", PASSWORD);
+        assert_ne!(first[MAGIC.len() + 1..HEADER_LENGTH], second[MAGIC.len() + 1..HEADER_LENGTH]);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn old_files_decrypt_with_the_legacy_key() {
+        // "old notes from 2023\n" under "correct horse" in the v1 format
+        let old_file = [
+            120, 43, 94, 194, 193, 218, 140, 157, 128, 46, 39, 177, 125, 170, 202, 114, 87, 202, 182, 118,
+        ];
+        assert!(!is_v2(&old_file));
+        let key = generate_legacy_key(PASSWORD, old_file.len());
+        assert_eq!(xor_encrypt_decrypt(&old_file, &key), b"old notes from 2023\n");
+    }
+}
@@ -1,25 +1,39 @@
 use std::env;
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
 use std::process;
 use argon2::{self, Algorithm, Argon2, Params, Version};
+use hmac::{Hmac, Mac};
 use rand::RngCore;
 use rand::rngs::OsRng;
+use sha2::Sha256;
 
+type HmacSha256 = Hmac<Sha256>;
+
+// Files start with MAGIC, VERSION, the salt and the nonce. The ciphertext
+// follows, then an HMAC-SHA256 tag over everything before it. Files from
+// before the header are just salt | nonce | ciphertext.
+const MAGIC: &[u8; 4] = b"X004";
+const VERSION: u8 = 2;
 const SALT_LENGTH: usize = 32;
 const NONCE_LENGTH: usize = 32;
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + SALT_LENGTH + NONCE_LENGTH;
+const TAG_LENGTH: usize = 32;
+// Bytes held in memory at a time
+const CHUNK_SIZE: usize = 64 * 1024;
 
 /// Generate a key based on the password using Argon2, with a random salt.
-fn generate_key(password: &str, salt: &[u8]) -> Vec<u8> {
+fn generate_key(password: &str, salt: &[u8], length: usize) -> Vec<u8> {
     const ARGON2_MEMORY_COST: u32 = 131072; // Increase memory cost to 128 MB
     const ARGON2_TIME_COST: u32 = 5;       // Increase time cost for more resistance to brute force
     const ARGON2_PARALLELISM: u32 = 1;
 
-    let params = Params::new(ARGON2_MEMORY_COST, ARGON2_TIME_COST, ARGON2_PARALLELISM, Some(32))
+    let params = Params::new(ARGON2_MEMORY_COST, ARGON2_TIME_COST, ARGON2_PARALLELISM, Some(length))
         .expect("Failed to create Argon2 parameters");
     let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
 
-    let mut derived_key = vec![0u8; 32];
+    let mut derived_key = vec![0u8; length];
 
     argon2
         .hash_password_into(password.as_bytes(), salt, &mut derived_key)
@@ -28,12 +42,208 @@ fn generate_key(password: &str, salt: &[u8]) -> Vec<u8> {
     derived_key
 }
 
-/// XOR encryption/decryption function using a nonce.
-fn xor_encrypt_decrypt(data: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
-    data.iter()
-        .enumerate()
-        .map(|(i, &byte)| byte ^ key[i % key.len()] ^ nonce[i % nonce.len()])
-        .collect()
+/// The keys of a v2 file: 64 bytes of Argon2 output, split into the BLAKE3
+/// keystream key and the HMAC key.
+fn generate_keys(password: &str, salt: &[u8]) -> ([u8; 32], HmacSha256) {
+    let derived_key = generate_key(password, salt, 64);
+    let mut keystream_key = [0u8; 32];
+    keystream_key.copy_from_slice(&derived_key[..32]);
+    let mac = HmacSha256::new_from_slice(&derived_key[32..]).expect("HMAC can take key of any size");
+    (keystream_key, mac)
+}
+
+/// The keystream of a v2 file: BLAKE3 keyed with the Argon2 output, over the
+/// nonce, read as an XOF. Unlike the old 32-byte key it never repeats.
+fn keystream(keystream_key: &[u8; 32], nonce: &[u8]) -> blake3::OutputReader {
+    let mut hasher = blake3::Hasher::new_keyed(keystream_key);
+    hasher.update(nonce);
+    hasher.finalize_xof()
+}
+
+/// XOR `data` in place with the next bytes of the keystream.
+fn xor_keystream(data: &mut [u8], keystream: &mut blake3::OutputReader, buffer: &mut [u8]) {
+    let buffer = &mut buffer[..data.len()];
+    keystream.fill(buffer);
+    for (byte, key) in data.iter_mut().zip(buffer.iter()) {
+        *byte ^= key;
+    }
+}
+
+/// XOR for files from before the header: the 32-byte key and the nonce,
+/// both repeating. `offset` is the position of `data` in the file's data.
+fn xor_legacy(data: &mut [u8], key: &[u8], nonce: &[u8], offset: u64) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        let position = offset + i as u64;
+        *byte ^= key[(position % key.len() as u64) as usize] ^ nonce[(position % nonce.len() as u64) as usize];
+    }
+}
+
+/// Reads until `buffer` is full or the input ends.
+fn read_full(input: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match input.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+fn too_short() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "Input file is too short to be an encrypted file.")
+}
+
+/// Encrypts `input_path` to `output_path` a chunk at a time: header,
+/// ciphertext, tag. A partly written output is removed on error.
+fn encrypt(input_path: &Path, output_path: &Path, password: &str) -> io::Result<()> {
+    let mut input = File::open(input_path)?;
+    let mut output = File::create(output_path)?;
+    let result = (|| {
+        // Generate a random salt and nonce for encryption
+        let mut salt = [0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+
+        // Generate the encryption and MAC keys
+        let (keystream_key, mut mac) = generate_keys(password, &salt);
+        let mut keystream = keystream(&keystream_key, &nonce);
+
+        // Write the header: magic, version, salt and nonce
+        let header = [MAGIC.as_ref(), &[VERSION], salt.as_ref(), nonce.as_ref()].concat();
+        output.write_all(&header)?;
+        mac.update(&header);
+
+        // Encrypt the data, and MAC the ciphertext
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        let mut keystream_buffer = vec![0u8; CHUNK_SIZE];
+        loop {
+            let n = read_full(&mut input, &mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            xor_keystream(&mut buffer[..n], &mut keystream, &mut keystream_buffer);
+            mac.update(&buffer[..n]);
+            output.write_all(&buffer[..n])?;
+        }
+        output.write_all(&mac.finalize().into_bytes())?;
+        output.sync_all()
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(output_path);
+    }
+    result
+}
+
+/// Decrypts `input_path` to `output_path`. The tag is checked in a first
+/// pass and the data decrypted in a second, so nothing is written for a
+/// wrong password or a modified file.
+fn decrypt(input_path: &Path, output_path: &Path, password: &str) -> io::Result<()> {
+    let mut input = File::open(input_path)?;
+    let length = input.metadata()?.len();
+    let mut magic = [0u8; MAGIC.len() + 1];
+    if read_full(&mut input, &mut magic)? < magic.len() || &magic[..MAGIC.len()] != MAGIC {
+        return decrypt_legacy(input, length, output_path, password);
+    }
+    if magic[MAGIC.len()] != VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported file version {}.", magic[MAGIC.len()]),
+        ));
+    }
+    if length < (HEADER_LENGTH + TAG_LENGTH) as u64 {
+        return Err(too_short());
+    }
+
+    // Extract the salt and nonce from the header
+    let mut header = magic.to_vec();
+    header.resize(HEADER_LENGTH, 0);
+    input.read_exact(&mut header[magic.len()..])?;
+    let salt = &header[magic.len()..magic.len() + SALT_LENGTH];
+    let nonce = &header[magic.len() + SALT_LENGTH..];
+
+    // Generate the decryption and MAC keys, and check the tag
+    let (keystream_key, mut mac) = generate_keys(password, salt);
+    mac.update(&header);
+    let ciphertext_length = length - (HEADER_LENGTH + TAG_LENGTH) as u64;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut remaining = ciphertext_length;
+    while remaining > 0 {
+        let n = remaining.min(CHUNK_SIZE as u64) as usize;
+        input.read_exact(&mut buffer[..n])?;
+        mac.update(&buffer[..n]);
+        remaining -= n as u64;
+    }
+    let mut tag = [0u8; TAG_LENGTH];
+    input.read_exact(&mut tag)?;
+    mac.verify_slice(&tag).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidData, "Wrong password, or the file has been modified.")
+    })?;
+
+    // Decrypt the data using the keystream
+    let mut keystream = keystream(&keystream_key, nonce);
+    let mut keystream_buffer = vec![0u8; CHUNK_SIZE];
+    input.seek(SeekFrom::Start(HEADER_LENGTH as u64))?;
+    let mut output = File::create(output_path)?;
+    let result = (|| {
+        let mut remaining = ciphertext_length;
+        while remaining > 0 {
+            let n = remaining.min(CHUNK_SIZE as u64) as usize;
+            input.read_exact(&mut buffer[..n])?;
+            xor_keystream(&mut buffer[..n], &mut keystream, &mut keystream_buffer);
+            output.write_all(&buffer[..n])?;
+            remaining -= n as u64;
+        }
+        output.sync_all()
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(output_path);
+    }
+    result
+}
+
+/// Decrypts a file from before the header (salt | nonce | ciphertext). These
+/// files have no tag, so a wrong password gives garbage instead of an error.
+fn decrypt_legacy(mut input: File, length: u64, output_path: &Path, password: &str) -> io::Result<()> {
+    if length < (SALT_LENGTH + NONCE_LENGTH) as u64 {
+        return Err(too_short());
+    }
+
+    // Extract the salt and nonce from the beginning of the input data
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    input.seek(SeekFrom::Start(0))?;
+    input.read_exact(&mut salt)?;
+    input.read_exact(&mut nonce)?;
+
+    // Generate the decryption key
+    let key = generate_key(password, &salt, 32);
+
+    let mut output = File::create(output_path)?;
+    let result = (|| {
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        let mut offset = 0u64;
+        loop {
+            let n = read_full(&mut input, &mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            xor_legacy(&mut buffer[..n], &key, &nonce, offset);
+            output.write_all(&buffer[..n])?;
+            offset += n as u64;
+        }
+        output.sync_all()
+    })();
+    match result {
+        Ok(()) => println!("Note: this file is in the old format, which has no tag. Encrypt it again to upgrade it."),
+        Err(_) => {
+            let _ = fs::remove_file(output_path);
+        }
+    }
+    result
 }
 
 fn main() {
@@ -65,60 +275,118 @@ fn main() {
     }
     let password = &args[4];
 
-    // Read the input file into memory
-    let input_data = match fs::read(&input_path) {
-        Ok(data) => data,
-        Err(err) => {
-            eprintln!("Failed to read input file: {}", err);
+    let (result, action) = match mode.as_str() {
+        "e" => (encrypt(&input_path, &output_path, password), "encrypted"),
+        "d" => (decrypt(&input_path, &output_path, password), "decrypted"),
+        _ => {
+            eprintln!("Invalid mode. Use 'e' for encryption or 'd' for decryption.");
             process::exit(1);
         }
     };
 
-    if mode == "e" {
-        // Generate a random salt for encryption
-        let mut salt = [0u8; SALT_LENGTH];
-        OsRng.fill_bytes(&mut salt);
+    match result {
+        Ok(_) => println!("File successfully {} and saved to {}", action, output_file),
+        Err(err) => {
+            eprintln!("Failed to process {}: {}", input_file, err);
+            process::exit(1);
+        }
+    }
+}
 
-        // Generate a random nonce for encryption
-        let mut nonce = [0u8; NONCE_LENGTH];
-        OsRng.fill_bytes(&mut nonce);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
 
-        // Generate the encryption key
-        let key = generate_key(password, &salt);
+    const PASSWORD: &str = "plum tree 42";
 
-        // Encrypt the data using XOR
-        let encrypted_data = xor_encrypt_decrypt(&input_data, &key, &nonce);
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("xor004-{}-{}", process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
 
-        // Write the salt, nonce, and encrypted data to the output file
-        let output_data = [salt.as_ref(), nonce.as_ref(), encrypted_data.as_ref()].concat();
-        match File::create(&output_path).and_then(|mut file| file.write_all(&output_data)) {
-            Ok(_) => println!("File successfully encrypted and saved to {}", output_file),
-            Err(err) => {
-                eprintln!("Failed to write output file: {}", err);
-                process::exit(1);
-            }
+    fn assert_too_short(err: io::Error) {
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), too_short().to_string());
+    }
+
+    #[test]
+    fn round_trips_across_chunk_boundaries() {
+        let dir = scratch_dir("round-trip");
+        let data: Vec<u8> = (0..3 * CHUNK_SIZE + 17).map(|i| (i % 253) as u8).collect();
+        fs::write(dir.join("plain"), &data).unwrap();
+
+        encrypt(&dir.join("plain"), &dir.join("sealed"), PASSWORD).unwrap();
+        let sealed = fs::read(dir.join("sealed")).unwrap();
+        assert_eq!(&sealed[..MAGIC.len()], MAGIC);
+        assert_eq!(sealed.len(), HEADER_LENGTH + data.len() + TAG_LENGTH);
+
+        decrypt(&dir.join("sealed"), &dir.join("opened"), PASSWORD).unwrap();
+        assert_eq!(fs::read(dir.join("opened")).unwrap(), data);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tampering_or_a_wrong_password_leaves_no_output() {
+        let dir = scratch_dir("refused");
+        fs::write(dir.join("plain"), b"the vault code is 7301").unwrap();
+        encrypt(&dir.join("plain"), &dir.join("sealed"), PASSWORD).unwrap();
+        let sealed = fs::read(dir.join("sealed")).unwrap();
+
+        let err = decrypt(&dir.join("sealed"), &dir.join("opened"), "plum tree 43").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(!dir.join("opened").exists());
+
+        // A salt byte, a ciphertext byte and a tag byte
+        for position in [MAGIC.len() + 1, HEADER_LENGTH + 2, sealed.len() - 1] {
+            let mut tampered = sealed.clone();
+            tampered[position] ^= 0x80;
+            fs::write(dir.join("tampered"), tampered).unwrap();
+            let err = decrypt(&dir.join("tampered"), &dir.join("opened"), PASSWORD).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "byte {}", position);
+            assert!(!dir.join("opened").exists(), "byte {}", position);
         }
-    } else if mode == "d" {
-        // Extract the salt and nonce from the beginning of the input data
-        let (salt, rest) = input_data.split_at(SALT_LENGTH);
-        let (nonce, encrypted_data) = rest.split_at(NONCE_LENGTH);
-
-        // Generate the decryption key
-        let key = generate_key(password, salt);
-
-        // Decrypt the data using XOR
-        let decrypted_data = xor_encrypt_decrypt(encrypted_data, &key, nonce);
-
-        // Write the decrypted data to the output file
-        match File::create(&output_path).and_then(|mut file| file.write_all(&decrypted_data)) {
-            Ok(_) => println!("File successfully decrypted and saved to {}", output_file),
-            Err(err) => {
-                eprintln!("Failed to write output file: {}", err);
-                process::exit(1);
-            }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn short_inputs_are_errors_not_panics() {
+        let dir = scratch_dir("short");
+        let mut headed = MAGIC.to_vec();
+        headed.push(VERSION);
+        headed.resize(HEADER_LENGTH + TAG_LENGTH - 1, 0);
+        // Nothing, the magic alone, and a header with one byte short of a tag
+        for (name, contents) in [("empty", vec![]), ("magic", MAGIC.to_vec()), ("headed", headed)] {
+            fs::write(dir.join(name), &contents).unwrap();
+            let err = decrypt(&dir.join(name), &dir.join("opened"), PASSWORD).unwrap_err();
+            assert_too_short(err);
+            assert!(!dir.join("opened").exists(), "{}", name);
         }
-    } else {
-        eprintln!("Invalid mode. Use 'e' for encryption or 'd' for decryption.");
-        process::exit(1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn headerless_files_decrypt_with_the_repeating_key() {
+        let dir = scratch_dir("legacy");
+        let plaintext = b"written by the tool before it had a header";
+        let salt = [0x11u8; SALT_LENGTH];
+        let nonce = [0x22u8; NONCE_LENGTH];
+
+        // salt | nonce | plaintext XOR the 32-byte key and the nonce, both repeating
+        let key = generate_key(PASSWORD, &salt, 32);
+        let mut file = [salt.as_ref(), nonce.as_ref()].concat();
+        file.extend(
+            plaintext
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ key[i % 32] ^ nonce[i % NONCE_LENGTH]),
+        );
+        fs::write(dir.join("old"), &file).unwrap();
+
+        let input = File::open(dir.join("old")).unwrap();
+        decrypt_legacy(input, file.len() as u64, &dir.join("opened"), PASSWORD).unwrap();
+        assert_eq!(fs::read(dir.join("opened")).unwrap(), plaintext);
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -0,0 +1,216 @@
+//! Recombining shares into the original file.
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use secmem::SecretBuf;
+use sha2::{Digest, Sha256};
+
+use crate::gf256;
+use crate::header::{Scheme, ShareHeader, HEADER_LEN};
+use crate::split::{create_output, remove_all, CHUNK_SIZE};
+
+struct Share {
+    path: PathBuf,
+    file: File,
+    header: ShareHeader,
+    hasher: Sha256,
+}
+
+/// Rebuilds the secret from `paths` into `output`.
+///
+/// Headers are checked before anything is written: every share must come
+/// from the same split, indices must be distinct, and there must be enough
+/// of them. Checksums are checked while streaming, and a mismatch removes
+/// the output.
+pub fn combine(paths: &[PathBuf], output: &Path, force: bool) -> Result<()> {
+    let mut shares = Vec::new();
+    for path in paths {
+        shares.push(open_share(path)?);
+    }
+    let first = shares[0].header;
+    for share in &shares[1..] {
+        if !first.same_set(&share.header) {
+            bail!(
+                "'{}' and '{}' are from different splits.",
+                shares[0].path.display(),
+                share.path.display()
+            );
+        }
+    }
+    shares.sort_by_key(|share| share.header.index);
+    for pair in shares.windows(2) {
+        if pair[0].header.index == pair[1].header.index {
+            bail!(
+                "'{}' and '{}' are both share {}.",
+                pair[0].path.display(),
+                pair[1].path.display(),
+                pair[0].header.index
+            );
+        }
+    }
+    let needed = first.threshold as usize;
+    if shares.len() < needed {
+        bail!(
+            "{} of the {} shares are needed, but only {} were given.",
+            needed,
+            first.count,
+            shares.len()
+        );
+    }
+    // Any `threshold` shares will do; the rest are not read.
+    shares.truncate(needed);
+
+    let file = create_output(output, force)?;
+    if let Err(e) = write_secret(&mut shares, file) {
+        remove_all(&[output.to_path_buf()]);
+        return Err(e);
+    }
+    Ok(())
+}
+
+fn open_share(path: &Path) -> Result<Share> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open share '{}'.", path.display()))?;
+    let mut bytes = [0u8; HEADER_LEN];
+    file.read_exact(&mut bytes)
+        .with_context(|| format!("'{}' is too short to be a share.", path.display()))?;
+    let header = ShareHeader::parse(&bytes)
+        .with_context(|| format!("'{}' was rejected.", path.display()))?;
+    if file.metadata()?.len() != HEADER_LEN as u64 + header.length {
+        bail!("'{}' is truncated or has trailing data.", path.display());
+    }
+    Ok(Share {
+        path: path.to_path_buf(),
+        file,
+        hasher: header.hasher(),
+        header,
+    })
+}
+
+fn write_secret(shares: &mut [Share], output: File) -> Result<()> {
+    // XOR shares simply add up; Shamir shares are weighted by their
+    // Lagrange coefficients at zero.
+    let tables: Vec<[u8; 256]> = match shares[0].header.scheme {
+        Scheme::Xor => vec![gf256::mul_table(1); shares.len()],
+        Scheme::Shamir => {
+            let xs: Vec<u8> = shares.iter().map(|share| share.header.index).collect();
+            gf256::lagrange_at_zero(&xs)
+                .into_iter()
+                .map(gf256::mul_table)
+                .collect()
+        }
+    };
+
+    let mut writer = BufWriter::with_capacity(CHUNK_SIZE, output);
+    let mut secret = SecretBuf::new(CHUNK_SIZE)?;
+    let mut y = SecretBuf::new(CHUNK_SIZE)?;
+    let mut remaining = shares[0].header.length;
+    while remaining > 0 {
+        let n = remaining.min(CHUNK_SIZE as u64) as usize;
+        secret[..n].fill(0);
+        for (share, table) in shares.iter_mut().zip(&tables) {
+            share.file.read_exact(&mut y[..n])?;
+            share.hasher.update(&y[..n]);
+            for (s, y) in secret[..n].iter_mut().zip(&y[..n]) {
+                *s ^= table[*y as usize];
+            }
+        }
+        writer.write_all(&secret[..n])?;
+        remaining -= n as u64;
+    }
+
+    for share in shares.iter_mut() {
+        let checksum = std::mem::take(&mut share.hasher).finalize();
+        if checksum[..] != share.header.checksum {
+            bail!(
+                "'{}' is corrupted: its checksum does not match.",
+                share.path.display()
+            );
+        }
+    }
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split::split;
+    use std::fs;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("padsplit-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_secret_file(dir: &Path, len: usize) -> (PathBuf, Vec<u8>) {
+        let secret: Vec<u8> = (0..len).map(|i| (i * 31 + 7) as u8).collect();
+        let path = dir.join("key.key");
+        fs::write(&path, &secret).unwrap();
+        (path, secret)
+    }
+
+    #[test]
+    fn xor_shares_need_all_of_them() {
+        let dir = scratch("xor");
+        let (input, secret) = write_secret_file(&dir, 2 * CHUNK_SIZE + 5);
+        let shares = split(&input, &dir, 3, None, false).unwrap();
+        for share in &shares {
+            assert_ne!(fs::read(share).unwrap()[HEADER_LEN..], secret[..]);
+        }
+
+        let output = dir.join("out");
+        combine(&shares, &output, false).unwrap();
+        assert!(fs::read(&output).unwrap() == secret);
+        assert!(combine(&shares[..2], &dir.join("short"), false).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn any_threshold_shamir_shares_recover_the_secret() {
+        let dir = scratch("shamir");
+        let (input, secret) = write_secret_file(&dir, CHUNK_SIZE + 100);
+        let shares = split(&input, &dir, 5, Some(3), false).unwrap();
+        for (i, subset) in [[0, 1, 2], [4, 2, 0], [1, 3, 4]].iter().enumerate() {
+            let picked: Vec<PathBuf> = subset.iter().map(|&j| shares[j].clone()).collect();
+            let output = dir.join(format!("out{}", i));
+            combine(&picked, &output, false).unwrap();
+            assert!(fs::read(&output).unwrap() == secret);
+        }
+        assert!(combine(&shares[..2], &dir.join("short"), false).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mismatched_and_corrupted_shares_are_rejected() {
+        let dir = scratch("reject");
+        let (input, _) = write_secret_file(&dir, 1000);
+        let first = split(&input, &dir, 2, None, false).unwrap();
+        let other_dir = dir.join("other");
+        fs::create_dir(&other_dir).unwrap();
+        let second = split(&input, &other_dir, 2, None, false).unwrap();
+
+        let mixed = [first[0].clone(), second[1].clone()];
+        let output = dir.join("out");
+        let err = combine(&mixed, &output, false).unwrap_err();
+        assert!(err.to_string().contains("different splits"));
+        assert!(!output.exists());
+
+        let twice = [first[0].clone(), first[0].clone()];
+        assert!(combine(&twice, &output, false).is_err());
+
+        let mut bytes = fs::read(&first[1]).unwrap();
+        bytes[HEADER_LEN + 10] ^= 1;
+        fs::write(&first[1], bytes).unwrap();
+        let err = combine(&first, &output, false).unwrap_err();
+        assert!(err.to_string().contains("corrupted"));
+        assert!(!output.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+//! Arithmetic in GF(2^8) with the AES polynomial x^8 + x^4 + x^3 + x + 1.
+
+const fn build_tables() -> ([u8; 512], [u8; 256]) {
+    let mut exp = [0u8; 512];
+    let mut log = [0u8; 256];
+    let mut x: u8 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x;
+        exp[i + 255] = x;
+        log[x as usize] = i as u8;
+        // Multiply by the generator 3: x * 2 + x.
+        let doubled = (x << 1) ^ if x & 0x80 != 0 { 0x1b } else { 0 };
+        x ^= doubled;
+        i += 1;
+    }
+    (exp, log)
+}
+
+const TABLES: ([u8; 512], [u8; 256]) = build_tables();
+const EXP: [u8; 512] = TABLES.0;
+const LOG: [u8; 256] = TABLES.1;
+
+pub fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    EXP[LOG[a as usize] as usize + LOG[b as usize] as usize]
+}
+
+/// Multiplicative inverse. Zero has none.
+pub fn inv(a: u8) -> u8 {
+    assert!(a != 0, "zero has no inverse in GF(256)");
+    EXP[255 - LOG[a as usize] as usize]
+}
+
+/// The table of `x * c` for every `x`, so a whole buffer can be multiplied by
+/// `c` with one lookup per byte.
+pub fn mul_table(c: u8) -> [u8; 256] {
+    let mut table = [0u8; 256];
+    for (x, entry) in table.iter_mut().enumerate() {
+        *entry = mul(x as u8, c);
+    }
+    table
+}
+
+/// The Lagrange basis values at zero for the share indices `xs`: the secret
+/// is the sum of `weights[i] * y[i]`.
+pub fn lagrange_at_zero(xs: &[u8]) -> Vec<u8> {
+    xs.iter()
+        .map(|&xi| {
+            xs.iter().filter(|&&xj| xj != xi).fold(1, |acc, &xj| {
+                // x_j / (x_j - x_i); subtraction is XOR.
+                mul(acc, mul(xj, inv(xj ^ xi)))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_nonzero_element_has_an_inverse() {
+        for a in 1..=255u8 {
+            assert_eq!(mul(a, inv(a)), 1, "a = {}", a);
+        }
+        // The AES S-box reference pair.
+        assert_eq!(mul(0x53, 0xca), 1);
+    }
+
+    #[test]
+    fn interpolation_recovers_the_constant_term() {
+        // p(x) = 0x2a + 0x17 x + 0xc3 x^2
+        let p = |x: u8| 0x2a ^ mul(0x17, x) ^ mul(0xc3, mul(x, x));
+        let xs = [3u8, 7, 200];
+        let secret = lagrange_at_zero(&xs)
+            .iter()
+            .zip(xs)
+            .fold(0, |acc, (&w, x)| acc ^ mul(w, p(x)));
+        assert_eq!(secret, 0x2a);
+    }
+}
@@ -0,0 +1,115 @@
+//! The header at the start of every share.
+//!
+//! Layout (65 bytes):
+//!
+//! | bytes | field |
+//! |---|---|
+//! | 0..4 | `ESKD` |
+//! | 4 | version (1) |
+//! | 5 | scheme: 0 = XOR, 1 = Shamir |
+//! | 6 | threshold: shares needed to combine |
+//! | 7 | count: shares made |
+//! | 8 | index of this share, 1..=count |
+//! | 9..25 | set id, random per split |
+//! | 25..33 | secret length, u64 little-endian |
+//! | 33..65 | SHA-256 of bytes 0..33 and the share body |
+
+use anyhow::{anyhow, bail, Result};
+use sha2::{Digest, Sha256};
+
+pub const MAGIC: &[u8; 4] = b"ESKD";
+pub const VERSION: u8 = 1;
+pub const HEADER_LEN: usize = 65;
+/// Where the checksum starts; everything before it is covered by it.
+pub const CHECKSUM_AT: usize = 33;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheme {
+    /// Every share is needed; the secret is the XOR of all of them.
+    Xor,
+    /// Any `threshold` shares recover the secret, byte-wise Shamir over GF(256).
+    Shamir,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShareHeader {
+    pub scheme: Scheme,
+    pub threshold: u8,
+    pub count: u8,
+    pub index: u8,
+    pub set_id: [u8; 16],
+    pub length: u64,
+    pub checksum: [u8; 32],
+}
+
+impl ShareHeader {
+    pub fn to_bytes(self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        bytes[..4].copy_from_slice(MAGIC);
+        bytes[4] = VERSION;
+        bytes[5] = match self.scheme {
+            Scheme::Xor => 0,
+            Scheme::Shamir => 1,
+        };
+        bytes[6] = self.threshold;
+        bytes[7] = self.count;
+        bytes[8] = self.index;
+        bytes[9..25].copy_from_slice(&self.set_id);
+        bytes[25..33].copy_from_slice(&self.length.to_le_bytes());
+        bytes[CHECKSUM_AT..].copy_from_slice(&self.checksum);
+        bytes
+    }
+
+    pub fn parse(bytes: &[u8; HEADER_LEN]) -> Result<ShareHeader> {
+        if &bytes[..4] != MAGIC {
+            bail!("not a padsplit share");
+        }
+        if bytes[4] != VERSION {
+            bail!("unsupported share version {}", bytes[4]);
+        }
+        let scheme = match bytes[5] {
+            0 => Scheme::Xor,
+            1 => Scheme::Shamir,
+            other => bail!("unknown sharing scheme {}", other),
+        };
+        let header = ShareHeader {
+            scheme,
+            threshold: bytes[6],
+            count: bytes[7],
+            index: bytes[8],
+            set_id: bytes[9..25].try_into().unwrap(),
+            length: u64::from_le_bytes(bytes[25..33].try_into().unwrap()),
+            checksum: bytes[CHECKSUM_AT..].try_into().unwrap(),
+        };
+        let consistent = match scheme {
+            Scheme::Xor => header.threshold == header.count,
+            Scheme::Shamir => header.threshold <= header.count,
+        };
+        if header.threshold < 2 || !consistent || header.index == 0 || header.index > header.count {
+            return Err(anyhow!(
+                "share {} of {} (threshold {}) is not a valid share",
+                header.index,
+                header.count,
+                header.threshold
+            ));
+        }
+        Ok(header)
+    }
+
+    /// A hasher that has absorbed the checksummed header fields; feed it the
+    /// body to get the checksum.
+    pub fn hasher(&self) -> Sha256 {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.to_bytes()[..CHECKSUM_AT]);
+        hasher
+    }
+
+    /// Whether `other` comes from the same split.
+    pub fn same_set(&self, other: &ShareHeader) -> bool {
+        self.set_id == other.set_id
+            && self.scheme == other.scheme
+            && self.threshold == other.threshold
+            && self.count == other.count
+            && self.length == other.length
+    }
+}
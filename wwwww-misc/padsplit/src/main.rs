@@ -0,0 +1,95 @@
+mod combine;
+mod gf256;
+mod header;
+mod split;
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use clap::{value_parser, Parser, Subcommand};
+
+/// Splits OTP pads and other key files into shares, and combines them again.
+#[derive(Parser)]
+#[command(name = "padsplit", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Splits a file into shares; all of them are needed unless --threshold is given
+    Split {
+        /// File to split
+        input: PathBuf,
+        /// Number of shares to make
+        #[arg(short = 'n', long, value_parser = value_parser!(u8).range(2..))]
+        shares: u8,
+        /// Make any K of the shares enough (Shamir), instead of needing all (XOR)
+        #[arg(short = 'k', long, value_name = "K", value_parser = value_parser!(u8).range(2..))]
+        threshold: Option<u8>,
+        /// Directory for the shares (default: the input's directory)
+        #[arg(short, long)]
+        out_dir: Option<PathBuf>,
+        /// Overwrite existing shares
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Rebuilds a file from its shares
+    Combine {
+        /// Share files, in any order
+        #[arg(required = true)]
+        shares: Vec<PathBuf>,
+        /// Where to write the rebuilt file
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Overwrite an existing output file
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+fn main() -> Result<()> {
+    // Keep pad bytes out of core dumps.
+    if let Err(e) = secmem::harden_process() {
+        eprintln!("Warning: could not disable core dumps: {}", e);
+    }
+
+    let cli = Cli::parse();
+    match cli.command {
+        Commands::Split {
+            input,
+            shares,
+            threshold,
+            out_dir,
+            force,
+        } => {
+            let out_dir =
+                out_dir.unwrap_or_else(|| input.parent().unwrap_or(Path::new("")).to_path_buf());
+            let paths = split::split(&input, &out_dir, shares, threshold, force)?;
+            for path in &paths {
+                println!("{}", path.display());
+            }
+            println!(
+                "Split '{}' into {} shares; {} of them are needed to combine it.",
+                input.display(),
+                shares,
+                threshold.unwrap_or(shares)
+            );
+            Ok(())
+        }
+        Commands::Combine {
+            shares,
+            output,
+            force,
+        } => {
+            combine::combine(&shares, &output, force)?;
+            println!(
+                "Combined {} shares into '{}'.",
+                shares.len(),
+                output.display()
+            );
+            Ok(())
+        }
+    }
+}
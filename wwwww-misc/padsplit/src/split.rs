@@ -0,0 +1,202 @@
+//! Splitting a file into shares.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use rand::rngs::OsRng;
+use rand::{Rng, RngCore};
+use secmem::SecretBuf;
+use sha2::{Digest, Sha256};
+
+use crate::gf256;
+use crate::header::{Scheme, ShareHeader, CHECKSUM_AT};
+
+/// Bytes of the secret processed at a time.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Splits `input` into `count` shares in `out_dir`, named
+/// `<input>.share1` and so on. With a threshold, any `threshold` of them
+/// recover the input (Shamir); without one, all of them are needed (XOR).
+pub fn split(
+    input: &Path,
+    out_dir: &Path,
+    count: u8,
+    threshold: Option<u8>,
+    force: bool,
+) -> Result<Vec<PathBuf>> {
+    let (scheme, threshold) = match threshold {
+        Some(k) if k > count => bail!(
+            "The threshold ({}) cannot exceed the number of shares ({}).",
+            k,
+            count
+        ),
+        Some(k) => (Scheme::Shamir, k),
+        None => (Scheme::Xor, count),
+    };
+    let mut secret = File::open(input)
+        .with_context(|| format!("Failed to open input file '{}'.", input.display()))?;
+    let length = secret.metadata()?.len();
+    let name = input
+        .file_name()
+        .with_context(|| format!("'{}' is not a file name.", input.display()))?
+        .to_string_lossy();
+
+    let set_id: [u8; 16] = OsRng.gen();
+    let headers: Vec<ShareHeader> = (1..=count)
+        .map(|index| ShareHeader {
+            scheme,
+            threshold,
+            count,
+            index,
+            set_id,
+            length,
+            checksum: [0; 32],
+        })
+        .collect();
+    let paths: Vec<PathBuf> = (1..=count)
+        .map(|index| out_dir.join(format!("{}.share{}", name, index)))
+        .collect();
+
+    // Never leave a partial set behind, but only remove what was created here.
+    let mut files = Vec::new();
+    for path in &paths {
+        match create_output(path, force) {
+            Ok(file) => files.push(file),
+            Err(e) => {
+                remove_all(&paths[..files.len()]);
+                return Err(e);
+            }
+        }
+    }
+    if let Err(e) = write_shares(&mut secret, &headers, files) {
+        remove_all(&paths);
+        return Err(e);
+    }
+    Ok(paths)
+}
+
+pub(crate) fn remove_all(paths: &[PathBuf]) {
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
+
+pub(crate) fn create_output(path: &Path, force: bool) -> Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path).with_context(|| {
+        format!(
+            "Failed to create '{}' (use --force to overwrite).",
+            path.display()
+        )
+    })
+}
+
+fn write_shares(secret: &mut File, headers: &[ShareHeader], files: Vec<File>) -> Result<()> {
+    let mut outputs: Vec<(BufWriter<File>, Sha256)> = Vec::new();
+    for (file, header) in files.into_iter().zip(headers) {
+        let mut writer = BufWriter::with_capacity(CHUNK_SIZE, file);
+        writer.write_all(&header.to_bytes())?;
+        outputs.push((writer, header.hasher()));
+    }
+    let scheme = headers[0].scheme;
+    let threshold = headers[0].threshold;
+    let tables: Vec<[u8; 256]> = headers.iter().map(|h| gf256::mul_table(h.index)).collect();
+
+    let mut chunk = SecretBuf::new(CHUNK_SIZE)?;
+    let mut random = SecretBuf::new(CHUNK_SIZE)?;
+    let mut ys = Vec::new();
+    if scheme == Scheme::Shamir {
+        for _ in headers {
+            ys.push(SecretBuf::new(CHUNK_SIZE)?);
+        }
+    }
+
+    let mut remaining = headers[0].length;
+    while remaining > 0 {
+        let n = remaining.min(CHUNK_SIZE as u64) as usize;
+        secret.read_exact(&mut chunk[..n])?;
+        match scheme {
+            Scheme::Xor => {
+                // Every share but the last is random; the last is the
+                // secret XORed with all of them.
+                let (last, random_shares) = outputs.split_last_mut().unwrap();
+                for (writer, hasher) in random_shares {
+                    OsRng.fill_bytes(&mut random[..n]);
+                    emit(writer, hasher, &random[..n])?;
+                    for (s, r) in chunk[..n].iter_mut().zip(&random[..n]) {
+                        *s ^= r;
+                    }
+                }
+                emit(&mut last.0, &mut last.1, &chunk[..n])?;
+            }
+            Scheme::Shamir => {
+                shamir_chunk(&chunk[..n], threshold, &tables, &mut ys, &mut random);
+                for ((writer, hasher), y) in outputs.iter_mut().zip(&ys) {
+                    emit(writer, hasher, &y[..n])?;
+                }
+            }
+        }
+        remaining -= n as u64;
+    }
+    if secret.read(&mut chunk[..1])? != 0 {
+        bail!("The input grew while it was being split.");
+    }
+
+    // Only now is each checksum known.
+    for (writer, hasher) in outputs {
+        let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(CHECKSUM_AT as u64))?;
+        file.write_all(&hasher.finalize())?;
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+fn emit(writer: &mut BufWriter<File>, hasher: &mut Sha256, bytes: &[u8]) -> io::Result<()> {
+    hasher.update(bytes);
+    writer.write_all(bytes)
+}
+
+/// Evaluates, for every byte of `secret`, a random polynomial of degree
+/// `threshold - 1` whose constant term is that byte, at each share's index.
+///
+/// Horner's rule runs from the highest coefficient down, so only one chunk of
+/// random coefficients is needed at a time.
+fn shamir_chunk(
+    secret: &[u8],
+    threshold: u8,
+    tables: &[[u8; 256]],
+    ys: &mut [SecretBuf],
+    coefficients: &mut [u8],
+) {
+    let n = secret.len();
+    for y in ys.iter_mut() {
+        y[..n].fill(0);
+    }
+    for _ in 1..threshold {
+        OsRng.fill_bytes(&mut coefficients[..n]);
+        for (y, table) in ys.iter_mut().zip(tables) {
+            for (y, c) in y[..n].iter_mut().zip(&coefficients[..n]) {
+                *y = table[*y as usize] ^ c;
+            }
+        }
+    }
+    for (y, table) in ys.iter_mut().zip(tables) {
+        for (y, s) in y[..n].iter_mut().zip(secret) {
+            *y = table[*y as usize] ^ s;
+        }
+    }
+}
@@ -1,19 +1,21 @@
-use std::fs::{File, metadata};
-use std::io::{self, Read, Write};
+use std::io;
+use std::path::Path;
 use std::process;
 
+use otp::{KeyLength, Output, Overwrite, PadMode, Policy, XorEngine, DEFAULT_KEY};
+
 fn main() {
+    // `--burn` destroys the used key bytes after encrypting or decrypting, and
+    // `--legacy` reads files from before the key ledger
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let burn = args.iter().any(|arg| arg == "--burn");
+    let legacy = args.iter().any(|arg| arg == "--legacy");
+
     // Check if the key file exists at the start
-    let key_filename = "key.key";
-    let mut key_file = match File::open(key_filename) {
-        Ok(file) => file,
-        Err(_) => {
-            println!("Key file 'key.key' not found. Please ensure the key file is present in the current directory.");
-            process::exit(1);
-        }
-    };
-    let mut key = Vec::new();
-    key_file.read_to_end(&mut key).expect("Failed to read key file");
+    if !Path::new(DEFAULT_KEY).is_file() {
+        println!("Key file 'key.key' not found. Please ensure the key file is present in the current directory.");
+        process::exit(1);
+    }
 
     // Get input and output file names from the user
     println!("Enter input file name:");
@@ -26,39 +28,30 @@ fn main() {
     io::stdin().read_line(&mut output_filename).expect("Failed to read output file name");
     let output_filename = output_filename.trim();
 
-    // Open the input file
-    let mut input_file = File::open(input_filename).expect("Unable to open input file");
-    let mut input_data = Vec::new();
-    input_file.read_to_end(&mut input_data).expect("Failed to read input file");
-
-    // Ensure key length is at least as long as the input data
-    if key.len() < input_data.len() {
-        println!("Key is shorter than input data. Please provide a key of sufficient length.");
-        process::exit(1);
-    }
-
-    // Encrypt or decrypt using XOR
-    let processed_data = xor_process(&input_data, &key);
-
-    // Write the processed data to the output file
-    let mut output_file = File::create(output_filename).expect("Unable to create output file");
-    output_file.write_all(&processed_data).expect("Failed to write to output file");
-
-    // Verify output file size matches input file size
-    let input_size = metadata(input_filename).expect("Unable to read input file metadata").len();
-    let output_size = metadata(output_filename).expect("Unable to read output file metadata").len();
-    if input_size != output_size {
-        println!("Error: Output file size does not match input file size.");
-        process::exit(1);
+    // Encrypting takes a fresh, tagged range of the key from its ledger, and
+    // decrypting checks and uses the range named in the pad header. Burn mode
+    // then overwrites that range of the key with random data so it can never
+    // be used again. `--legacy` XORs the key from its start instead, for files
+    // from before the ledger, and the key must be at least as long as the input.
+    let policy = Policy {
+        pad: if legacy { PadMode::Raw } else { PadMode::Ledger },
+        key_length: KeyLength::Require,
+        output: Output::File(output_filename.into()),
+        overwrite: Overwrite::Replace,
+        burn,
+    };
+    let result = XorEngine::new(DEFAULT_KEY, policy)
+        .and_then(|engine| engine.run(Path::new(input_filename)));
+    let outcome = match result {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            println!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    if let (true, Some(header)) = (outcome.burned, outcome.header) {
+        println!("Burned key bytes {}..{}.", header.offset, header.offset + header.length);
     }
 
     println!("Operation completed successfully.");
 }
-
-// Function to XOR the input data with the key
-fn xor_process(data: &[u8], key: &[u8]) -> Vec<u8> {
-    data.iter()
-        .enumerate()
-        .map(|(i, &data_byte)| data_byte ^ key[i % key.len()])
-        .collect()
-}
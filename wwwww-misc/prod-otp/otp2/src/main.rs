@@ -1,15 +1,23 @@
-use std::fs::File;
-use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::io;
+use std::path::Path;
 use std::process;
 
-const CHUNK_SIZE: usize = 4096; // Define the chunk size to be 4KB
+use otp::{KeyLength, Output, Overwrite, PadMode, Policy, XorEngine, DEFAULT_KEY};
 
 fn main() {
-    // Open the key file
-    let key_filename = "key.key";
-    let mut key_file = File::open(key_filename).expect("Key file 'key.key' not found.");
+    // `--burn` destroys the used key bytes after encrypting or decrypting, and
+    // `--legacy` reads files from before the key ledger
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let burn = args.iter().any(|arg| arg == "--burn");
+    let legacy = args.iter().any(|arg| arg == "--legacy");
+
+    // Check if the key file exists at the start
+    if !Path::new(DEFAULT_KEY).is_file() {
+        println!("Key file 'key.key' not found. Please ensure the key file is present in the current directory.");
+        process::exit(1);
+    }
 
-    // Get input and output file names
+    // Get input and output file names from the user
     println!("Enter input file name:");
     let mut input_filename = String::new();
     io::stdin().read_line(&mut input_filename).expect("Failed to read input file name");
@@ -20,42 +28,29 @@ fn main() {
     io::stdin().read_line(&mut output_filename).expect("Failed to read output file name");
     let output_filename = output_filename.trim();
 
-    // Open input and output files
-    let input_file = File::open(input_filename).expect("Unable to open input file");
-    let mut output_file = File::create(output_filename).expect("Unable to create output file");
-
-    // Use buffered readers and writers for better performance with chunks
-    let mut input_reader = BufReader::new(input_file);
-    let mut output_writer = BufWriter::new(&mut output_file);
-
-    // Read the key into a buffer
-    let mut key = Vec::new();
-    key_file.read_to_end(&mut key).expect("Failed to read key file");
-
-    // Ensure that the key length is sufficient
-    if key.is_empty() {
-        println!("Key file is empty. Please provide a valid key.");
-        process::exit(1);
-    }
-
-    let mut buffer = vec![0u8; CHUNK_SIZE];
-    let mut key_index = 0;
-
-    loop {
-        // Read a chunk from the input file
-        let bytes_read = input_reader.read(&mut buffer).expect("Failed to read from input file");
-        if bytes_read == 0 {
-            break; // End of file reached
+    // Encrypting takes a fresh, tagged range of the key from its ledger, and
+    // decrypting checks and uses the range named in the pad header. Burn mode
+    // then overwrites that range of the key with random data so it can never
+    // be used again. `--legacy` XORs the key from its start instead, for files
+    // from before the ledger, and the key must be at least as long as the input.
+    let policy = Policy {
+        pad: if legacy { PadMode::Raw } else { PadMode::Ledger },
+        key_length: KeyLength::Require,
+        output: Output::File(output_filename.into()),
+        overwrite: Overwrite::Replace,
+        burn,
+    };
+    let result = XorEngine::new(DEFAULT_KEY, policy)
+        .and_then(|engine| engine.run(Path::new(input_filename)));
+    let outcome = match result {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            println!("Error: {}", e);
+            process::exit(1);
         }
-
-        // XOR the chunk with the key
-        for i in 0..bytes_read {
-            buffer[i] ^= key[key_index];
-            key_index = (key_index + 1) % key.len(); // Wrap around the key if necessary
-        }
-
-        // Write the processed chunk to the output file
-        output_writer.write_all(&buffer[..bytes_read]).expect("Failed to write to output file");
+    };
+    if let (true, Some(header)) = (outcome.burned, outcome.header) {
+        println!("Burned key bytes {}..{}.", header.offset, header.offset + header.length);
     }
 
     println!("Operation completed successfully.");
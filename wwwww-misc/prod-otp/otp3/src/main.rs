@@ -1,66 +1,85 @@
-use std::fs::File;
-use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process;
 
-const CHUNK_SIZE: usize = 4096; // Define the chunk size to be 4KB
+use clap::Parser;
+use otp::{KeyLength, Output, Overwrite, PadMode, Policy, XorEngine, DEFAULT_KEY};
 
-fn main() {
-    // Check if the key file exists at the start
-    let key_filename = "key.key";
-    let mut key_file = match File::open(key_filename) {
-        Ok(file) => file,
-        Err(_) => {
-            println!("Key file 'key.key' not found. Please ensure the key file is present in the current directory.");
-            process::exit(1);
-        }
-    };
-    let mut key = Vec::new();
-    key_file.read_to_end(&mut key).expect("Failed to read key file");
+/// XORs a file with `key.key` from the current directory.
+///
+/// Input and output names that are left out are asked for.
+#[derive(Parser)]
+#[command(name = "otp3", version, about)]
+struct Cli {
+    /// File to encrypt or decrypt
+    input: Option<PathBuf>,
 
-    // Get input and output file names from the user
-    println!("Enter input file name:");
-    let mut input_filename = String::new();
-    io::stdin().read_line(&mut input_filename).expect("Failed to read input file name");
-    let input_filename = input_filename.trim();
+    /// Where to write the result
+    output: Option<PathBuf>,
 
-    println!("Enter output file name:");
-    let mut output_filename = String::new();
-    io::stdin().read_line(&mut output_filename).expect("Failed to read output file name");
-    let output_filename = output_filename.trim();
+    /// Destroy the used key bytes after encrypting or decrypting
+    #[arg(long, conflicts_with = "legacy")]
+    burn: bool,
 
-    // Open input and output files
-    let input_file = File::open(input_filename).expect("Unable to open input file");
-    let output_file = File::create(output_filename).expect("Unable to create output file");
+    /// XOR from the start of the key, without a pad header, tag or ledger
+    /// (for files from before the ledger)
+    #[arg(long)]
+    legacy: bool,
+}
 
-    // Use buffered readers and writers for efficient streaming
-    let mut input_reader = BufReader::new(input_file);
-    let mut output_writer = BufWriter::new(output_file);
+fn main() {
+    let cli = Cli::parse();
+    let (burn, legacy) = (cli.burn, cli.legacy);
 
-    // Ensure that the key is not empty
-    if key.is_empty() {
-        println!("Key file is empty. Please provide a valid key.");
+    // Check if the key file exists at the start
+    if !Path::new(DEFAULT_KEY).is_file() {
+        println!("Key file 'key.key' not found. Please ensure the key file is present in the current directory.");
         process::exit(1);
     }
 
-    let mut buffer = vec![0u8; CHUNK_SIZE];
-    let mut key_index = 0;
-
-    loop {
-        // Read a chunk from the input file
-        let bytes_read = input_reader.read(&mut buffer).expect("Failed to read from input file");
-        if bytes_read == 0 {
-            break; // End of file reached
-        }
+    // Take the input and output file names from the arguments, or ask for them
+    let input_filename = match cli.input {
+        Some(input) => input,
+        None => prompt("Enter input file name:").expect("Failed to read input file name"),
+    };
+    let output_filename = match cli.output {
+        Some(output) => output,
+        None => prompt("Enter output file name:").expect("Failed to read output file name"),
+    };
 
-        // XOR the chunk with the key
-        for i in 0..bytes_read {
-            buffer[i] ^= key[key_index];
-            key_index = (key_index + 1) % key.len(); // Wrap around the key if necessary
+    // Encrypting takes a fresh, tagged range of the key from its ledger, and
+    // decrypting checks and uses the range named in the pad header. Burn mode
+    // then overwrites that range of the key with random data so it can never
+    // be used again. `--legacy` XORs the key from its start instead, for files
+    // from before the ledger, and the key must be at least as long as the input.
+    let policy = Policy {
+        pad: if legacy { PadMode::Raw } else { PadMode::Ledger },
+        key_length: KeyLength::Require,
+        output: Output::File(output_filename),
+        overwrite: Overwrite::Replace,
+        burn,
+    };
+    let result = XorEngine::new(DEFAULT_KEY, policy)
+        .and_then(|engine| engine.run(&input_filename));
+    let outcome = match result {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            println!("Error: {}", e);
+            process::exit(1);
         }
-
-        // Write the processed chunk to the output file
-        output_writer.write_all(&buffer[..bytes_read]).expect("Failed to write to output file");
+    };
+    if let (true, Some(header)) = (outcome.burned, outcome.header) {
+        println!("Burned key bytes {}..{}.", header.offset, header.offset + header.length);
     }
 
     println!("Operation completed successfully.");
 }
+
+/// Asks for a file name on standard input, for arguments that were left out.
+fn prompt(question: &str) -> io::Result<PathBuf> {
+    println!("{}", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(PathBuf::from(answer.trim()))
+}
@@ -0,0 +1,134 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const NOTE: &[u8] = b"Shipment 4471 cleared customs at 06:10.\n";
+/// The 29-byte pad header and the 16-byte tag of a ledger ciphertext.
+const HEADER_AND_TAG: usize = 29 + 16;
+/// Key bytes that key each message's Poly1305 tag.
+const MAC_KEY_LEN: u64 = 32;
+
+fn key_dir() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("key.key"), key()).unwrap();
+    fs::write(dir.path().join("note.txt"), NOTE).unwrap();
+    dir
+}
+
+fn key() -> Vec<u8> {
+    (0..4096u32).map(|i| (i * 7 + 3) as u8).collect()
+}
+
+fn otp3(dir: &Path, args: &[&str]) -> bool {
+    Command::new(env!("CARGO_BIN_EXE_otp3"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()
+        .unwrap()
+        .success()
+}
+
+/// The key offset recorded in a pad header.
+fn header_offset(message: &[u8]) -> u64 {
+    u64::from_le_bytes(message[13..21].try_into().unwrap())
+}
+
+#[test]
+fn each_encryption_takes_the_next_range_of_the_key() {
+    let dir = key_dir();
+
+    assert!(otp3(dir.path(), &["note.txt", "first.otp"]));
+    assert!(otp3(dir.path(), &["note.txt", "second.otp"]));
+    let first = fs::read(dir.path().join("first.otp")).unwrap();
+    let second = fs::read(dir.path().join("second.otp")).unwrap();
+    assert_eq!(&first[..4], b"ESKO");
+    assert_eq!(first.len(), HEADER_AND_TAG + NOTE.len());
+
+    // The first 32 key bytes name the pad and are never used
+    assert_eq!(header_offset(&first), 32);
+    assert_eq!(header_offset(&second), 32 + MAC_KEY_LEN + NOTE.len() as u64);
+    assert_ne!(first[29..], second[29..]);
+
+    for (message, back) in [("first.otp", "first.txt"), ("second.otp", "second.txt")] {
+        assert!(otp3(dir.path(), &[message, back]));
+        assert_eq!(fs::read(dir.path().join(back)).unwrap(), NOTE);
+    }
+}
+
+#[test]
+fn a_tampered_message_is_refused() {
+    let dir = key_dir();
+
+    assert!(otp3(dir.path(), &["note.txt", "note.otp"]));
+    let mut message = fs::read(dir.path().join("note.otp")).unwrap();
+    message[29] ^= 1;
+    fs::write(dir.path().join("note.otp"), message).unwrap();
+    assert!(!otp3(dir.path(), &["note.otp", "back.txt"]));
+    assert!(!dir.path().join("back.txt").exists());
+}
+
+#[test]
+fn burn_overwrites_the_range_it_used() {
+    let dir = key_dir();
+
+    assert!(otp3(dir.path(), &["note.txt", "note.otp", "--burn"]));
+    let message = fs::read(dir.path().join("note.otp")).unwrap();
+    let start = header_offset(&message) as usize;
+    let end = start + MAC_KEY_LEN as usize + NOTE.len();
+    let burned = fs::read(dir.path().join("key.key")).unwrap();
+    assert_ne!(burned[start..end], key()[start..end]);
+    assert_eq!(burned[end..], key()[end..]);
+    assert!(dir.path().join("key.key.burnlog").exists());
+}
+
+#[test]
+fn legacy_xors_from_the_start_of_the_key() {
+    let dir = key_dir();
+
+    assert!(otp3(dir.path(), &["note.txt", "note.raw", "--legacy"]));
+    let expected: Vec<u8> = NOTE
+        .iter()
+        .zip(key())
+        .map(|(byte, key)| byte ^ key)
+        .collect();
+    assert_eq!(fs::read(dir.path().join("note.raw")).unwrap(), expected);
+    assert!(!dir.path().join("key.key.ledger").exists());
+}
+
+#[test]
+fn asks_for_the_output_when_it_is_left_out() {
+    let dir = key_dir();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_otp3"))
+        .arg("note.txt")
+        .current_dir(dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    writeln!(child.stdin.take().unwrap(), "note.otp").unwrap();
+    assert!(child.wait().unwrap().success());
+    assert_eq!(
+        fs::read(dir.path().join("note.otp")).unwrap().len(),
+        HEADER_AND_TAG + NOTE.len()
+    );
+}
+
+#[test]
+fn stops_before_anything_else_without_a_key_file() {
+    let dir = key_dir();
+    fs::remove_file(dir.path().join("key.key")).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_otp3"))
+        .args(["note.txt", "note.otp"])
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Key file 'key.key' not found."));
+    assert!(!dir.path().join("note.otp").exists());
+}
@@ -0,0 +1,45 @@
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+
+use padtext::Format;
+
+// Prints pages of a key file as digit, letter, hex or base32 groups with line
+// checksums, for carrying a pad on paper
+pub fn run_booklet(args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.len() < 3 || args.len() > 5 {
+        eprintln!("Usage: booklet <key file> <output file> <digits|letters|hex|base32> [first page] [page count]");
+        return Err("Invalid number of arguments.".into());
+    }
+
+    let key_path = Path::new(&args[0]);
+    let output_filename = &args[1];
+    let format = Format::from_name(&args[2]).ok_or_else(|| {
+        format!("Unknown format '{}'. Use digits, letters, hex or base32.", args[2])
+    })?;
+
+    // Default to every whole page of the key
+    let pages = padtext::page_count(key_path)
+        .map_err(|_| format!("Key file '{}' not found.", args[0]))?;
+    let first: u64 = match args.get(3) {
+        Some(first) => first.parse().map_err(|_| "First page must be a number.")?,
+        None => 0,
+    };
+    let count: u64 = match args.get(4) {
+        Some(count) => count.parse().map_err(|_| "Page count must be a number.")?,
+        None => pages.saturating_sub(first),
+    };
+
+    // The booklet is the pad itself, so it gets the same owner-only mode as
+    // any other output, and an existing file is never replaced
+    let booklet = padtext::booklet(key_path, format, first..first.saturating_add(count))?;
+    otp::write_new(Path::new(output_filename), |file| file.write_all(booklet.as_bytes()))
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => e.to_string(),
+            _ => format!("Unable to write output file '{}': {}", output_filename, e),
+        })?;
+
+    println!("Wrote pages {}..{} to '{}'.", first, first + count, output_filename);
+
+    Ok(())
+}
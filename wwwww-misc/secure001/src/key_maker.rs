@@ -57,7 +57,7 @@ pub fn run_key_maker(args: &[String]) -> Result<(), Box<dyn std::error::Error>>
         }
     };
 
-    if total_size < 1 || total_size > 5 * 1024 * 1024 * 1024 {
+    if !(1..=5 * 1024 * 1024 * 1024).contains(&total_size) {
         eprintln!("Size must be between 1 byte and 5 GB.");
         process::exit(1);
     }
@@ -104,7 +104,7 @@ pub fn run_key_maker(args: &[String]) -> Result<(), Box<dyn std::error::Error>>
     let mut file = match OpenOptions::new()
         .write(true)
         .create_new(true)
-        .open(&output_path)
+        .open(output_path)
     {
         Ok(f) => f,
         Err(e) => {
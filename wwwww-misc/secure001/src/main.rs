@@ -1,6 +1,7 @@
 use std::env;
 use std::process;
 
+mod booklet;
 mod key_maker;
 mod otp_processor;
 
@@ -17,8 +18,9 @@ fn main() {
     let result = match mode.as_str() {
         "keygen" => key_maker::run_key_maker(&args[2..]),
         "process" => otp_processor::run_otp_processor(&args[2..]),
+        "booklet" => booklet::run_booklet(&args[2..]),
         _ => {
-            eprintln!("Invalid mode: {}. Use 'keygen' or 'process'.", mode);
+            eprintln!("Invalid mode: {}. Use 'keygen', 'process' or 'booklet'.", mode);
             process::exit(1);
         }
     };
@@ -1,87 +1,79 @@
 use std::error::Error;
-use std::fs::{File, metadata};
-use std::io::{Read, Write};
+use std::fs::metadata;
+use std::path::Path;
+
+use otp::{KeyLength, Output, Overwrite, PadMode, Policy, XorEngine};
+use padtext::Format;
 
 
 pub fn run_otp_processor(args: &[String]) -> Result<(), Box<dyn Error>> {
-    // Ensure proper usage
-    if args.len() != 3 {
-        eprintln!("Usage: process <input file> <output file> <key file>");
+    // Ensure proper usage; `--legacy` reads files from before the pad ledger,
+    // `--text` writes or reads printable digit or letter groups
+    let legacy = args.len() == 4 && args[3] == "--legacy";
+    let text = args.len() == 5 && args[3] == "--text";
+    if args.len() != 3 && !legacy && !text {
+        eprintln!("Usage: process <input file> <output file> <key file> [--legacy | --text digits|letters]");
         return Err("Invalid number of arguments.".into());
     }
+    if text {
+        return process_text(&args[0], &args[1], &args[2], &args[4]);
+    }
 
     // Get input, output, and key file names from the arguments
     let input_filename = &args[0];
     let output_filename = &args[1];
     let key_filename = &args[2];
 
-    // Open the key file
-    let mut key_file = File::open(key_filename).map_err(|_| {
-        format!(
-            "Key file '{}' not found. Please ensure the key file is present.",
-            key_filename
-        )
-    })?;
-    let mut key = Vec::new();
-    key_file
-        .read_to_end(&mut key)
-        .map_err(|_| "Failed to read key file.")?;
-
-    // Check if the key file is empty
-    if key.is_empty() {
-        return Err(format!("Key file '{}' is empty. Please provide a valid key.", key_filename).into());
-    }
-
-    // Open the input file
-    let mut input_file = File::open(input_filename).map_err(|_| {
-        format!("Unable to open input file '{}'.", input_filename)
-    })?;
-    let mut input_data = Vec::new();
-    input_file
-        .read_to_end(&mut input_data)
-        .map_err(|_| "Failed to read input file.")?;
-
     // Check if the input file is empty
-    if input_data.is_empty() {
+    let input_size = metadata(input_filename)
+        .map_err(|_| format!("Unable to open input file '{}'.", input_filename))?
+        .len();
+    if input_size == 0 {
         return Err(format!("Input file '{}' is empty. Nothing to process.", input_filename).into());
     }
 
-    // Ensure key length is at least as long as the input data
-    if key.len() < input_data.len() {
-        return Err("Key is shorter than input data. Please provide a key of sufficient length.".into());
-    }
+    // Pick the pad bytes. A pad header names the range to decrypt with, and
+    // its tag is checked first; otherwise the ledger hands out a fresh range
+    // so no byte is reused. Legacy files use the key from its start, which
+    // must be at least as long as the input.
+    let policy = Policy {
+        pad: if legacy { PadMode::Raw } else { PadMode::Ledger },
+        key_length: KeyLength::Require,
+        output: Output::File(output_filename.into()),
+        overwrite: Overwrite::Replace,
+        burn: false,
+    };
+    XorEngine::new(key_filename, policy)?.run(Path::new(input_filename))?;
 
-    // Encrypt or decrypt using XOR
-    let processed_data = xor_process(&input_data, &key);
+    println!("Operation completed successfully.");
 
-    // Write the processed data to the output file
-    let mut output_file = File::create(output_filename).map_err(|_| {
-        format!("Unable to create output file '{}'.", output_filename)
-    })?;
-    output_file
-        .write_all(&processed_data)
-        .map_err(|_| "Failed to write to output file.")?;
+    Ok(())
+}
 
-    // Verify output file size matches input file size
-    let input_size = metadata(input_filename)
-        .map_err(|_| "Unable to read input file metadata.")?
-        .len();
-    let output_size = metadata(output_filename)
-        .map_err(|_| "Unable to read output file metadata.")?
-        .len();
-    if input_size != output_size {
-        return Err("Error: Output file size does not match input file size.".into());
-    }
+// Encrypts a text file into digit or letter groups on whole booklet pages of
+// the key, or decrypts such a message after checking every line.
+fn process_text(
+    input_filename: &str,
+    output_filename: &str,
+    key_filename: &str,
+    format: &str,
+) -> Result<(), Box<dyn Error>> {
+    let format = match Format::from_name(format) {
+        Some(format @ (Format::Digits | Format::Letters)) => format,
+        _ => return Err(format!("Unknown text format '{}'. Use 'digits' or 'letters'.", format).into()),
+    };
+    let input = std::fs::read_to_string(input_filename)
+        .map_err(|_| format!("Unable to read input file '{}' as text.", input_filename))?;
+    let key_path = Path::new(key_filename);
+    let output = if padtext::is_message(&input) {
+        padtext::decrypt_text(key_path, &input)?.1 + "\n"
+    } else {
+        padtext::encrypt_text(key_path, format, &input)?.1
+    };
+    std::fs::write(output_filename, output)
+        .map_err(|_| format!("Unable to write output file '{}'.", output_filename))?;
 
     println!("Operation completed successfully.");
 
     Ok(())
 }
-
-// Function to XOR the input data with the key
-fn xor_process(data: &[u8], key: &[u8]) -> Vec<u8> {
-    data.iter()
-        .zip(key.iter())
-        .map(|(&data_byte, &key_byte)| data_byte ^ key_byte)
-        .collect()
-}
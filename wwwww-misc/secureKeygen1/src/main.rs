@@ -2,33 +2,58 @@ use rand::rngs::OsRng;
 use rand::RngCore;
 use std::fs::OpenOptions;
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::PathBuf;
+use std::process;
 
+use clap::Parser;
+
+/// Writes a key of random bytes from the operating system.
+///
+/// A size that is left out is asked for. An existing key file is never
+/// overwritten.
+#[derive(Parser)]
+#[command(name = "secure_key_generator", version, about)]
+struct Cli {
+    /// Key size, e.g. 1024B, 10MB, 1GB
+    size: Option<String>,
+
+    /// Where to write the key
+    #[arg(short, long, default_value = "key.key")]
+    output: PathBuf,
+}
 
 fn main() -> io::Result<()> {
-    // Get user input for key size
-    println!("Enter the key size (e.g., 1024B, 10MB, 1GB):");
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
+    let cli = Cli::parse();
+
+    // Take the key size from the arguments, or ask for it
+    let input = match cli.size {
+        Some(size) => size,
+        None => {
+            println!("Enter the key size (e.g., 1024B, 10MB, 1GB):");
+            let mut input = String::new();
+            io::stdin().read_line(&mut input)?;
+            input
+        }
+    };
     let input = input.trim().to_lowercase();
 
     let key_length = match parse_size(&input) {
         Some(size) if size > 0 && size <= 5 * 1024 * 1024 * 1024 => size,
         _ => {
             eprintln!("Invalid size. Please enter a value between 1 byte and 5 GB.");
-            return Ok(());
+            process::exit(1);
         }
     };
 
     // File output name and check if it already exists
-    let file_name = "key.key";
-    if Path::new(file_name).exists() {
+    let file_name = cli.output.display();
+    if cli.output.exists() {
         eprintln!("Error: The file '{}' already exists. To avoid overwriting, please delete or rename the existing file.", file_name);
-        return Ok(());
+        process::exit(1);
     }
 
     // Open the file for writing
-    let mut file = OpenOptions::new().create_new(true).write(true).open(file_name)?;
+    let mut file = OpenOptions::new().create_new(true).write(true).open(&cli.output)?;
     let chunk_size = 1024 * 1024 * 10; // 10 MB chunks
     let mut rng = OsRng;
     let mut chunk = vec![0u8; chunk_size];
@@ -49,13 +74,14 @@ fn main() -> io::Result<()> {
     Ok(())
 }
 
+// "mb" and "gb" are checked before "b", which they also end with.
 fn parse_size(input: &str) -> Option<usize> {
-    if let Some(bytes) = input.strip_suffix("b") {
-        bytes.trim().parse::<usize>().ok()
-    } else if let Some(mb) = input.strip_suffix("mb") {
+    if let Some(mb) = input.strip_suffix("mb") {
         mb.trim().parse::<usize>().ok().map(|v| v * 1024 * 1024)
     } else if let Some(gb) = input.strip_suffix("gb") {
         gb.trim().parse::<usize>().ok().map(|v| v * 1024 * 1024 * 1024)
+    } else if let Some(bytes) = input.strip_suffix("b") {
+        bytes.trim().parse::<usize>().ok()
     } else {
         input.parse::<usize>().ok()
     }
@@ -69,7 +95,7 @@ mod tests {
     fn test_parse_size() {
         assert_eq!(parse_size("1024b"), Some(1024));
         assert_eq!(parse_size("10mb"), Some(10 * 1024 * 1024));
-        assert_eq!(parse_size("1gb"), Some(1 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("1gb"), Some(1024 * 1024 * 1024));
         assert_eq!(parse_size("5gb"), Some(5 * 1024 * 1024 * 1024));
         assert_eq!(parse_size("0"), Some(0));
         assert_eq!(parse_size("invalid"), None);
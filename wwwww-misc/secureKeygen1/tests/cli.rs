@@ -0,0 +1,80 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+fn keygen(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_secure_key_generator"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn sizes_past_one_chunk_are_written_in_full() {
+    let dir = TempDir::new().unwrap();
+
+    // 11 MB is one whole 10 MB chunk and part of a second
+    assert!(keygen(dir.path(), &["11MB", "--output", "big.key"])
+        .status
+        .success());
+    let key = fs::read(dir.path().join("big.key")).unwrap();
+    assert_eq!(key.len(), 11 * 1024 * 1024);
+    // Each chunk is fresh randomness, not the first one again
+    assert_ne!(key[..1024 * 1024], key[10 * 1024 * 1024..]);
+}
+
+#[test]
+fn every_key_is_new_random_bytes() {
+    let dir = TempDir::new().unwrap();
+
+    assert!(keygen(dir.path(), &["2048B", "-o", "a.key"])
+        .status
+        .success());
+    assert!(keygen(dir.path(), &["2048b", "-o", "b.key"])
+        .status
+        .success());
+    let a = fs::read(dir.path().join("a.key")).unwrap();
+    let b = fs::read(dir.path().join("b.key")).unwrap();
+    assert_eq!((a.len(), b.len()), (2048, 2048));
+    assert_ne!(a, b);
+    assert!(a.iter().any(|&byte| byte != 0));
+}
+
+#[test]
+fn asks_for_the_size_when_it_is_left_out() {
+    let dir = TempDir::new().unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_secure_key_generator"))
+        .current_dir(dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    writeln!(child.stdin.take().unwrap(), " 1MB ").unwrap();
+    assert!(child.wait().unwrap().success());
+    assert_eq!(
+        fs::read(dir.path().join("key.key")).unwrap().len(),
+        1024 * 1024
+    );
+}
+
+#[test]
+fn never_overwrites_a_key_and_rejects_bad_sizes() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("key.key"), b"old").unwrap();
+
+    let output = keygen(dir.path(), &["16B"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+    assert_eq!(fs::read(dir.path().join("key.key")).unwrap(), b"old");
+
+    for size in ["0", "6GB", "lots"] {
+        let output = keygen(dir.path(), &[size, "-o", "new.key"]);
+        assert_eq!(output.status.code(), Some(1), "{}", size);
+        assert!(!dir.path().join("new.key").exists(), "{}", size);
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const LETTER: &[u8] = b"Dear Sam,\nthe crates leave on the 9th.\n";
+
+/// A pad longer than the letter, so only its start should be used.
+fn pad() -> Vec<u8> {
+    (0..256u32).map(|i| (i * 13 + 5) as u8).collect()
+}
+
+fn xotp(dir: &Path, args: &[&str]) -> bool {
+    Command::new(env!("CARGO_BIN_EXE_xotp"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap()
+        .success()
+}
+
+#[test]
+fn xors_with_the_start_of_the_key_and_back() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("pad.bin"), pad()).unwrap();
+    fs::write(dir.path().join("letter.txt"), LETTER).unwrap();
+
+    assert!(xotp(
+        dir.path(),
+        &["letter.txt", "letter.otp", "--key", "pad.bin"]
+    ));
+    let expected: Vec<u8> = LETTER
+        .iter()
+        .zip(pad())
+        .map(|(byte, key)| byte ^ key)
+        .collect();
+    assert_eq!(fs::read(dir.path().join("letter.otp")).unwrap(), expected);
+
+    // The key is not consumed: the same command on the output undoes it
+    assert!(xotp(
+        dir.path(),
+        &["letter.otp", "back.txt", "--key", "pad.bin"]
+    ));
+    assert_eq!(fs::read(dir.path().join("back.txt")).unwrap(), LETTER);
+    assert_eq!(fs::read(dir.path().join("pad.bin")).unwrap(), pad());
+}
+
+#[test]
+fn asks_for_each_path_in_turn() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("pad.bin"), pad()).unwrap();
+    fs::write(dir.path().join("letter.txt"), LETTER).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_xotp"))
+        .current_dir(dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Input, then key, then output
+    write!(
+        child.stdin.take().unwrap(),
+        "letter.txt\npad.bin\nletter.otp\n"
+    )
+    .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let input_at = stdout.find("input file").unwrap();
+    let key_at = stdout.find("key file").unwrap();
+    let output_at = stdout.find("output file").unwrap();
+    assert!(input_at < key_at && key_at < output_at, "{}", stdout);
+    assert_eq!(
+        fs::read(dir.path().join("letter.otp")).unwrap()[0],
+        LETTER[0] ^ pad()[0]
+    );
+}
+
+#[test]
+fn a_key_exactly_as_long_as_the_input_is_enough() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("pad.bin"), &pad()[..LETTER.len()]).unwrap();
+    fs::write(dir.path().join("letter.txt"), LETTER).unwrap();
+    fs::write(dir.path().join("letter.otp"), b"stale output").unwrap();
+
+    // An existing output is replaced
+    assert!(xotp(
+        dir.path(),
+        &["letter.txt", "letter.otp", "-k", "pad.bin"]
+    ));
+    assert_eq!(
+        fs::read(dir.path().join("letter.otp")).unwrap().len(),
+        LETTER.len()
+    );
+}
+
+#[test]
+fn refuses_a_key_shorter_than_the_input() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("pad.bin"), &pad()[..LETTER.len() - 1]).unwrap();
+    fs::write(dir.path().join("letter.txt"), LETTER).unwrap();
+
+    assert!(!xotp(
+        dir.path(),
+        &["letter.txt", "letter.otp", "-k", "pad.bin"]
+    ));
+    assert!(!dir.path().join("letter.otp").exists());
+}
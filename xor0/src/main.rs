@@ -1,25 +1,40 @@
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+
+/// XORs a file with a fixed, repeating salt and writes `<file>.enc`.
+///
+/// A file name that is left out is asked for.
+#[derive(Parser)]
+#[command(name = "enc", version, about)]
+struct Cli {
+    /// File to encrypt
+    file: Option<PathBuf>,
+
+    /// Where to write the result [default: <file>.enc]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
 
 fn main() {
     // -------------------------------------------------------------
     // Settings: Modify SALT here to change the deterministic key
     // -------------------------------------------------------------
-    const SALT: &[u8] = b"MY_SALT_FOR_XOR_KEY"; 
+    const SALT: &[u8] = b"MY_SALT_FOR_XOR_KEY";
     // -------------------------------------------------------------
 
-    // Prompt user for the file name
-    println!("Enter the file name to encrypt:");
-    let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .expect("Failed to read line from stdin");
+    let cli = Cli::parse();
 
-    // Trim to remove any trailing newline characters
-    let filename = input.trim();
+    // Take the file name from the arguments, or ask for it
+    let filename = match cli.file {
+        Some(file) => file,
+        None => prompt("Enter the file name to encrypt:").expect("Failed to read line from stdin"),
+    };
 
     // Read the contents of the file
-    let mut file = File::open(filename).expect("Failed to open input file");
+    let mut file = File::open(&filename).expect("Failed to open input file");
     let mut file_buffer = Vec::new();
     file.read_to_end(&mut file_buffer)
         .expect("Failed to read file contents");
@@ -36,18 +51,31 @@ fn main() {
         *file_byte ^= *key_byte;
     }
 
-    // Write the encrypted contents to a new file (filename.enc)
-    let output_filename = format!("{}.enc", filename);
+    // Write the encrypted contents to the output file (filename.enc by default)
+    let output_filename = cli.output.unwrap_or_else(|| {
+        let mut name = filename.into_os_string();
+        name.push(".enc");
+        name.into()
+    });
     let mut output_file = OpenOptions::new()
         .create(true)
         .write(true)
         .truncate(true)
         .open(&output_filename)
         .expect("Failed to create output file");
-    
+
     output_file
         .write_all(&file_buffer)
         .expect("Failed to write encrypted file");
 
-    println!("Encryption finished! Encrypted file: {}", output_filename);
+    println!("Encryption finished! Encrypted file: {}", output_filename.display());
+}
+
+/// Asks for a value on standard input, for arguments that were left out.
+fn prompt(question: &str) -> io::Result<PathBuf> {
+    println!("{}", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(PathBuf::from(answer.trim()))
 }
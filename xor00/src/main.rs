@@ -1,31 +1,62 @@
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process;
+
+use clap::Parser;
+use password::Source;
+
+/// XORs a file with a fixed salt and a password, repeated, and writes
+/// `<file>.enc`.
+///
+/// A file name that is left out is asked for. Without --password-file or
+/// --password-env the password is read from the terminal, without echo.
+#[derive(Parser)]
+#[command(name = "enc", version, about)]
+struct Cli {
+    /// File to encrypt
+    file: Option<PathBuf>,
+
+    /// Where to write the result [default: <file>.enc]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Read the password from the first line of this file
+    #[arg(long, group = "password_source")]
+    password_file: Option<PathBuf>,
+
+    /// Read the password from this environment variable
+    #[arg(long, group = "password_source")]
+    password_env: Option<String>,
+}
 
 fn main() {
     // -------------------------------------------------------------
     // Settings: Modify SALT here to change the base for key derivation
     // -------------------------------------------------------------
-    const SALT: &[u8] = b"MY_SALT_FOR_XOR_KEY"; 
+    const SALT: &[u8] = b"MY_SALT_FOR_XOR_KEY";
     // -------------------------------------------------------------
 
-    // 1) Ask the user for the file name
-    println!("Enter the file name to encrypt:");
-    let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .expect("Failed to read line from stdin");
-    let filename = input.trim();
-
-    // 2) Ask the user for a password
-    println!("Enter the password:");
-    let mut pwd_input = String::new();
-    io::stdin()
-        .read_line(&mut pwd_input)
-        .expect("Failed to read password from stdin");
-    let password = pwd_input.trim();
+    let cli = Cli::parse();
+
+    // 1) Take the file name from the arguments, or ask for it
+    let filename = match &cli.file {
+        Some(file) => file.clone(),
+        None => prompt("Enter the file name to encrypt:").expect("Failed to read line from stdin"),
+    };
+
+    // 2) Get the password
+    let source = Source::new(cli.password_file.as_deref(), cli.password_env.as_deref(), None);
+    let password = source.read("Enter the password: ").unwrap_or_else(|e| {
+        eprintln!("Failed to read the password: {}", e);
+        process::exit(1);
+    });
+    // Surrounding whitespace is dropped, as the old prompt did, so files it
+    // wrote still decrypt.
+    let password = password.trim();
 
     // 3) Read the file contents into a buffer
-    let mut file = File::open(filename).expect("Failed to open input file");
+    let mut file = File::open(&filename).expect("Failed to open input file");
     let mut file_buffer = Vec::new();
     file.read_to_end(&mut file_buffer)
         .expect("Failed to read file contents");
@@ -47,18 +78,31 @@ fn main() {
         *file_byte ^= *key_byte;
     }
 
-    // 7) Write the encrypted contents to a new file: <filename>.enc
-    let output_filename = format!("{}.enc", filename);
+    // 7) Write the encrypted contents to the output file: <filename>.enc by default
+    let output_filename = cli.output.unwrap_or_else(|| {
+        let mut name = filename.into_os_string();
+        name.push(".enc");
+        name.into()
+    });
     let mut output_file = OpenOptions::new()
         .create(true)
         .write(true)
         .truncate(true)
         .open(&output_filename)
         .expect("Failed to create output file");
-    
+
     output_file
         .write_all(&file_buffer)
         .expect("Failed to write encrypted file");
 
-    println!("Encryption finished! Encrypted file: {}", output_filename);
+    println!("Encryption finished! Encrypted file: {}", output_filename.display());
+}
+
+/// Asks for a value on standard input, for arguments that were left out.
+fn prompt(question: &str) -> io::Result<PathBuf> {
+    println!("{}", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(PathBuf::from(answer.trim()))
 }
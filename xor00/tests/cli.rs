@@ -0,0 +1,98 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const SALT: &[u8] = b"MY_SALT_FOR_XOR_KEY";
+
+/// The key the tool repeats: the salt, then the password.
+fn key_stream(password: &str, len: usize) -> Vec<u8> {
+    SALT.iter()
+        .chain(password.as_bytes())
+        .cycle()
+        .take(len)
+        .copied()
+        .collect()
+}
+
+#[test]
+fn zeros_show_the_salt_followed_by_the_password() {
+    let dir = TempDir::new().unwrap();
+    let input = dir.path().join("zeros.bin");
+    fs::write(&input, [0u8; 64]).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_enc"))
+        .arg(&input)
+        .args(["--password-env", "XOR00_TEST_PASSWORD"])
+        .env("XOR00_TEST_PASSWORD", "  swordfish\t")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    // Surrounding whitespace is not part of the key
+    assert_eq!(
+        fs::read(dir.path().join("zeros.bin.enc")).unwrap(),
+        key_stream("swordfish", 64)
+    );
+}
+
+#[test]
+fn only_the_first_line_of_a_password_file_is_used() {
+    let dir = TempDir::new().unwrap();
+    let input = dir.path().join("ledger.csv");
+    let output = dir.path().join("ledger.csv.xor");
+    let text = b"date,amount\n2024-03-01,-40.00\n";
+    fs::write(&input, text).unwrap();
+    fs::write(
+        dir.path().join("password.txt"),
+        "opensesame\r\nnot this one\n",
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_enc"))
+        .arg("--password-file")
+        .arg(dir.path().join("password.txt"))
+        .arg("-o")
+        .arg(&output)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    // The file name is left out, so it is asked for
+    writeln!(child.stdin.take().unwrap(), "{}", input.display()).unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let key = key_stream("opensesame", text.len());
+    let expected: Vec<u8> = text.iter().zip(key).map(|(byte, key)| byte ^ key).collect();
+    assert_eq!(fs::read(output).unwrap(), expected);
+}
+
+#[test]
+fn an_unset_password_variable_is_an_error() {
+    let dir = TempDir::new().unwrap();
+    let input = dir.path().join("zeros.bin");
+    fs::write(&input, [0u8; 8]).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_enc"))
+        .arg(&input)
+        .args(["--password-env", "XOR00_TEST_UNSET"])
+        .env_remove("XOR00_TEST_UNSET")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Environment variable XOR00_TEST_UNSET is not set."));
+    assert!(!dir.path().join("zeros.bin.enc").exists());
+}
+
+#[test]
+fn refuses_two_password_sources() {
+    let status = Command::new(env!("CARGO_BIN_EXE_enc"))
+        .args(["notes.txt", "--password-env", "A", "--password-file", "B"])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(2));
+}
@@ -1,22 +1,53 @@
 use sha2::{Digest, Sha256};
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process;
+
+use clap::Parser;
+use password::Source;
+
+/// XORs a file in place with a key expanded from a password. Running it
+/// again with the same password decrypts.
+///
+/// A file name that is left out is asked for. Without --password-file or
+/// --password-env the password is read from the terminal, without echo.
+#[derive(Parser)]
+#[command(name = "xor00", version, about)]
+struct Cli {
+    /// File to encrypt or decrypt in place
+    file: Option<PathBuf>,
+
+    /// Read the password from the first line of this file
+    #[arg(long, group = "password_source")]
+    password_file: Option<PathBuf>,
+
+    /// Read the password from this environment variable
+    #[arg(long, group = "password_source")]
+    password_env: Option<String>,
+}
 
 fn main() {
-    // 1) Ask the user for the file name
-    println!("Enter the file name to encrypt/decrypt in-place:");
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).expect("Failed to read file name");
-    let filename = input.trim();
-
-    // 2) Ask the user for the password
-    println!("Enter your password:");
-    let mut pwd_input = String::new();
-    io::stdin().read_line(&mut pwd_input).expect("Failed to read password");
-    let password = pwd_input.trim();
+    let cli = Cli::parse();
+
+    // 1) Take the file name from the arguments, or ask for it
+    let filename = match &cli.file {
+        Some(file) => file.clone(),
+        None => prompt("Enter the file name to encrypt/decrypt in-place:").expect("Failed to read file name"),
+    };
+
+    // 2) Get the password
+    let source = Source::new(cli.password_file.as_deref(), cli.password_env.as_deref(), None);
+    let password = source.read("Enter your password: ").unwrap_or_else(|e| {
+        eprintln!("Failed to read the password: {}", e);
+        process::exit(1);
+    });
+    // Surrounding whitespace is dropped, as the old prompt did, so files it
+    // wrote still decrypt.
+    let password = password.trim();
 
     // 3) Read the file contents into a buffer
-    let mut file = File::open(filename).expect("Failed to open file");
+    let mut file = File::open(&filename).expect("Failed to open file");
     let mut file_buffer = Vec::new();
     file.read_to_end(&mut file_buffer).expect("Failed to read file");
 
@@ -32,7 +63,7 @@ fn main() {
     let mut output_file = OpenOptions::new()
         .write(true)
         .truncate(true)
-        .open(filename)
+        .open(&filename)
         .expect("Failed to open file for writing");
 
     output_file
@@ -43,6 +74,15 @@ fn main() {
     println!("Use the **same password** again on this file to decrypt it.");
 }
 
+/// Asks for a value on standard input, for arguments that were left out.
+fn prompt(question: &str) -> io::Result<PathBuf> {
+    println!("{}", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(PathBuf::from(answer.trim()))
+}
+
 /// Expand the user’s password into a key of `length` bytes by hashing 
 /// repeatedly with a counter. This helps avoid obvious repetition 
 /// if the file is larger than 32 bytes. 
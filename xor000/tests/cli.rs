@@ -0,0 +1,102 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+/// Block `n` of the key: SHA-256 of the password and a little-endian counter.
+fn key_block(password: &str, n: u64) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(password.as_bytes());
+    hasher.update(n.to_le_bytes());
+    hasher.finalize().to_vec()
+}
+
+fn run_with_env(file: &Path, password: &str) -> bool {
+    Command::new(env!("CARGO_BIN_EXE_xor00"))
+        .arg(file)
+        .args(["--password-env", "XOR000_TEST_PASSWORD"])
+        .env("XOR000_TEST_PASSWORD", password)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()
+        .unwrap()
+        .success()
+}
+
+#[test]
+fn zeros_are_replaced_by_the_hash_chain() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("blank.img");
+    fs::write(&file, [0u8; 80]).unwrap();
+
+    assert!(run_with_env(&file, "correct horse"));
+
+    // 80 bytes take two whole blocks and half of a third, with no repetition
+    let key = fs::read(&file).unwrap();
+    assert_eq!(key.len(), 80);
+    assert_eq!(key[..32], key_block("correct horse", 0));
+    assert_eq!(key[32..64], key_block("correct horse", 1));
+    assert_eq!(key[64..], key_block("correct horse", 2)[..16]);
+    assert_ne!(key[..32], key[32..64]);
+}
+
+#[test]
+fn a_second_run_with_the_same_password_restores_the_file() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("draft.md");
+    let text = b"# Draft\n\nSecond paragraph, still rough.\n";
+    fs::write(&file, text).unwrap();
+
+    assert!(run_with_env(&file, "tr0ub4dor"));
+    assert_ne!(fs::read(&file).unwrap(), text);
+    assert!(run_with_env(&file, "tr0ub4dor"));
+    assert_eq!(fs::read(&file).unwrap(), text);
+
+    // A different password scrambles it further instead
+    assert!(run_with_env(&file, "tr0ub4dor"));
+    assert!(run_with_env(&file, "Tr0ub4dor"));
+    assert_ne!(fs::read(&file).unwrap(), text);
+}
+
+#[test]
+fn asks_for_the_file_and_trims_the_password_file_line() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("blank.img");
+    fs::write(&file, [0u8; 32]).unwrap();
+    // Surrounding whitespace is dropped, as the old prompt did
+    fs::write(
+        dir.path().join("password.txt"),
+        "  correct horse \nsecond line\n",
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_xor00"))
+        .arg("--password-file")
+        .arg(dir.path().join("password.txt"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    writeln!(child.stdin.take().unwrap(), "{}", file.display()).unwrap();
+    assert!(child.wait().unwrap().success());
+    assert_eq!(fs::read(&file).unwrap(), key_block("correct horse", 0));
+}
+
+#[test]
+fn leaves_the_file_alone_when_the_password_variable_is_unset() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("draft.md");
+    fs::write(&file, b"untouched").unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_xor00"))
+        .arg(&file)
+        .args(["--password-env", "XOR000_TEST_UNSET"])
+        .env_remove("XOR000_TEST_UNSET")
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(1));
+    assert_eq!(fs::read(&file).unwrap(), b"untouched");
+}
@@ -1,25 +1,56 @@
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process;
+
+use clap::Parser;
+use password::Source;
+
+/// XORs a file in place with a password, repeated. Running it again with the
+/// same password reverts it.
+///
+/// A file name that is left out is asked for. Without --password-file or
+/// --password-env the password is read from the terminal, without echo.
+#[derive(Parser)]
+#[command(name = "xor0000", version, about)]
+struct Cli {
+    /// File to encrypt or decrypt in place
+    file: Option<PathBuf>,
+
+    /// Read the password from the first line of this file
+    #[arg(long, group = "password_source")]
+    password_file: Option<PathBuf>,
+
+    /// Read the password from this environment variable
+    #[arg(long, group = "password_source")]
+    password_env: Option<String>,
+}
 
 fn main() {
+    let cli = Cli::parse();
+
     // 1) Get the file name
-    println!("Enter the file name to encrypt/decrypt in-place:");
-    let mut input_filename = String::new();
-    io::stdin()
-        .read_line(&mut input_filename)
-        .expect("Failed to read file name");
-    let filename = input_filename.trim();
+    let filename = match &cli.file {
+        Some(file) => file.clone(),
+        None => prompt("Enter the file name to encrypt/decrypt in-place:").expect("Failed to read file name"),
+    };
 
     // 2) Get the password (entire password is used as key)
-    println!("Enter your password (entire password is the key):");
-    let mut password_input = String::new();
-    io::stdin()
-        .read_line(&mut password_input)
-        .expect("Failed to read password");
-    let password = password_input.trim(); // Trim whitespace/newline
+    let source = Source::new(cli.password_file.as_deref(), cli.password_env.as_deref(), None);
+    let password = source.read("Enter your password (entire password is the key): ").unwrap_or_else(|e| {
+        eprintln!("Failed to read the password: {}", e);
+        process::exit(1);
+    });
+    // Surrounding whitespace is dropped, as the old prompt did, so files it
+    // wrote still decrypt.
+    let password = password.trim();
+    if password.is_empty() {
+        eprintln!("The password is empty.");
+        process::exit(1);
+    }
 
     // 3) Read the file into a buffer
-    let mut file = File::open(filename).expect("Failed to open file");
+    let mut file = File::open(&filename).expect("Failed to open file");
     let mut file_buffer = Vec::new();
     file.read_to_end(&mut file_buffer)
         .expect("Failed to read file contents");
@@ -42,7 +73,7 @@ fn main() {
     let mut output_file = OpenOptions::new()
         .write(true)
         .truncate(true)
-        .open(filename)
+        .open(&filename)
         .expect("Failed to open file for writing");
     output_file
         .write_all(&file_buffer)
@@ -51,3 +82,12 @@ fn main() {
     println!("Done! The file has been overwritten in-place.");
     println!("Use the SAME password again on this file to revert it.");
 }
+
+/// Asks for a value on standard input, for arguments that were left out.
+fn prompt(question: &str) -> io::Result<PathBuf> {
+    println!("{}", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(PathBuf::from(answer.trim()))
+}
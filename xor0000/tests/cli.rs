@@ -0,0 +1,74 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+fn run_with_env(file: &Path, password: &str) -> bool {
+    Command::new(env!("CARGO_BIN_EXE_xor0000"))
+        .arg(file)
+        .args(["--password-env", "XOR0000_TEST_PASSWORD"])
+        .env("XOR0000_TEST_PASSWORD", password)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap()
+        .success()
+}
+
+#[test]
+fn the_password_itself_is_the_repeating_key() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("zeros.bin");
+    fs::write(&file, [0u8; 20]).unwrap();
+
+    assert!(run_with_env(&file, "swordfish"));
+    assert_eq!(fs::read(&file).unwrap(), b"swordfishswordfishsw");
+    assert!(run_with_env(&file, "swordfish"));
+    assert_eq!(fs::read(&file).unwrap(), [0u8; 20]);
+}
+
+#[test]
+fn a_password_longer_than_the_file_is_cut_short() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("pin.txt");
+    fs::write(&file, b"1234").unwrap();
+
+    assert!(run_with_env(&file, "a much longer passphrase"));
+    let expected: Vec<u8> = b"1234"
+        .iter()
+        .zip(b"a mu")
+        .map(|(byte, key)| byte ^ key)
+        .collect();
+    assert_eq!(fs::read(&file).unwrap(), expected);
+}
+
+#[test]
+fn asks_for_the_file_and_reads_the_password_file() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("zeros.bin");
+    fs::write(&file, [0u8; 6]).unwrap();
+    fs::write(dir.path().join("password.txt"), "key\nnot the key\n").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_xor0000"))
+        .arg("--password-file")
+        .arg(dir.path().join("password.txt"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    writeln!(child.stdin.take().unwrap(), "{}", file.display()).unwrap();
+    assert!(child.wait().unwrap().success());
+    assert_eq!(fs::read(&file).unwrap(), b"keykey");
+}
+
+#[test]
+fn refuses_a_password_that_is_only_whitespace() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("pin.txt");
+    fs::write(&file, b"1234").unwrap();
+
+    assert!(!run_with_env(&file, " \t "));
+    assert_eq!(fs::read(&file).unwrap(), b"1234");
+}
@@ -1,70 +1,75 @@
 use std::{
     env,
-    fs::{self, OpenOptions},
-    io::{self, Write},
+    fs::{self, File},
+    io::Read,
     path::Path,
-    process,
 };
 
+use diag::{IoContext, LogOptions, ToolError, LOG_USAGE};
+use otp::{KeyLength, Output, Overwrite, PadMode, Policy, XorEngine, DEFAULT_KEY};
+use padledger::{Ledger, PadHeader, HEADER_LEN, MAC_KEY_LEN, PAD_ID_SOURCE_LEN};
+use tracing::{debug, info};
+
 fn main() {
-    if let Err(e) = run() {
-        // If an error occurs, log it to error.txt and exit with a failure code
-        log_error(&format!("Error: {}", e));
-        eprintln!("An error occurred. Check 'error.txt' for details.");
-        process::exit(1);
+    let (logging, args) =
+        LogOptions::from_args(env::args().collect()).unwrap_or_else(|e| diag::fail(e));
+    if let Err(e) = logging.init() {
+        diag::fail(e);
+    }
+    if let Err(e) = run(&args) {
+        // If an error occurs, report it, log it and exit with its code
+        diag::fail(e);
     }
 }
 
-fn run() -> io::Result<()> {
+fn run(args: &[String]) -> Result<(), ToolError> {
     // Read command-line arguments
-    let args: Vec<String> = env::args().collect();
     if args.len() < 2 {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Usage: <app> <file_to_encrypt>",
-        ));
+        return Err(ToolError::usage(format!(
+            "Usage: <app> <file_to_encrypt> [--legacy] {}",
+            LOG_USAGE
+        )));
     }
     let filename = &args[1];
+    // `--legacy` XORs from the start of the key, for files from before the pad ledger
+    let legacy = args.get(2).is_some_and(|a| a == "--legacy");
 
     // Hard-coded key filename (in the same directory)
-    let key_filename = "key.key";
+    let key_filename = DEFAULT_KEY;
 
     // Check if the key file exists
     if !Path::new(key_filename).exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Key file '{}' does not exist.", key_filename),
-        ));
+        return Err(ToolError::KeyMissing {
+            path: key_filename.into(),
+        });
     }
+    check_key_length(Path::new(key_filename), Path::new(filename), legacy)?;
 
-    // 1. Read the file to be encrypted
-    let mut file_data = fs::read(filename)?;
-
-    // 2. Read the key file into memory
-    let key_data = fs::read(key_filename)?;
-
-    // 3. Ensure the key is large enough
-    if key_data.len() < file_data.len() {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!(
-                "Key file '{}' is smaller than '{}'.",
-                key_filename, filename
-            ),
-        ));
-    }
-
-    // 4. Perform XOR in-place
-    for (i, byte) in file_data.iter_mut().enumerate() {
-        *byte ^= key_data[i];
-    }
+    info!(legacy, "xoring file");
+    debug!(file = %filename, key = %key_filename, "paths");
 
-    // 5. Write encrypted data to a temporary file (to preserve data integrity)
-    let temp_filename = format!("{}.tmp", filename);
-    fs::write(&temp_filename, &file_data)?;
-
-    // 6. Atomically replace the original file with the encrypted file
-    fs::rename(&temp_filename, filename)?;
+    // Pick the key bytes. Legacy files use the key from its start, which must
+    // cover the whole file. Otherwise a pad header names the range to decrypt
+    // with, and its tag is checked before anything is written; without one
+    // the ledger hands out a fresh range so no byte is reused. The result goes
+    // to an fsync'd temporary file that is renamed over the original.
+    let policy = Policy {
+        pad: if legacy { PadMode::Raw } else { PadMode::Ledger },
+        key_length: KeyLength::Require,
+        output: Output::InPlace,
+        overwrite: Overwrite::Replace,
+        burn: false,
+    };
+    let engine = XorEngine::new(key_filename, policy).path_context(key_filename)?;
+    let outcome = engine
+        .run(Path::new(filename))
+        .map_err(|e| match e.kind() {
+            // The engine reports a pad header or tag that does not check out
+            // as invalid data
+            std::io::ErrorKind::InvalidData => ToolError::integrity(e.to_string()),
+            _ => ToolError::io(filename, e),
+        })?;
+    info!(action = ?outcome.action, "done");
 
     println!(
         "Successfully XOR-encrypted '{}' with key '{}'.",
@@ -74,13 +79,44 @@ fn run() -> io::Result<()> {
     Ok(())
 }
 
-/// Logs the given error message to 'error.txt', appending if the file already exists.
-fn log_error(msg: &str) {
-    if let Ok(mut f) = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("error.txt")
-    {
-        let _ = writeln!(f, "{}", msg);
+/// Refuses a key that cannot cover the file before the engine starts: from
+/// its first byte for legacy files, or the ledger's unused bytes plus the
+/// tag key for a file to encrypt.
+fn check_key_length(key: &Path, input: &Path, legacy: bool) -> Result<(), ToolError> {
+    let key_len = fs::metadata(key).path_context(key)?.len();
+    let input_len = fs::metadata(input).path_context(input)?.len();
+    let too_short = |needed, available| ToolError::KeyTooShort {
+        path: key.to_path_buf(),
+        needed,
+        available,
+    };
+
+    if legacy {
+        return if key_len < input_len {
+            Err(too_short(input_len, key_len))
+        } else {
+            Ok(())
+        };
+    }
+    if key_len < PAD_ID_SOURCE_LEN {
+        return Err(too_short(PAD_ID_SOURCE_LEN, key_len));
+    }
+
+    // A file with a pad header is decrypted from the range it names
+    let mut head = [0u8; HEADER_LEN];
+    let has_header = input_len >= HEADER_LEN as u64
+        && File::open(input)
+            .and_then(|mut file| file.read_exact(&mut head))
+            .is_ok()
+        && PadHeader::parse(&head).is_some();
+    if has_header {
+        return Ok(());
+    }
+    // Other ledger problems are left for the engine to report
+    match Ledger::open(key) {
+        Ok(ledger) if ledger.remaining() < input_len + MAC_KEY_LEN => {
+            Err(too_short(input_len + MAC_KEY_LEN, ledger.remaining()))
+        }
+        _ => Ok(()),
     }
 }
@@ -3,252 +3,405 @@ use std::{
     fs::{self, File, OpenOptions},
     io::{self, Write},
     path::Path,
-    process,
 };
 
+use diag::{IoContext, LogOptions, ToolError, LOG_USAGE};
+use hkdf::Hkdf;
 use hmac::{Hmac, Mac};
 use rand::rngs::OsRng;
 use rand::RngCore;
 use sha2::Sha256;
+use tracing::{debug, info};
 
 // We'll use HMAC-SHA256 for integrity checks.
 type HmacSha256 = Hmac<Sha256>;
 
+/// Magic bytes at the start of an encrypted file.
+const MAGIC: &[u8; 4] = b"XOR2";
+/// Current container version.
+const VERSION: u8 = 1;
+/// Length of the random per-file id the MAC subkey is derived from.
+const FILE_ID_LEN: usize = 16;
+/// Magic + version + file id + plaintext length (u64 LE).
+const HEADER_LEN: usize = 4 + 1 + FILE_ID_LEN + 8;
+/// Length of the HMAC-SHA256 tag in the trailer.
+const TAG_LEN: usize = 32;
+/// HKDF `info` for the per-file MAC subkey.
+const MAC_INFO: &[u8] = b"xor2 file mac v1";
+
 fn main() {
-    if let Err(e) = run() {
-        log_error(&format!("Error: {}", e));
-        eprintln!("An error occurred. Check 'error.txt' for details.");
-        process::exit(1);
+    let (logging, args) =
+        LogOptions::from_args(env::args().collect()).unwrap_or_else(|e| diag::fail(e));
+    if let Err(e) = logging.init() {
+        diag::fail(e);
+    }
+    if let Err(e) = run(&args) {
+        diag::fail(e);
     }
 }
 
 /// Parses the arguments: `e file.txt` (encrypt) or `d file.txt` (decrypt).
-fn run() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
+fn run(args: &[String]) -> Result<(), ToolError> {
     if args.len() < 3 {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Usage: <app> <e|d> <file>",
-        ));
+        return Err(ToolError::usage(format!(
+            "Usage: <app> <e|d> <file> {}",
+            LOG_USAGE
+        )));
     }
 
     let mode = &args[1];     // "e" or "d"
     let filename = &args[2]; // e.g. "file.txt"
+    info!(mode = %mode, "starting");
+    debug!(file = %filename, "input");
 
-    // 1. Ensure `mac.key` exists; if not, create one with random data.
-    create_mac_key_if_missing()?;
-
-    // 2. Proceed based on user command.
     match mode.as_str() {
-        "e" => encrypt_and_verify(filename),
+        "e" => {
+            // Ensure `mac.key` exists; if not, create one with random data.
+            create_mac_key_if_missing()?;
+            encrypt_and_verify(filename)
+        }
         "d" => decrypt_and_verify(filename),
-        _ => Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
+        _ => Err(ToolError::usage(
             "Invalid mode. Use 'e' for encrypt or 'd' for decrypt.",
         )),
     }
 }
 
-/// Creates a new 32-byte `mac.key` if it does not exist (demo only).
-fn create_mac_key_if_missing() -> io::Result<()> {
+/// Creates a new 32-byte `mac.key` if it does not exist, readable only by
+/// the owner on Unix.
+fn create_mac_key_if_missing() -> Result<(), ToolError> {
     let path = Path::new("mac.key");
     if !path.exists() {
-        let mut file = File::create(path)?;
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path).path_context(path)?;
         let mut random_bytes = [0u8; 32];
         // Fill an array with secure random bytes from the OS.
         OsRng.fill_bytes(&mut random_bytes);
-        file.write_all(&random_bytes)?;
-        println!("Created a new 32-byte mac.key (demo only).");
+        file.write_all(&random_bytes).path_context(path)?;
+        file.sync_all().path_context(path)?;
+        info!("created mac.key");
+        println!("Created a new 32-byte mac.key. Keep it with xor.key; files cannot be checked without it.");
     }
     Ok(())
 }
 
-/// Encrypts the file, writes a .mac file, then verifies the result automatically.
-fn encrypt_and_verify(filename: &str) -> io::Result<()> {
-    // 1. Read plaintext from disk.
-    let mut plaintext = fs::read(filename)?;
+/// Derives the MAC key for one file from `mac.key` and the file's id, so no
+/// two files are authenticated under the same key.
+fn file_mac(mac_key: &[u8], file_id: &[u8]) -> Result<HmacSha256, ToolError> {
+    let hkdf = Hkdf::<Sha256>::new(Some(file_id), mac_key);
+    let mut subkey = [0u8; 32];
+    hkdf.expand(MAC_INFO, &mut subkey)
+        .map_err(|_| ToolError::integrity("Invalid MAC key"))?;
+    HmacSha256::new_from_slice(&subkey).map_err(|_| ToolError::integrity("Invalid MAC key"))
+}
 
-    // 2. Load keys: XOR key + MAC key.
-    let xor_key = load_key("xor.key")?;
-    let mac_key = load_key("mac.key")?;
+/// XORs `data` in place with the start of the XOR key.
+fn xor_with_key(data: &mut [u8], xor_key: &[u8]) -> Result<(), ToolError> {
+    if xor_key.len() < data.len() {
+        return Err(ToolError::KeyTooShort {
+            path: "xor.key".into(),
+            needed: data.len() as u64,
+            available: xor_key.len() as u64,
+        });
+    }
+    for (byte, key) in data.iter_mut().zip(xor_key) {
+        *byte ^= key;
+    }
+    Ok(())
+}
+
+/// Builds the container: header, ciphertext, then the tag over both
+/// (encrypt-then-MAC).
+fn seal(plaintext: &[u8], xor_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, ToolError> {
+    let mut file_id = [0u8; FILE_ID_LEN];
+    OsRng.fill_bytes(&mut file_id);
+
+    let mut contents = Vec::with_capacity(HEADER_LEN + plaintext.len() + TAG_LEN);
+    contents.extend_from_slice(MAGIC);
+    contents.push(VERSION);
+    contents.extend_from_slice(&file_id);
+    contents.extend_from_slice(&(plaintext.len() as u64).to_le_bytes());
+    contents.extend_from_slice(plaintext);
+    xor_with_key(&mut contents[HEADER_LEN..], xor_key)?;
+
+    let mut mac = file_mac(mac_key, &file_id)?;
+    mac.update(&contents);
+    let tag = mac.finalize().into_bytes();
+    contents.extend_from_slice(&tag);
+    Ok(contents)
+}
+
+/// Whether `contents` is a container (as opposed to a legacy file with a
+/// `.mac` sidecar).
+fn is_container(contents: &[u8]) -> bool {
+    contents.len() >= HEADER_LEN + TAG_LEN && &contents[..4] == MAGIC
+}
 
-    // 3. Check XOR key length.
-    if xor_key.len() < plaintext.len() {
+/// Checks the container's tag in constant time, before anything is
+/// decrypted, and returns the plaintext.
+fn open(contents: &[u8], xor_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, ToolError> {
+    if contents[4] != VERSION {
         return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "xor.key is smaller than the file to encrypt.",
+            io::ErrorKind::InvalidData,
+            format!("Unsupported xor2 file version {}.", contents[4]),
+        )
+        .into());
+    }
+    let (authenticated, tag) = contents.split_at(contents.len() - TAG_LEN);
+    let file_id = &authenticated[5..5 + FILE_ID_LEN];
+    let mut mac = file_mac(mac_key, file_id)?;
+    mac.update(authenticated);
+    mac.verify_slice(tag).map_err(|_| {
+        ToolError::integrity("Integrity check failed. The file may be tampered with.")
+    })?;
+
+    let mut length = [0u8; 8];
+    length.copy_from_slice(&authenticated[5 + FILE_ID_LEN..HEADER_LEN]);
+    let mut plaintext = authenticated[HEADER_LEN..].to_vec();
+    if u64::from_le_bytes(length) != plaintext.len() as u64 {
+        return Err(ToolError::integrity(
+            "The file length does not match its header.",
         ));
     }
+    xor_with_key(&mut plaintext, xor_key)?;
+    Ok(plaintext)
+}
 
-    // 4. Compute HMAC over (plaintext || length) => tag as Vec<u8>.
-    let mut mac = HmacSha256::new_from_slice(&mac_key)
-        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid MAC key"))?;
-    mac.update(&plaintext);
-    mac.update(&(plaintext.len() as u64).to_le_bytes());
-    let tag = mac.finalize().into_bytes().to_vec(); // convert GenericArray -> Vec<u8>
+/// Replaces `filename` with `contents` through a temporary file.
+fn replace(filename: &str, contents: &[u8]) -> Result<(), ToolError> {
+    let temp_filename = format!("{}.tmp", filename);
+    let mut temp = File::create(&temp_filename).path_context(&temp_filename)?;
+    temp.write_all(contents).path_context(&temp_filename)?;
+    temp.sync_all().path_context(&temp_filename)?;
+    fs::rename(&temp_filename, filename).path_context(filename)
+}
 
-    // 5. Write the HMAC to a separate file: "file.txt.mac"
-    let mac_filename = format!("{}.mac", filename);
-    fs::write(&mac_filename, &tag)?;
+/// Encrypts the file in place, with the tag in a trailer, then verifies the
+/// result automatically.
+fn encrypt_and_verify(filename: &str) -> Result<(), ToolError> {
+    // 1. Read plaintext from disk.
+    let plaintext = fs::read(filename).path_context(filename)?;
 
-    // 6. XOR the plaintext in-place.
-    for (i, byte) in plaintext.iter_mut().enumerate() {
-        *byte ^= xor_key[i];
-    }
+    // 2. Load keys: XOR key + MAC key.
+    let xor_key = load_key("xor.key")?;
+    let mac_key = load_key("mac.key")?;
 
-    // 7. Overwrite the original file with the ciphertext (temp file for safety).
-    let temp_filename = format!("{}.tmp", filename);
-    fs::write(&temp_filename, &plaintext)?;
-    fs::rename(&temp_filename, filename)?;
+    // 3. Encrypt, then MAC the header and ciphertext.
+    let contents = seal(&plaintext, &xor_key, &mac_key)?;
+
+    // 4. Overwrite the original file with the container (temp file for safety).
+    replace(filename, &contents)?;
 
-    println!("Encrypted '{}' and wrote MAC to '{}'.", filename, mac_filename);
+    println!("Encrypted '{}'.", filename);
+    info!(bytes = plaintext.len(), "encrypted");
 
-    // 8. Automatically verify the newly encrypted file.
-    verify_ciphertext(filename, &xor_key, &mac_key)?;
+    // 5. Automatically verify the newly encrypted file.
+    let written = fs::read(filename).path_context(filename)?;
+    if !is_container(&written) || open(&written, &xor_key, &mac_key)? != plaintext {
+        return Err(ToolError::integrity(
+            "Ciphertext integrity check failed right after encryption.",
+        ));
+    }
+    info!("verified after encryption");
 
     println!("Automatic verification after encryption succeeded.");
     Ok(())
 }
 
-/// Decrypts the file, verifies via .mac, then automatically checks the resulting plaintext.
-fn decrypt_and_verify(filename: &str) -> io::Result<()> {
-    // 1. Read ciphertext.
-    let mut ciphertext = fs::read(filename)?;
+/// Decrypts the file in place after checking its tag. Files from before the
+/// container are checked against their `.mac` sidecar instead.
+fn decrypt_and_verify(filename: &str) -> Result<(), ToolError> {
+    // 1. Read the file.
+    let contents = fs::read(filename).path_context(filename)?;
 
     // 2. Load keys: XOR key + MAC key.
     let xor_key = load_key("xor.key")?;
     let mac_key = load_key("mac.key")?;
 
-    // 3. Read stored MAC from "file.txt.mac".
+    decrypt_contents(filename, contents, &xor_key, &mac_key)
+}
+
+/// Decrypts `contents`, read from `filename`, back into that file.
+fn decrypt_contents(
+    filename: &str,
+    contents: Vec<u8>,
+    xor_key: &[u8],
+    mac_key: &[u8],
+) -> Result<(), ToolError> {
+    if !is_container(&contents) {
+        return decrypt_legacy(filename, contents, xor_key, mac_key);
+    }
+
+    // 3. Check the tag, then decrypt. A legacy ciphertext can start with the
+    //    magic bytes by chance, so if its sidecar is there it gets a say.
+    let plaintext = match open(&contents, xor_key, mac_key) {
+        Ok(plaintext) => plaintext,
+        Err(e) if Path::new(&format!("{}.mac", filename)).exists() => {
+            debug!(error = %e, "not a valid container, trying the .mac sidecar");
+            return decrypt_legacy(filename, contents, xor_key, mac_key);
+        }
+        Err(e) => return Err(e),
+    };
+
+    // 4. Write the recovered plaintext back to disk (temp file).
+    replace(filename, &plaintext)?;
+    info!(bytes = plaintext.len(), "decrypted");
+
+    println!("Decrypted '{}' successfully.", filename);
+    Ok(())
+}
+
+/// Decrypts a file written before the container, whose HMAC over the
+/// plaintext is in `<file>.mac`.
+fn decrypt_legacy(
+    filename: &str,
+    mut ciphertext: Vec<u8>,
+    xor_key: &[u8],
+    mac_key: &[u8],
+) -> Result<(), ToolError> {
+    // 1. Read stored MAC from "file.txt.mac".
     let mac_filename = format!("{}.mac", filename);
     if !Path::new(&mac_filename).exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("MAC file '{}' not found.", mac_filename),
+        return Err(ToolError::io(
+            &mac_filename,
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "'{}' is not an xor2 file and its MAC file was not found.",
+                    filename
+                ),
+            ),
         ));
     }
-    let stored_tag = fs::read(&mac_filename)?; // a Vec<u8>
+    info!("decrypting legacy file");
+    let stored_tag = fs::read(&mac_filename).path_context(&mac_filename)?;
 
-    // 4. XOR decrypt in-place to recover plaintext.
-    if xor_key.len() < ciphertext.len() {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "xor.key is smaller than the ciphertext.",
-        ));
-    }
-    for (i, byte) in ciphertext.iter_mut().enumerate() {
-        *byte ^= xor_key[i];
-    }
+    // 2. XOR decrypt in-place to recover plaintext.
+    xor_with_key(&mut ciphertext, xor_key)?;
     let plaintext = ciphertext; // now the recovered plaintext
 
-    // 5. Compute HMAC over (plaintext || length) => recomputed_tag
-    let mut mac = HmacSha256::new_from_slice(&mac_key)
-        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid MAC key"))?;
+    // 3. Compute HMAC over (plaintext || length) and compare in constant time.
+    let mut mac = HmacSha256::new_from_slice(mac_key)
+        .map_err(|_| ToolError::integrity("Invalid MAC key"))?;
     mac.update(&plaintext);
     mac.update(&(plaintext.len() as u64).to_le_bytes());
-    let recomputed_tag = mac.finalize().into_bytes().to_vec();
-
-    if recomputed_tag != stored_tag {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Integrity check failed. The file may be tampered with.",
-        ));
-    }
-
-    // 6. Write the recovered plaintext back to disk (temp file).
-    let temp_filename = format!("{}.tmp", filename);
-    fs::write(&temp_filename, &plaintext)?;
-    fs::rename(&temp_filename, filename)?;
-
-    println!("Decrypted '{}' successfully.", filename);
+    mac.verify_slice(&stored_tag).map_err(|_| {
+        ToolError::integrity("Integrity check failed. The file may be tampered with.")
+    })?;
 
-    // 7. Automatically verify the newly decrypted file.
-    verify_plaintext(filename, &mac_key)?;
+    // 4. Write the recovered plaintext back to disk (temp file). The sidecar
+    //    has done its job; encrypting again writes a container.
+    replace(filename, &plaintext)?;
+    fs::remove_file(&mac_filename).path_context(&mac_filename)?;
 
-    println!("Automatic verification after decryption succeeded.");
+    println!("Decrypted legacy file '{}' successfully.", filename);
     Ok(())
 }
 
-/// Verify the newly encrypted file by reading the ciphertext, XORing in-memory, and checking the MAC.
-fn verify_ciphertext(filename: &str, xor_key: &[u8], mac_key: &[u8]) -> io::Result<()> {
-    // 1. Read the ciphertext from file.
-    let mut ciphertext = fs::read(filename)?;
-
-    // 2. XOR in-place to get plaintext.
-    if xor_key.len() < ciphertext.len() {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "xor.key is smaller than the ciphertext.",
-        ));
-    }
-    for (i, byte) in ciphertext.iter_mut().enumerate() {
-        *byte ^= xor_key[i];
+/// Helper to load a key (for XOR or MAC) from disk.
+fn load_key(filename: &str) -> Result<Vec<u8>, ToolError> {
+    if !Path::new(filename).exists() {
+        return Err(ToolError::KeyMissing {
+            path: filename.into(),
+        });
     }
+    fs::read(filename).path_context(filename)
+}
 
-    // 3. Read the stored MAC and recompute the MAC on the plaintext.
-    let mac_filename = format!("{}.mac", filename);
-    let stored_tag = fs::read(&mac_filename)?; // Vec<u8>
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::process;
 
-    let mut mac = HmacSha256::new_from_slice(mac_key)
-        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid MAC key"))?;
-    mac.update(&ciphertext);
-    mac.update(&(ciphertext.len() as u64).to_le_bytes());
-    let recomputed_tag = mac.finalize().into_bytes().to_vec();
+    const XOR_KEY: [u8; 256] = [0x3c; 256];
+    const MAC_KEY: [u8; 32] = [0xa5; 32];
+    const PLAINTEXT: &[u8] = b"inventory: 14 crates, 3 pallets";
 
-    if recomputed_tag != stored_tag {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Ciphertext integrity check failed right after encryption.",
-        ));
+    fn scratch_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("xor2-{}-{}", process::id(), name))
     }
 
-    Ok(())
-}
-
-/// Verify the newly decrypted file by reading the plaintext, computing the MAC, and comparing it.
-fn verify_plaintext(filename: &str, mac_key: &[u8]) -> io::Result<()> {
-    // 1. Read the plaintext from disk.
-    let plaintext = fs::read(filename)?;
-
-    // 2. Read the stored MAC
-    let mac_filename = format!("{}.mac", filename);
-    let stored_tag = fs::read(mac_filename)?; // Vec<u8>
+    /// A file as the tool wrote it before the container: the bare XOR
+    /// ciphertext, and an HMAC over the plaintext and its length in `.mac`.
+    fn write_legacy(path: &Path, plaintext: &[u8]) {
+        let mut ciphertext = plaintext.to_vec();
+        xor_with_key(&mut ciphertext, &XOR_KEY).unwrap();
+        let mut mac = HmacSha256::new_from_slice(&MAC_KEY).unwrap();
+        mac.update(plaintext);
+        mac.update(&(plaintext.len() as u64).to_le_bytes());
+        fs::write(path, ciphertext).unwrap();
+        fs::write(format!("{}.mac", path.display()), mac.finalize().into_bytes()).unwrap();
+    }
 
-    // 3. Compute MAC over (plaintext || length) => recomputed_tag
-    let mut mac = HmacSha256::new_from_slice(mac_key)
-        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid MAC key"))?;
-    mac.update(&plaintext);
-    mac.update(&(plaintext.len() as u64).to_le_bytes());
-    let recomputed_tag = mac.finalize().into_bytes().to_vec();
+    #[test]
+    fn sealed_files_open_to_the_plaintext() {
+        let contents = seal(PLAINTEXT, &XOR_KEY, &MAC_KEY).unwrap();
+        assert_eq!(&contents[..4], MAGIC);
+        assert_eq!(contents.len(), HEADER_LEN + PLAINTEXT.len() + TAG_LEN);
+        assert!(is_container(&contents));
+        assert_eq!(open(&contents, &XOR_KEY, &MAC_KEY).unwrap(), PLAINTEXT);
+    }
 
-    if recomputed_tag != stored_tag {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Plaintext integrity check failed right after decryption.",
-        ));
+    #[test]
+    fn any_flipped_byte_fails_the_integrity_check() {
+        let contents = seal(PLAINTEXT, &XOR_KEY, &MAC_KEY).unwrap();
+        // A file id byte, a length byte, a ciphertext byte and a tag byte
+        for position in [6, HEADER_LEN - 1, HEADER_LEN + 3, contents.len() - 1] {
+            let mut tampered = contents.clone();
+            tampered[position] ^= 0x01;
+            let err = open(&tampered, &XOR_KEY, &MAC_KEY).unwrap_err();
+            assert!(matches!(err, ToolError::Integrity(_)), "byte {}: {}", position, err);
+        }
     }
 
-    Ok(())
-}
+    #[test]
+    fn each_seal_gets_its_own_file_id_and_tag() {
+        let first = seal(PLAINTEXT, &XOR_KEY, &MAC_KEY).unwrap();
+        let second = seal(PLAINTEXT, &XOR_KEY, &MAC_KEY).unwrap();
+        assert_ne!(first[5..5 + FILE_ID_LEN], second[5..5 + FILE_ID_LEN]);
+        assert_ne!(first[first.len() - TAG_LEN..], second[second.len() - TAG_LEN..]);
+        // Same key stream, so the ciphertext bodies match
+        let body = |contents: &[u8]| contents[HEADER_LEN..contents.len() - TAG_LEN].to_vec();
+        assert_eq!(body(&first), body(&second));
+    }
 
-/// Helper to load a key (for XOR or MAC) from disk.
-fn load_key(filename: &str) -> io::Result<Vec<u8>> {
-    if !Path::new(filename).exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Key file '{}' not found.", filename),
-        ));
+    #[test]
+    fn legacy_files_decrypt_and_lose_their_sidecar() {
+        let path = scratch_file("legacy.txt");
+        write_legacy(&path, PLAINTEXT);
+        let filename = path.to_str().unwrap();
+
+        let contents = fs::read(&path).unwrap();
+        assert!(!is_container(&contents));
+        decrypt_contents(filename, contents, &XOR_KEY, &MAC_KEY).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), PLAINTEXT);
+        assert!(!Path::new(&format!("{}.mac", filename)).exists());
+        fs::remove_file(&path).unwrap();
     }
-    fs::read(filename)
-}
 
-/// Logs errors to `error.txt`, appending if the file already exists.
-fn log_error(msg: &str) {
-    if let Ok(mut f) = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("error.txt")
-    {
-        let _ = writeln!(f, "{}", msg);
+    #[test]
+    fn legacy_files_that_start_with_the_magic_use_their_sidecar() {
+        let path = scratch_file("magic.txt");
+        // Chosen so the ciphertext starts with "XOR2" and is long enough to
+        // pass for a container
+        let mut plaintext = b"XOR2".to_vec();
+        xor_with_key(&mut plaintext, &XOR_KEY).unwrap();
+        plaintext.extend_from_slice(&[b'.'; HEADER_LEN + TAG_LEN]);
+        write_legacy(&path, &plaintext);
+        let filename = path.to_str().unwrap();
+
+        let contents = fs::read(&path).unwrap();
+        assert!(is_container(&contents));
+        decrypt_contents(filename, contents, &XOR_KEY, &MAC_KEY).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), plaintext);
+        assert!(!Path::new(&format!("{}.mac", filename)).exists());
+        fs::remove_file(&path).unwrap();
     }
 }
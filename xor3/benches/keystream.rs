@@ -0,0 +1,34 @@
+//! Compares the throughput of the keystreams xor3 can write: the SHA-256
+//! counter keystream of older files against ChaCha20 and AES-256-CTR.
+//!
+//!     cargo bench
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use secure_xor::keystream::Algorithm;
+
+/// Matches the chunk size of the binary.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+fn keystreams(c: &mut Criterion) {
+    let len = 16 * 1024 * 1024;
+    let subkey = [7u8; 32];
+    let nonce = [9u8; 16];
+    let mut data = vec![0u8; len];
+
+    let mut group = c.benchmark_group("keystream");
+    group.throughput(Throughput::Bytes(len as u64));
+    for algorithm in Algorithm::ALL {
+        group.bench_function(BenchmarkId::new(algorithm.name(), "16 MiB"), |b| {
+            b.iter(|| {
+                let mut keystream = algorithm.keystream(&subkey, &nonce);
+                for (i, chunk) in data.chunks_mut(CHUNK_SIZE).enumerate() {
+                    keystream.apply((i * CHUNK_SIZE) as u64, chunk);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, keystreams);
+criterion_main!(benches);
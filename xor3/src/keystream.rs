@@ -0,0 +1,202 @@
+// keystream.rs
+
+use aes::Aes256;
+use chacha20::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use chacha20::ChaCha20;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+type Aes256Ctr = ctr::Ctr128BE<Aes256>;
+
+/// Bytes of keystream per counter value of the SHA-256 keystream.
+pub const BLOCK_SIZE: usize = 32;
+
+/// A keystream that can start at any byte, so a file can be processed in
+/// chunks, or from the middle, without generating what comes before.
+pub trait Keystream {
+    /// XORs `data` with the keystream, starting at byte `offset` of the
+    /// keystream.
+    fn apply(&mut self, offset: u64, data: &mut [u8]);
+}
+
+/// The keystreams xor3 can write. The id is stored in the file header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// SHA-256 in counter mode, the keystream of files without a header.
+    Sha256,
+    /// ChaCha20 (RFC 8439).
+    ChaCha20,
+    /// AES-256 in CTR mode with a 128-bit big-endian counter.
+    Aes256Ctr,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 3] = [Algorithm::Sha256, Algorithm::ChaCha20, Algorithm::Aes256Ctr];
+
+    pub fn id(self) -> u8 {
+        match self {
+            Algorithm::Sha256 => 1,
+            Algorithm::ChaCha20 => 2,
+            Algorithm::Aes256Ctr => 3,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Algorithm> {
+        Algorithm::ALL.into_iter().find(|algorithm| algorithm.id() == id)
+    }
+
+    /// The name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::ChaCha20 => "chacha20",
+            Algorithm::Aes256Ctr => "aes256ctr",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Algorithm> {
+        Algorithm::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The longest plaintext the keystream covers. ChaCha20's 32-bit block
+    /// counter runs out at 256 GiB.
+    pub fn max_len(self) -> u64 {
+        match self {
+            Algorithm::ChaCha20 => u32::MAX as u64 * 64,
+            Algorithm::Sha256 | Algorithm::Aes256Ctr => u64::MAX,
+        }
+    }
+
+    /// The keystream for one file. `encryption_subkey` is the 32-byte HKDF
+    /// subkey and `nonce` the file's 16-byte nonce; ChaCha20 uses its first
+    /// 12 bytes.
+    pub fn keystream<'a>(
+        self,
+        encryption_subkey: &'a [u8],
+        nonce: &'a [u8],
+    ) -> Box<dyn Keystream + 'a> {
+        match self {
+            Algorithm::Sha256 => Box::new(Sha256Keystream::new(encryption_subkey, nonce)),
+            Algorithm::ChaCha20 => Box::new(CipherKeystream(ChaCha20::new(
+                encryption_subkey.into(),
+                nonce[..12].into(),
+            ))),
+            Algorithm::Aes256Ctr => Box::new(CipherKeystream(Aes256Ctr::new(
+                encryption_subkey.into(),
+                nonce.into(),
+            ))),
+        }
+    }
+}
+
+/// The SHA-256 counter keystream: block `i` is
+/// SHA-256(encryption_subkey || nonce || i as a big-endian u64).
+pub struct Sha256Keystream<'a> {
+    encryption_subkey: &'a [u8],
+    nonce: &'a [u8],
+}
+
+impl<'a> Sha256Keystream<'a> {
+    pub fn new(encryption_subkey: &'a [u8], nonce: &'a [u8]) -> Self {
+        Sha256Keystream {
+            encryption_subkey,
+            nonce,
+        }
+    }
+
+    fn block(&self, counter: u64) -> [u8; BLOCK_SIZE] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.encryption_subkey);
+        hasher.update(self.nonce);
+        hasher.update(counter.to_be_bytes());
+        hasher.finalize().into()
+    }
+}
+
+impl Keystream for Sha256Keystream<'_> {
+    fn apply(&mut self, offset: u64, data: &mut [u8]) {
+        let mut done = 0;
+        while done < data.len() {
+            let position = offset + done as u64;
+            let skip = (position % BLOCK_SIZE as u64) as usize;
+            let mut block = self.block(position / BLOCK_SIZE as u64);
+            let n = (BLOCK_SIZE - skip).min(data.len() - done);
+            for (byte, key) in data[done..done + n].iter_mut().zip(&block[skip..]) {
+                *byte ^= key;
+            }
+            block.zeroize();
+            done += n;
+        }
+    }
+}
+
+/// A seekable stream cipher from the RustCrypto crates. Their key schedules
+/// are wiped on drop.
+struct CipherKeystream<C>(C);
+
+impl<C: StreamCipher + StreamCipherSeek> Keystream for CipherKeystream<C> {
+    fn apply(&mut self, offset: u64, data: &mut [u8]) {
+        self.0.seek(offset);
+        self.0.apply_keystream(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUBKEY: [u8; 32] = [0x42; 32];
+    const NONCE: [u8; 16] = [0x24; 16];
+
+    #[test]
+    fn chunks_at_any_offset_match_one_pass() {
+        let plaintext: Vec<u8> = (0..1000u32).map(|i| (i * 31 + 7) as u8).collect();
+        for algorithm in Algorithm::ALL {
+            let mut whole = plaintext.clone();
+            algorithm.keystream(&SUBKEY, &NONCE).apply(0, &mut whole);
+            assert_ne!(whole, plaintext, "{}", algorithm.name());
+
+            // Uneven chunks that straddle block boundaries, applied back to
+            // front with one keystream, as a ranged decryption would.
+            let cuts = [0, 1, 31, 32, 33, 64, 100, 129, 500, 511, 999, 1000];
+            let mut chunked = plaintext.clone();
+            let mut keystream = algorithm.keystream(&SUBKEY, &NONCE);
+            for window in cuts.windows(2).rev() {
+                let (start, end) = (window[0], window[1]);
+                keystream.apply(start as u64, &mut chunked[start..end]);
+            }
+            assert_eq!(chunked, whole, "{}", algorithm.name());
+
+            // A slice from the middle on its own matches the same bytes.
+            let mut middle = plaintext[333..777].to_vec();
+            algorithm.keystream(&SUBKEY, &NONCE).apply(333, &mut middle);
+            assert_eq!(middle, whole[333..777], "{}", algorithm.name());
+        }
+    }
+
+    #[test]
+    fn sha256_blocks_follow_the_counter() {
+        let mut data = [0u8; 2 * BLOCK_SIZE];
+        Sha256Keystream::new(&SUBKEY, &NONCE).apply(0, &mut data);
+        for (counter, block) in data.chunks(BLOCK_SIZE).enumerate() {
+            let expected = Sha256::new()
+                .chain_update(SUBKEY)
+                .chain_update(NONCE)
+                .chain_update((counter as u64).to_be_bytes())
+                .finalize();
+            assert_eq!(block, &expected[..]);
+        }
+    }
+
+    #[test]
+    fn algorithm_ids_and_names_round_trip() {
+        for algorithm in Algorithm::ALL {
+            assert_eq!(Algorithm::from_id(algorithm.id()), Some(algorithm));
+            assert_eq!(Algorithm::from_name(&algorithm.name().to_uppercase()), Some(algorithm));
+        }
+        assert_eq!(Algorithm::from_id(0), None);
+        assert_eq!(Algorithm::from_name("rot13"), None);
+    }
+}
@@ -0,0 +1,3 @@
+//! The keystreams shared by the `secure_xor` binary and its benchmark.
+
+pub mod keystream;
@@ -3,160 +3,379 @@
 use rand::rngs::OsRng;
 use rand::RngCore;
 use std::env;
-use std::fs::File;
-use std::io::{Read, Write};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 use std::path::Path;
 use std::process;
 
-use sha2::{Digest, Sha256};
+use sha2::Sha256;
 use hmac::{Hmac, Mac};
-use hkdf::Hkdf;
-use zeroize::Zeroize;
+use hkdf::HkdfExtract;
+use zeroize::Zeroizing;
+
+use secure_xor::keystream::Algorithm;
 
 type HmacSha256 = Hmac<Sha256>;
+/// A 32-byte subkey, wiped when dropped.
+type Subkey = Zeroizing<[u8; 32]>;
 
 const NONCE_SIZE: usize = 16; // 128-bit nonce
 const MAC_SIZE: usize = 32;   // HMAC-SHA256 output size
+const CHUNK_SIZE: usize = 64 * 1024; // bytes held in memory at a time
+
+// Files start with MAGIC, VERSION and the keystream's algorithm id. Files
+// written before the header are just nonce | ciphertext | MAC and use the
+// SHA-256 keystream.
+const MAGIC: &[u8; 4] = b"XOR3";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = MAGIC.len() + 2;
+
+const USAGE: &str = "Usage: <E|D> <input_file> <output_file> <key_file> [--keystream NAME] [--range START..END]\n\
+    E and D may be spelled encrypt and decrypt. An output file of - writes to standard output.\n\
+    --keystream (encrypt only) is chacha20 (the default), aes256ctr or sha256.\n\
+    --range (decrypt only) writes plaintext bytes START up to END; either may be left out,\n\
+    and negative values count from the end (--range -4096.. is the last 4 KiB).";
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 5 {
-        eprintln!("Usage: <E|D> <input_file> <output_file> <key_file>");
+    if let Err(err) = run() {
+        eprintln!("{}", err);
         process::exit(1);
     }
+}
+
+fn run() -> io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 5 || args.len().is_multiple_of(2) {
+        return Err(invalid(USAGE));
+    }
 
-    let mode = &args[1];
+    let mode = match args[1].as_str() {
+        "E" | "encrypt" => "E",
+        "D" | "decrypt" => "D",
+        _ => return Err(invalid("Invalid mode. Use 'E' for encrypt or 'D' for decrypt.")),
+    };
     let input_file = &args[2];
     let output_file = &args[3];
     let key_file = &args[4];
 
-    if mode != "E" && mode != "D" {
-        eprintln!("Invalid mode. Use 'E' for encrypt or 'D' for decrypt.");
-        process::exit(1);
+    let mut algorithm = Algorithm::ChaCha20;
+    let mut range = None;
+    for option in args[5..].chunks(2) {
+        match (mode, option[0].as_str()) {
+            ("E", "--keystream") => {
+                algorithm = Algorithm::from_name(&option[1]).ok_or_else(|| {
+                    invalid(&format!(
+                        "Unknown keystream '{}'. Use chacha20, aes256ctr or sha256.",
+                        option[1]
+                    ))
+                })?
+            }
+            ("D", "--range") => range = Some(option[1].as_str()),
+            ("D", "--keystream") => {
+                return Err(invalid("--keystream only applies to encryption; files record their own."))
+            }
+            ("E", "--range") => return Err(invalid("--range only applies to decryption.")),
+            _ => return Err(invalid(USAGE)),
+        }
     }
 
     // Prevent overwriting the input file or an existing output file
     if input_file == output_file {
-        eprintln!("Input and output file paths cannot be the same.");
-        process::exit(1);
+        return Err(invalid("Input and output file paths cannot be the same."));
     }
-    if file_exists(output_file) {
-        eprintln!(
+    if output_file != "-" && file_exists(output_file) {
+        return Err(invalid(&format!(
             "Output file '{}' already exists. Aborting to prevent overwrite.",
             output_file
-        );
-        process::exit(1);
+        )));
     }
 
-    // Load the key
-    let mut key = Vec::new();
-    if let Err(err) = load_file(key_file, &mut key) {
-        eprintln!("Failed to load key: {}", err);
-        process::exit(1);
+    match mode {
+        "E" => encrypt(input_file, output_file, key_file, algorithm),
+        _ => decrypt(input_file, output_file, key_file, range),
     }
+}
 
-    // Load the input data
-    let mut input_data = Vec::new();
-    if let Err(err) = load_file(input_file, &mut input_data) {
-        eprintln!("Failed to load input file: {}", err);
-        process::exit(1);
+/// Encrypts in chunks: header and nonce, then plaintext XOR key file XOR
+/// keystream, then an HMAC over everything before it.
+fn encrypt(
+    input_file: &str,
+    output_file: &str,
+    key_file: &str,
+    algorithm: Algorithm,
+) -> io::Result<()> {
+    let mut input = File::open(input_file)
+        .map_err(|err| context("Failed to load input file", err))?;
+    let length = input.metadata()?.len();
+    check_key_length(key_file, length)?;
+    check_keystream_length(algorithm, length)?;
+
+    // 1) Generate a random nonce
+    let nonce = generate_random_bytes(NONCE_SIZE);
+
+    // 2) Derive subkeys (encryption_subkey, hmac_subkey) from (key, nonce) using HKDF
+    let (encryption_subkey, hmac_subkey) = derive_subkeys_with_hkdf(key_file, &nonce)?;
+    let mut keystream = algorithm.keystream(&encryption_subkey[..], &nonce);
+    let mut mac = new_hmac(&hmac_subkey[..]);
+
+    let mut key = File::open(key_file).map_err(|err| context("Failed to load key", err))?;
+    let mut output = Output::create(output_file)?;
+    let result = (|| {
+        // 3) Write the header and the nonce
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&[VERSION, algorithm.id()]);
+        header.extend_from_slice(&nonce);
+        output.write_all(&header)?;
+        mac.update(&header);
+
+        // 4) XOR the plaintext with the key file AND the keystream, a chunk at a time
+        let mut buffer = Zeroizing::new(vec![0u8; CHUNK_SIZE]);
+        let mut key_buffer = Zeroizing::new(vec![0u8; CHUNK_SIZE]);
+        let mut offset = 0u64;
+        loop {
+            let n = read_full(&mut input, &mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            key.read_exact(&mut key_buffer[..n])
+                .map_err(|_| invalid("The key is shorter than the input file."))?;
+            xor_into(&mut buffer[..n], &key_buffer[..n]);
+            keystream.apply(offset, &mut buffer[..n]);
+
+            // 5) The HMAC covers the header, nonce and ciphertext
+            mac.update(&buffer[..n]);
+            output.write_all(&buffer[..n])?;
+            offset += n as u64;
+        }
+        output.write_all(&mac.finalize().into_bytes())?;
+        output.finish()
+    })();
+    if result.is_err() {
+        output.discard();
     }
+    result
+}
 
-    // Basic key length check
-    // For encryption, key must be >= plaintext length
-    // For decryption, key must be >= (ciphertext length minus nonce+MAC)
-    if (mode == "E" && key.len() < input_data.len()) ||
-       (mode == "D" && key.len() < input_data.len().saturating_sub(NONCE_SIZE + MAC_SIZE)) {
-        eprintln!("The key is too short. (Key length: {}, Input length: {})",
-                  key.len(),
-                  input_data.len());
-        process::exit(1);
+/// Checks the HMAC over the whole file in one streaming pass, then decrypts
+/// `range` (default: everything) in a second pass. Nothing is written unless
+/// the whole file verifies.
+fn decrypt(
+    input_file: &str,
+    output_file: &str,
+    key_file: &str,
+    range: Option<&str>,
+) -> io::Result<()> {
+    let mut input = File::open(input_file)
+        .map_err(|err| context("Failed to load input file", err))?;
+    let file_length = input.metadata()?.len();
+    if file_length < (NONCE_SIZE + MAC_SIZE) as u64 {
+        return Err(invalid("Invalid input file: missing nonce or MAC."));
     }
 
-    let mut output_data = Vec::new();
-
-    match mode.as_str() {
-        "E" => {
-            // 1) Generate a random nonce
-            let nonce = generate_random_bytes(NONCE_SIZE);
-
-            // 2) Derive subkeys (encryption_subkey, hmac_subkey) from (key, nonce) using HKDF
-            let (encryption_subkey, hmac_subkey) = derive_subkeys_with_hkdf(&key, &nonce);
-
-            // 3) Generate a keystream from the encryption_subkey + nonce 
-            //    (similar approach to the original generate_keystream, but we incorporate the encryption_subkey)
-            let keystream = generate_keystream(&encryption_subkey, &nonce, input_data.len());
-
-            // Prepend the nonce
-            output_data.extend_from_slice(&nonce);
-
-            // 4) XOR the plaintext with the key file AND the keystream
-            let mut temp_data = Vec::new();
-            xor_with_key_and_keystream(&input_data, &key, &keystream, &mut temp_data);
-            output_data.extend_from_slice(&temp_data);
-
-            // 5) Compute HMAC over (nonce + ciphertext) using the derived hmac_subkey
-            let mac = generate_hmac(&hmac_subkey, &output_data);
-            output_data.extend_from_slice(&mac);
+    // 1) Read the header, if there is one, and the nonce
+    let (algorithm, prefix) = read_prefix(&mut input, file_length)?;
+    let nonce = &prefix[prefix.len() - NONCE_SIZE..];
+    let length = file_length - (prefix.len() + MAC_SIZE) as u64;
+    check_key_length(key_file, length)?;
+    check_keystream_length(algorithm, length)?;
+    let range = match range {
+        Some(text) => parse_range(text, length)?,
+        None => 0..length,
+    };
+
+    // 2) Derive the subkeys using the same key+nonce
+    let (encryption_subkey, hmac_subkey) = derive_subkeys_with_hkdf(key_file, nonce)?;
+
+    // 3) Verify the HMAC over header + nonce + ciphertext before decrypting anything
+    let mut mac = new_hmac(&hmac_subkey[..]);
+    mac.update(&prefix);
+    let mut buffer = Zeroizing::new(vec![0u8; CHUNK_SIZE]);
+    let mut remaining = length;
+    while remaining > 0 {
+        let n = (remaining.min(CHUNK_SIZE as u64)) as usize;
+        input.read_exact(&mut buffer[..n])?;
+        mac.update(&buffer[..n]);
+        remaining -= n as u64;
+    }
+    let mut received_mac = [0u8; MAC_SIZE];
+    input.read_exact(&mut received_mac)?;
+    if mac.verify_slice(&received_mac).is_err() {
+        return Err(invalid("MAC verification failed. Data may have been tampered with."));
+    }
 
-            // Zeroize sensitive data
-            zeroize_slices(&mut [ &mut key, &mut temp_data ]);
+    // 4) XOR only the requested range with the key file and the keystream
+    let mut keystream = algorithm.keystream(&encryption_subkey[..], nonce);
+    let mut key = File::open(key_file).map_err(|err| context("Failed to load key", err))?;
+    input.seek(SeekFrom::Start(prefix.len() as u64 + range.start))?;
+    key.seek(SeekFrom::Start(range.start))?;
+
+    let mut output = Output::create(output_file)?;
+    let result = (|| {
+        let mut key_buffer = Zeroizing::new(vec![0u8; CHUNK_SIZE]);
+        let mut offset = range.start;
+        while offset < range.end {
+            let n = ((range.end - offset).min(CHUNK_SIZE as u64)) as usize;
+            input.read_exact(&mut buffer[..n])?;
+            key.read_exact(&mut key_buffer[..n])?;
+            xor_into(&mut buffer[..n], &key_buffer[..n]);
+            keystream.apply(offset, &mut buffer[..n]);
+            output.write_all(&buffer[..n])?;
+            offset += n as u64;
         }
-        "D" => {
-            if input_data.len() < NONCE_SIZE + MAC_SIZE {
-                eprintln!("Invalid input file: missing nonce or MAC.");
-                process::exit(1);
+        output.finish()
+    })();
+    if result.is_err() {
+        output.discard();
+    }
+    result
+}
+
+/// Reads everything before the ciphertext: the header and nonce, or just the
+/// nonce for files from before the header. A headerless file is only taken
+/// for a new one if its random nonce happens to start with the whole header,
+/// about a 1 in 2^38 chance; its MAC then fails to verify.
+fn read_prefix(input: &mut File, file_length: u64) -> io::Result<(Algorithm, Vec<u8>)> {
+    let mut prefix = vec![0u8; HEADER_SIZE + NONCE_SIZE];
+    let has_room = file_length >= (HEADER_SIZE + NONCE_SIZE + MAC_SIZE) as u64;
+    if has_room {
+        input.read_exact(&mut prefix)?;
+        if &prefix[..MAGIC.len()] == MAGIC && prefix[MAGIC.len()] == VERSION {
+            if let Some(algorithm) = Algorithm::from_id(prefix[MAGIC.len() + 1]) {
+                return Ok((algorithm, prefix));
             }
+        }
+    }
+    prefix.truncate(NONCE_SIZE);
+    input.seek(SeekFrom::Start(0))?;
+    input.read_exact(&mut prefix)?;
+    Ok((Algorithm::Sha256, prefix))
+}
 
-            // 1) Separate nonce, ciphertext, and MAC
-            let (nonce, rest) = input_data.split_at(NONCE_SIZE);
-            let (ciphertext, received_mac) = rest.split_at(rest.len() - MAC_SIZE);
+// -------------------- File I/O Helpers --------------------
 
-            // 2) Derive subkeys using the same key+nonce
-            let (encryption_subkey, hmac_subkey) = derive_subkeys_with_hkdf(&key, nonce);
+/// Where the result goes: a new file, or standard output for `-`.
+enum Output {
+    File { file: File, path: String },
+    Stdout(io::Stdout),
+}
 
-            // 3) Verify the HMAC before decryption 
-            //    (compute HMAC on everything except the last 32 bytes, i.e. the MAC)
-            verify_hmac(&hmac_subkey, &input_data[..input_data.len() - MAC_SIZE], received_mac);
+impl Output {
+    fn create(path: &str) -> io::Result<Output> {
+        if path == "-" {
+            return Ok(Output::Stdout(io::stdout()));
+        }
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|err| context("Failed to save output file", err))?;
+        Ok(Output::File {
+            file,
+            path: path.to_string(),
+        })
+    }
 
-            // 4) Generate the same keystream
-            let keystream = generate_keystream(&encryption_subkey, nonce, ciphertext.len());
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        match self {
+            Output::File { file, .. } => file.write_all(data),
+            Output::Stdout(stdout) => stdout.write_all(data),
+        }
+    }
 
-            // 5) XOR to get the original plaintext
-            xor_with_key_and_keystream(ciphertext, &key, &keystream, &mut output_data);
+    fn finish(&mut self) -> io::Result<()> {
+        match self {
+            Output::File { file, .. } => file.sync_all(),
+            Output::Stdout(stdout) => stdout.flush(),
+        }
+    }
 
-            // Zeroize sensitive data
-            zeroize_slices(&mut [ &mut key ]);
+    /// Removes a partly written output file.
+    fn discard(self) {
+        if let Output::File { file, path } = self {
+            drop(file);
+            let _ = fs::remove_file(path);
         }
-        _ => unreachable!(),
     }
+}
 
-    // Finally, save the output
-    if let Err(err) = save_file(output_file, &output_data) {
-        eprintln!("Failed to save output file: {}", err);
-        process::exit(1);
+/// Reads until `buffer` is full or the input ends.
+fn read_full(input: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match input.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
     }
+    Ok(filled)
+}
 
-    // Zeroize output_data if desired (this is the final ciphertext or plaintext).
-    // In some cases you may want to keep plaintext in memory, so this is optional.
-    // output_data.zeroize();
+fn file_exists(filename: &str) -> bool {
+    Path::new(filename).exists()
 }
 
-// -------------------- File I/O Helpers --------------------
-fn load_file(filename: &str, buffer: &mut Vec<u8>) -> std::io::Result<()> {
-    let mut file = File::open(filename)?;
-    file.read_to_end(buffer)?;
+/// The key file must cover every byte of the plaintext.
+fn check_key_length(key_file: &str, length: u64) -> io::Result<()> {
+    let key_length = fs::metadata(key_file)
+        .map_err(|err| context("Failed to load key", err))?
+        .len();
+    if key_length < length {
+        return Err(invalid(&format!(
+            "The key is too short. (Key length: {}, Input length: {})",
+            key_length, length
+        )));
+    }
     Ok(())
 }
 
-fn save_file(filename: &str, data: &[u8]) -> std::io::Result<()> {
-    let mut file = File::create(filename)?;
-    file.write_all(data)?;
+/// ChaCha20 files are limited by its block counter.
+fn check_keystream_length(algorithm: Algorithm, length: u64) -> io::Result<()> {
+    if length > algorithm.max_len() {
+        return Err(invalid(&format!(
+            "The {} keystream covers at most {} bytes. (Input length: {})",
+            algorithm.name(),
+            algorithm.max_len(),
+            length
+        )));
+    }
     Ok(())
 }
 
-fn file_exists(filename: &str) -> bool {
-    Path::new(filename).exists()
+/// Parses `START..END`, `START..` or `..END` into a range of plaintext
+/// offsets. Negative values count back from the end.
+fn parse_range(text: &str, length: u64) -> io::Result<Range<u64>> {
+    let bad = || invalid(&format!("Invalid range '{}'. Use START..END.", text));
+    let (start, end) = text.split_once("..").ok_or_else(bad)?;
+    let bound = |value: &str, default: u64| -> io::Result<u64> {
+        if value.is_empty() {
+            return Ok(default);
+        }
+        let value: i64 = value.parse().map_err(|_| bad())?;
+        if value < 0 {
+            length.checked_sub(value.unsigned_abs()).ok_or_else(bad)
+        } else {
+            Ok(value as u64)
+        }
+    };
+    let range = bound(start, 0)?..bound(end, length)?;
+    if range.start > range.end || range.end > length {
+        return Err(invalid(&format!(
+            "Range '{}' is outside the {} bytes of plaintext.",
+            text, length
+        )));
+    }
+    Ok(range)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message.to_string())
+}
+
+fn context(message: &str, err: io::Error) -> io::Error {
+    io::Error::new(err.kind(), format!("{}: {}", message, err))
 }
 
 // -------------------- Randomness --------------------
@@ -167,78 +386,107 @@ fn generate_random_bytes(size: usize) -> Vec<u8> {
 }
 
 // -------------------- HKDF Key Derivation --------------------
-fn derive_subkeys_with_hkdf(master_key: &[u8], salt: &[u8]) -> (Vec<u8>, Vec<u8>) {
-    let hk = Hkdf::<Sha256>::new(Some(salt), master_key);
 
-    let mut encryption_subkey = vec![0u8; 32];
-    let mut hmac_subkey = vec![0u8; 32];
+/// HKDF-SHA256 with the nonce as salt and the whole key file as input key
+/// material. The key file is streamed through HKDF-Extract, so it is never
+/// held in memory.
+fn derive_subkeys_with_hkdf(
+    key_file: &str,
+    salt: &[u8],
+) -> io::Result<(Subkey, Subkey)> {
+    let mut extract = HkdfExtract::<Sha256>::new(Some(salt));
+    let mut key = File::open(key_file).map_err(|err| context("Failed to load key", err))?;
+    let mut buffer = Zeroizing::new(vec![0u8; CHUNK_SIZE]);
+    loop {
+        let n = read_full(&mut key, &mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        extract.input_ikm(&buffer[..n]);
+    }
+    let (_, hk) = extract.finalize();
+
+    let mut encryption_subkey = Zeroizing::new([0u8; 32]);
+    let mut hmac_subkey = Zeroizing::new([0u8; 32]);
 
-    hk.expand(b"encryption_subkey", &mut encryption_subkey)
+    hk.expand(b"encryption_subkey", &mut encryption_subkey[..])
         .expect("HKDF expand failed for encryption subkey");
-    hk.expand(b"hmac_subkey", &mut hmac_subkey)
+    hk.expand(b"hmac_subkey", &mut hmac_subkey[..])
         .expect("HKDF expand failed for hmac subkey");
 
-    (encryption_subkey, hmac_subkey)
-}
-
-// -------------------- Keystream Generation --------------------
-fn generate_keystream(encryption_subkey: &[u8], nonce: &[u8], length: usize) -> Vec<u8> {
-    let mut keystream = Vec::with_capacity(length);
-    let mut counter = 0u64;
-
-    // We take the original XOR-based approach but incorporate the encryption_subkey into the hash each time
-    while keystream.len() < length {
-        let mut hasher = Sha256::new();
-        hasher.update(encryption_subkey);
-        hasher.update(nonce);
-        hasher.update(&counter.to_be_bytes());
-        let hash_output = hasher.finalize();
-
-        // Only take as many bytes as we need
-        let chunk = if keystream.len() + hash_output.len() > length {
-            &hash_output[..(length - keystream.len())]
-        } else {
-            &hash_output
-        };
-        keystream.extend_from_slice(chunk);
-
-        counter += 1;
-    }
-
-    keystream
+    Ok((encryption_subkey, hmac_subkey))
 }
 
 // -------------------- XOR and HMAC --------------------
-fn xor_with_key_and_keystream(
-    input: &[u8],
-    key: &[u8],
-    keystream: &[u8],
-    output: &mut Vec<u8>,
-) {
-    for i in 0..input.len() {
-        let byte = input[i] ^ key[i] ^ keystream[i];
-        output.push(byte);
+fn xor_into(data: &mut [u8], key: &[u8]) {
+    for (byte, k) in data.iter_mut().zip(key) {
+        *byte ^= k;
     }
 }
 
-fn generate_hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
-    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC can take key of any size");
-    mac.update(data);
-    mac.finalize().into_bytes().to_vec()
+fn new_hmac(key: &[u8]) -> HmacSha256 {
+    HmacSha256::new_from_slice(key).expect("HMAC can take key of any size")
 }
 
-fn verify_hmac(key: &[u8], data: &[u8], received_mac: &[u8]) {
-    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC can take key of any size");
-    mac.update(data);
-    if mac.verify_slice(received_mac).is_err() {
-        eprintln!("MAC verification failed. Data may have been tampered with.");
-        process::exit(1);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secure_xor::keystream::{Keystream, Sha256Keystream};
+
+    #[test]
+    fn parse_range_takes_open_and_negative_bounds() {
+        assert_eq!(parse_range("..", 100).unwrap(), 0..100);
+        assert_eq!(parse_range("10..20", 100).unwrap(), 10..20);
+        assert_eq!(parse_range("10..", 100).unwrap(), 10..100);
+        assert_eq!(parse_range("..20", 100).unwrap(), 0..20);
+        assert_eq!(parse_range("-30..", 100).unwrap(), 70..100);
+        assert_eq!(parse_range("-30..-10", 100).unwrap(), 70..90);
+        assert_eq!(parse_range("..-100", 100).unwrap(), 0..0);
+        assert_eq!(parse_range("100..", 100).unwrap(), 100..100);
+    }
+
+    #[test]
+    fn parse_range_refuses_bad_ranges() {
+        for text in ["", "10", "a..b", "1.5..2", "20..10", "-10..-20", "0..101", "101..", "-101.."] {
+            assert!(parse_range(text, 100).is_err(), "{}", text);
+        }
     }
-}
 
-// -------------------- Zeroize Helper --------------------
-fn zeroize_slices(slices: &mut [&mut [u8]]) {
-    for slice in slices.iter_mut() {
-        slice.zeroize();
+    #[test]
+    fn headerless_files_decrypt_with_the_sha256_keystream() {
+        let dir = std::env::temp_dir().join(format!("xor3-headerless-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (input, output, key_file) = (dir.join("in"), dir.join("out"), dir.join("key"));
+        let plaintext = b"written before the header existed".to_vec();
+        let key: Vec<u8> = (0..plaintext.len() as u32).map(|i| (i * 13 + 5) as u8).collect();
+        fs::write(&key_file, &key).unwrap();
+
+        // The old layout: nonce | plaintext XOR key XOR SHA-256 keystream | MAC.
+        let nonce = [0x5au8; NONCE_SIZE];
+        let (encryption_subkey, hmac_subkey) =
+            derive_subkeys_with_hkdf(key_file.to_str().unwrap(), &nonce).unwrap();
+        let mut ciphertext = plaintext.clone();
+        xor_into(&mut ciphertext, &key);
+        Sha256Keystream::new(&encryption_subkey[..], &nonce).apply(0, &mut ciphertext);
+        let mut mac = new_hmac(&hmac_subkey[..]);
+        mac.update(&nonce);
+        mac.update(&ciphertext);
+        let file = [&nonce[..], &ciphertext, &mac.finalize().into_bytes()].concat();
+        fs::write(&input, &file).unwrap();
+
+        let mut opened = File::open(&input).unwrap();
+        let (algorithm, prefix) = read_prefix(&mut opened, file.len() as u64).unwrap();
+        assert_eq!((algorithm, prefix.as_slice()), (Algorithm::Sha256, &nonce[..]));
+
+        decrypt(
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            key_file.to_str().unwrap(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(fs::read(&output).unwrap(), plaintext);
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -0,0 +1,305 @@
+//! AES-256-GCM file container with Argon2id key derivation.
+//!
+//! The `enc` binary and the `esk` front-end both build on these functions.
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use aes_gcm::{Aes256Gcm, Nonce};
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::KeyInit;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+
+use sha2::{Digest, Sha256};
+
+use secmem::SecretBuf;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Magic bytes identifying the headered file format.
+pub const MAGIC: &[u8; 4] = b"ESKG";
+/// Current header version.
+pub const VERSION: u8 = 1;
+/// Header flag: a password is part of the composite key.
+pub const FLAG_PASSWORD: u8 = 0b0000_0001;
+/// Header flag: a key file is part of the composite key.
+pub const FLAG_KEY_FILE: u8 = 0b0000_0010;
+/// Magic + version + flags.
+pub const HEADER_LEN: usize = 6;
+/// Header + salt + nonce.
+pub const PREFIX_LEN: usize = HEADER_LEN + SALT_LEN + NONCE_LEN;
+/// Length of the Argon2 salt.
+pub const SALT_LEN: usize = 16;
+/// Length of the GCM nonce.
+pub const NONCE_LEN: usize = 12;
+/// Length of the GCM authentication tag.
+pub const TAG_LEN: usize = 16;
+
+/// A file that did not authenticate. The whole body is one GCM message, so the
+/// failure covers everything from `offset`, the first ciphertext byte, onwards.
+#[derive(Debug)]
+pub struct AuthenticationFailed {
+    pub offset: usize,
+}
+
+impl fmt::Display for AuthenticationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Authentication failed at offset {} (wrong credentials or corrupted data).",
+            self.offset
+        )
+    }
+}
+
+impl std::error::Error for AuthenticationFailed {}
+
+/// The factors that make up the composite key.
+pub struct Credentials<'a> {
+    pub password: Option<&'a [u8]>,
+    pub key_file_hash: Option<&'a [u8]>,
+}
+
+impl Credentials<'_> {
+    /// Header flags describing which factors are present.
+    pub fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.password.is_some() {
+            flags |= FLAG_PASSWORD;
+        }
+        if self.key_file_hash.is_some() {
+            flags |= FLAG_KEY_FILE;
+        }
+        flags
+    }
+
+    /// Builds the Argon2 input, KeePass style: SHA-256 of each factor, concatenated.
+    pub fn composite(&self) -> anyhow::Result<SecretBuf> {
+        let factors = self.password.is_some() as usize + self.key_file_hash.is_some() as usize;
+        let mut composite = SecretBuf::new(32 * factors)?;
+        let mut chunks = composite.chunks_mut(32);
+        if let Some(password) = self.password {
+            let mut digest = Sha256::digest(password);
+            chunks.next().unwrap().copy_from_slice(&digest);
+            digest.zeroize();
+        }
+        if let Some(hash) = self.key_file_hash {
+            chunks.next().unwrap().copy_from_slice(hash);
+        }
+        Ok(composite)
+    }
+}
+
+/// Reads the factor flags from an encrypted file's header.
+///
+/// Files without the magic bytes predate the header and are password-only.
+pub fn container_flags(contents: &[u8]) -> u8 {
+    if contents.len() >= HEADER_LEN && &contents[..4] == MAGIC {
+        contents[5]
+    } else {
+        FLAG_PASSWORD
+    }
+}
+
+/// Hashes a key file's contents with SHA-256. Any bytes will do.
+pub fn hash_key_file(path: &Path) -> anyhow::Result<SecretBuf> {
+    let mut key_file = File::open(path)
+        .map_err(|e| anyhow::anyhow!("Cannot open key file {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut key_file, &mut hasher)?;
+    let mut digest = hasher.finalize();
+    let hash = SecretBuf::from_slice(&digest)?;
+    digest.zeroize();
+    Ok(hash)
+}
+
+/// Encrypts `plaintext` into a headered container: header, salt, nonce, then
+/// ciphertext and tag.
+pub fn seal_container(plaintext: &[u8], credentials: &Credentials) -> anyhow::Result<Vec<u8>> {
+    // Generate a random salt.
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+
+    // Derive a key from the composite credentials and salt using Argon2.
+    let key_bytes = derive_key(&credentials.composite()?, &salt)?;
+
+    // Generate a random nonce.
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce_bytes);
+    let nonce = Nonce::from_slice(&nonce_bytes);
+
+    // Create an AES-GCM cipher instance.
+    let cipher = Aes256Gcm::new_from_slice(&key_bytes)
+        .map_err(|e| anyhow::anyhow!("Key init error: {:?}", e))?;
+
+    // Build the header; it is authenticated as associated data so the
+    // factor flags cannot be altered.
+    let mut header = [0u8; HEADER_LEN];
+    header[..4].copy_from_slice(MAGIC);
+    header[4] = VERSION;
+    header[5] = credentials.flags();
+
+    // Encrypt the plaintext.
+    let ciphertext = cipher
+        .encrypt(nonce, Payload { msg: plaintext, aad: &header })
+        .map_err(|e| anyhow::anyhow!("Encryption error: {:?}", e))?;
+
+    // Concatenate the header, salt, nonce, and ciphertext.
+    let mut contents = Vec::with_capacity(PREFIX_LEN + ciphertext.len());
+    contents.extend_from_slice(&header);
+    contents.extend_from_slice(&salt);
+    contents.extend_from_slice(&nonce_bytes);
+    contents.extend_from_slice(&ciphertext);
+    Ok(contents)
+}
+
+/// Authenticates and decrypts the contents of an encrypted file.
+///
+/// Headerless files from before key-file support are still accepted; their
+/// key is derived from the raw password.
+pub fn open_container(
+    contents: &[u8],
+    credentials: &Credentials,
+) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    // Split off the header, if there is one.
+    let (header, body): (&[u8], &[u8]) =
+        if contents.len() >= HEADER_LEN && &contents[..4] == MAGIC {
+            if contents[4] != VERSION {
+                return Err(anyhow::anyhow!("Unsupported file version {}.", contents[4]));
+            }
+            if contents[5] != credentials.flags() {
+                return Err(anyhow::anyhow!("Credentials do not match the file header."));
+            }
+            contents.split_at(HEADER_LEN)
+        } else {
+            (&[], contents)
+        };
+
+    // Ensure the file is long enough to contain the salt, nonce and tag.
+    if body.len() < SALT_LEN + NONCE_LEN + TAG_LEN {
+        return Err(anyhow::anyhow!("File too short to be valid."));
+    }
+
+    // Extract the salt, nonce, and ciphertext.
+    let salt = &body[..SALT_LEN];
+    let nonce_bytes = &body[SALT_LEN..SALT_LEN + NONCE_LEN];
+    let ciphertext = &body[SALT_LEN + NONCE_LEN..];
+
+    // Derive the key from the credentials and salt.
+    let key_bytes = if header.is_empty() {
+        let password = credentials
+            .password
+            .ok_or_else(|| anyhow::anyhow!("Legacy files require a password."))?;
+        derive_key(password, salt)?
+    } else {
+        derive_key(&credentials.composite()?, salt)?
+    };
+
+    // Create an AES-GCM cipher instance.
+    let cipher = Aes256Gcm::new_from_slice(&key_bytes)
+        .map_err(|e| anyhow::anyhow!("Key init error: {:?}", e))?;
+    let nonce = Nonce::from_slice(nonce_bytes);
+
+    // Decrypt the ciphertext.
+    cipher
+        .decrypt(nonce, Payload { msg: ciphertext, aad: header })
+        .map(Zeroizing::new)
+        .map_err(|_| {
+            AuthenticationFailed { offset: contents.len() - body.len() + SALT_LEN + NONCE_LEN }
+                .into()
+        })
+}
+
+/// Derives a 256-bit key from the secret and salt using Argon2.
+pub fn derive_key(secret: &[u8], salt: &[u8]) -> anyhow::Result<SecretBuf> {
+    let mut key = SecretBuf::new(32)?;
+    let params = Params::default();
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+    argon2
+        .hash_password_into(secret, salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Key derivation error: {:?}", e))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAINTEXT: &[u8] = b"quarterly figures, draft 3";
+    const KEY_FILE_HASH: [u8; 32] = [7; 32];
+
+    fn password_only() -> Credentials<'static> {
+        Credentials { password: Some(b"hunter2"), key_file_hash: None }
+    }
+
+    fn key_file_only() -> Credentials<'static> {
+        Credentials { password: None, key_file_hash: Some(&KEY_FILE_HASH) }
+    }
+
+    fn both() -> Credentials<'static> {
+        Credentials { password: Some(b"hunter2"), key_file_hash: Some(&KEY_FILE_HASH) }
+    }
+
+    #[test]
+    fn each_factor_combination_round_trips() {
+        for (credentials, flags) in [
+            (password_only(), FLAG_PASSWORD),
+            (key_file_only(), FLAG_KEY_FILE),
+            (both(), FLAG_PASSWORD | FLAG_KEY_FILE),
+        ] {
+            let contents = seal_container(PLAINTEXT, &credentials).unwrap();
+            assert_eq!(&contents[..4], MAGIC);
+            assert_eq!(container_flags(&contents), flags);
+            assert_eq!(&open_container(&contents, &credentials).unwrap()[..], PLAINTEXT);
+        }
+    }
+
+    #[test]
+    fn a_missing_or_wrong_factor_is_refused() {
+        let contents = seal_container(PLAINTEXT, &both()).unwrap();
+        assert!(open_container(&contents, &password_only()).is_err());
+        assert!(open_container(&contents, &key_file_only()).is_err());
+
+        let other_hash = [8u8; 32];
+        let wrong_key_file = Credentials { password: Some(b"hunter2"), key_file_hash: Some(&other_hash) };
+        assert!(open_container(&contents, &wrong_key_file).is_err());
+    }
+
+    #[test]
+    fn flipped_flag_bits_fail_authentication() {
+        let contents = seal_container(PLAINTEXT, &both()).unwrap();
+
+        // Claim the file is password-only and offer exactly that: the header
+        // now agrees with the credentials, but it is authenticated data.
+        let mut downgraded = contents.clone();
+        downgraded[5] = FLAG_PASSWORD;
+        let err = open_container(&downgraded, &password_only()).unwrap_err();
+        assert!(err.to_string().starts_with("Authentication failed"), "{}", err);
+
+        // Even with every factor supplied, an altered header does not open.
+        let mut extra_bit = contents;
+        extra_bit[5] |= 0b1000_0000;
+        assert!(open_container(&extra_bit, &both()).is_err());
+    }
+
+    #[test]
+    fn headerless_files_still_open_with_the_password() {
+        // The pre-header layout: salt, nonce, ciphertext and tag, with the
+        // key derived from the raw password.
+        let salt = [1u8; SALT_LEN];
+        let nonce = [2u8; NONCE_LEN];
+        let key = derive_key(b"hunter2", &salt).unwrap();
+        let cipher = Aes256Gcm::new_from_slice(&key).unwrap();
+        let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), PLAINTEXT).unwrap();
+        let contents = [&salt[..], &nonce, &ciphertext].concat();
+
+        assert_eq!(container_flags(&contents), FLAG_PASSWORD);
+        assert_eq!(&open_container(&contents, &password_only()).unwrap()[..], PLAINTEXT);
+        assert!(open_container(&contents, &key_file_only()).is_err());
+    }
+}
@@ -1,157 +1,455 @@
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-use structopt::StructOpt;
+use clap::{Args, Parser, Subcommand};
+use password::Source;
 
-use rand::rngs::OsRng;
-use rand::RngCore;
+use secmem::SecretBuf;
+use zeroize::Zeroizing;
 
-use aes_gcm::{Aes256Gcm, Nonce};
-use aes_gcm::aead::Aead;
-use aes_gcm::KeyInit;
+use enc::{
+    container_flags, hash_key_file, open_container, seal_container, AuthenticationFailed,
+    Credentials, FLAG_KEY_FILE, FLAG_PASSWORD, PREFIX_LEN, TAG_LEN,
+};
+use sidecar::{Layout, TagPosition};
 
-use argon2::{Algorithm, Argon2, Params, Version};
+/// Extension appended to encrypted files when no output is given.
+const ENC_EXTENSION: &str = "enc";
+
+/// Detached mode moves the header, salt, nonce and tag to the sidecar.
+const SIDECAR_LAYOUT: Layout =
+    Layout { prefix_len: PREFIX_LEN, tag_len: TAG_LEN, tag: TagPosition::AfterBody };
+
+/// Exit code when every file succeeded.
+const EXIT_OK: u8 = 0;
+/// Exit code when at least one file failed.
+const EXIT_FAILED: u8 = 1;
+/// Exit code for bad arguments or unreadable credentials.
+const EXIT_USAGE: u8 = 2;
 
 /// A simple and secure file encryption tool.
-#[derive(StructOpt)]
-#[structopt(
-    name = "Encryptor",
-    about = "A simple file encryption tool.",
-    no_version, // Disable the -V/--version flag
-    setting = structopt::clap::AppSettings::DisableHelpFlags, // Disable the -h/--help flags
-)]
+#[derive(Parser)]
+#[command(name = "enc", version, about = "A simple file encryption tool.")]
 struct Opt {
     /// The operation to perform: encrypt or decrypt.
-    #[structopt(subcommand)]
+    #[command(subcommand)]
     cmd: Command,
 }
 
-#[derive(StructOpt)]
+#[derive(Subcommand)]
 enum Command {
-    /// Encrypt a file.
+    /// Encrypt one or more files.
     Encrypt {
-        /// Input file to encrypt.
-        #[structopt(parse(from_os_str))]
-        input: PathBuf,
+        /// Input files or glob patterns to encrypt.
+        #[arg(required = true)]
+        inputs: Vec<String>,
+
+        /// Output file; only valid with a single input. Defaults to `<input>.enc`.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Do not use a password; requires --key-file.
+        #[arg(long, requires = "key_file")]
+        no_password: bool,
+
+        /// Overwrite output and sidecar files that already exist.
+        #[arg(short, long)]
+        force: bool,
+
+        #[command(flatten)]
+        detached: DetachedArgs,
 
-        /// Output file for the encrypted data.
-        #[structopt(parse(from_os_str))]
-        output: PathBuf,
+        #[command(flatten)]
+        credentials: CredentialArgs,
     },
-    /// Decrypt a file.
+    /// Decrypt one or more files.
     Decrypt {
-        /// Input file to decrypt.
-        #[structopt(parse(from_os_str))]
-        input: PathBuf,
+        /// Input files or glob patterns to decrypt.
+        #[arg(required = true)]
+        inputs: Vec<String>,
 
-        /// Output file for the decrypted data.
-        #[structopt(parse(from_os_str))]
-        output: PathBuf,
+        /// Output file; only valid with a single input. Defaults to the input
+        /// without its `.enc` extension.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Overwrite output files that already exist.
+        #[arg(short, long)]
+        force: bool,
+
+        #[command(flatten)]
+        detached: DetachedArgs,
+
+        #[command(flatten)]
+        credentials: CredentialArgs,
+    },
+    /// Check that files authenticate, without writing any plaintext.
+    Verify {
+        /// Input files or glob patterns to verify.
+        #[arg(required = true)]
+        inputs: Vec<String>,
+
+        #[command(flatten)]
+        detached: DetachedArgs,
+
+        #[command(flatten)]
+        credentials: CredentialArgs,
     },
 }
 
-fn main() -> anyhow::Result<()> {
-    // Parse command-line arguments.
-    let opt = Opt::from_args();
+/// What to do with each input.
+#[derive(Clone, Copy)]
+enum Mode {
+    Encrypt,
+    Decrypt,
+    Verify,
+}
+
+/// Where the header and tag live when they travel apart from the ciphertext.
+#[derive(Args)]
+struct DetachedArgs {
+    /// Keep the header and tag in a sidecar file instead of the ciphertext.
+    #[arg(long)]
+    detached: bool,
+
+    /// Sidecar path; only valid with a single input. Defaults to `<ciphertext>.meta`.
+    #[arg(long, requires = "detached")]
+    sidecar: Option<PathBuf>,
+}
+
+impl DetachedArgs {
+    /// The sidecar for the given ciphertext path, if running detached.
+    fn sidecar_for(&self, ciphertext: &Path) -> Option<PathBuf> {
+        if !self.detached {
+            return None;
+        }
+        Some(self.sidecar.clone().unwrap_or_else(|| sidecar::default_path(ciphertext)))
+    }
+}
 
-    match opt.cmd {
-        Command::Encrypt { input, output } => {
-            // Prompt for the password.
-            let password = rpassword::prompt_password("Password: ")?;
-            encrypt_file(&input, &output, &password)?;
+/// Where the password and key file come from.
+#[derive(Args)]
+struct CredentialArgs {
+    /// Key file to use as an additional (or sole) factor.
+    #[arg(long)]
+    key_file: Option<PathBuf>,
+
+    /// Read the password from the first line of this file.
+    #[arg(long, group = "password_source")]
+    password_file: Option<PathBuf>,
+
+    /// Read the password from this environment variable.
+    #[arg(long, group = "password_source")]
+    password_env: Option<String>,
+
+    /// Read the password from this already-open file descriptor.
+    #[arg(long, group = "password_source")]
+    password_fd: Option<i32>,
+}
+
+/// Fetches each factor at most once, the first time a file needs it.
+struct CredentialSource {
+    args: CredentialArgs,
+    password: Option<SecretBuf>,
+    key_file_hash: Option<SecretBuf>,
+}
+
+impl CredentialSource {
+    fn new(args: CredentialArgs) -> Self {
+        CredentialSource { args, password: None, key_file_hash: None }
+    }
+
+    /// Returns the credentials for the given header flags.
+    fn get(&mut self, flags: u8) -> anyhow::Result<Credentials<'_>> {
+        if flags & FLAG_PASSWORD != 0 && self.password.is_none() {
+            self.password = Some(SecretBuf::from_slice(self.read_password()?.as_bytes())?);
         }
-        Command::Decrypt { input, output } => {
-            // Prompt for the password.
-            let password = rpassword::prompt_password("Password: ")?;
-            decrypt_file(&input, &output, &password)?;
+        if flags & FLAG_KEY_FILE != 0 && self.key_file_hash.is_none() {
+            let path = match &self.args.key_file {
+                Some(path) => path.clone(),
+                None => prompt_key_file()?,
+            };
+            self.key_file_hash = Some(hash_key_file(&path)?);
         }
+
+        Ok(Credentials {
+            password: self.password.as_deref().filter(|_| flags & FLAG_PASSWORD != 0),
+            key_file_hash: self.key_file_hash.as_deref().filter(|_| flags & FLAG_KEY_FILE != 0),
+        })
     }
 
-    Ok(())
+    /// Reads the password from the configured source, or the terminal.
+    fn read_password(&self) -> anyhow::Result<Zeroizing<String>> {
+        let source = Source::new(
+            self.args.password_file.as_deref(),
+            self.args.password_env.as_deref(),
+            self.args.password_fd,
+        );
+        Ok(source.read("Password: ")?)
+    }
 }
 
-/// Encrypts a file using AES-256-GCM and Argon2 key derivation.
-fn encrypt_file(input: &PathBuf, output: &PathBuf, password: &str) -> anyhow::Result<()> {
-    // Read the input file.
-    let mut input_file = File::open(input)?;
-    let mut plaintext = Vec::new();
-    input_file.read_to_end(&mut plaintext)?;
+/// Expands glob patterns; arguments that match nothing are kept as literal paths.
+fn expand_inputs(patterns: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut inputs = Vec::new();
+    for pattern in patterns {
+        let matches: Vec<PathBuf> = glob::glob(pattern)
+            .map_err(|e| anyhow::anyhow!("Bad pattern {}: {}", pattern, e))?
+            .filter_map(Result::ok)
+            .filter(|path| path.is_file())
+            .collect();
+        if matches.is_empty() {
+            inputs.push(PathBuf::from(pattern));
+        } else {
+            inputs.extend(matches);
+        }
+    }
+    Ok(inputs)
+}
+
+/// Default output path for encryption: `<input>.enc`.
+fn encrypted_path(input: &Path) -> PathBuf {
+    let mut name = input.as_os_str().to_owned();
+    name.push(".");
+    name.push(ENC_EXTENSION);
+    PathBuf::from(name)
+}
 
-    // Generate a random salt.
-    let mut salt = [0u8; 16];
-    OsRng.fill_bytes(&mut salt);
+/// Default output path for decryption: strips `.enc`, or appends `.dec`.
+fn decrypted_path(input: &Path) -> PathBuf {
+    if input.extension().is_some_and(|ext| ext == ENC_EXTENSION) {
+        input.with_extension("")
+    } else {
+        let mut name = input.as_os_str().to_owned();
+        name.push(".dec");
+        PathBuf::from(name)
+    }
+}
 
-    // Derive a key from the password and salt using Argon2.
-    let key_bytes = derive_key(password, &salt)?;
+fn main() -> ExitCode {
+    // Parse command-line arguments.
+    let opt = Opt::parse();
 
-    // Generate a random nonce.
-    let mut nonce_bytes = [0u8; 12];
-    OsRng.fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
+    // Keep keys and passwords out of core dumps.
+    if let Err(e) = secmem::harden_process() {
+        eprintln!("Warning: could not disable core dumps: {}", e);
+    }
 
-    // Create an AES-GCM cipher instance.
-    let cipher = Aes256Gcm::new_from_slice(&key_bytes)
-        .map_err(|e| anyhow::anyhow!("Key init error: {:?}", e))?;
+    match run(opt) {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(EXIT_USAGE)
+        }
+    }
+}
 
-    // Encrypt the plaintext.
-    let ciphertext = cipher
-        .encrypt(nonce, plaintext.as_ref())
-        .map_err(|e| anyhow::anyhow!("Encryption error: {:?}", e))?;
+/// Processes every input and prints a per-file summary.
+fn run(opt: Opt) -> anyhow::Result<u8> {
+    let (mode, patterns, output, no_password, force, detached_args, credential_args) = match opt.cmd {
+        Command::Encrypt { inputs, output, no_password, force, detached, credentials } => {
+            (Mode::Encrypt, inputs, output, no_password, force, detached, credentials)
+        }
+        Command::Decrypt { inputs, output, force, detached, credentials } => {
+            (Mode::Decrypt, inputs, output, false, force, detached, credentials)
+        }
+        Command::Verify { inputs, detached, credentials } => {
+            (Mode::Verify, inputs, None, false, false, detached, credentials)
+        }
+    };
 
-    // Write the salt, nonce, and ciphertext to the output file.
-    let mut output_file = File::create(output)?;
-    output_file.write_all(&salt)?;
-    output_file.write_all(&nonce_bytes)?;
-    output_file.write_all(&ciphertext)?;
+    let inputs = expand_inputs(&patterns)?;
+    if output.is_some() && inputs.len() != 1 {
+        return Err(anyhow::anyhow!("--output can only be used with a single input."));
+    }
+    if detached_args.sidecar.is_some() && inputs.len() != 1 {
+        return Err(anyhow::anyhow!("--sidecar can only be used with a single input."));
+    }
 
-    Ok(())
+    let mut source = CredentialSource::new(credential_args);
+    let encrypt_flags = if no_password {
+        FLAG_KEY_FILE
+    } else if source.args.key_file.is_some() {
+        FLAG_PASSWORD | FLAG_KEY_FILE
+    } else {
+        FLAG_PASSWORD
+    };
+
+    let mut failures = 0;
+    let mut credential_failures = 0;
+    for input in &inputs {
+        // Every problem, credentials included, is reported against its file.
+        let result = match mode {
+            Mode::Encrypt => {
+                let output = output.clone().unwrap_or_else(|| encrypted_path(input));
+                let sidecar = detached_args.sidecar_for(&output);
+                let existing = std::iter::once(&output).chain(&sidecar).find(|path| path.exists());
+                match existing {
+                    // Checked up front so no password is asked for in vain.
+                    Some(path) if !force => Err(already_exists(path)),
+                    _ => match source.get(encrypt_flags) {
+                        Ok(credentials) => {
+                            encrypt_file(input, &output, sidecar.as_deref(), &credentials, force)
+                        }
+                        Err(e) => {
+                            credential_failures += 1;
+                            Err(e)
+                        }
+                    },
+                }
+            }
+            Mode::Decrypt | Mode::Verify => {
+                let output = output.clone().unwrap_or_else(|| decrypted_path(input));
+                let sidecar = detached_args.sidecar_for(input);
+                if matches!(mode, Mode::Decrypt) && !force && output.exists() {
+                    // Checked up front so no password is asked for in vain.
+                    Err(already_exists(&output))
+                } else {
+                    match read_container(input, sidecar.as_deref()) {
+                        // The header tells us which factors to ask for.
+                        Ok(contents) => match source.get(container_flags(&contents)) {
+                            Ok(credentials) => open_container(&contents, &credentials)
+                                .map_err(|e| detached_offset(e, sidecar.is_some()))
+                                .and_then(|plaintext| match mode {
+                                    Mode::Decrypt => write_output(&output, &plaintext, force),
+                                    _ => Ok(()),
+                                }),
+                            Err(e) => {
+                                credential_failures += 1;
+                                Err(e)
+                            }
+                        },
+                        Err(e) => Err(e),
+                    }
+                }
+            }
+        };
+
+        match result {
+            Ok(()) => println!("OK      {}", input.display()),
+            Err(e) => {
+                failures += 1;
+                println!("FAILED  {}: {}", input.display(), e);
+            }
+        }
+    }
+
+    if inputs.len() > 1 {
+        println!(
+            "{} succeeded, {} failed.",
+            inputs.len() - failures,
+            failures
+        );
+    }
+
+    Ok(if credential_failures > 0 {
+        EXIT_USAGE
+    } else if failures == 0 {
+        EXIT_OK
+    } else {
+        EXIT_FAILED
+    })
 }
 
-/// Decrypts a file using AES-256-GCM and Argon2 key derivation.
-fn decrypt_file(input: &PathBuf, output: &PathBuf, password: &str) -> anyhow::Result<()> {
-    // Read the input file.
-    let mut input_file = File::open(input)?;
-    let mut contents = Vec::new();
-    input_file.read_to_end(&mut contents)?;
+/// Writes decrypted plaintext, refusing to replace an existing file unless forced.
+fn write_output(path: &Path, plaintext: &[u8], force: bool) -> anyhow::Result<()> {
+    create_output(path, force)?.write_all(plaintext)?;
+    Ok(())
+}
 
-    // Ensure the file is long enough to contain the salt and nonce.
-    if contents.len() < 16 + 12 {
-        return Err(anyhow::anyhow!("File too short to be valid."));
+/// Creates an output file, refusing to replace an existing one unless forced.
+fn create_output(path: &Path, force: bool) -> anyhow::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
     }
+    options.open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::AlreadyExists => already_exists(path),
+        _ => e.into(),
+    })
+}
 
-    // Extract the salt, nonce, and ciphertext.
-    let salt = &contents[..16];
-    let nonce_bytes = &contents[16..28];
-    let ciphertext = &contents[28..];
+/// The error for an output that would be overwritten.
+fn already_exists(path: &Path) -> anyhow::Error {
+    anyhow::anyhow!("{} already exists; use --force to overwrite it.", path.display())
+}
 
-    // Derive the key from the password and salt.
-    let key_bytes = derive_key(password, salt)?;
+/// Asks for the key file path on the terminal.
+fn prompt_key_file() -> anyhow::Result<PathBuf> {
+    print!("Key file: ");
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    let path = line.trim();
+    if path.is_empty() {
+        return Err(anyhow::anyhow!("This file requires a key file."));
+    }
+    Ok(PathBuf::from(path))
+}
 
-    // Create an AES-GCM cipher instance.
-    let cipher = Aes256Gcm::new_from_slice(&key_bytes)
-        .map_err(|e| anyhow::anyhow!("Key init error: {:?}", e))?;
-    let nonce = Nonce::from_slice(nonce_bytes);
+/// Encrypts a file using AES-256-GCM and Argon2 key derivation.
+///
+/// With a sidecar, the output holds only the ciphertext and the sidecar holds
+/// the header, salt, nonce and tag. Existing files are only replaced if forced.
+fn encrypt_file(
+    input: &Path,
+    output: &Path,
+    sidecar: Option<&Path>,
+    credentials: &Credentials,
+    force: bool,
+) -> anyhow::Result<()> {
+    // Read the input file.
+    let mut input_file = File::open(input)?;
+    let mut plaintext = Zeroizing::new(Vec::new());
+    input_file.read_to_end(&mut plaintext)?;
 
-    // Decrypt the ciphertext.
-    let plaintext = cipher
-        .decrypt(nonce, ciphertext.as_ref())
-        .map_err(|e| anyhow::anyhow!("Decryption error: {:?}", e))?;
+    let contents = seal_container(&plaintext, credentials)?;
 
-    // Write the plaintext to the output file.
-    let mut output_file = File::create(output)?;
-    output_file.write_all(&plaintext)?;
+    match sidecar {
+        Some(sidecar) => {
+            // Keep the header, salt, nonce and tag together; the body goes alone.
+            let (meta, body) = SIDECAR_LAYOUT.detach(&contents)?;
+            create_output(sidecar, force)?.write_all(&meta)?;
+            create_output(output, force)?.write_all(body)?;
+        }
+        None => {
+            create_output(output, force)?.write_all(&contents)?;
+        }
+    }
 
     Ok(())
 }
 
-/// Derives a 256-bit key from the password and salt using Argon2.
-fn derive_key(password: &str, salt: &[u8]) -> anyhow::Result<[u8; 32]> {
-    let mut key = [0u8; 32];
-    let params = Params::default();
-    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
-    argon2
-        .hash_password_into(password.as_bytes(), salt, &mut key)
-        .map_err(|e| anyhow::anyhow!("Key derivation error: {:?}", e))?;
-    Ok(key)
+/// Reads an encrypted file, reattaching the header, salt, nonce and tag from
+/// its sidecar if it was written in detached mode.
+fn read_container(input: &Path, sidecar: Option<&Path>) -> anyhow::Result<Vec<u8>> {
+    // Read the input file.
+    let mut input_file = File::open(input)?;
+    let mut body = Vec::new();
+    input_file.read_to_end(&mut body)?;
+
+    let Some(sidecar) = sidecar else {
+        return Ok(body);
+    };
+
+    let mut sidecar_file = File::open(sidecar)
+        .map_err(|e| anyhow::anyhow!("Cannot open sidecar {}: {}", sidecar.display(), e))?;
+    let mut meta = Vec::new();
+    sidecar_file.read_to_end(&mut meta)?;
+    Ok(SIDECAR_LAYOUT.attach(&meta, &body)?)
+}
+
+/// A detached input holds only the ciphertext body, so an authentication
+/// failure starts at its first byte.
+fn detached_offset(error: anyhow::Error, detached: bool) -> anyhow::Error {
+    match error.downcast::<AuthenticationFailed>() {
+        Ok(_) if detached => AuthenticationFailed { offset: 0 }.into(),
+        Ok(failure) => failure.into(),
+        Err(error) => error,
+    }
 }
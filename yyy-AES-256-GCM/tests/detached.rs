@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const PLAINTEXT: &[u8] = b"Invoice 2291: 14 pallets, paid in full.\n";
+
+/// Runs `enc` in `dir` with the password taken from the environment.
+fn enc(dir: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_enc"))
+        .args(args)
+        .args(["--password-env", "ENC_TEST_PASSWORD"])
+        .env("ENC_TEST_PASSWORD", "tulip-ledger")
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn detached_files_verify_decrypt_and_catch_a_tampered_sidecar() {
+    let dir = std::env::temp_dir().join(format!("enc-detached-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("invoice.txt"), PLAINTEXT).unwrap();
+
+    assert!(enc(&dir, &["encrypt", "invoice.txt", "--detached"]).status.success());
+
+    // The body is the ciphertext alone; the sidecar starts with the header.
+    let body = fs::read(dir.join("invoice.txt.enc")).unwrap();
+    let meta = fs::read(dir.join("invoice.txt.enc.meta")).unwrap();
+    assert_eq!(body.len(), PLAINTEXT.len());
+    assert_eq!(meta.len(), enc::PREFIX_LEN + enc::TAG_LEN);
+    assert_eq!(&meta[..4], enc::MAGIC);
+
+    assert!(enc(&dir, &["verify", "invoice.txt.enc", "--detached"]).status.success());
+    let out = enc(&dir, &["decrypt", "invoice.txt.enc", "-o", "back.txt", "--detached"]);
+    assert!(out.status.success());
+    assert_eq!(fs::read(dir.join("back.txt")).unwrap(), PLAINTEXT);
+
+    // Without the sidecar the body alone does not open.
+    assert!(!enc(&dir, &["verify", "invoice.txt.enc"]).status.success());
+
+    // A flipped nonce bit in the sidecar fails at the first byte of the body.
+    let mut tampered = meta.clone();
+    tampered[enc::HEADER_LEN + enc::SALT_LEN] ^= 1;
+    fs::write(dir.join("invoice.txt.enc.meta"), &tampered).unwrap();
+    let out = enc(&dir, &["verify", "invoice.txt.enc", "--detached"]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("failed at offset 0"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn attached_files_report_the_offset_of_the_body() {
+    let dir = std::env::temp_dir().join(format!("enc-attached-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("invoice.txt"), PLAINTEXT).unwrap();
+
+    assert!(enc(&dir, &["encrypt", "invoice.txt"]).status.success());
+    let mut contents = fs::read(dir.join("invoice.txt.enc")).unwrap();
+    let last = contents.len() - 1;
+    contents[last] ^= 1;
+    fs::write(dir.join("invoice.txt.enc"), &contents).unwrap();
+
+    let out = enc(&dir, &["verify", "invoice.txt.enc"]);
+    assert_eq!(out.status.code(), Some(1));
+    let expected = format!("failed at offset {}", enc::PREFIX_LEN);
+    assert!(String::from_utf8_lossy(&out.stdout).contains(&expected));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn encryption_refuses_to_replace_files_unless_forced() {
+    let dir = std::env::temp_dir().join(format!("enc-force-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("invoice.txt"), PLAINTEXT).unwrap();
+    fs::write(dir.join("invoice.txt.enc"), b"keep me").unwrap();
+    fs::write(dir.join("other.enc.meta"), b"keep me too").unwrap();
+
+    let out = enc(&dir, &["encrypt", "invoice.txt"]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("already exists"));
+    assert_eq!(fs::read(dir.join("invoice.txt.enc")).unwrap(), b"keep me");
+
+    // An existing sidecar stops a detached encryption as well.
+    let out = enc(&dir, &["encrypt", "invoice.txt", "-o", "other.enc", "--detached"]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(!dir.join("other.enc").exists());
+    assert_eq!(fs::read(dir.join("other.enc.meta")).unwrap(), b"keep me too");
+
+    assert!(enc(&dir, &["encrypt", "invoice.txt", "--force"]).status.success());
+    assert!(enc(&dir, &["verify", "invoice.txt.enc"]).status.success());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
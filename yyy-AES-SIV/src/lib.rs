@@ -0,0 +1,90 @@
+//! AES-SIV file format shared by the CLI and the `esk` front-end.
+//!
+//! Files start with a 5-byte header (`ESKS` + version), which is bound to the
+//! ciphertext as an extra associated-data component. Files written before the
+//! header existed are the bare SIV output and are still accepted.
+
+use aes_siv::aead::{generic_array::GenericArray, KeyInit};
+use aes_siv::siv::Aes256Siv;
+use anyhow::{anyhow, Context, Result};
+use secmem::SecretBuf;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+pub const KEY_LENGTH: usize = 64;
+pub const TAG_LENGTH: usize = 16;
+pub const MAGIC: &[u8; 4] = b"ESKS";
+pub const VERSION: u8 = 1;
+pub const HEADER_LENGTH: usize = 5;
+
+pub fn load_key(key_path: &str) -> Result<SecretBuf> {
+    if !Path::new(key_path).exists() {
+        anyhow::bail!(
+            "Key file '{}' not found. Please generate the key first.",
+            key_path
+        );
+    }
+
+    let file_size = std::fs::metadata(key_path)
+        .with_context(|| format!("Failed to read metadata of '{}'", key_path))?
+        .len();
+
+    if file_size != KEY_LENGTH as u64 {
+        anyhow::bail!(
+            "Key file '{}' has incorrect length (expected {} bytes, found {}). The key file may be corrupted.",
+            key_path,
+            KEY_LENGTH,
+            file_size
+        );
+    }
+
+    let mut key = SecretBuf::new(KEY_LENGTH)?;
+    let mut file = File::open(key_path)
+        .with_context(|| format!("Failed to open key file at '{}'", key_path))?;
+    file.read_exact(&mut key)
+        .with_context(|| "Failed to read key file.")?;
+    Ok(key)
+}
+
+/// Whether `data` starts with the current header.
+pub fn has_header(data: &[u8]) -> bool {
+    data.len() >= HEADER_LENGTH && &data[..4] == MAGIC && data[4] == VERSION
+}
+
+/// Encrypts `plaintext` into header || SIV tag || ciphertext.
+pub fn seal(key: &[u8], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let mut header = [0u8; HEADER_LENGTH];
+    header[..4].copy_from_slice(MAGIC);
+    header[4] = VERSION;
+
+    // Initialize the cipher with the key
+    let mut cipher = Aes256Siv::new(GenericArray::from_slice(key));
+    let sealed = cipher
+        .encrypt([&header[..], aad], plaintext)
+        .map_err(|_| anyhow!("Encryption failed."))?;
+
+    let mut data = Vec::with_capacity(HEADER_LENGTH + sealed.len());
+    data.extend_from_slice(&header);
+    data.extend_from_slice(&sealed);
+    Ok(data)
+}
+
+/// Authenticates and decrypts a headered or legacy headerless file.
+pub fn open(key: &[u8], aad: &[u8], data: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let mut cipher = Aes256Siv::new(GenericArray::from_slice(key));
+
+    if has_header(data) {
+        let (header, sealed) = data.split_at(HEADER_LENGTH);
+        if let Ok(plaintext) = cipher.decrypt([header, aad], sealed) {
+            return Ok(Zeroizing::new(plaintext));
+        }
+        // A legacy file whose tag happens to start with the magic bytes.
+    }
+
+    cipher
+        .decrypt([aad], data)
+        .map(Zeroizing::new)
+        .map_err(|_| anyhow!("Decryption failed. Incorrect key, associated data, or corrupted data."))
+}
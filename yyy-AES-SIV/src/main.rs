@@ -1,17 +1,28 @@
-use aes_siv::aead::{generic_array::GenericArray, KeyInit};
-use aes_siv::siv::Aes256Siv;
-use anyhow::{anyhow, Context, Result};
-use clap::{Arg, Command};
+use aes_siv_encryption_app::{
+    has_header, load_key, open, seal, HEADER_LENGTH, KEY_LENGTH, TAG_LENGTH,
+};
+use anyhow::{Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use rand::RngCore;
 use std::fs::{File, OpenOptions};
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
+use secmem::SecretBuf;
+use sidecar::{Layout, TagPosition};
 use zeroize::Zeroizing;
 
 const KEY_FILENAME: &str = "key.key";
-const KEY_LENGTH: usize = 64;
+
+// Detached mode moves the header and SIV tag to the sidecar.
+const SIDECAR_LAYOUT: Layout =
+    Layout { prefix_len: HEADER_LENGTH, tag_len: TAG_LENGTH, tag: TagPosition::BeforeBody };
 
 fn main() -> Result<()> {
+    // Keep key material out of core dumps.
+    if let Err(e) = secmem::harden_process() {
+        eprintln!("Warning: could not disable core dumps: {}", e);
+    }
+
     let mut cmd = Command::new("AES-SIV Encryption App")
         .version("1.0")
         .author("Your Name")
@@ -73,6 +84,19 @@ Usage: {usage}
                         .long("aad")
                         .value_name("ASSOCIATED_DATA"),
                 )
+                .arg(
+                    Arg::new("detached")
+                        .help("Keep the header and SIV tag in a sidecar file instead of the ciphertext")
+                        .long("detached")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("sidecar")
+                        .help("Path of the sidecar file (defaults to <CIPHERTEXT>.meta)")
+                        .long("sidecar")
+                        .value_name("SIDECAR_PATH")
+                        .requires("detached"),
+                )
                 .help_template(
                     "{about}
 
@@ -111,6 +135,63 @@ Usage: {usage}
                         .long("aad")
                         .value_name("ASSOCIATED_DATA"),
                 )
+                .arg(
+                    Arg::new("detached")
+                        .help("Keep the header and SIV tag in a sidecar file instead of the ciphertext")
+                        .long("detached")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("sidecar")
+                        .help("Path of the sidecar file (defaults to <CIPHERTEXT>.meta)")
+                        .long("sidecar")
+                        .value_name("SIDECAR_PATH")
+                        .requires("detached"),
+                )
+                .help_template(
+                    "{about}
+
+Usage: {usage}
+",
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Checks that a file authenticates without writing the plaintext")
+                .arg(
+                    Arg::new("input")
+                        .help("Input file to verify")
+                        .required(true)
+                        .value_name("INPUT")
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("key")
+                        .help("Path to the encryption key file")
+                        .short('k')
+                        .long("key")
+                        .value_name("KEY_PATH"),
+                )
+                .arg(
+                    Arg::new("aad")
+                        .help("Associated data used during encryption")
+                        .short('a')
+                        .long("aad")
+                        .value_name("ASSOCIATED_DATA"),
+                )
+                .arg(
+                    Arg::new("detached")
+                        .help("Keep the header and SIV tag in a sidecar file instead of the ciphertext")
+                        .long("detached")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("sidecar")
+                        .help("Path of the sidecar file (defaults to <CIPHERTEXT>.meta)")
+                        .long("sidecar")
+                        .value_name("SIDECAR_PATH")
+                        .requires("detached"),
+                )
                 .help_template(
                     "{about}
 
@@ -140,8 +221,9 @@ Usage: {usage}
                 .get_one::<String>("aad")
                 .map(|s| s.as_bytes())
                 .unwrap_or(&[]);
+            let sidecar = sidecar_path(sub_m, output);
             let key = load_key(key_path)?;
-            process_file(input, output, &key, true, aad)?;
+            process_file(input, output, &key, true, aad, sidecar.as_deref())?;
             Ok(())
         }
         Some(("decrypt", sub_m)) => {
@@ -155,10 +237,25 @@ Usage: {usage}
                 .get_one::<String>("aad")
                 .map(|s| s.as_bytes())
                 .unwrap_or(&[]);
+            let sidecar = sidecar_path(sub_m, input);
             let key = load_key(key_path)?;
-            process_file(input, output, &key, false, aad)?;
+            process_file(input, output, &key, false, aad, sidecar.as_deref())?;
             Ok(())
         }
+        Some(("verify", sub_m)) => {
+            let input = sub_m.get_one::<String>("input").unwrap();
+            let key_path = sub_m
+                .get_one::<String>("key")
+                .map(|s| s.as_str())
+                .unwrap_or(KEY_FILENAME);
+            let aad = sub_m
+                .get_one::<String>("aad")
+                .map(|s| s.as_bytes())
+                .unwrap_or(&[]);
+            let sidecar = sidecar_path(sub_m, input);
+            let key = load_key(key_path)?;
+            verify_file(input, &key, aad, sidecar.as_deref())
+        }
         _ => {
             cmd.print_long_help()?;
             println!(); // Ensure there's a newline after the help message
@@ -167,8 +264,21 @@ Usage: {usage}
     }
 }
 
+/// Returns the sidecar path when `--detached` was given.
+fn sidecar_path(matches: &ArgMatches, ciphertext: &str) -> Option<String> {
+    if !matches.get_flag("detached") {
+        return None;
+    }
+    Some(
+        matches
+            .get_one::<String>("sidecar")
+            .cloned()
+            .unwrap_or_else(|| sidecar::default_path(Path::new(ciphertext)).display().to_string()),
+    )
+}
+
 fn generate_key(length: usize, key_path: &str) -> Result<()> {
-    let mut key = Zeroizing::new(vec![0u8; length]);
+    let mut key = SecretBuf::new(length)?;
     rand::thread_rng().fill_bytes(&mut key);
     let mut file = File::create(key_path)
         .with_context(|| format!("Failed to create key file at '{}'", key_path))?;
@@ -180,45 +290,19 @@ fn generate_key(length: usize, key_path: &str) -> Result<()> {
     Ok(())
 }
 
-fn load_key(key_path: &str) -> Result<Zeroizing<Vec<u8>>> {
-    if !Path::new(key_path).exists() {
-        anyhow::bail!(
-            "Key file '{}' not found. Please generate the key first.",
-            key_path
-        );
-    }
-
-    let file_size = std::fs::metadata(key_path)
-        .with_context(|| format!("Failed to read metadata of '{}'", key_path))?
-        .len();
-
-    if file_size != KEY_LENGTH as u64 {
-        anyhow::bail!(
-            "Key file '{}' has incorrect length (expected {} bytes, found {}). The key file may be corrupted.",
-            key_path,
-            KEY_LENGTH,
-            file_size
-        );
-    }
-
-    let mut key = vec![0u8; KEY_LENGTH];
-    let mut file = File::open(key_path)
-        .with_context(|| format!("Failed to open key file at '{}'", key_path))?;
-    file.read_exact(&mut key)
-        .with_context(|| "Failed to read key file.")?;
-    Ok(Zeroizing::new(key))
-}
-
 fn process_file(
     input: &str,
     output: &str,
     key: &[u8],
     encrypt: bool,
     aad: &[u8],
+    sidecar: Option<&str>,
 ) -> Result<()> {
-    let input_file = File::open(input)
-        .with_context(|| format!("Failed to open input file '{}'.", input))?;
-    let mut reader = BufReader::new(input_file);
+    let data = if encrypt {
+        read_file(input)?
+    } else {
+        read_ciphertext(input, sidecar)?
+    };
 
     let output_file = OpenOptions::new()
         .write(true)
@@ -228,29 +312,26 @@ fn process_file(
         .with_context(|| format!("Failed to create output file '{}'.", output))?;
     let mut writer = BufWriter::new(output_file);
 
-    let mut data = Zeroizing::new(Vec::new());
-    reader
-        .read_to_end(&mut data)
-        .with_context(|| "Failed to read input file.")?;
-
-    // Initialize the cipher with the key
-    let mut cipher = Aes256Siv::new(GenericArray::from_slice(key));
-
     let result = if encrypt {
-        cipher
-            .encrypt(&[aad], &data)
-            .map_err(|_| anyhow!("Encryption failed."))?
+        Zeroizing::new(seal(key, aad, &data)?)
     } else {
-        cipher
-            .decrypt(&[aad], &data)
-            .map_err(|_| {
-                anyhow!("Decryption failed. Incorrect key, associated data, or corrupted data.")
-            })?
+        open(key, aad, &data)?
     };
 
-    writer
-        .write_all(&result)
-        .with_context(|| "Failed to write output file.")?;
+    match sidecar {
+        // In detached mode the header and SIV tag go to the sidecar, the body to the output.
+        Some(sidecar) if encrypt => {
+            let (meta, body) = SIDECAR_LAYOUT.detach(&result)?;
+            std::fs::write(sidecar, &meta)
+                .with_context(|| format!("Failed to write sidecar file '{}'.", sidecar))?;
+            writer
+                .write_all(body)
+                .with_context(|| "Failed to write output file.")?;
+        }
+        _ => writer
+            .write_all(&result)
+            .with_context(|| "Failed to write output file.")?,
+    }
 
     // Data will be zeroized when it goes out of scope
 
@@ -262,3 +343,56 @@ fn process_file(
 
     Ok(())
 }
+
+fn read_file(path: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open input file '{}'.", path))?;
+    let mut reader = BufReader::new(file);
+    let mut data = Zeroizing::new(Vec::new());
+    reader
+        .read_to_end(&mut data)
+        .with_context(|| "Failed to read input file.")?;
+    Ok(data)
+}
+
+/// Reads a ciphertext, putting the header and SIV tag back in front of the body
+/// if they were detached.
+fn read_ciphertext(input: &str, sidecar: Option<&str>) -> Result<Zeroizing<Vec<u8>>> {
+    let body = read_file(input)?;
+    let Some(sidecar) = sidecar else {
+        return Ok(body);
+    };
+
+    let meta = std::fs::read(sidecar)
+        .with_context(|| format!("Failed to read sidecar file '{}'.", sidecar))?;
+    let data = SIDECAR_LAYOUT
+        .attach(&meta, &body)
+        .with_context(|| format!("Sidecar file '{}' is not valid.", sidecar))?;
+    Ok(Zeroizing::new(data))
+}
+
+fn verify_file(input: &str, key: &[u8], aad: &[u8], sidecar: Option<&str>) -> Result<()> {
+    let data = read_ciphertext(input, sidecar)?;
+
+    // SIV authenticates the whole message at once, so a failure covers the
+    // body from its first byte: right after the tag, or the start of a
+    // detached ciphertext.
+    let offset = if sidecar.is_some() {
+        0
+    } else if has_header(&data) {
+        SIDECAR_LAYOUT.body_offset()
+    } else {
+        TAG_LENGTH
+    };
+    match open(key, aad, &data) {
+        Ok(_plaintext) => {
+            println!("OK: '{}' authenticated ({} bytes).", input, data.len());
+            Ok(())
+        }
+        Err(_) => anyhow::bail!(
+            "Verification of '{}' failed at offset {}. Incorrect key, associated data, or corrupted data.",
+            input,
+            offset
+        ),
+    }
+}
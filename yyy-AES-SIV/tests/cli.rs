@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use aes_siv_encryption_app::{HEADER_LENGTH, MAGIC, TAG_LENGTH};
+
+const PLAINTEXT: &[u8] = b"build 4417 signed off; ship to the mirror at 02:00 UTC\n";
+
+/// Runs the tool in `dir`, where `gen-key` has left `key.key`.
+fn siv(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aes_siv_encryption_app"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+fn keyed_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("siv-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    assert!(siv(&dir, &["gen-key"]).status.success());
+    fs::write(dir.join("notes.txt"), PLAINTEXT).unwrap();
+    dir
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn verify_checks_the_associated_data_and_reports_the_body_offset() {
+    let dir = keyed_dir("verify");
+    assert!(siv(&dir, &["encrypt", "notes.txt", "notes.siv", "-a", "release"]).status.success());
+
+    let ok = siv(&dir, &["verify", "notes.siv", "-a", "release"]);
+    assert!(ok.status.success(), "{}", stderr(&ok));
+    assert!(!dir.join("notes.txt.dec").exists());
+
+    // The body starts after the header and the SIV.
+    let wrong = siv(&dir, &["verify", "notes.siv", "-a", "nightly"]);
+    assert!(!wrong.status.success());
+    let offset = format!("failed at offset {}", HEADER_LENGTH + TAG_LENGTH);
+    assert!(stderr(&wrong).contains(&offset), "{}", stderr(&wrong));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn detached_mode_moves_the_header_and_tag_to_the_sidecar() {
+    let dir = keyed_dir("detached");
+    assert!(siv(&dir, &["encrypt", "notes.txt", "notes.siv", "--detached"]).status.success());
+
+    let meta = fs::read(dir.join("notes.siv.meta")).unwrap();
+    assert_eq!(meta.len(), HEADER_LENGTH + TAG_LENGTH);
+    assert_eq!(&meta[..4], MAGIC);
+    assert_eq!(fs::read(dir.join("notes.siv")).unwrap().len(), PLAINTEXT.len());
+
+    assert!(siv(&dir, &["verify", "notes.siv", "--detached"]).status.success());
+    assert!(siv(&dir, &["decrypt", "notes.siv", "back.txt", "--detached"]).status.success());
+    assert_eq!(fs::read(dir.join("back.txt")).unwrap(), PLAINTEXT);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_tampered_or_truncated_sidecar_is_refused() {
+    let dir = keyed_dir("tampered");
+    let sidecar = ["--detached", "--sidecar", "notes.meta"];
+    let mut args = vec!["encrypt", "notes.txt", "notes.siv"];
+    args.extend(sidecar);
+    assert!(siv(&dir, &args).status.success());
+    let meta = fs::read(dir.join("notes.meta")).unwrap();
+
+    // One flipped bit in the SIV fails the whole body, from its first byte.
+    let mut tampered = meta.clone();
+    tampered[HEADER_LENGTH] ^= 0x80;
+    fs::write(dir.join("notes.meta"), &tampered).unwrap();
+    let mut args = vec!["verify", "notes.siv"];
+    args.extend(sidecar);
+    let out = siv(&dir, &args);
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("failed at offset 0"), "{}", stderr(&out));
+
+    fs::write(dir.join("notes.meta"), &meta[..TAG_LENGTH]).unwrap();
+    let out = siv(&dir, &args);
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("is not valid"), "{}", stderr(&out));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,95 @@
+//! XChaCha20-Poly1305 file format shared by the CLI and the `esk` front-end.
+//!
+//! New files start with a 5-byte header (`ESKX` + version) that is bound to the
+//! ciphertext as associated data, followed by the nonce and the sealed data.
+//! Headerless files (nonce || ciphertext) from earlier versions, and from the
+//! older `wwwww-misc/cha` tool, are still accepted.
+
+use anyhow::{anyhow, Context, Result};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::{rngs::OsRng, RngCore};
+use secmem::SecretBuf;
+use zeroize::Zeroizing;
+use std::{fs::File, io::Read, path::Path};
+
+pub const KEY_SIZE: usize = 32;    // 32 bytes for XChaCha20 key
+pub const NONCE_SIZE: usize = 24;  // 24 bytes for XChaCha20 nonce
+pub const TAG_SIZE: usize = 16;    // 16 bytes for the Poly1305 tag
+pub const MAGIC: &[u8; 4] = b"ESKX";
+pub const VERSION: u8 = 1;
+pub const HEADER_SIZE: usize = 5;  // magic + version
+
+pub fn load_key(key_path: &str) -> Result<SecretBuf> {
+    if !Path::new(key_path).exists() {
+        return Err(anyhow!(
+            "Key file '{}' not found. Please generate the key first.",
+            key_path
+        ));
+    }
+
+    let mut key_data = SecretBuf::new(KEY_SIZE)?;
+    let mut key_file = File::open(key_path)
+        .with_context(|| format!("Failed to open key file at '{}'.", key_path))?;
+    key_file
+        .read_exact(&mut key_data)
+        .with_context(|| format!("Failed to read key from '{}'.", key_path))?;
+
+    Ok(key_data)
+}
+
+/// Whether `data` starts with the current header.
+pub fn has_header(data: &[u8]) -> bool {
+    data.len() >= HEADER_SIZE && &data[..4] == MAGIC && data[4] == VERSION
+}
+
+/// Encrypts `plaintext` into header || nonce || ciphertext || tag.
+pub fn seal(key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let cipher = XChaCha20Poly1305::new_from_slice(key)
+        .map_err(|_| anyhow!("Invalid key length."))?;
+
+    let mut header = [0u8; HEADER_SIZE];
+    header[..4].copy_from_slice(MAGIC);
+    header[4] = VERSION;
+
+    let mut nonce_bytes = [0u8; NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce_bytes);   // Use OsRng for secure nonce generation
+    let nonce = XNonce::from_slice(&nonce_bytes);
+
+    let ciphertext = cipher
+        .encrypt(nonce, Payload { msg: plaintext, aad: &header })
+        .map_err(|_| anyhow!("Encryption failed."))?;
+
+    let mut sealed = Vec::with_capacity(HEADER_SIZE + NONCE_SIZE + ciphertext.len());
+    sealed.extend_from_slice(&header);
+    sealed.extend_from_slice(&nonce_bytes);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Authenticates and decrypts a headered or legacy headerless file.
+pub fn open(key: &[u8], sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let cipher = XChaCha20Poly1305::new_from_slice(key)
+        .map_err(|_| anyhow!("Invalid key length."))?;
+
+    if has_header(sealed) && sealed.len() >= HEADER_SIZE + NONCE_SIZE + TAG_SIZE {
+        let (header, rest) = sealed.split_at(HEADER_SIZE);
+        let (nonce_bytes, ciphertext) = rest.split_at(NONCE_SIZE);
+        let payload = Payload { msg: ciphertext, aad: header };
+        if let Ok(plaintext) = cipher.decrypt(XNonce::from_slice(nonce_bytes), payload) {
+            return Ok(Zeroizing::new(plaintext));
+        }
+        // A legacy file whose nonce happens to start with the magic bytes.
+    }
+
+    if sealed.len() < NONCE_SIZE + TAG_SIZE {
+        return Err(anyhow!("File too short to contain a nonce and tag."));
+    }
+    let (nonce_bytes, ciphertext) = sealed.split_at(NONCE_SIZE);
+    cipher
+        .decrypt(XNonce::from_slice(nonce_bytes), ciphertext)
+        .map(Zeroizing::new)
+        .map_err(|_| anyhow!("Decryption failed. Incorrect key or corrupted data."))
+}
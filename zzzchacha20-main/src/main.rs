@@ -1,11 +1,12 @@
 use anyhow::{anyhow, Context, Result};
-use chacha20poly1305::{
-    aead::{Aead, KeyInit},
-    XChaCha20Poly1305, XNonce,
-};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use rand::{rngs::OsRng, RngCore};
-use zeroize::Zeroize;
+use secmem::SecretBuf;
+use sidecar::{Layout, TagPosition};
+use xchacha20_encryption_app::{
+    has_header, load_key, open, seal, HEADER_SIZE, KEY_SIZE, NONCE_SIZE, TAG_SIZE,
+};
+use zeroize::{Zeroize, Zeroizing};
 use std::{
     fs::{File, OpenOptions},
     io::{BufReader, BufWriter, Read, Write},
@@ -13,8 +14,13 @@ use std::{
 };
 
 const DEFAULT_KEY_FILE: &str = "key.key";
-const KEY_SIZE: usize = 32;    // 32 bytes for XChaCha20 key
-const NONCE_SIZE: usize = 24;  // 24 bytes for XChaCha20 nonce
+
+// Detached mode moves the header, nonce and tag to the sidecar.
+const SIDECAR_LAYOUT: Layout = Layout {
+    prefix_len: HEADER_SIZE + NONCE_SIZE,
+    tag_len: TAG_SIZE,
+    tag: TagPosition::AfterBody,
+};
 
 #[derive(Parser)]
 #[command(
@@ -51,6 +57,8 @@ enum Commands {
         /// Path to the encryption key file
         #[arg(short, long, default_value = DEFAULT_KEY_FILE)]
         key: String,
+        #[command(flatten)]
+        detached: Detached,
     },
     /// Decrypts a file
     Decrypt {
@@ -61,16 +69,66 @@ enum Commands {
         /// Path to the encryption key file
         #[arg(short, long, default_value = DEFAULT_KEY_FILE)]
         key: String,
+        #[command(flatten)]
+        detached: Detached,
     },
+    /// Checks that a file authenticates without writing the plaintext
+    Verify {
+        /// The encrypted file to verify
+        input: String,
+        /// Path to the encryption key file
+        #[arg(short, long, default_value = DEFAULT_KEY_FILE)]
+        key: String,
+        #[command(flatten)]
+        detached: Detached,
+    },
+}
+
+#[derive(Args)]
+struct Detached {
+    /// Keep the header, nonce and tag in a sidecar file instead of the ciphertext
+    #[arg(long)]
+    detached: bool,
+    /// Path of the sidecar file (defaults to <CIPHERTEXT>.meta)
+    #[arg(long, requires = "detached")]
+    sidecar: Option<String>,
+}
+
+impl Detached {
+    fn sidecar_for(&self, ciphertext_path: &str) -> Option<String> {
+        if !self.detached {
+            return None;
+        }
+        Some(
+            self.sidecar
+                .clone()
+                .unwrap_or_else(|| sidecar::default_path(Path::new(ciphertext_path)).display().to_string()),
+        )
+    }
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    // Keep key material out of core dumps.
+    if let Err(e) = secmem::harden_process() {
+        eprintln!("Warning: could not disable core dumps: {}", e);
+    }
+
     match cli.command {
         Commands::GenKey { key } => generate_key(&key),
-        Commands::Encrypt { input, output, key } => encrypt_file(&input, &output, &key),
-        Commands::Decrypt { input, output, key } => decrypt_file(&input, &output, &key),
+        Commands::Encrypt { input, output, key, detached } => {
+            let sidecar = detached.sidecar_for(&output);
+            encrypt_file(&input, &output, &key, sidecar.as_deref())
+        }
+        Commands::Decrypt { input, output, key, detached } => {
+            let sidecar = detached.sidecar_for(&input);
+            decrypt_file(&input, &output, &key, sidecar.as_deref())
+        }
+        Commands::Verify { input, key, detached } => {
+            let sidecar = detached.sidecar_for(&input);
+            verify_file(&input, &key, sidecar.as_deref())
+        }
     }
 }
 
@@ -88,7 +146,7 @@ fn generate_key(key_path: &str) -> Result<()> {
         }
     }
 
-    let mut key = [0u8; KEY_SIZE];
+    let mut key = SecretBuf::new(KEY_SIZE)?;
     OsRng.fill_bytes(&mut key);   // Use OsRng for secure random number generation
 
     let mut key_file = File::create(key_path)
@@ -127,47 +185,24 @@ fn set_key_file_permissions(file: &File) -> Result<()> {
     Ok(())
 }
 
-fn load_key(key_path: &str) -> Result<[u8; KEY_SIZE]> {
-    if !Path::new(key_path).exists() {
-        return Err(anyhow!(
-            "Key file '{}' not found. Please generate the key first.",
-            key_path
-        ));
-    }
-
-    let mut key_data = [0u8; KEY_SIZE];
-    let mut key_file = File::open(key_path)
-        .with_context(|| format!("Failed to open key file at '{}'.", key_path))?;
-    key_file
-        .read_exact(&mut key_data)
-        .with_context(|| format!("Failed to read key from '{}'.", key_path))?;
-
-    Ok(key_data)
-}
-
-fn encrypt_file(input_path: &str, output_path: &str, key_path: &str) -> Result<()> {
+fn encrypt_file(
+    input_path: &str,
+    output_path: &str,
+    key_path: &str,
+    sidecar_path: Option<&str>,
+) -> Result<()> {
     let key = load_key(key_path)?;
-    let cipher = XChaCha20Poly1305::new_from_slice(&key)
-        .map_err(|_| anyhow!("Invalid key length."))?;
 
     let input_file = File::open(input_path)
         .with_context(|| format!("Failed to open input file '{}'.", input_path))?;
     let mut reader = BufReader::new(input_file);
-    let mut data = Vec::new();
+    // Wiped on every exit path, not just the happy one.
+    let mut data = Zeroizing::new(Vec::new());
     reader
         .read_to_end(&mut data)
         .with_context(|| "Failed to read input file.")?;
 
-    let mut nonce_bytes = [0u8; NONCE_SIZE];
-    OsRng.fill_bytes(&mut nonce_bytes);   // Use OsRng for secure nonce generation
-    let nonce = XNonce::from_slice(&nonce_bytes);
-
-    let ciphertext = cipher
-        .encrypt(nonce, data.as_ref())
-        .map_err(|_| anyhow!("Encryption failed."))?;
-
-    // Zeroize sensitive data
-    data.zeroize();
+    let sealed = seal(&key, &data)?;
 
     let output_file = OpenOptions::new()
         .write(true)
@@ -177,50 +212,62 @@ fn encrypt_file(input_path: &str, output_path: &str, key_path: &str) -> Result<(
         .with_context(|| format!("Failed to create output file '{}'.", output_path))?;
     let mut writer = BufWriter::new(output_file);
 
-    writer
-        .write_all(&nonce_bytes)
-        .with_context(|| "Failed to write nonce to output file.")?;
-    writer
-        .write_all(&ciphertext)
-        .with_context(|| "Failed to write ciphertext to output file.")?;
-
-    // Zeroize nonce and key
-    nonce_bytes.zeroize();
-    drop(cipher); // Ensure cipher is dropped before zeroizing the key
-    let mut key = key;
-    key.zeroize();
+    match sidecar_path {
+        Some(sidecar_path) => {
+            // Detached: the header, nonce and tag travel in the sidecar, the body in the output.
+            let (meta, body) = SIDECAR_LAYOUT.detach(&sealed)?;
+            std::fs::write(sidecar_path, &meta)
+                .with_context(|| format!("Failed to write sidecar file '{}'.", sidecar_path))?;
+            writer
+                .write_all(body)
+                .with_context(|| "Failed to write ciphertext to output file.")?;
+        }
+        None => {
+            writer
+                .write_all(&sealed)
+                .with_context(|| "Failed to write ciphertext to output file.")?;
+        }
+    }
 
     println!("File successfully encrypted to '{}'.", output_path);
     Ok(())
 }
 
-fn decrypt_file(input_path: &str, output_path: &str, key_path: &str) -> Result<()> {
-    let key = load_key(key_path)?;
-    let cipher = XChaCha20Poly1305::new_from_slice(&key)
-        .map_err(|_| anyhow!("Invalid key length."))?;
-
+/// Reads an encrypted file, reattaching the header, nonce and tag from the sidecar in detached mode.
+fn read_sealed(input_path: &str, sidecar_path: Option<&str>) -> Result<Vec<u8>> {
     let input_file = File::open(input_path)
         .with_context(|| format!("Failed to open input file '{}'.", input_path))?;
     let mut reader = BufReader::new(input_file);
 
-    let mut nonce_bytes = [0u8; NONCE_SIZE];
+    let mut body = Vec::new();
     reader
-        .read_exact(&mut nonce_bytes)
-        .with_context(|| "Failed to read nonce from input file.")?;
-    let nonce = XNonce::from_slice(&nonce_bytes);
-
-    let mut ciphertext = Vec::new();
-    reader
-        .read_to_end(&mut ciphertext)
+        .read_to_end(&mut body)
         .with_context(|| "Failed to read ciphertext from input file.")?;
 
-    let plaintext = cipher
-        .decrypt(nonce, ciphertext.as_ref())
-        .map_err(|_| anyhow!("Decryption failed. Incorrect key or corrupted data."))?;
+    let Some(sidecar_path) = sidecar_path else {
+        return Ok(body);
+    };
+
+    let meta = std::fs::read(sidecar_path)
+        .with_context(|| format!("Failed to read sidecar file '{}'.", sidecar_path))?;
+    SIDECAR_LAYOUT
+        .attach(&meta, &body)
+        .with_context(|| format!("Sidecar file '{}' is not valid.", sidecar_path))
+}
+
+fn decrypt_file(
+    input_path: &str,
+    output_path: &str,
+    key_path: &str,
+    sidecar_path: Option<&str>,
+) -> Result<()> {
+    let key = load_key(key_path)?;
+
+    let mut sealed = read_sealed(input_path, sidecar_path)?;
+    let plaintext = open(&key, &sealed)?;
 
     // Zeroize sensitive data
-    ciphertext.zeroize();
-    nonce_bytes.zeroize();
+    sealed.zeroize();
 
     let output_file = OpenOptions::new()
         .write(true)
@@ -234,13 +281,34 @@ fn decrypt_file(input_path: &str, output_path: &str, key_path: &str) -> Result<(
         .write_all(&plaintext)
         .with_context(|| "Failed to write plaintext to output file.")?;
 
-    // Zeroize plaintext and key
-    drop(cipher); // Ensure cipher is dropped before zeroizing the key
-    let mut key = key;
-    key.zeroize();
-    let mut plaintext = plaintext;
-    plaintext.zeroize();
+    // Plaintext and key are wiped when dropped
 
     println!("File successfully decrypted to '{}'.", output_path);
     Ok(())
 }
+
+fn verify_file(input_path: &str, key_path: &str, sidecar_path: Option<&str>) -> Result<()> {
+    let key = load_key(key_path)?;
+    let sealed = read_sealed(input_path, sidecar_path)?;
+
+    // The file is a single Poly1305-tagged message, so a failure covers it from the first
+    // ciphertext byte, which is the first byte of a detached body.
+    let offset = if sidecar_path.is_some() {
+        0
+    } else if has_header(&sealed) {
+        SIDECAR_LAYOUT.body_offset()
+    } else {
+        NONCE_SIZE
+    };
+    // Nothing is written; the plaintext is wiped as soon as it is dropped.
+    open(&key, &sealed).map_err(|_| {
+        anyhow!(
+            "Verification of '{}' failed at offset {}. Incorrect key or corrupted data.",
+            input_path,
+            offset
+        )
+    })?;
+
+    println!("OK: '{}' authenticated ({} bytes).", input_path, sealed.len());
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use xchacha20_encryption_app::{HEADER_SIZE, MAGIC, NONCE_SIZE, TAG_SIZE};
+
+const PLAINTEXT: &[u8] = b"door code changes Monday: 4-1-1-9, then the green button\n";
+
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_xchacha20_encryption_app"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+/// A fresh directory holding a generated `key.key` and the plaintext.
+fn workspace(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("xchacha-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    assert!(run(&dir, &["gen-key"]).status.success());
+    fs::write(dir.join("memo.txt"), PLAINTEXT).unwrap();
+    dir
+}
+
+fn failure_offset(output: &Output) -> String {
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let at = stderr.find("offset ").expect("no offset reported");
+    stderr[at + 7..].split('.').next().unwrap().to_string()
+}
+
+#[test]
+fn a_truncated_tag_fails_verification_after_the_header_and_nonce() {
+    let dir = workspace("attached");
+    assert!(run(&dir, &["encrypt", "memo.txt", "memo.bin"]).status.success());
+    assert!(run(&dir, &["verify", "memo.bin"]).status.success());
+
+    let sealed = fs::read(dir.join("memo.bin")).unwrap();
+    fs::write(dir.join("memo.bin"), &sealed[..sealed.len() - 1]).unwrap();
+    let offset = failure_offset(&run(&dir, &["verify", "memo.bin"]));
+    assert_eq!(offset, (HEADER_SIZE + NONCE_SIZE).to_string());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn detached_files_keep_only_the_body_and_check_their_sidecar() {
+    let dir = workspace("detached");
+    assert!(run(&dir, &["encrypt", "memo.txt", "memo.bin", "--detached"]).status.success());
+
+    let meta = fs::read(dir.join("memo.bin.meta")).unwrap();
+    assert_eq!(meta.len(), HEADER_SIZE + NONCE_SIZE + TAG_SIZE);
+    assert_eq!(&meta[..4], MAGIC);
+    assert_eq!(fs::read(dir.join("memo.bin")).unwrap().len(), PLAINTEXT.len());
+
+    assert!(run(&dir, &["decrypt", "memo.bin", "memo.out", "--detached"]).status.success());
+    assert_eq!(fs::read(dir.join("memo.out")).unwrap(), PLAINTEXT);
+
+    // The header is authenticated too: changing its version byte breaks the tag.
+    let mut tampered = meta.clone();
+    tampered[4] ^= 0x02;
+    fs::write(dir.join("memo.bin.meta"), &tampered).unwrap();
+    assert_eq!(failure_offset(&run(&dir, &["verify", "memo.bin", "--detached"])), "0");
+
+    fs::remove_file(dir.join("memo.bin.meta")).unwrap();
+    assert!(!run(&dir, &["verify", "memo.bin", "--detached"]).status.success());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn headerless_files_still_verify_and_fail_after_the_nonce() {
+    let dir = workspace("legacy");
+    let key = fs::read(dir.join("key.key")).unwrap();
+    let nonce = [9u8; NONCE_SIZE];
+    let cipher = XChaCha20Poly1305::new_from_slice(&key).unwrap();
+    let ciphertext = cipher.encrypt(XNonce::from_slice(&nonce), PLAINTEXT).unwrap();
+    let mut legacy = [&nonce[..], &ciphertext].concat();
+    fs::write(dir.join("old.bin"), &legacy).unwrap();
+
+    assert!(run(&dir, &["verify", "old.bin"]).status.success());
+
+    legacy[NONCE_SIZE] ^= 1;
+    fs::write(dir.join("old.bin"), &legacy).unwrap();
+    assert_eq!(failure_offset(&run(&dir, &["verify", "old.bin"])), NONCE_SIZE.to_string());
+
+    fs::remove_dir_all(&dir).unwrap();
+}